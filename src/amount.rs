@@ -0,0 +1,200 @@
+use anyhow::{Context, Result};
+use bigdecimal::num_bigint::{BigInt, BigUint};
+use bigdecimal::{BigDecimal, RoundingMode, Zero};
+use std::fmt;
+use std::str::FromStr;
+
+// Exact amounts for balances and USD values. Chains report balances as
+// integers in the token's smallest unit (wei, lamports, yoctoNEAR); these are
+// kept as-is and only turned into decimals for arithmetic and display, so no
+// value ever goes through f64.
+
+/// An on-chain integer amount and the number of decimals of its token
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenAmount {
+    raw: BigInt,
+    decimals: u8,
+    /// Displayed amount when it isn't simply `raw / 10^decimals`
+    ui_amount: Option<BigDecimal>,
+}
+
+impl TokenAmount {
+    pub fn new(raw: impl Into<BigInt>, decimals: u8) -> Self {
+        Self {
+            raw: raw.into(),
+            decimals,
+            ui_amount: None,
+        }
+    }
+
+    /// Show (and value) the amount as `ui_amount` while keeping the on-chain
+    /// integer, e.g. a Token-2022 balance with accrued interest
+    pub fn with_ui_amount(mut self, ui_amount: BigDecimal) -> Self {
+        self.ui_amount = Some(ui_amount);
+        self
+    }
+
+    /// Parse a hex quantity of any size, e.g. an EVM uint256
+    pub fn from_hex(hex: &str, decimals: u8) -> Result<Self> {
+        Ok(Self::new(parse_hex_uint(hex)?, decimals))
+    }
+
+    /// Parse a decimal integer in the smallest unit, e.g. "1000000000000000000000000" yoctoNEAR
+    pub fn from_raw_str(raw: &str, decimals: u8) -> Result<Self> {
+        let raw = BigInt::from_str(raw.trim())
+            .with_context(|| format!("Invalid integer amount: {}", raw))?;
+        Ok(Self::new(raw, decimals))
+    }
+
+    /// An amount given in whole units, e.g. a bank balance of 1234.56. Values
+    /// with more fractional digits than `decimals` keep them.
+    pub fn from_decimal(value: &BigDecimal, decimals: u8) -> Self {
+        let decimals = value.fractional_digit_count().clamp(decimals as i64, u8::MAX as i64) as u8;
+        let (raw, _) = value.with_scale(decimals as i64).into_bigint_and_scale();
+        Self::new(raw, decimals)
+    }
+
+    /// The integer amount in the token's smallest unit
+    pub fn raw(&self) -> &BigInt {
+        &self.raw
+    }
+
+    pub fn decimals(&self) -> u8 {
+        self.decimals
+    }
+
+    /// The amount in whole tokens, exactly
+    pub fn to_decimal(&self) -> BigDecimal {
+        match &self.ui_amount {
+            Some(ui_amount) => ui_amount.clone(),
+            None => BigDecimal::new(self.raw.clone(), self.decimals as i64),
+        }
+    }
+}
+
+/// Whole tokens in plain notation, e.g. "1.5"
+impl fmt::Display for TokenAmount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&plain(&self.to_decimal()))
+    }
+}
+
+/// Parse an unsigned hex quantity ("0x" alone is zero)
+pub fn parse_hex_uint(hex: &str) -> Result<BigUint> {
+    let digits = hex.trim_start_matches("0x");
+    if digits.is_empty() {
+        return Ok(BigUint::zero());
+    }
+    BigUint::parse_bytes(digits.as_bytes(), 16)
+        .with_context(|| format!("Invalid hex quantity: {}", hex))
+}
+
+/// A price or amount reported as a float, taken at its shortest decimal
+/// representation (0.1 stays 0.1 instead of 0.1000000000000000055...)
+pub fn from_f64(value: f64) -> BigDecimal {
+    BigDecimal::from_str(&value.to_string()).unwrap_or_default()
+}
+
+/// Plain decimal notation without trailing zeros, e.g. "300" or "0.000001"
+pub fn plain(value: &BigDecimal) -> String {
+    let value = value.normalized();
+    if value.fractional_digit_count() < 0 {
+        value.with_scale(0).to_plain_string()
+    } else {
+        value.to_plain_string()
+    }
+}
+
+/// Rounded half up to exactly `places` fractional digits, e.g. "1.50"
+pub fn format_fixed(value: &BigDecimal, places: i64) -> String {
+    value.with_scale_round(places, RoundingMode::HalfUp).to_plain_string()
+}
+
+/// Dollar amount with cents and thousands separators, e.g. "1,234.56"
+pub fn format_usd(value: &BigDecimal) -> String {
+    group_thousands(&format_fixed(value, 2))
+}
+
+/// Insert thousands separators into the integer part of a plain decimal
+pub fn group_thousands(value: &str) -> String {
+    let (sign, digits) = match value.strip_prefix('-') {
+        Some(digits) => ("-", digits),
+        None => ("", value),
+    };
+    let (integer, fraction) = match digits.split_once('.') {
+        Some((integer, fraction)) => (integer, Some(fraction)),
+        None => (digits, None),
+    };
+
+    let mut grouped = String::new();
+    for (i, ch) in integer.chars().enumerate() {
+        if i > 0 && (integer.len() - i) % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(ch);
+    }
+
+    match fraction {
+        Some(fraction) => format!("{}{}.{}", sign, grouped, fraction),
+        None => format!("{}{}", sign, grouped),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_amount_is_exact() {
+        // Largest uint256, far beyond u128 and f64 precision
+        let max = TokenAmount::from_hex(&format!("0x{}", "f".repeat(64)), 18).unwrap();
+        assert_eq!(
+            max.raw().to_string(),
+            "115792089237316195423570985008687907853269984665640564039457584007913129639935"
+        );
+        assert_eq!(
+            max.to_string(),
+            "115792089237316195423570985008687907853269984665640564039457.584007913129639935"
+        );
+
+        let wei = TokenAmount::from_raw_str("1000000000000000001", 18).unwrap();
+        assert_eq!(wei.to_string(), "1.000000000000000001");
+        assert_eq!(TokenAmount::new(1_500_000u64, 6).to_string(), "1.5");
+        assert_eq!(TokenAmount::from_hex("0x", 18).unwrap().to_string(), "0");
+        assert!(TokenAmount::from_hex("0xzz", 18).is_err());
+    }
+
+    #[test]
+    fn test_from_decimal() {
+        let cents = TokenAmount::from_decimal(&BigDecimal::from_str("1234.5").unwrap(), 2);
+        assert_eq!(cents.raw().to_string(), "123450");
+        assert_eq!(cents.decimals(), 2);
+
+        // Extra precision is kept rather than rounded away
+        let precise = TokenAmount::from_decimal(&BigDecimal::from_str("0.123456").unwrap(), 2);
+        assert_eq!(precise.decimals(), 6);
+        assert_eq!(precise.to_string(), "0.123456");
+    }
+
+    #[test]
+    fn test_ui_amount_keeps_raw() {
+        let accrued = TokenAmount::new(1_000_000u64, 6).with_ui_amount(BigDecimal::from_str("1.05").unwrap());
+        assert_eq!(accrued.to_string(), "1.05");
+        assert_eq!(accrued.raw().to_string(), "1000000");
+    }
+
+    #[test]
+    fn test_decimal_math_has_no_float_drift() {
+        let sum: BigDecimal = ["0.1", "0.2"].iter().map(|v| from_f64(v.parse().unwrap())).sum();
+        assert_eq!(plain(&sum), "0.3");
+        assert_eq!(plain(&BigDecimal::from(300)), "300");
+    }
+
+    #[test]
+    fn test_format_usd() {
+        assert_eq!(format_usd(&BigDecimal::from_str("1234567.005").unwrap()), "1,234,567.01");
+        assert_eq!(format_usd(&BigDecimal::from_str("-1234.5").unwrap()), "-1,234.50");
+        assert_eq!(format_usd(&BigDecimal::zero()), "0.00");
+        assert_eq!(format_fixed(&BigDecimal::from_str("2").unwrap(), 6), "2.000000");
+    }
+}
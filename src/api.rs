@@ -0,0 +1,487 @@
+use crate::amount;
+use crate::evm::EvmClient;
+use crate::mercury::MercuryClient;
+use crate::portfolio::PortfolioSummary;
+use crate::price::PriceService;
+use crate::solana::SolanaClient;
+use crate::output::{company_totals, AccountBalanceDto, AccountDto, CompanyTotalDto, PortfolioDto};
+use crate::source::{self, TransactionRange, WalletTransaction};
+use crate::storage::{AddressBook, BankingService, Chain, TrackedAccount};
+use crate::web::{self, AppState};
+
+use axum::{
+    extract::{
+        rejection::{JsonRejection, QueryRejection},
+        Path, Query, State,
+    },
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use bigdecimal::BigDecimal;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use utoipa::{OpenApi, ToSchema};
+
+/// Versioned JSON API, mounted under /api/v1
+pub(crate) fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/accounts", get(list_accounts).post(create_account))
+        .route("/accounts/:name", get(get_account).delete(delete_account))
+        .route("/balances", get(get_balances))
+        .route("/balances/:name", get(get_account_balance))
+        .route("/companies", get(get_companies))
+        .route("/prices", get(get_prices))
+        .route("/transactions/:name", get(get_transactions))
+        .route("/openapi.json", get(openapi))
+        .fallback(not_found)
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Gringotts API", description = "Balances, accounts and transactions tracked by Gringotts"),
+    servers((url = "/api/v1")),
+    paths(
+        list_accounts,
+        create_account,
+        get_account,
+        delete_account,
+        get_balances,
+        get_account_balance,
+        get_companies,
+        get_prices,
+        get_transactions,
+    )
+)]
+struct ApiDoc;
+
+async fn openapi() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
+
+async fn not_found() -> ApiError {
+    ApiError::new(StatusCode::NOT_FOUND, "No such API endpoint")
+}
+
+// ==================== Errors ====================
+
+/// Error body returned with every non-2xx response
+#[derive(Serialize, ToSchema)]
+struct ErrorBody {
+    /// HTTP status code
+    status: u16,
+    error: String,
+}
+
+struct ApiError {
+    status: StatusCode,
+    message: String,
+}
+
+impl ApiError {
+    fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+        }
+    }
+
+    fn not_found(name: &str) -> Self {
+        Self::new(StatusCode::NOT_FOUND, format!("Account '{}' not found", name))
+    }
+
+    fn internal(e: anyhow::Error) -> Self {
+        Self::new(StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", e))
+    }
+
+    fn upstream(e: anyhow::Error) -> Self {
+        Self::new(StatusCode::BAD_GATEWAY, format!("{:#}", e))
+    }
+}
+
+// Malformed request bodies and query strings get the same JSON error shape
+impl From<JsonRejection> for ApiError {
+    fn from(rejection: JsonRejection) -> Self {
+        Self::new(rejection.status(), rejection.body_text())
+    }
+}
+
+impl From<QueryRejection> for ApiError {
+    fn from(rejection: QueryRejection) -> Self {
+        Self::new(rejection.status(), rejection.body_text())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = ErrorBody {
+            status: self.status.as_u16(),
+            error: self.message,
+        };
+        (self.status, Json(body)).into_response()
+    }
+}
+
+type ApiResult<T> = Result<T, ApiError>;
+
+fn load_book() -> ApiResult<AddressBook> {
+    AddressBook::load().map_err(ApiError::internal)
+}
+
+fn find_account(book: &AddressBook, name: &str) -> ApiResult<TrackedAccount> {
+    book.find_account(name).ok_or_else(|| ApiError::not_found(name))
+}
+
+// ==================== Schemas ====================
+
+/// Account to start tracking
+#[derive(Deserialize, ToSchema)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+enum CreateAccountRequest {
+    Wallet {
+        name: String,
+        address: String,
+        /// Chain name (e.g. solana, ethereum); auto-detected when omitted
+        chain: Option<String>,
+        #[serde(default)]
+        company: String,
+    },
+    Bank {
+        name: String,
+        account_id: String,
+        /// Banking service (mercury or circle)
+        service: String,
+        #[serde(default)]
+        company: String,
+    },
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+struct PricesQuery {
+    /// Comma-separated list of symbols, e.g. SOL,ETH,USDC
+    symbols: String,
+}
+
+/// One asset's balance change in a transaction; a wallet transaction that
+/// moved several assets has one entry per asset, all with the same `id`
+#[derive(Serialize, ToSchema)]
+struct TransactionDto {
+    /// Transaction id or signature
+    id: String,
+    /// RFC 3339 timestamp or date, when known
+    date: Option<String>,
+    description: String,
+    /// Signed amount as a decimal string, e.g. "-0.5"
+    amount: String,
+    /// Symbol of the asset, when known
+    currency: String,
+    /// Mint, contract address or coin type; absent for the chain's gas token and USD
+    asset_id: Option<String>,
+    status: String,
+    counterparty: Option<String>,
+}
+
+/// Entries for a wallet transaction: one per asset whose balance changed, or
+/// the fee alone for a transaction that only cost a fee
+fn wallet_transaction_dtos(tx: WalletTransaction, native_symbol: &str, block_label: &str) -> Vec<TransactionDto> {
+    let date = tx
+        .timestamp
+        .and_then(|ts| chrono::DateTime::from_timestamp(ts, 0))
+        .map(|dt| dt.to_rfc3339());
+    let description = tx.memo.unwrap_or_else(|| format!("{} {}", block_label, tx.block));
+    let status = if tx.success { "completed" } else { "failed" };
+    let counterparty = tx.counterparties.into_iter().next();
+
+    let mut rows: Vec<(BigDecimal, String, Option<String>)> = tx
+        .changes
+        .into_iter()
+        .map(|change| {
+            let currency = match (change.symbol, &change.asset_id) {
+                (Some(symbol), _) => symbol,
+                (None, Some(asset_id)) => asset_id.clone(),
+                (None, None) => native_symbol.to_string(),
+            };
+            (change.amount.to_decimal(), currency, change.asset_id)
+        })
+        .collect();
+    if rows.is_empty() {
+        if let Some(fee) = &tx.fee {
+            rows.push((-fee.to_decimal(), native_symbol.to_string(), None));
+        }
+    }
+
+    rows.into_iter()
+        .map(|(amount, currency, asset_id)| TransactionDto {
+            id: tx.id.clone(),
+            date: date.clone(),
+            description: description.clone(),
+            amount: amount::plain(&amount),
+            currency,
+            asset_id,
+            status: status.to_string(),
+            counterparty: counterparty.clone(),
+        })
+        .collect()
+}
+
+// ==================== Handlers ====================
+
+#[utoipa::path(get, path = "/accounts", responses(
+    (status = 200, description = "Tracked accounts", body = [AccountDto]),
+    (status = 500, body = ErrorBody),
+))]
+async fn list_accounts() -> ApiResult<Json<Vec<AccountDto>>> {
+    let book = load_book()?;
+    Ok(Json(book.accounts().iter().map(AccountDto::from).collect()))
+}
+
+#[utoipa::path(post, path = "/accounts", request_body = CreateAccountRequest, responses(
+    (status = 201, description = "Account added", body = AccountDto),
+    (status = 400, body = ErrorBody),
+    (status = 500, body = ErrorBody),
+))]
+async fn create_account(
+    request: Result<Json<CreateAccountRequest>, JsonRejection>,
+) -> ApiResult<(StatusCode, Json<AccountDto>)> {
+    let Json(request) = request?;
+    let mut book = load_book()?;
+
+    let name = match request {
+        CreateAccountRequest::Wallet { name, address, chain, company } => {
+            book.add_address(company, name.clone(), address, chain)
+                .map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, format!("{:#}", e)))?;
+            name
+        }
+        CreateAccountRequest::Bank { name, account_id, service, company } => {
+            book.add_banking_account(company, name.clone(), account_id, service)
+                .map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, format!("{:#}", e)))?;
+            name
+        }
+    };
+
+    book.save().map_err(ApiError::internal)?;
+
+    let account = find_account(&book, name.trim())?;
+    Ok((StatusCode::CREATED, Json(AccountDto::from(&account))))
+}
+
+#[utoipa::path(get, path = "/accounts/{name}", params(("name" = String, Path, description = "Account name or identifier")), responses(
+    (status = 200, body = AccountDto),
+    (status = 404, body = ErrorBody),
+))]
+async fn get_account(Path(name): Path<String>) -> ApiResult<Json<AccountDto>> {
+    let book = load_book()?;
+    Ok(Json(AccountDto::from(&find_account(&book, &name)?)))
+}
+
+#[utoipa::path(delete, path = "/accounts/{name}", params(("name" = String, Path, description = "Account name or identifier")), responses(
+    (status = 204, description = "Account removed"),
+    (status = 404, body = ErrorBody),
+))]
+async fn delete_account(Path(name): Path<String>) -> ApiResult<StatusCode> {
+    let mut book = load_book()?;
+
+    let removed = book.remove_by_identifier(&name).is_ok()
+        || book.remove_banking_account_by_identifier(&name).is_ok();
+    if !removed {
+        return Err(ApiError::not_found(&name));
+    }
+
+    book.save().map_err(ApiError::internal)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(get, path = "/balances", responses(
+    (status = 200, body = PortfolioDto),
+    (status = 500, body = ErrorBody),
+))]
+async fn get_balances(State(state): State<AppState>) -> ApiResult<Json<PortfolioDto>> {
+    let book = load_book()?;
+    let fetched = web::fetch_priced_snapshots(&book.accounts(), &state).await;
+    Ok(Json(PortfolioDto::new(&fetched.snapshots, &fetched.prices, &fetched.failures)))
+}
+
+#[utoipa::path(get, path = "/balances/{name}", params(("name" = String, Path, description = "Account name or identifier")), responses(
+    (status = 200, body = AccountBalanceDto),
+    (status = 404, body = ErrorBody),
+    (status = 502, description = "The chain or banking API failed", body = ErrorBody),
+))]
+async fn get_account_balance(State(state): State<AppState>, Path(name): Path<String>) -> ApiResult<Json<AccountBalanceDto>> {
+    let book = load_book()?;
+    let account = find_account(&book, &name)?;
+
+    let mut snapshot = source::fetch_snapshot(&account, &state.rpc).await.map_err(ApiError::upstream)?;
+    let prices = web::fetch_prices(std::slice::from_ref(&snapshot)).await;
+    snapshot.enrich_from_cache(&prices);
+
+    Ok(Json(AccountBalanceDto::from(&snapshot)))
+}
+
+#[utoipa::path(get, path = "/companies", responses(
+    (status = 200, description = "USD totals per company", body = [CompanyTotalDto]),
+    (status = 500, body = ErrorBody),
+))]
+async fn get_companies(State(state): State<AppState>) -> ApiResult<Json<Vec<CompanyTotalDto>>> {
+    let book = load_book()?;
+    let fetched = web::fetch_priced_snapshots(&book.accounts(), &state).await;
+    Ok(Json(company_totals(PortfolioSummary::from_snapshots(&fetched.snapshots))))
+}
+
+#[utoipa::path(get, path = "/prices", params(PricesQuery), responses(
+    (status = 200, description = "Symbol -> USD price; symbols without a price are omitted", body = BTreeMap<String, f64>),
+    (status = 400, body = ErrorBody),
+    (status = 502, body = ErrorBody),
+))]
+async fn get_prices(query: Result<Query<PricesQuery>, QueryRejection>) -> ApiResult<Json<BTreeMap<String, f64>>> {
+    let Query(query) = query?;
+    let symbols: Vec<String> = query
+        .symbols
+        .split(',')
+        .map(|s| s.trim().to_uppercase())
+        .filter(|s| !s.is_empty())
+        .collect();
+    if symbols.is_empty() {
+        return Err(ApiError::new(StatusCode::BAD_REQUEST, "No symbols given"));
+    }
+
+    let price_service = PriceService::new().map_err(ApiError::internal)?;
+    let prices = price_service.batch_fetch_prices(&symbols).await.map_err(ApiError::upstream)?;
+    Ok(Json(prices.into_iter().collect()))
+}
+
+#[utoipa::path(get, path = "/transactions/{name}", params(("name" = String, Path, description = "Account name or identifier")), responses(
+    (status = 200, body = [TransactionDto]),
+    (status = 404, body = ErrorBody),
+    (status = 501, description = "Transaction history is not available for this chain or service", body = ErrorBody),
+    (status = 502, body = ErrorBody),
+))]
+async fn get_transactions(State(state): State<AppState>, Path(name): Path<String>) -> ApiResult<Json<Vec<TransactionDto>>> {
+    let book = load_book()?;
+    let account = find_account(&book, &name)?;
+
+    let transactions = match &account {
+        TrackedAccount::Bank(bank) if bank.service == BankingService::Mercury => {
+            let client = MercuryClient::new().map_err(ApiError::internal)?;
+            let txs = client
+                .get_transactions(&bank.account_id, None, None)
+                .await
+                .map_err(ApiError::upstream)?;
+
+            txs.into_iter()
+                .map(|tx| TransactionDto {
+                    description: tx
+                        .bank_description
+                        .or(tx.note)
+                        .or(tx.external_memo)
+                        .unwrap_or_else(|| tx.kind.clone()),
+                    date: Some(tx.posted_at.unwrap_or(tx.created_at)),
+                    id: tx.id,
+                    amount: amount::plain(&amount::from_f64(tx.amount)),
+                    currency: "USD".to_string(),
+                    asset_id: None,
+                    status: tx.status,
+                    counterparty: tx.counterparty_name,
+                })
+                .collect()
+        }
+        TrackedAccount::Wallet(wallet) if wallet.chain == Chain::Solana || wallet.chain.is_evm() => {
+            let endpoints = state.rpc.endpoints_for(&wallet.chain);
+            let range = TransactionRange {
+                limit: Some(50),
+                ..Default::default()
+            };
+            let txs = if wallet.chain == Chain::Solana {
+                SolanaClient::new(endpoints).get_transactions(&wallet.address, range).await
+            } else {
+                EvmClient::new(endpoints, wallet.chain.clone()).get_transactions(&wallet.address, range).await
+            }
+            .map_err(ApiError::upstream)?;
+            let block_label = if wallet.chain == Chain::Solana { "Slot" } else { "Block" };
+
+            txs.into_iter()
+                .flat_map(|tx| wallet_transaction_dtos(tx, wallet.chain.native_token_symbol(), block_label))
+                .collect()
+        }
+        _ => {
+            return Err(ApiError::new(
+                StatusCode::NOT_IMPLEMENTED,
+                format!("Transaction history is not available for {} accounts", account.source_name()),
+            ));
+        }
+    };
+
+    Ok(Json(transactions))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create_account_request_kinds() {
+        let wallet: CreateAccountRequest =
+            serde_json::from_str(r#"{"kind": "wallet", "name": "Hot", "address": "abc"}"#).unwrap();
+        assert!(matches!(wallet, CreateAccountRequest::Wallet { chain: None, ref company, .. } if company.is_empty()));
+
+        let bank: CreateAccountRequest = serde_json::from_str(
+            r#"{"kind": "bank", "name": "Ops", "account_id": "123", "service": "mercury", "company": "Acme"}"#,
+        )
+        .unwrap();
+        assert!(matches!(bank, CreateAccountRequest::Bank { ref company, .. } if company == "Acme"));
+
+        assert!(serde_json::from_str::<CreateAccountRequest>(r#"{"kind": "exchange", "name": "x"}"#).is_err());
+    }
+
+    #[test]
+    fn test_wallet_transaction_has_an_entry_per_change() {
+        use crate::amount::TokenAmount;
+        use crate::source::BalanceChange;
+
+        let usdc = "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48";
+        let swap = WalletTransaction {
+            id: "0xaa".to_string(),
+            block: 100,
+            timestamp: Some(1_700_000_000),
+            success: true,
+            memo: None,
+            fee: Some(TokenAmount::new(21_000_000_000_000u64, 18)),
+            changes: vec![
+                BalanceChange {
+                    asset_id: None,
+                    symbol: Some("ETH".to_string()),
+                    amount: TokenAmount::new(-500_000_000_000_000_000i64, 18),
+                },
+                BalanceChange {
+                    asset_id: Some(usdc.to_string()),
+                    symbol: Some("USDC".to_string()),
+                    amount: TokenAmount::new(1_234_500_000u64, 6),
+                },
+            ],
+            counterparties: vec!["0xde0b295669a9fd93d5f28d9ec85e40f4cb697bae".to_string()],
+        };
+
+        let entries = wallet_transaction_dtos(swap, "ETH", "Block");
+        assert_eq!(entries.len(), 2);
+        assert_eq!((entries[0].amount.as_str(), entries[0].currency.as_str()), ("-0.5", "ETH"));
+        assert!(entries[0].asset_id.is_none());
+        assert_eq!((entries[1].amount.as_str(), entries[1].currency.as_str()), ("1234.5", "USDC"));
+        assert_eq!(entries[1].asset_id.as_deref(), Some(usdc));
+        assert!(entries.iter().all(|entry| entry.id == "0xaa" && entry.description == "Block 100"));
+
+        let fee_only = WalletTransaction {
+            id: "0xbb".to_string(),
+            block: 101,
+            timestamp: None,
+            success: false,
+            memo: None,
+            fee: Some(TokenAmount::new(21_000_000_000_000u64, 18)),
+            changes: Vec::new(),
+            counterparties: Vec::new(),
+        };
+        let entries = wallet_transaction_dtos(fee_only, "ETH", "Block");
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].amount, "-0.000021");
+        assert_eq!(entries[0].status, "failed");
+    }
+}
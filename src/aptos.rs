@@ -1,30 +1,72 @@
+use crate::amount::TokenAmount;
+use crate::rpc::{self, HttpPool, RpcEndpoint};
+use crate::source::{AccountSnapshot, BalanceSource, Holding};
+use crate::storage::{Chain, TrackedAccount};
+use crate::token_cache::{self, TokenMetadata};
+use crate::token_list::{self, TokenList};
 use anyhow::{Context, Result};
-use serde::Serialize;
+use async_trait::async_trait;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeSet;
+
+const APT_COIN_TYPE: &str = "0x1::aptos_coin::AptosCoin";
+/// Metadata address of APT as a fungible asset (0xa), where migrated APT is held
+const APT_METADATA: &str = "0x000000000000000000000000000000000000000000000000000000000000000a";
+
+/// Fungible assets checked for every account
+const COMMON_ASSETS: &[(&str, &str)] = &[
+    ("0xbae207659db88bea0cbead6da0ed00aac12edcdda169e591cd41c94180b46f3b", "USDC"),
+    ("0x357b0b74bc833e95a115ad22604854d6b0fca151cecd94111770e5d6ffc9dc2b", "USDT"),
+];
+
+/// Aptos Labs indexer, used to discover the fungible assets and delegation
+/// pools an account holds
+const APTOS_INDEXER_URL: &str = "https://api.mainnet.aptoslabs.com/v1/graphql";
+
+/// Resources per page when listing an account's coin stores
+const RESOURCES_PAGE: usize = 1000;
 
 #[derive(Debug)]
-#[allow(dead_code)]
 pub struct TokenBalance {
+    /// Coin type (`0x...::module::Coin`), or the metadata address of a fungible asset
     pub coin_type: String,
+    pub name: Option<String>,
     pub symbol: Option<String>,
-    pub decimals: u8,
-    pub ui_amount: f64,
-    pub usd_price: Option<f64>,
-    pub usd_value: Option<f64>,
+    pub amount: TokenAmount,
+}
+
+/// APT delegated to a delegation pool, in octas
+#[derive(Debug, Default, PartialEq)]
+pub struct DelegatedStake {
+    pub pool: String,
+    pub active: u64,
+    /// Unlocked and ready to withdraw
+    pub inactive: u64,
+    /// Unlocking at the end of the current lockup cycle
+    pub pending_inactive: u64,
+}
+
+impl DelegatedStake {
+    pub fn octas(&self) -> u64 {
+        self.active + self.inactive + self.pending_inactive
+    }
 }
 
 #[derive(Debug)]
 pub struct AccountBalances {
-    pub apt_balance: f64,
-    pub apt_usd_price: Option<f64>,
-    pub apt_usd_value: Option<f64>,
-    #[allow(dead_code)]
+    /// False when the address has no account on chain yet; it can still
+    /// hold fungible assets
+    pub account_exists: bool,
+    pub apt_balance: TokenAmount,
     pub token_balances: Vec<TokenBalance>,
-    pub total_usd_value: Option<f64>,
+    pub delegated_stakes: Vec<DelegatedStake>,
 }
 
 pub struct AptosClient {
+    rpc: HttpPool,
     client: reqwest::Client,
-    api_url: String,
 }
 
 #[derive(Serialize)]
@@ -34,106 +76,389 @@ struct ViewRequest {
     arguments: Vec<String>,
 }
 
-impl AptosClient {
-    pub fn new(api_url: Option<String>) -> Self {
-        let url = api_url.unwrap_or_else(|| "https://fullnode.mainnet.aptoslabs.com/v1".to_string());
-        let client = reqwest::Client::builder()
-            .timeout(std::time::Duration::from_secs(30))
-            .build()
-            .unwrap_or_else(|_| reqwest::Client::new());
+/// Error body of the Aptos node API
+#[derive(Deserialize)]
+struct ApiError {
+    message: String,
+    error_code: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Resource {
+    #[serde(rename = "type")]
+    resource_type: String,
+    data: serde_json::Value,
+}
+
+/// `0x1::coin::CoinInfo<T>` and `0x1::fungible_asset::Metadata` share these fields
+#[derive(Deserialize)]
+struct AssetInfo {
+    name: String,
+    symbol: String,
+    decimals: u8,
+}
+
+#[derive(Deserialize)]
+struct IndexerResponse {
+    data: Option<IndexedAccount>,
+    #[serde(default)]
+    errors: Vec<IndexerError>,
+}
+
+#[derive(Deserialize)]
+struct IndexerError {
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct IndexedAccount {
+    current_fungible_asset_balances: Vec<IndexedBalance>,
+    delegator_distinct_pool: Vec<IndexedPool>,
+}
+
+#[derive(Deserialize)]
+struct IndexedBalance {
+    asset_type: String,
+    token_standard: String,
+}
+
+#[derive(Deserialize)]
+struct IndexedPool {
+    pool_address: String,
+}
 
+/// Full-length lowercase form of an account or object address
+fn normalize_address(address: &str) -> Result<String> {
+    let hex = address.strip_prefix("0x").unwrap_or(address);
+    if hex.is_empty() || hex.len() > 64 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        anyhow::bail!("Invalid Aptos address format: must be hexadecimal");
+    }
+    Ok(format!("0x{:0>64}", hex.to_lowercase()))
+}
+
+/// Token list entries are coin types or fungible asset metadata addresses
+fn normalize_asset(asset: &str) -> Result<String> {
+    if asset.contains("::") {
+        Ok(asset.to_string())
+    } else {
+        normalize_address(asset)
+    }
+}
+
+/// The body of a successful response. A 404 with `not_found` as its error
+/// code gives None; any other error carries the API's message.
+async fn read_json<T: DeserializeOwned>(response: reqwest::Response, not_found: &str) -> Result<Option<T>> {
+    let status = response.status();
+    if status.is_success() {
+        return rpc::json_body(response).await.context("Failed to parse Aptos API response").map(Some);
+    }
+
+    let error: Option<ApiError> = response.json().await.ok();
+    match error {
+        Some(error) if status == reqwest::StatusCode::NOT_FOUND && error.error_code.as_deref() == Some(not_found) => Ok(None),
+        Some(error) => anyhow::bail!("Aptos API error ({}): {}", status, error.message),
+        None => anyhow::bail!("Aptos API error ({})", status),
+    }
+}
+
+impl AptosClient {
+    pub fn new(endpoints: Vec<RpcEndpoint>) -> Self {
         Self {
-            client,
-            api_url: url,
+            rpc: HttpPool::http(endpoints),
+            client: reqwest::Client::new(),
         }
     }
 
-    pub async fn get_balances(&self, address: &str) -> Result<AccountBalances> {
-        // Normalize address: add 0x prefix if missing and validate hex format
-        let normalized_address = if address.starts_with("0x") {
-            address.to_string()
-        } else {
-            // Validate it's valid hex before adding prefix
-            if !address.chars().all(|c| c.is_ascii_hexdigit()) {
-                anyhow::bail!("Invalid Aptos address format: must be hexadecimal");
-            }
-            format!("0x{}", address)
-        };
-
-        // Use the view function to get balance (recommended approach)
-        // This is more reliable than querying CoinStore resource
+    async fn view<T: DeserializeOwned>(&self, function: &str, type_arguments: &[&str], arguments: &[&str]) -> Result<T> {
         let view_request = ViewRequest {
-            function: "0x1::coin::balance".to_string(),
-            type_arguments: vec!["0x1::aptos_coin::AptosCoin".to_string()],
-            arguments: vec![normalized_address.clone()],
+            function: function.to_string(),
+            type_arguments: type_arguments.iter().map(|s| s.to_string()).collect(),
+            arguments: arguments.iter().map(|s| s.to_string()).collect(),
         };
 
-        let url = format!("{}/view", self.api_url);
         let response = self
-            .client
-            .post(&url)
-            .json(&view_request)
-            .send()
+            .rpc
+            .post("/view", &view_request)
             .await
             .context("Failed to send view request")?;
 
-        if !response.status().is_success() {
-            // If view function fails, account might not exist or have no balance
-            return Ok(AccountBalances {
-                apt_balance: 0.0,
-                apt_usd_price: None,
-                apt_usd_value: None,
-                token_balances: Vec::new(),
-                total_usd_value: None,
-            });
+        read_json(response, "")
+            .await
+            .with_context(|| format!("{} failed", function))?
+            .ok_or_else(|| anyhow::anyhow!("{} failed", function))
+    }
+
+    /// Coin type and raw balance of each coin store the account holds, or
+    /// None when the account doesn't exist
+    async fn coin_stores(&self, address: &str) -> Result<Option<Vec<(String, u64)>>> {
+        let base = format!("/accounts/{}/resources?limit={}", address, RESOURCES_PAGE);
+        let mut path = base.clone();
+        let mut stores = Vec::new();
+
+        loop {
+            let response = self.rpc.get(&path).await.context("Failed to fetch account resources")?;
+            let cursor = response
+                .headers()
+                .get("x-aptos-cursor")
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string);
+
+            let Some(resources) = read_json::<Vec<Resource>>(response, "account_not_found").await? else {
+                return Ok(None);
+            };
+            for resource in resources {
+                let Some(coin_type) = resource
+                    .resource_type
+                    .strip_prefix("0x1::coin::CoinStore<")
+                    .and_then(|rest| rest.strip_suffix('>'))
+                else {
+                    continue;
+                };
+                let value = resource.data["coin"]["value"]
+                    .as_str()
+                    .and_then(|value| value.parse().ok())
+                    .with_context(|| format!("Invalid coin store for {}", coin_type))?;
+                stores.push((coin_type.to_string(), value));
+            }
+
+            match cursor {
+                Some(cursor) => path = format!("{}&start={}", base, urlencoding::encode(&cursor)),
+                None => return Ok(Some(stores)),
+            }
         }
+    }
 
-        // Parse response - view functions return an array with the result
-        let result: Vec<String> = response
+    /// Balance of the account's primary store of a fungible asset (0 without one)
+    async fn fungible_balance(&self, address: &str, metadata: &str) -> Result<u64> {
+        let result: Vec<String> = self
+            .view(
+                "0x1::primary_fungible_store::balance",
+                &["0x1::fungible_asset::Metadata"],
+                &[address, metadata],
+            )
+            .await?;
+
+        result
+            .first()
+            .and_then(|s| s.parse().ok())
+            .ok_or_else(|| anyhow::anyhow!("Invalid fungible asset balance"))
+    }
+
+    async fn delegated_stake(&self, pool: &str, address: &str) -> Result<DelegatedStake> {
+        let result: Vec<String> = self.view("0x1::delegation_pool::get_stake", &[], &[pool, address]).await?;
+        let amounts: Vec<u64> = result.iter().filter_map(|s| s.parse().ok()).collect();
+        let [active, inactive, pending_inactive] = amounts[..] else {
+            anyhow::bail!("Invalid delegation pool stake");
+        };
+
+        Ok(DelegatedStake {
+            pool: pool.to_string(),
+            active,
+            inactive,
+            pending_inactive,
+        })
+    }
+
+    /// Name, symbol and decimals of a coin type (from its `CoinInfo`) or a
+    /// fungible asset (from its `Metadata`), via the token list or cache
+    async fn asset_metadata(&self, asset: &str, token_list: &TokenList) -> Result<TokenMetadata> {
+        if let Some(metadata) = token_list.metadata(asset) {
+            return Ok(metadata);
+        }
+        if let Some(metadata) = token_cache::with_cache(|cache| cache.metadata(&Chain::Aptos, asset).cloned()) {
+            return Ok(metadata);
+        }
+
+        let (owner, resource_type) = match asset.split_once("::") {
+            Some((creator, _)) => (creator.to_string(), format!("0x1::coin::CoinInfo<{}>", asset)),
+            None => (asset.to_string(), "0x1::fungible_asset::Metadata".to_string()),
+        };
+        let path = format!("/accounts/{}/resource/{}", owner, urlencoding::encode(&resource_type));
+        let response = self.rpc.get(&path).await.context("Failed to fetch asset metadata")?;
+        let resource: Resource = read_json(response, "resource_not_found")
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("No {} resource", resource_type))?;
+        let info: AssetInfo = serde_json::from_value(resource.data).context("Invalid asset metadata")?;
+
+        let metadata = TokenMetadata {
+            decimals: info.decimals,
+            symbol: Some(info.symbol),
+            name: Some(info.name),
+        };
+        token_cache::with_cache(|cache| cache.insert_metadata(&Chain::Aptos, asset, metadata.clone()));
+        Ok(metadata)
+    }
+
+    /// Fungible assets (metadata addresses) and delegation pools the indexer
+    /// has seen the account hold
+    async fn discover(&self, address: &str) -> Result<(Vec<String>, Vec<String>)> {
+        let query = r#"query Holdings($owner: String!) {
+            current_fungible_asset_balances(where: {owner_address: {_eq: $owner}, amount: {_gt: "0"}}) {
+                asset_type
+                token_standard
+            }
+            delegator_distinct_pool(where: {delegator_address: {_eq: $owner}}) {
+                pool_address
+            }
+        }"#;
+
+        let response: IndexerResponse = self
+            .client
+            .post(APTOS_INDEXER_URL)
+            .json(&json!({ "query": query, "variables": { "owner": address } }))
+            .send()
+            .await
+            .context("Failed to send request to Aptos indexer")?
+            .error_for_status()
+            .context("Aptos indexer error")?
             .json()
             .await
-            .context("Failed to parse view response")?;
+            .context("Failed to parse Aptos indexer response")?;
 
-        let balance_octas: u64 = result.first()
-            .and_then(|s| s.parse().ok())
-            .unwrap_or(0);
+        if let Some(error) = response.errors.first() {
+            anyhow::bail!("Aptos indexer error: {}", error.message);
+        }
+        let indexed = response.data.ok_or_else(|| anyhow::anyhow!("No data in Aptos indexer response"))?;
+
+        let assets = indexed
+            .current_fungible_asset_balances
+            .into_iter()
+            .filter(|balance| balance.token_standard == "v2")
+            .filter_map(|balance| normalize_address(&balance.asset_type).ok())
+            .collect();
+        let pools = indexed
+            .delegator_distinct_pool
+            .into_iter()
+            .filter_map(|pool| normalize_address(&pool.pool_address).ok())
+            .collect();
+        Ok((assets, pools))
+    }
+
+    pub async fn get_balances(&self, address: &str) -> Result<AccountBalances> {
+        let address = normalize_address(address)?;
+
+        let coin_stores = self.coin_stores(&address).await?;
+        let account_exists = coin_stores.is_some();
+        let coin_stores = coin_stores.unwrap_or_default();
 
-        // Convert octas to APT (1 APT = 10^8 octas)
-        let apt_balance = balance_octas as f64 / 100_000_000.0;
+        // Fungible assets and pools to check: the common assets, the user's
+        // token list and whatever the indexer knows about
+        let token_list = token_list::load(&Chain::Aptos, normalize_asset).unwrap_or_else(|e| {
+            eprintln!("Warning: Ignoring Aptos token list: {:#}", e);
+            TokenList::default()
+        });
+        let mut assets: BTreeSet<String> = COMMON_ASSETS.iter().map(|(metadata, _)| metadata.to_string()).collect();
+        assets.extend(
+            token_list
+                .tokens
+                .iter()
+                .filter(|token| !token.address.contains("::"))
+                .map(|token| token.address.clone()),
+        );
+        let mut pools: BTreeSet<String> = token_list.staking_pools.iter().cloned().collect();
 
-        // Token balances would require querying other coin stores
-        // For now, we'll just return the native APT balance
-        let token_balances = Vec::new();
+        match self.discover(&address).await {
+            Ok((indexed_assets, indexed_pools)) => {
+                assets.extend(indexed_assets);
+                pools.extend(indexed_pools);
+            }
+            Err(e) => eprintln!("Warning: Token discovery failed on Aptos: {:#}", e),
+        }
+        // APT held as a fungible asset is counted with the coin store
+        assets.remove(APT_METADATA);
+
+        // 1 APT = 10^8 octas; migrated APT sits in the 0xa primary store
+        let apt_coin = coin_stores
+            .iter()
+            .find(|(coin_type, _)| coin_type == APT_COIN_TYPE)
+            .map_or(0, |(_, value)| *value);
+        let apt_fungible = self.fungible_balance(&address, APT_METADATA).await?;
+        let apt_balance = TokenAmount::new(u128::from(apt_coin) + u128::from(apt_fungible), 8);
+
+        let fungible_results =
+            futures::future::join_all(assets.iter().map(|metadata| self.fungible_balance(&address, metadata))).await;
+        let mut held: Vec<(String, u64)> = coin_stores
+            .into_iter()
+            .filter(|(coin_type, value)| coin_type != APT_COIN_TYPE && *value > 0)
+            .collect();
+        for (metadata, result) in assets.iter().zip(fungible_results) {
+            match result {
+                Ok(0) => {}
+                Ok(balance) => held.push((metadata.clone(), balance)),
+                Err(e) => eprintln!("Warning: Failed to query token {} balance: {:#}", metadata, e),
+            }
+        }
+
+        let metadata_results =
+            futures::future::join_all(held.iter().map(|(asset, _)| self.asset_metadata(asset, &token_list))).await;
+        let mut token_balances = Vec::new();
+        for ((asset, balance), metadata) in held.into_iter().zip(metadata_results) {
+            match metadata {
+                Ok(metadata) => token_balances.push(TokenBalance {
+                    coin_type: asset,
+                    name: metadata.name,
+                    symbol: metadata.symbol,
+                    amount: TokenAmount::new(balance, metadata.decimals),
+                }),
+                Err(e) => eprintln!("Warning: Failed to query token {} metadata: {:#}", asset, e),
+            }
+        }
+        token_cache::save();
+
+        let stake_results =
+            futures::future::join_all(pools.iter().map(|pool| self.delegated_stake(pool, &address))).await;
+        let mut delegated_stakes = Vec::new();
+        for (pool, result) in pools.iter().zip(stake_results) {
+            match result {
+                Ok(stake) if stake.octas() > 0 => delegated_stakes.push(stake),
+                Ok(_) => {}
+                Err(e) => eprintln!("Warning: Failed to query delegation pool {}: {:#}", pool, e),
+            }
+        }
 
         Ok(AccountBalances {
+            account_exists,
             apt_balance,
-            apt_usd_price: None,
-            apt_usd_value: None,
             token_balances,
-            total_usd_value: None,
+            delegated_stakes,
         })
     }
 }
 
-// Implement PriceEnrichable trait for Aptos balances
-impl crate::PriceEnrichable for AccountBalances {
-    const NATIVE_SYMBOL: &'static str = "APT";
+#[async_trait]
+impl BalanceSource for AptosClient {
+    async fn fetch_snapshot(&self, account: &TrackedAccount) -> Result<AccountSnapshot> {
+        let balances = self.get_balances(account.identifier()).await?;
 
-    fn native_balance(&self) -> f64 {
-        self.apt_balance
-    }
+        let mut holdings = vec![Holding::native("APT", balances.apt_balance)];
+        holdings.extend(balances.delegated_stakes.iter().map(|stake| {
+            Holding::staked("APT", stake.pool.clone(), TokenAmount::new(stake.octas(), 8))
+        }));
+        holdings.extend(balances.token_balances.into_iter().map(|token| {
+            Holding::token(token.coin_type, token.symbol, token.name, token.amount)
+        }));
 
-    fn set_native_usd_price(&mut self, price: f64) {
-        self.apt_usd_price = Some(price);
+        let mut snapshot = AccountSnapshot::new(account, holdings);
+        if !balances.account_exists {
+            snapshot = snapshot.with_detail("Account", "Not created on chain");
+        }
+        Ok(snapshot.with_detail("RPC Endpoint", self.rpc.served_by()))
     }
+}
 
-    fn set_native_usd_value(&mut self, value: f64) {
-        self.apt_usd_value = Some(value);
-    }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    fn set_total_usd_value(&mut self, value: f64) {
-        self.total_usd_value = Some(value);
-    }
+    #[test]
+    fn test_normalize_address() {
+        assert_eq!(normalize_address("0xA").unwrap(), APT_METADATA);
+        assert_eq!(normalize_address("a").unwrap(), APT_METADATA);
+        assert!(normalize_address("0x").is_err());
+        assert!(normalize_address("0xzz").is_err());
+        assert!(normalize_address(&format!("0x{}", "1".repeat(65))).is_err());
 
-    // Aptos doesn't have token balances yet, use default implementation
+        // Coin types in token lists are kept as written
+        assert_eq!(normalize_asset("0x1::aptos_coin::AptosCoin").unwrap(), APT_COIN_TYPE);
+    }
 }
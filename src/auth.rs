@@ -0,0 +1,579 @@
+use crate::secrets;
+use crate::web::AppState;
+
+use anyhow::{Context, Result};
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderMap, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Redirect, Response},
+    Json,
+};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+pub const SESSION_COOKIE: &str = "gringotts_session";
+/// Readable by the page's JavaScript, which echoes it back in the CSRF header
+pub const CSRF_COOKIE: &str = "gringotts_csrf";
+pub const CSRF_HEADER: &str = "x-csrf-token";
+/// Set with the login form and echoed in it, so a login can't be posted from another site
+pub const LOGIN_CSRF_COOKIE: &str = "gringotts_login";
+
+const SESSION_TTL: Duration = Duration::from_secs(12 * 60 * 60);
+const LOGIN_FORM_TTL: Duration = Duration::from_secs(30 * 60);
+
+/// Failed logins allowed per client IP or username before backoff starts
+const MAX_FAILED_LOGINS: u32 = 5;
+/// Lockout after MAX_FAILED_LOGINS failures, doubling with each further one
+const LOGIN_BACKOFF: Duration = Duration::from_secs(30);
+const MAX_LOGIN_BACKOFF: Duration = Duration::from_secs(15 * 60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Role {
+    /// Can view balances, history and transactions
+    ReadOnly,
+    /// Can also add and remove tracked accounts
+    Admin,
+}
+
+impl Role {
+    pub fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "read-only" | "readonly" | "viewer" => Ok(Role::ReadOnly),
+            "admin" => Ok(Role::Admin),
+            _ => anyhow::bail!("Unknown role: {}. Use read-only or admin", s),
+        }
+    }
+
+    pub fn display_name(&self) -> &str {
+        match self {
+            Role::ReadOnly => "read-only",
+            Role::Admin => "admin",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct User {
+    pub username: String,
+    /// Argon2 hash in PHC string format
+    password_hash: String,
+    pub role: Role,
+    /// SHA-256 hashes of the user's API bearer tokens
+    #[serde(default)]
+    token_hashes: Vec<String>,
+}
+
+impl User {
+    pub fn token_count(&self) -> usize {
+        self.token_hashes.len()
+    }
+}
+
+/// Web server users, stored in ~/.gringotts/users.json
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct UserStore {
+    pub users: Vec<User>,
+}
+
+impl UserStore {
+    pub fn load() -> Result<Self> {
+        let path = Self::get_storage_path()?;
+
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = fs::read_to_string(&path)
+            .context("Failed to read users file")?;
+
+        serde_json::from_str(&content)
+            .context("Failed to parse users file")
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = Self::get_storage_path()?;
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .context("Failed to create storage directory")?;
+        }
+
+        let content = serde_json::to_string_pretty(self)
+            .context("Failed to serialize users")?;
+
+        // Password and token hashes are only readable by the owner
+        secrets::write_private_file(&path, &content)
+            .context("Failed to write users file")?;
+
+        Ok(())
+    }
+
+    fn get_storage_path() -> Result<PathBuf> {
+        let home = dirs::home_dir()
+            .context("Failed to get home directory")?;
+
+        Ok(home.join(".gringotts").join("users.json"))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.users.is_empty()
+    }
+
+    pub fn add_user(&mut self, username: &str, password: &str, role: Role) -> Result<()> {
+        let username = username.trim();
+        if username.is_empty() {
+            anyhow::bail!("Username cannot be empty");
+        }
+        if password.len() < 8 {
+            anyhow::bail!("Password must be at least 8 characters");
+        }
+        if self.users.iter().any(|u| u.username == username) {
+            anyhow::bail!("User '{}' already exists", username);
+        }
+
+        let salt = SaltString::generate(&mut OsRng);
+        let password_hash = Argon2::default()
+            .hash_password(password.as_bytes(), &salt)
+            .map_err(|e| anyhow::anyhow!("Failed to hash password: {}", e))?
+            .to_string();
+
+        self.users.push(User {
+            username: username.to_string(),
+            password_hash,
+            role,
+            token_hashes: Vec::new(),
+        });
+        Ok(())
+    }
+
+    pub fn remove_user(&mut self, username: &str) -> Result<()> {
+        let initial_len = self.users.len();
+        self.users.retain(|u| u.username != username);
+
+        if self.users.len() == initial_len {
+            anyhow::bail!("User '{}' not found", username);
+        }
+
+        Ok(())
+    }
+
+    /// Create a new API token for a user. Only its hash is stored, so the
+    /// returned token cannot be shown again.
+    pub fn create_token(&mut self, username: &str) -> Result<String> {
+        let user = self
+            .users
+            .iter_mut()
+            .find(|u| u.username == username)
+            .with_context(|| format!("User '{}' not found", username))?;
+
+        let token = format!("grg_{}", random_hex(32));
+        user.token_hashes.push(hash_token(&token));
+        Ok(token)
+    }
+
+    pub fn revoke_tokens(&mut self, username: &str) -> Result<usize> {
+        let user = self
+            .users
+            .iter_mut()
+            .find(|u| u.username == username)
+            .with_context(|| format!("User '{}' not found", username))?;
+
+        let count = user.token_hashes.len();
+        user.token_hashes.clear();
+        Ok(count)
+    }
+
+    pub fn verify_password(&self, username: &str, password: &str) -> Option<Principal> {
+        let user = self.users.iter().find(|u| u.username == username)?;
+        let hash = PasswordHash::new(&user.password_hash).ok()?;
+
+        Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .ok()
+            .map(|_| Principal::from(user))
+    }
+
+    pub fn verify_token(&self, token: &str) -> Option<Principal> {
+        let hash = hash_token(token);
+        self.users
+            .iter()
+            .find(|u| u.token_hashes.iter().any(|h| constant_time_eq(h.as_bytes(), hash.as_bytes())))
+            .map(Principal::from)
+    }
+}
+
+/// The authenticated caller of a request
+#[derive(Debug, Clone)]
+pub struct Principal {
+    pub username: String,
+    pub role: Role,
+}
+
+impl Principal {
+    /// Used for every request when authentication is disabled
+    pub fn local() -> Self {
+        Self {
+            username: "local".to_string(),
+            role: Role::Admin,
+        }
+    }
+
+    pub fn is_admin(&self) -> bool {
+        self.role == Role::Admin
+    }
+}
+
+impl From<&User> for Principal {
+    fn from(user: &User) -> Self {
+        Self {
+            username: user.username.clone(),
+            role: user.role,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Session {
+    principal: Principal,
+    csrf_token: String,
+    expires_at: Instant,
+}
+
+/// Recent failed logins from one client IP or for one username
+struct FailedLogins {
+    count: u32,
+    last_failure: Instant,
+    locked_until: Option<Instant>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum LoginError {
+    InvalidCredentials,
+    /// Too many failed attempts; retry after the given time
+    Throttled(Duration),
+}
+
+/// Users plus the in-memory sessions and failed logins of the running server
+pub struct Auth {
+    users: UserStore,
+    sessions: Mutex<HashMap<String, Session>>,
+    failed_logins: Mutex<HashMap<String, FailedLogins>>,
+}
+
+impl Auth {
+    pub fn new(users: UserStore) -> Self {
+        Self {
+            users,
+            sessions: Mutex::new(HashMap::new()),
+            failed_logins: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Check a username and password, returning (session id, CSRF token) on
+    /// success. Repeated failures from the same client or for the same user
+    /// are locked out with an increasing backoff.
+    pub fn login(&self, client: IpAddr, username: &str, password: &str) -> std::result::Result<(String, String), LoginError> {
+        let keys = [format!("ip:{}", client), format!("user:{}", username.to_lowercase())];
+        if let Some(wait) = self.login_backoff(&keys) {
+            return Err(LoginError::Throttled(wait));
+        }
+
+        let Some(principal) = self.users.verify_password(username, password) else {
+            self.record_failed_login(&keys);
+            return Err(LoginError::InvalidCredentials);
+        };
+        // The client's count is left to expire, so one valid account can't
+        // be used to reset the limit while guessing others
+        self.failed_logins.lock().expect("login lock poisoned").remove(&keys[1]);
+
+        let session_id = random_hex(32);
+        let csrf_token = random_hex(32);
+
+        let mut sessions = self.sessions.lock().expect("session lock poisoned");
+        sessions.retain(|_, s| s.expires_at > Instant::now());
+        sessions.insert(
+            session_id.clone(),
+            Session {
+                principal,
+                csrf_token: csrf_token.clone(),
+                expires_at: Instant::now() + SESSION_TTL,
+            },
+        );
+
+        Ok((session_id, csrf_token))
+    }
+
+    fn login_backoff(&self, keys: &[String]) -> Option<Duration> {
+        let now = Instant::now();
+        let failed_logins = self.failed_logins.lock().expect("login lock poisoned");
+        keys.iter()
+            .filter_map(|key| failed_logins.get(key)?.locked_until?.checked_duration_since(now))
+            .max()
+    }
+
+    fn record_failed_login(&self, keys: &[String]) {
+        let now = Instant::now();
+        let mut failed_logins = self.failed_logins.lock().expect("login lock poisoned");
+        failed_logins.retain(|_, failures| now.duration_since(failures.last_failure) < MAX_LOGIN_BACKOFF);
+
+        for key in keys {
+            let failures = failed_logins.entry(key.clone()).or_insert(FailedLogins {
+                count: 0,
+                last_failure: now,
+                locked_until: None,
+            });
+            failures.count += 1;
+            failures.last_failure = now;
+            if failures.count >= MAX_FAILED_LOGINS {
+                let doublings = (failures.count - MAX_FAILED_LOGINS).min(16);
+                failures.locked_until = Some(now + (LOGIN_BACKOFF * 2u32.pow(doublings)).min(MAX_LOGIN_BACKOFF));
+            }
+        }
+    }
+
+    pub fn logout(&self, session_id: &str) {
+        self.sessions.lock().expect("session lock poisoned").remove(session_id);
+    }
+
+    fn session(&self, session_id: &str) -> Option<Session> {
+        let mut sessions = self.sessions.lock().expect("session lock poisoned");
+        match sessions.get(session_id) {
+            Some(s) if s.expires_at > Instant::now() => Some(s.clone()),
+            Some(_) => {
+                sessions.remove(session_id);
+                None
+            }
+            None => None,
+        }
+    }
+}
+
+/// Session and CSRF cookies set after a successful login
+pub fn login_cookies(session_id: &str, csrf_token: &str) -> [String; 2] {
+    let max_age = SESSION_TTL.as_secs();
+    [
+        format!("{}={}; Path=/; HttpOnly; SameSite=Strict; Max-Age={}", SESSION_COOKIE, session_id, max_age),
+        format!("{}={}; Path=/; SameSite=Strict; Max-Age={}", CSRF_COOKIE, csrf_token, max_age),
+    ]
+}
+
+/// A token for the login form, and the cookie that must come back with it
+pub fn login_form_token() -> (String, String) {
+    let token = random_hex(32);
+    let cookie = format!(
+        "{}={}; Path=/login; HttpOnly; SameSite=Strict; Max-Age={}",
+        LOGIN_CSRF_COOKIE,
+        token,
+        LOGIN_FORM_TTL.as_secs()
+    );
+    (token, cookie)
+}
+
+/// Whether a posted login form carries the token from its own cookie
+pub fn verify_login_form(headers: &HeaderMap, form_token: &str) -> bool {
+    cookie_value(headers, LOGIN_CSRF_COOKIE)
+        .is_some_and(|token| !token.is_empty() && constant_time_eq(token.as_bytes(), form_token.as_bytes()))
+}
+
+pub fn logout_cookies() -> [String; 2] {
+    [
+        format!("{}=; Path=/; HttpOnly; SameSite=Strict; Max-Age=0", SESSION_COOKIE),
+        format!("{}=; Path=/; SameSite=Strict; Max-Age=0", CSRF_COOKIE),
+    ]
+}
+
+pub fn cookie_value<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+}
+
+/// Middleware guarding every page and API route except the login page.
+///
+/// Callers authenticate with a session cookie (browser) or an
+/// `Authorization: Bearer` token (scripts). Mutating requests need the admin
+/// role, and cookie-authenticated ones must also echo the session's CSRF token
+/// in the `X-CSRF-Token` header.
+pub async fn require_auth(State(state): State<AppState>, mut req: Request, next: Next) -> Response {
+    let Some(auth) = state.auth.as_ref() else {
+        req.extensions_mut().insert(Principal::local());
+        return next.run(req).await;
+    };
+
+    let bearer = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+
+    let (principal, expected_csrf) = if let Some(token) = bearer {
+        match auth.users.verify_token(token.trim()) {
+            Some(principal) => (principal, None),
+            None => return reject(&req, StatusCode::UNAUTHORIZED, "Invalid API token"),
+        }
+    } else {
+        match cookie_value(req.headers(), SESSION_COOKIE).and_then(|id| auth.session(id)) {
+            Some(session) => (session.principal, Some(session.csrf_token)),
+            None => return reject(&req, StatusCode::UNAUTHORIZED, "Authentication required"),
+        }
+    };
+
+    let mutating = !matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS);
+    if mutating {
+        if let Some(expected) = expected_csrf {
+            let provided = req.headers().get(CSRF_HEADER).and_then(|v| v.to_str().ok()).unwrap_or("");
+            if !constant_time_eq(provided.as_bytes(), expected.as_bytes()) {
+                return reject(&req, StatusCode::FORBIDDEN, "Invalid or missing CSRF token");
+            }
+        }
+
+        if !principal.is_admin() && req.uri().path() != "/logout" {
+            return reject(&req, StatusCode::FORBIDDEN, "Admin role required");
+        }
+    }
+
+    req.extensions_mut().insert(principal);
+    next.run(req).await
+}
+
+/// JSON errors for the API, a login redirect for pages and HTMX requests
+fn reject(req: &Request, status: StatusCode, message: &str) -> Response {
+    if req.uri().path().starts_with("/api/") {
+        let body = serde_json::json!({ "status": status.as_u16(), "error": message });
+        return (status, Json(body)).into_response();
+    }
+
+    if status == StatusCode::UNAUTHORIZED {
+        if req.headers().contains_key("hx-request") {
+            return (status, [("HX-Redirect", "/login")]).into_response();
+        }
+        return Redirect::to("/login").into_response();
+    }
+
+    (status, message.to_string()).into_response()
+}
+
+fn random_hex(bytes: usize) -> String {
+    let mut buf = vec![0u8; bytes];
+    OsRng.fill_bytes(&mut buf);
+    buf.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn hash_token(token: &str) -> String {
+    Sha256::digest(token.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_password_round_trip() {
+        let mut store = UserStore::default();
+        store.add_user("alice", "correct horse", Role::Admin).unwrap();
+
+        assert!(store.verify_password("alice", "correct horse").unwrap().is_admin());
+        assert!(store.verify_password("alice", "wrong password").is_none());
+        assert!(store.verify_password("bob", "correct horse").is_none());
+        assert!(!store.users[0].password_hash.contains("correct horse"));
+    }
+
+    #[test]
+    fn test_add_user_validation() {
+        let mut store = UserStore::default();
+        assert!(store.add_user("alice", "short", Role::Admin).is_err());
+        store.add_user("alice", "long enough", Role::ReadOnly).unwrap();
+        assert!(store.add_user("alice", "long enough", Role::Admin).is_err());
+    }
+
+    #[test]
+    fn test_tokens() {
+        let mut store = UserStore::default();
+        store.add_user("viewer", "long enough", Role::ReadOnly).unwrap();
+
+        let token = store.create_token("viewer").unwrap();
+        let principal = store.verify_token(&token).unwrap();
+        assert_eq!(principal.username, "viewer");
+        assert_eq!(principal.role, Role::ReadOnly);
+        assert!(store.verify_token("grg_bogus").is_none());
+
+        assert_eq!(store.revoke_tokens("viewer").unwrap(), 1);
+        assert!(store.verify_token(&token).is_none());
+    }
+
+    #[test]
+    fn test_sessions() {
+        let mut store = UserStore::default();
+        store.add_user("alice", "long enough", Role::Admin).unwrap();
+        let auth = Auth::new(store);
+
+        let client = IpAddr::from([192, 0, 2, 1]);
+        assert_eq!(auth.login(client, "alice", "nope"), Err(LoginError::InvalidCredentials));
+        let (session_id, csrf) = auth.login(client, "alice", "long enough").unwrap();
+        assert_eq!(auth.session(&session_id).unwrap().csrf_token, csrf);
+
+        auth.logout(&session_id);
+        assert!(auth.session(&session_id).is_none());
+    }
+
+    #[test]
+    fn test_failed_logins_are_throttled() {
+        let mut store = UserStore::default();
+        store.add_user("alice", "long enough", Role::Admin).unwrap();
+        store.add_user("bob", "long enough", Role::Admin).unwrap();
+        let auth = Auth::new(store);
+        let attacker = IpAddr::from([192, 0, 2, 1]);
+        let other = IpAddr::from([192, 0, 2, 2]);
+
+        for _ in 0..MAX_FAILED_LOGINS {
+            assert_eq!(auth.login(attacker, "alice", "guess"), Err(LoginError::InvalidCredentials));
+        }
+
+        // Locked out even with the right password, from the same client or for the same user
+        assert!(matches!(auth.login(attacker, "alice", "long enough"), Err(LoginError::Throttled(_))));
+        assert!(matches!(auth.login(attacker, "bob", "long enough"), Err(LoginError::Throttled(_))));
+        assert!(matches!(auth.login(other, "Alice", "long enough"), Err(LoginError::Throttled(_))));
+        assert!(auth.login(other, "bob", "long enough").is_ok());
+    }
+
+    #[test]
+    fn test_login_form_token() {
+        let (token, cookie) = login_form_token();
+        let mut headers = HeaderMap::new();
+        headers.insert(header::COOKIE, cookie.split(';').next().unwrap().parse().unwrap());
+
+        assert!(verify_login_form(&headers, &token));
+        assert!(!verify_login_form(&headers, "forged"));
+        assert!(!verify_login_form(&HeaderMap::new(), &token));
+    }
+
+    #[test]
+    fn test_cookie_value() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::COOKIE, "theme=dark; gringotts_session=abc123; other=1".parse().unwrap());
+
+        assert_eq!(cookie_value(&headers, SESSION_COOKIE), Some("abc123"));
+        assert_eq!(cookie_value(&headers, CSRF_COOKIE), None);
+    }
+}
@@ -0,0 +1,368 @@
+use crate::amount::TokenAmount;
+use crate::rpc::{self, HttpPool, RpcEndpoint};
+use crate::source::{AccountSnapshot, BalanceSource, Holding};
+use crate::storage::TrackedAccount;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use futures::stream::{self, StreamExt};
+use miniscript::bitcoin::{base58, Address, Network};
+use miniscript::descriptor::{Descriptor, DescriptorPublicKey};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::str::FromStr;
+
+/// Consecutive unused addresses after which a descriptor chain is assumed
+/// to have no more activity (BIP44 default)
+const GAP_LIMIT: u32 = 20;
+
+/// Esplora requests in flight at once while scanning addresses
+const REQUEST_CONCURRENCY: usize = 8;
+
+/// SLIP-132 version bytes of mainnet extended public keys. ypub and zpub
+/// are xpubs that also name the script type.
+const XPUB_VERSION: [u8; 4] = [0x04, 0x88, 0xb2, 0x1e];
+const YPUB_VERSION: [u8; 4] = [0x04, 0x9d, 0x7c, 0xb2];
+const ZPUB_VERSION: [u8; 4] = [0x04, 0xb2, 0x47, 0x46];
+
+/// An address of the wallet that has been used, or the single tracked address
+#[derive(Debug)]
+pub struct AddressBalance {
+    pub address: String,
+    /// Confirmed balance in satoshis
+    pub confirmed: u64,
+    /// Net change from mempool transactions, in satoshis
+    pub unconfirmed: i64,
+    pub tx_count: u64,
+}
+
+#[derive(Debug)]
+pub struct Utxo {
+    pub txid: String,
+    pub vout: u32,
+    pub address: String,
+    /// Satoshis
+    pub value: u64,
+    /// None while unconfirmed
+    pub block_height: Option<u64>,
+}
+
+#[derive(Debug)]
+pub struct AccountBalances {
+    /// Addresses with transactions; for a single address, that address
+    pub addresses: Vec<AddressBalance>,
+    pub utxos: Vec<Utxo>,
+    /// True when the account is an extended public key or descriptor
+    pub derived: bool,
+}
+
+impl AccountBalances {
+    pub fn confirmed(&self) -> u64 {
+        self.addresses.iter().map(|address| address.confirmed).sum()
+    }
+
+    pub fn unconfirmed(&self) -> i64 {
+        self.addresses.iter().map(|address| address.unconfirmed).sum()
+    }
+
+    /// Confirmed plus unconfirmed balance, as wallets show it
+    pub fn btc_balance(&self) -> TokenAmount {
+        TokenAmount::new(i128::from(self.confirmed()) + i128::from(self.unconfirmed()), 8)
+    }
+}
+
+/// What a tracked Bitcoin entry refers to
+enum Tracked {
+    Address(String),
+    /// Ranged descriptors (receive and change chains), scanned up to the gap limit
+    Descriptors(Vec<Descriptor<DescriptorPublicKey>>),
+}
+
+/// Descriptors for the receive and change chains of an xpub, ypub or zpub:
+/// legacy, nested segwit and native segwit respectively. None when `key`
+/// isn't a mainnet extended public key.
+fn extended_key_descriptors(key: &str) -> Option<Vec<String>> {
+    let mut data = base58::decode_check(key).ok()?;
+    if data.len() != 78 {
+        return None;
+    }
+
+    let (prefix, suffix) = match <[u8; 4]>::try_from(&data[..4]).ok()? {
+        XPUB_VERSION => ("pkh(", ")"),
+        YPUB_VERSION => ("sh(wpkh(", "))"),
+        ZPUB_VERSION => ("wpkh(", ")"),
+        _ => return None,
+    };
+    data[..4].copy_from_slice(&XPUB_VERSION);
+    let xpub = base58::encode_check(&data);
+
+    Some((0..2).map(|chain| format!("{}{}/{}/*{}", prefix, xpub, chain, suffix)).collect())
+}
+
+fn parse_descriptor(descriptor: &str) -> Result<Descriptor<DescriptorPublicKey>> {
+    Descriptor::<DescriptorPublicKey>::from_str(descriptor).map_err(|e| anyhow::anyhow!("Invalid descriptor: {}", e))
+}
+
+fn derive_address(descriptor: &Descriptor<DescriptorPublicKey>, index: u32) -> Result<String> {
+    let definite = descriptor
+        .at_derivation_index(index)
+        .map_err(|e| anyhow::anyhow!("Failed to derive address {}: {}", index, e))?;
+    let address = definite
+        .address(Network::Bitcoin)
+        .map_err(|e| anyhow::anyhow!("Descriptor has no address form: {}", e))?;
+    Ok(address.to_string())
+}
+
+/// Parse a mainnet address (legacy, segwit or taproot), extended public key
+/// or output descriptor
+fn parse_tracked(account: &str) -> Result<Tracked> {
+    if let Ok(address) = Address::from_str(account) {
+        let address = address
+            .require_network(Network::Bitcoin)
+            .map_err(|_| anyhow::anyhow!("Not a Bitcoin mainnet address: {}", account))?;
+        return Ok(Tracked::Address(address.to_string()));
+    }
+
+    let descriptors = match extended_key_descriptors(account) {
+        Some(descriptors) => descriptors.iter().map(|d| parse_descriptor(d)).collect::<Result<Vec<_>>>()?,
+        None => {
+            let descriptor = parse_descriptor(account)?;
+            if descriptor.is_multipath() {
+                descriptor
+                    .into_single_descriptors()
+                    .map_err(|e| anyhow::anyhow!("Invalid descriptor: {}", e))?
+            } else {
+                vec![descriptor]
+            }
+        }
+    };
+
+    // A descriptor without a wildcard stands for a single address
+    match descriptors.as_slice() {
+        [descriptor] if !descriptor.has_wildcard() => Ok(Tracked::Address(derive_address(descriptor, 0)?)),
+        _ => Ok(Tracked::Descriptors(descriptors)),
+    }
+}
+
+/// Whether `account` is a Bitcoin address, extended public key or output descriptor
+pub fn is_bitcoin_account(account: &str) -> bool {
+    parse_tracked(account).is_ok()
+}
+
+#[derive(Deserialize)]
+struct AddressInfo {
+    chain_stats: TxoStats,
+    mempool_stats: TxoStats,
+}
+
+#[derive(Deserialize)]
+struct TxoStats {
+    funded_txo_sum: u64,
+    spent_txo_sum: u64,
+    tx_count: u64,
+}
+
+#[derive(Deserialize)]
+struct EsploraUtxo {
+    txid: String,
+    vout: u32,
+    value: u64,
+    status: UtxoStatus,
+}
+
+#[derive(Deserialize)]
+struct UtxoStatus {
+    block_height: Option<u64>,
+}
+
+/// Client for an Esplora REST API (Blockstream, mempool.space or a
+/// self-hosted electrs/esplora)
+pub struct BitcoinClient {
+    rpc: HttpPool,
+}
+
+impl BitcoinClient {
+    pub fn new(endpoints: Vec<RpcEndpoint>) -> Self {
+        Self {
+            rpc: HttpPool::http(endpoints),
+        }
+    }
+
+    async fn get_json<T: DeserializeOwned>(&self, path: &str) -> Result<T> {
+        let response = self.rpc.get(path).await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let message = response.text().await.unwrap_or_default();
+            anyhow::bail!("Esplora API error ({}): {}", status, message.trim());
+        }
+
+        rpc::json_body(response).await.context("Failed to parse Esplora API response")
+    }
+
+    async fn address_balance(&self, address: String) -> Result<AddressBalance> {
+        let info: AddressInfo = self
+            .get_json(&format!("/address/{}", address))
+            .await
+            .with_context(|| format!("Failed to fetch address {}", address))?;
+
+        let confirmed = info
+            .chain_stats
+            .funded_txo_sum
+            .checked_sub(info.chain_stats.spent_txo_sum)
+            .context("Invalid address balance")?;
+        let unconfirmed = i128::from(info.mempool_stats.funded_txo_sum) - i128::from(info.mempool_stats.spent_txo_sum);
+        Ok(AddressBalance {
+            confirmed,
+            unconfirmed: i64::try_from(unconfirmed).context("Invalid mempool balance")?,
+            tx_count: info.chain_stats.tx_count + info.mempool_stats.tx_count,
+            address,
+        })
+    }
+
+    async fn address_balances(&self, addresses: Vec<String>) -> Result<Vec<AddressBalance>> {
+        stream::iter(addresses)
+            .map(|address| self.address_balance(address))
+            .buffered(REQUEST_CONCURRENCY)
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .collect()
+    }
+
+    /// Used addresses of a ranged descriptor, derived in batches until
+    /// GAP_LIMIT addresses in a row have no transactions
+    async fn scan(&self, descriptor: &Descriptor<DescriptorPublicKey>) -> Result<Vec<AddressBalance>> {
+        let mut used = Vec::new();
+        let mut unused_run = 0;
+        let mut index = 0;
+
+        while unused_run < GAP_LIMIT {
+            let addresses = (index..index + GAP_LIMIT)
+                .map(|i| derive_address(descriptor, i))
+                .collect::<Result<Vec<_>>>()?;
+
+            for balance in self.address_balances(addresses).await? {
+                if balance.tx_count > 0 {
+                    unused_run = 0;
+                    used.push(balance);
+                } else {
+                    unused_run += 1;
+                }
+            }
+            index += GAP_LIMIT;
+        }
+
+        Ok(used)
+    }
+
+    async fn utxos(&self, address: String) -> Result<Vec<Utxo>> {
+        let utxos: Vec<EsploraUtxo> = self
+            .get_json(&format!("/address/{}/utxo", address))
+            .await
+            .with_context(|| format!("Failed to fetch UTXOs of {}", address))?;
+
+        Ok(utxos
+            .into_iter()
+            .map(|utxo| Utxo {
+                txid: utxo.txid,
+                vout: utxo.vout,
+                address: address.clone(),
+                value: utxo.value,
+                block_height: utxo.status.block_height,
+            })
+            .collect())
+    }
+
+    /// Balances and UTXOs of an address, extended public key or descriptor
+    pub async fn get_balances(&self, account: &str) -> Result<AccountBalances> {
+        let (addresses, derived) = match parse_tracked(account)? {
+            Tracked::Address(address) => (vec![self.address_balance(address).await?], false),
+            Tracked::Descriptors(descriptors) => {
+                let mut addresses = Vec::new();
+                for descriptor in &descriptors {
+                    addresses.extend(self.scan(descriptor).await?);
+                }
+                (addresses, true)
+            }
+        };
+
+        // Only addresses holding something have UTXOs to list
+        let funded: Vec<String> = addresses
+            .iter()
+            .filter(|address| address.confirmed > 0 || address.unconfirmed != 0)
+            .map(|address| address.address.clone())
+            .collect();
+        let mut utxos = Vec::new();
+        let results: Vec<Result<Vec<Utxo>>> = stream::iter(funded)
+            .map(|address| self.utxos(address))
+            .buffered(REQUEST_CONCURRENCY)
+            .collect()
+            .await;
+        for result in results {
+            utxos.extend(result?);
+        }
+
+        Ok(AccountBalances {
+            addresses,
+            utxos,
+            derived,
+        })
+    }
+}
+
+#[async_trait]
+impl BalanceSource for BitcoinClient {
+    async fn fetch_snapshot(&self, account: &TrackedAccount) -> Result<AccountSnapshot> {
+        let balances = self.get_balances(account.identifier()).await?;
+
+        let holdings = vec![Holding::native("BTC", balances.btc_balance())];
+        let mut snapshot = AccountSnapshot::new(account, holdings);
+        if balances.derived {
+            snapshot = snapshot.with_detail("Addresses Used", balances.addresses.len().to_string());
+        }
+        if balances.unconfirmed() != 0 {
+            snapshot = snapshot.with_detail("Unconfirmed", format!("{} BTC", TokenAmount::new(balances.unconfirmed(), 8)));
+        }
+
+        Ok(snapshot
+            .with_detail("UTXOs", balances.utxos.len().to_string())
+            .with_detail("RPC Endpoint", self.rpc.served_by()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detects_bitcoin_accounts() {
+        // Legacy, nested segwit, native segwit and taproot addresses
+        assert!(is_bitcoin_account("1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa"));
+        assert!(is_bitcoin_account("3J98t1WpEZ73CNmQviecrnyiWrnqRhWNLy"));
+        assert!(is_bitcoin_account("bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu"));
+        assert!(is_bitcoin_account("bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr"));
+
+        // Solana and EVM addresses, and testnet addresses, are not
+        assert!(!is_bitcoin_account("So11111111111111111111111111111111111111112"));
+        assert!(!is_bitcoin_account("0x52908400098527886E0F7030069857D2E4169EE7"));
+        assert!(!is_bitcoin_account("tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx"));
+    }
+
+    #[test]
+    fn test_zpub_derives_native_segwit_addresses() {
+        // BIP84 test vector: account 0 of the "abandon ... about" mnemonic
+        let zpub = "zpub6rFR7y4Q2AijBEqTUquhVz398htDFrtymD9xYYfG1m4wAcvPhXNfE3EfH1r1ADqtfSdVCToUG868RvUUkgDKf31mGDtKsAYz2oz2AGutZYs";
+        let Tracked::Descriptors(descriptors) = parse_tracked(zpub).unwrap() else {
+            panic!("expected descriptors");
+        };
+
+        assert_eq!(descriptors.len(), 2);
+        assert_eq!(derive_address(&descriptors[0], 0).unwrap(), "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu");
+        assert_eq!(derive_address(&descriptors[0], 1).unwrap(), "bc1qnjg0jd8228aq7egyzacy8cys3knf9xvrerkf9g");
+        assert_eq!(derive_address(&descriptors[1], 0).unwrap(), "bc1q8c6fshw2dlwun7ekn9qwf37cu2rn755upcp6el");
+
+        // A descriptor without a wildcard is a single address
+        let single = "wpkh(0330d54fd0dd420a6e5f8d3624f5f3482cae350f79d5f0753bf5beef9c2d91af3c)";
+        assert!(matches!(parse_tracked(single).unwrap(), Tracked::Address(address) if address == "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu"));
+    }
+}
@@ -1,18 +1,24 @@
+use crate::amount;
+use crate::config::{self, ApiKey};
+use crate::source::{AccountSnapshot, BalanceSource, Holding};
+use crate::storage::TrackedAccount;
 use anyhow::{Context, Result};
-use serde::{Deserialize, Serialize};
-use std::env;
+use async_trait::async_trait;
+use bigdecimal::{BigDecimal, Zero};
+use serde::Deserialize;
+use std::str::FromStr;
 
 const CIRCLE_API_BASE: &str = "https://api.circle.com";
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug)]
 pub struct AccountBalances {
     pub available_balances: Vec<Balance>,
     pub unsettled_balances: Vec<Balance>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Clone)]
 pub struct Balance {
-    pub amount: f64,
+    pub amount: BigDecimal,
     pub currency: String,
 }
 
@@ -40,8 +46,8 @@ pub struct CircleClient {
 
 impl CircleClient {
     pub fn new() -> Result<Self> {
-        let api_key = env::var("CIRCLE_API_KEY")
-            .context("CIRCLE_API_KEY environment variable not set")?;
+        let api_key = config::api_key(ApiKey::Circle)
+            .context("CIRCLE_API_KEY not set (environment or api_keys.circle in config.toml)")?;
 
         Ok(Self {
             api_key,
@@ -72,11 +78,11 @@ impl CircleClient {
             .await
             .context("Failed to parse Circle API response")?;
 
-        // Convert Circle amounts (strings) to floats
+        // Circle amounts are decimal strings; keep them exact
         let mut available_balances = Vec::new();
         for amount in balance_response.data.available {
             let balance = Balance {
-                amount: amount.amount.parse::<f64>()
+                amount: BigDecimal::from_str(&amount.amount)
                     .context(format!("Failed to parse available amount: {}", amount.amount))?,
                 currency: amount.currency,
             };
@@ -86,7 +92,7 @@ impl CircleClient {
         let mut unsettled_balances = Vec::new();
         for amount in balance_response.data.unsettled {
             let balance = Balance {
-                amount: amount.amount.parse::<f64>()
+                amount: BigDecimal::from_str(&amount.amount)
                     .context(format!("Failed to parse unsettled amount: {}", amount.amount))?,
                 currency: amount.currency,
             };
@@ -99,3 +105,35 @@ impl CircleClient {
         })
     }
 }
+
+/// Circle reports fiat currency codes; balances are held as the matching stablecoin
+fn stablecoin_symbol(currency: &str) -> &str {
+    match currency {
+        "USD" => "USDC",
+        "EUR" => "EURC",
+        other => other,
+    }
+}
+
+#[async_trait]
+impl BalanceSource for CircleClient {
+    async fn fetch_snapshot(&self, account: &TrackedAccount) -> Result<AccountSnapshot> {
+        let balances = self.get_balances().await?;
+
+        let holdings = balances
+            .available_balances
+            .iter()
+            .map(|b| Holding::fiat(stablecoin_symbol(&b.currency), b.amount.clone()))
+            .collect();
+
+        let mut snapshot = AccountSnapshot::new(account, holdings);
+        for balance in balances.unsettled_balances.iter().filter(|b| b.amount > BigDecimal::zero()) {
+            snapshot = snapshot.with_detail(
+                &format!("Unsettled {}", stablecoin_symbol(&balance.currency)),
+                amount::format_fixed(&balance.amount, 2),
+            );
+        }
+
+        Ok(snapshot)
+    }
+}
@@ -11,10 +11,23 @@ use clap::{Parser, Subcommand};
   gringotts list -c CompanyName
   gringotts query
   gringotts query-one \"My Wallet\"
+  gringotts query --save
+  gringotts query --rpc-url solana=https://my-node.example.com
+  gringotts query --output json > balances.json
+  gringotts query-one \"My Wallet\" -o csv
+  gringotts history list
+  gringotts history diff 12 15
+  gringotts --profile sandbox query
+  gringotts config show
+  gringotts secrets set MERCURY_API_KEY
   gringotts setup-mercury -c CompanyName
   gringotts export-transactions \"Checking\" --start 2025-01-01 --end 2025-01-31
   gringotts export-transactions \"Checking\" -f json -o transactions.json")]
 pub struct Cli {
+    /// Settings profile from ~/.gringotts/config.toml (defaults to GRINGOTTS_PROFILE)
+    #[arg(long, global = true)]
+    pub profile: Option<String>,
+
     #[command(subcommand)]
     pub command: Commands,
 }
@@ -31,11 +44,11 @@ pub enum Commands {
         #[arg(short, long)]
         name: String,
 
-        /// The blockchain address
+        /// The blockchain address (for Bitcoin, also an xpub/ypub/zpub or output descriptor)
         #[arg(short, long)]
         address: String,
 
-        /// Blockchain chain (solana, ethereum, polygon, bsc, arbitrum, optimism, avalanche, base, core, near, aptos, sui, starknet)
+        /// Blockchain chain (solana, ethereum, polygon, bsc, arbitrum, optimism, avalanche, base, core, near, aptos, sui, starknet, bitcoin, cosmos, osmosis, celestia, injective, dydx, or a network from [cosmos.<name>])
         /// If not specified, chain is auto-detected based on address format
         #[arg(long)]
         chain: Option<String>,
@@ -65,6 +78,10 @@ pub enum Commands {
         /// Filter by company name (case-insensitive, partial match)
         #[arg(short, long)]
         company: Option<String>,
+
+        /// Output format (table, json or csv)
+        #[arg(short, long, default_value = "table")]
+        output: String,
     },
 
     /// Remove an address or banking account by name
@@ -75,13 +92,29 @@ pub enum Commands {
 
     /// Query balances for all tracked addresses and banking accounts
     Query {
-        /// Optional RPC URL (defaults to mainnet)
-        #[arg(short, long)]
-        rpc_url: Option<String>,
+        /// RPC endpoint for one chain as CHAIN=URL, tried before the configured ones (repeatable)
+        #[arg(short, long, value_name = "CHAIN=URL")]
+        rpc_url: Vec<String>,
 
         /// Skip price lookups (faster, no USD values)
         #[arg(long)]
         no_prices: bool,
+
+        /// Maximum number of accounts to query at the same time
+        #[arg(long, default_value_t = 8)]
+        concurrency: usize,
+
+        /// Maximum number of concurrent queries against a single chain or banking service
+        #[arg(long, default_value_t = 2)]
+        per_endpoint: usize,
+
+        /// Save the results as a snapshot in the history database
+        #[arg(long)]
+        save: bool,
+
+        /// Output format (table, json or csv)
+        #[arg(short, long, default_value = "table")]
+        output: String,
     },
 
     /// Query balances for a specific address or banking account by name
@@ -89,13 +122,17 @@ pub enum Commands {
         /// Name of the address or account to query
         name: String,
 
-        /// Optional RPC URL (defaults to mainnet)
-        #[arg(short, long)]
-        rpc_url: Option<String>,
+        /// RPC endpoint to try first, as URL or CHAIN=URL (repeatable)
+        #[arg(short, long, value_name = "URL")]
+        rpc_url: Vec<String>,
 
         /// Skip price lookups (faster, no USD values)
         #[arg(long)]
         no_prices: bool,
+
+        /// Output format (table, json or csv)
+        #[arg(short, long, default_value = "table")]
+        output: String,
     },
 
     /// List all accounts from Mercury
@@ -108,9 +145,9 @@ pub enum Commands {
         company: String,
     },
 
-    /// Export transactions from a Mercury banking account
+    /// Export transactions from a Mercury banking account or a Solana or EVM wallet
     ExportTransactions {
-        /// Name of the Mercury account to export from
+        /// Name or identifier of the Mercury account or Solana or EVM wallet to export from
         name: String,
 
         /// Output format (csv or json)
@@ -130,10 +167,152 @@ pub enum Commands {
         output: Option<String>,
     },
 
+    /// List the unspent outputs of a Bitcoin wallet
+    Utxos {
+        /// Name or identifier of the Bitcoin address, xpub or descriptor
+        name: String,
+
+        /// Output format (table, json or csv)
+        #[arg(short, long, default_value = "table")]
+        output: String,
+    },
+
     /// Start the web server with HTMX frontend
     Serve {
-        /// Port to listen on
-        #[arg(short, long, default_value = "3000")]
-        port: u16,
+        /// Port to listen on [default: web.port from config.toml, or 3000]
+        #[arg(short, long)]
+        port: Option<u16>,
+
+        /// Save a snapshot to the history database every time balances are loaded
+        #[arg(long)]
+        save_snapshots: bool,
+
+        /// Only accept connections from this machine (binds to 127.0.0.1)
+        #[arg(long)]
+        localhost: bool,
+
+        /// Disable authentication even when users are configured
+        #[arg(long)]
+        no_auth: bool,
+    },
+
+    /// Manage web server users and API tokens
+    User {
+        #[command(subcommand)]
+        command: UserCommands,
+    },
+
+    /// Browse and compare saved balance snapshots
+    History {
+        #[command(subcommand)]
+        command: HistoryCommands,
+    },
+
+    /// Inspect the configuration file and the selected profile
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommands,
+    },
+
+    /// Manage API keys in the encrypted secrets vault
+    Secrets {
+        #[command(subcommand)]
+        command: SecretsCommands,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum SecretsCommands {
+    /// Store a secret (prompts for the value; creates the vault if needed)
+    Set {
+        /// Secret name, e.g. MERCURY_API_KEY, or sandbox/MERCURY_API_KEY for one profile
+        name: String,
+
+        /// Read the value from standard input instead of prompting
+        #[arg(long)]
+        stdin: bool,
+    },
+
+    /// Print a secret's value
+    Get {
+        /// Secret name
+        name: String,
+    },
+
+    /// Remove a secret
+    Rm {
+        /// Secret name
+        name: String,
+    },
+
+    /// List secret names (values are not shown)
+    List,
+}
+
+#[derive(Subcommand)]
+pub enum ConfigCommands {
+    /// Show the settings in effect for the selected profile (secrets redacted)
+    Show,
+
+    /// Check the configuration file and every profile in it
+    Validate,
+}
+
+#[derive(Subcommand)]
+pub enum UserCommands {
+    /// Add a web server user (prompts for a password)
+    Add {
+        /// Username to sign in with
+        username: String,
+
+        /// Role: read-only (view balances) or admin (also add/remove accounts)
+        #[arg(short, long, default_value = "read-only")]
+        role: String,
+    },
+
+    /// Remove a user and their API tokens
+    Remove {
+        /// Username to remove
+        username: String,
+    },
+
+    /// List users and their roles
+    List,
+
+    /// Create an API bearer token for a user (shown only once)
+    Token {
+        /// Username the token acts as
+        username: String,
+    },
+
+    /// Revoke all API tokens of a user
+    RevokeTokens {
+        /// Username whose tokens to revoke
+        username: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum HistoryCommands {
+    /// List saved snapshots, newest first
+    List {
+        /// Maximum number of snapshots to show
+        #[arg(short, long, default_value = "20")]
+        limit: usize,
+    },
+
+    /// Show the portfolio recorded in a snapshot
+    Show {
+        /// Snapshot id (see `gringotts history list`)
+        id: i64,
+    },
+
+    /// Compare two snapshots per company and per asset
+    Diff {
+        /// Older snapshot id
+        from: i64,
+
+        /// Newer snapshot id (defaults to the latest snapshot)
+        to: Option<i64>,
     },
 }
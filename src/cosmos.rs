@@ -0,0 +1,704 @@
+use crate::amount::TokenAmount;
+use crate::config;
+use crate::rpc::{self, HttpPool, RpcEndpoint};
+use crate::source::{AccountSnapshot, BalanceSource, Holding};
+use crate::storage::{Chain, TrackedAccount};
+use crate::token_cache::{self, TokenMetadata};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use bigdecimal::Zero;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+
+/// A Cosmos SDK chain: the prefix of its account addresses, its staking
+/// token and its LCD API. `[cosmos.<name>]` in config.toml adds networks or
+/// replaces the built-in ones.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct CosmosNetwork {
+    /// Name shown in output (default: the network's key)
+    pub name: Option<String>,
+    /// Other names accepted by --chain
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+    /// Bech32 prefix of account addresses (`cosmos` for `cosmos1...`)
+    pub prefix: String,
+    /// Base denom of the staking and gas token
+    pub staking_denom: String,
+    pub symbol: String,
+    pub decimals: u8,
+    /// LCD (REST) endpoints, used unless `[rpc]` lists some for the network
+    #[serde(default)]
+    pub lcd: Vec<String>,
+}
+
+/// Networks known without any configuration: PublicNode and cosmos.directory
+/// serve an LCD API for each
+fn builtin_networks() -> &'static BTreeMap<String, CosmosNetwork> {
+    static BUILTIN: OnceLock<BTreeMap<String, CosmosNetwork>> = OnceLock::new();
+    BUILTIN.get_or_init(|| {
+        [
+            ("cosmos", "Cosmos Hub", &["cosmoshub", "atom"][..], "cosmos", "uatom", "ATOM", 6, "cosmoshub"),
+            ("osmosis", "Osmosis", &["osmo"], "osmo", "uosmo", "OSMO", 6, "osmosis"),
+            ("celestia", "Celestia", &["tia"], "celestia", "utia", "TIA", 6, "celestia"),
+            ("injective", "Injective", &["inj"], "inj", "inj", "INJ", 18, "injective"),
+            ("dydx", "dYdX Chain", &[], "dydx", "adydx", "DYDX", 18, "dydx"),
+        ]
+        .into_iter()
+        .map(|(key, name, aliases, prefix, staking_denom, symbol, decimals, directory)| {
+            let network = CosmosNetwork {
+                name: Some(name.to_string()),
+                aliases: aliases.iter().map(|alias| alias.to_string()).collect(),
+                prefix: prefix.to_string(),
+                staking_denom: staking_denom.to_string(),
+                symbol: symbol.to_string(),
+                decimals,
+                lcd: vec![
+                    format!("https://{}-rest.publicnode.com", key),
+                    format!("https://rest.cosmos.directory/{}", directory),
+                ],
+            };
+            (key.to_string(), network)
+        })
+        .collect()
+    })
+}
+
+/// Cosmos SDK networks by key: the built-in ones plus `[cosmos.<name>]`
+/// tables, which take the place of a built-in network of the same key
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct CosmosNetworks {
+    configured: BTreeMap<String, CosmosNetwork>,
+}
+
+impl CosmosNetworks {
+    pub fn get(&self, key: &str) -> Option<&CosmosNetwork> {
+        self.configured.get(key).or_else(|| builtin_networks().get(key))
+    }
+
+    pub fn all(&self) -> BTreeMap<&str, &CosmosNetwork> {
+        builtin_networks()
+            .iter()
+            .chain(&self.configured)
+            .map(|(key, network)| (key.as_str(), network))
+            .collect()
+    }
+
+    /// The network with this key or alias
+    pub fn find(&self, name: &str) -> Option<Chain> {
+        let name = name.to_lowercase();
+        self.all()
+            .into_iter()
+            .find(|(key, network)| *key == name || network.aliases.contains(&name))
+            .map(|(key, _)| Chain::Cosmos(key.to_string()))
+    }
+
+    /// The network whose account prefix `address` is a valid bech32 address for
+    pub fn chain_for_address(&self, address: &str) -> Option<Chain> {
+        let (hrp, data) = bech32::decode(address).ok()?;
+        // 20-byte key hashes, or 32-byte module and contract accounts
+        if !matches!(data.len(), 20 | 32) {
+            return None;
+        }
+
+        let prefix = hrp.to_lowercase();
+        self.all()
+            .into_iter()
+            .find(|(_, network)| network.prefix == prefix)
+            .map(|(key, _)| Chain::Cosmos(key.to_string()))
+    }
+
+    /// Layer a profile's networks on top, per network
+    pub fn merge(&mut self, other: CosmosNetworks) {
+        self.configured.extend(other.configured);
+    }
+
+    /// Check names, prefixes and URLs so mistakes surface when the config is loaded
+    pub fn validate(&self) -> Result<()> {
+        for (key, network) in &self.configured {
+            for name in std::iter::once(key).chain(&network.aliases) {
+                if name.is_empty() || !name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-') {
+                    anyhow::bail!("[cosmos] '{}' must be lowercase letters, digits and dashes", name);
+                }
+                if Chain::builtin(name).is_some() {
+                    anyhow::bail!("[cosmos] '{}' is already the name of a chain", name);
+                }
+            }
+            if network.prefix.is_empty() || network.prefix != network.prefix.to_lowercase() {
+                anyhow::bail!("[cosmos.{}] prefix must be a lowercase bech32 prefix", key);
+            }
+            if network.staking_denom.is_empty() || network.symbol.is_empty() {
+                anyhow::bail!("[cosmos.{}] needs a staking_denom and a symbol", key);
+            }
+            for url in &network.lcd {
+                reqwest::Url::parse(url).with_context(|| format!("Invalid LCD URL for {}: {}", key, url))?;
+            }
+        }
+
+        // Each name and address prefix picks out one network
+        let mut names: BTreeMap<&str, &str> = BTreeMap::new();
+        let mut prefixes: BTreeMap<&str, &str> = BTreeMap::new();
+        for (key, network) in self.all() {
+            for name in std::iter::once(key).chain(network.aliases.iter().map(String::as_str)) {
+                if let Some(other) = names.insert(name, key).filter(|other| *other != key) {
+                    anyhow::bail!("[cosmos] {} and {} are both called '{}'", other, key, name);
+                }
+            }
+            if let Some(other) = prefixes.insert(&network.prefix, key) {
+                anyhow::bail!("[cosmos] {} and {} both use the address prefix '{}'", other, key, network.prefix);
+            }
+        }
+        Ok(())
+    }
+
+    /// Copy for display with URL query strings hidden
+    pub fn redacted(&self) -> CosmosNetworks {
+        let configured = self
+            .configured
+            .iter()
+            .map(|(key, network)| {
+                let lcd = network.lcd.iter().map(|url| rpc::redact_url(url)).collect();
+                (key.clone(), CosmosNetwork { lcd, ..network.clone() })
+            })
+            .collect();
+        CosmosNetworks { configured }
+    }
+}
+
+/// A network of the active config
+pub fn network(key: &str) -> Option<&'static CosmosNetwork> {
+    config::active().settings.cosmos.get(key)
+}
+
+/// The configured network whose account prefix `address` is a valid bech32 address for
+pub fn chain_for_address(address: &str) -> Option<Chain> {
+    config::active().settings.cosmos.chain_for_address(address)
+}
+
+/// Base denoms bridged over IBC that the receiving chain often has no bank
+/// metadata for, besides the networks' staking tokens
+const KNOWN_DENOMS: &[(&str, &str, u8)] = &[
+    ("uusdc", "USDC", 6),
+    ("untrn", "NTRN", 6),
+    ("ustrd", "STRD", 6),
+];
+
+#[derive(Debug)]
+pub struct TokenBalance {
+    /// Bank denom: `ibc/<hash>`, `factory/...` or a native denom
+    pub denom: String,
+    pub name: Option<String>,
+    pub symbol: Option<String>,
+    pub amount: TokenAmount,
+}
+
+/// Staking token delegated to a validator, in the base denom
+#[derive(Debug, Default, PartialEq)]
+pub struct ValidatorStake {
+    pub validator: String,
+    pub delegated: u128,
+    /// Undelegated but still locked until the unbonding period ends
+    pub unbonding: u128,
+}
+
+impl ValidatorStake {
+    pub fn total(&self) -> u128 {
+        self.delegated + self.unbonding
+    }
+}
+
+#[derive(Debug)]
+pub struct AccountBalances {
+    pub native_balance: TokenAmount,
+    pub stakes: Vec<ValidatorStake>,
+    /// Staking rewards not yet withdrawn, in the staking token
+    pub pending_rewards: TokenAmount,
+    pub token_balances: Vec<TokenBalance>,
+    /// Rewards paid in other denoms (e.g. USDC on dYdX)
+    pub reward_balances: Vec<TokenBalance>,
+}
+
+/// Symbol and decimals of a base denom without bank metadata: a staking
+/// token, a known bridged token, or a micro-unit denom such as `uakt`
+fn base_denom_metadata(denom: &str, networks: &CosmosNetworks) -> Option<TokenMetadata> {
+    if let Some(network) = networks.all().into_values().find(|network| network.staking_denom == denom) {
+        return Some(TokenMetadata {
+            decimals: network.decimals,
+            symbol: Some(network.symbol.clone()),
+            name: None,
+        });
+    }
+    if let Some((_, symbol, decimals)) = KNOWN_DENOMS.iter().find(|(base, _, _)| *base == denom) {
+        return Some(TokenMetadata {
+            decimals: *decimals,
+            symbol: Some(symbol.to_string()),
+            name: None,
+        });
+    }
+
+    let unit = denom.strip_prefix('u')?;
+    if !(2..=6).contains(&unit.len()) || !unit.chars().all(|c| c.is_ascii_lowercase()) {
+        return None;
+    }
+    Some(TokenMetadata {
+        decimals: 6,
+        symbol: Some(unit.to_uppercase()),
+        name: None,
+    })
+}
+
+fn parse_amount(amount: &str) -> Result<u128> {
+    amount.parse().with_context(|| format!("Invalid amount: {}", amount))
+}
+
+/// Whole base units of a decimal coin amount (rewards are reported with 18
+/// decimal places of sub-unit precision)
+fn parse_dec_amount(amount: &str) -> Result<u128> {
+    parse_amount(amount.split('.').next().unwrap_or_default())
+}
+
+#[derive(Deserialize)]
+struct Coin {
+    denom: String,
+    amount: String,
+}
+
+/// Error body of the LCD (gRPC gateway) API
+#[derive(Deserialize)]
+struct ApiError {
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct DelegationResponse {
+    delegation: Delegation,
+    balance: Coin,
+}
+
+#[derive(Deserialize)]
+struct Delegation {
+    validator_address: String,
+}
+
+#[derive(Deserialize)]
+struct UnbondingDelegation {
+    validator_address: String,
+    entries: Vec<UnbondingEntry>,
+}
+
+#[derive(Deserialize)]
+struct UnbondingEntry {
+    balance: String,
+}
+
+#[derive(Deserialize)]
+struct RewardsResponse {
+    #[serde(default)]
+    total: Vec<Coin>,
+}
+
+#[derive(Deserialize)]
+struct DenomMetadataResponse {
+    metadata: DenomMetadata,
+}
+
+#[derive(Deserialize)]
+struct DenomMetadata {
+    #[serde(default)]
+    denom_units: Vec<DenomUnit>,
+    display: String,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    symbol: String,
+}
+
+#[derive(Deserialize)]
+struct DenomUnit {
+    denom: String,
+    #[serde(default)]
+    exponent: u8,
+}
+
+#[derive(Deserialize)]
+struct DenomTraceResponse {
+    denom_trace: DenomTrace,
+}
+
+#[derive(Deserialize)]
+struct DenomTrace {
+    path: String,
+    base_denom: String,
+}
+
+/// ibc-go v8 replaced denom traces with denoms listing each hop
+#[derive(Deserialize)]
+struct IbcDenomResponse {
+    denom: IbcDenom,
+}
+
+#[derive(Deserialize)]
+struct IbcDenom {
+    base: String,
+    #[serde(default)]
+    trace: Vec<IbcHop>,
+}
+
+#[derive(Deserialize)]
+struct IbcHop {
+    port_id: String,
+    channel_id: String,
+}
+
+/// Client for the LCD (REST) API of a Cosmos SDK chain
+pub struct CosmosClient {
+    rpc: HttpPool,
+    chain: Chain,
+    network: &'static CosmosNetwork,
+}
+
+impl CosmosClient {
+    pub fn new(endpoints: Vec<RpcEndpoint>, chain: &Chain) -> Result<Self> {
+        let network = match chain {
+            Chain::Cosmos(key) => network(key).with_context(|| format!("Unknown Cosmos network '{}'; add it as [cosmos.{}] in config.toml", key, key))?,
+            _ => anyhow::bail!("{} is not a Cosmos SDK chain", chain.display_name()),
+        };
+
+        Ok(Self {
+            rpc: HttpPool::http(endpoints),
+            chain: chain.clone(),
+            network,
+        })
+    }
+
+    async fn get_json<T: DeserializeOwned>(&self, path: &str) -> Result<T> {
+        let response = self.rpc.get(path).await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let error: Option<ApiError> = response.json().await.ok();
+            match error {
+                Some(error) => anyhow::bail!("LCD API error ({}): {}", status, error.message),
+                None => anyhow::bail!("LCD API error ({})", status),
+            }
+        }
+
+        rpc::json_body(response).await.context("Failed to parse LCD API response")
+    }
+
+    /// Every page of a paginated list; `field` names the list in each page
+    async fn get_all<T: DeserializeOwned>(&self, path: &str, field: &str) -> Result<Vec<T>> {
+        let separator = if path.contains('?') { '&' } else { '?' };
+        let mut items = Vec::new();
+        let mut next_key: Option<String> = None;
+
+        loop {
+            let page_path = match &next_key {
+                Some(key) => format!("{}{}pagination.key={}", path, separator, urlencoding::encode(key)),
+                None => path.to_string(),
+            };
+            let mut page: serde_json::Value = self.get_json(&page_path).await?;
+
+            let page_items: Vec<T> = serde_json::from_value(page[field].take())
+                .with_context(|| format!("Invalid {} in LCD API response", field))?;
+            items.extend(page_items);
+
+            next_key = page["pagination"]["next_key"]
+                .as_str()
+                .filter(|key| !key.is_empty())
+                .map(str::to_string);
+            if next_key.is_none() {
+                return Ok(items);
+            }
+        }
+    }
+
+    /// Metadata the chain's bank module has registered for a denom, if any
+    async fn bank_metadata(&self, denom: &str) -> Option<TokenMetadata> {
+        let path = if denom.contains('/') {
+            format!("/cosmos/bank/v1beta1/denoms_metadata_by_query_string?denom={}", urlencoding::encode(denom))
+        } else {
+            format!("/cosmos/bank/v1beta1/denoms_metadata/{}", denom)
+        };
+        let metadata = self.get_json::<DenomMetadataResponse>(&path).await.ok()?.metadata;
+
+        let decimals = metadata
+            .denom_units
+            .iter()
+            .find(|unit| unit.denom == metadata.display)?
+            .exponent;
+        let symbol = if metadata.symbol.is_empty() {
+            metadata.display.to_uppercase()
+        } else {
+            metadata.symbol
+        };
+
+        Some(TokenMetadata {
+            decimals,
+            symbol: Some(symbol),
+            name: Some(metadata.name).filter(|name| !name.is_empty()),
+        })
+    }
+
+    /// Base denom and channel path (`transfer/channel-0`) of an `ibc/<hash>` denom
+    async fn denom_trace(&self, hash: &str) -> Result<(String, String)> {
+        let trace_error = match self
+            .get_json::<DenomTraceResponse>(&format!("/ibc/apps/transfer/v1/denom_traces/{}", hash))
+            .await
+        {
+            Ok(response) => return Ok((response.denom_trace.base_denom, response.denom_trace.path)),
+            Err(e) => e,
+        };
+
+        let response: IbcDenomResponse = self
+            .get_json(&format!("/ibc/apps/transfer/v1/denoms/{}", hash))
+            .await
+            .map_err(|_| trace_error)?;
+        let path = response
+            .denom
+            .trace
+            .iter()
+            .map(|hop| format!("{}/{}", hop.port_id, hop.channel_id))
+            .collect::<Vec<_>>()
+            .join("/");
+        Ok((response.denom.base, path))
+    }
+
+    /// Symbol and decimals of a denom: from the cache, the chain's bank
+    /// metadata, or for IBC denoms the base denom they trace back to
+    async fn denom_metadata(&self, denom: &str) -> Result<TokenMetadata> {
+        let chain = &self.chain;
+        if let Some(metadata) = token_cache::with_cache(|cache| cache.metadata(chain, denom).cloned()) {
+            return Ok(metadata);
+        }
+
+        let metadata = match self.bank_metadata(denom).await {
+            Some(metadata) => metadata,
+            None => match denom.strip_prefix("ibc/") {
+                Some(hash) => {
+                    let (base, path) = self
+                        .denom_trace(hash)
+                        .await
+                        .with_context(|| format!("Failed to resolve IBC denom {}", denom))?;
+                    let metadata = base_denom_metadata(&base, &config::active().settings.cosmos)
+                        .ok_or_else(|| anyhow::anyhow!("No metadata for {}/{}", path, base))?;
+                    TokenMetadata {
+                        name: Some(format!("{}/{}", path, base)),
+                        ..metadata
+                    }
+                }
+                None => base_denom_metadata(denom, &config::active().settings.cosmos).ok_or_else(|| anyhow::anyhow!("No metadata for {}", denom))?,
+            },
+        };
+
+        token_cache::with_cache(|cache| cache.insert_metadata(chain, denom, metadata.clone()));
+        Ok(metadata)
+    }
+
+    /// Resolve the symbol and decimals of each coin, skipping the ones
+    /// without metadata
+    async fn token_balances(&self, coins: Vec<(String, u128)>) -> Vec<TokenBalance> {
+        let results = futures::future::join_all(coins.iter().map(|(denom, _)| self.denom_metadata(denom))).await;
+
+        let mut balances = Vec::new();
+        for ((denom, amount), result) in coins.into_iter().zip(results) {
+            match result {
+                Ok(metadata) => balances.push(TokenBalance {
+                    denom,
+                    name: metadata.name,
+                    symbol: metadata.symbol,
+                    amount: TokenAmount::new(amount, metadata.decimals),
+                }),
+                Err(e) => eprintln!("Warning: Skipping {} on {}: {:#}", denom, self.chain.display_name(), e),
+            }
+        }
+        balances
+    }
+
+    pub async fn get_balances(&self, address: &str) -> Result<AccountBalances> {
+        let network = self.network;
+
+        let balances_path = format!("/cosmos/bank/v1beta1/balances/{}", address);
+        let delegations_path = format!("/cosmos/staking/v1beta1/delegations/{}", address);
+        let unbonding_path = format!("/cosmos/staking/v1beta1/delegators/{}/unbonding_delegations", address);
+        let rewards_path = format!("/cosmos/distribution/v1beta1/delegators/{}/rewards", address);
+        let (coins, delegations, unbondings, rewards) = futures::try_join!(
+            self.get_all::<Coin>(&balances_path, "balances"),
+            self.get_all::<DelegationResponse>(&delegations_path, "delegation_responses"),
+            self.get_all::<UnbondingDelegation>(&unbonding_path, "unbonding_responses"),
+            self.get_json::<RewardsResponse>(&rewards_path),
+        )?;
+
+        let mut native_balance = 0;
+        let mut other_coins = Vec::new();
+        for coin in coins {
+            let amount = parse_amount(&coin.amount)?;
+            if coin.denom == network.staking_denom {
+                native_balance = amount;
+            } else if amount > 0 {
+                other_coins.push((coin.denom, amount));
+            }
+        }
+
+        // Delegated and unbonding stake, per validator
+        let mut stakes: BTreeMap<String, ValidatorStake> = BTreeMap::new();
+        for response in delegations {
+            let validator = response.delegation.validator_address;
+            stakes
+                .entry(validator.clone())
+                .or_insert_with(|| ValidatorStake { validator, ..Default::default() })
+                .delegated += parse_amount(&response.balance.amount)?;
+        }
+        for unbonding in unbondings {
+            let validator = unbonding.validator_address;
+            let stake = stakes
+                .entry(validator.clone())
+                .or_insert_with(|| ValidatorStake { validator, ..Default::default() });
+            for entry in &unbonding.entries {
+                stake.unbonding += parse_amount(&entry.balance)?;
+            }
+        }
+
+        let mut pending_rewards = 0;
+        let mut reward_coins = Vec::new();
+        for coin in rewards.total {
+            let amount = parse_dec_amount(&coin.amount)?;
+            if coin.denom == network.staking_denom {
+                pending_rewards = amount;
+            } else if amount > 0 {
+                reward_coins.push((coin.denom, amount));
+            }
+        }
+
+        let token_balances = self.token_balances(other_coins).await;
+        let reward_balances = self.token_balances(reward_coins).await;
+        token_cache::save();
+
+        Ok(AccountBalances {
+            native_balance: TokenAmount::new(native_balance, network.decimals),
+            stakes: stakes.into_values().filter(|stake| stake.total() > 0).collect(),
+            pending_rewards: TokenAmount::new(pending_rewards, network.decimals),
+            token_balances,
+            reward_balances,
+        })
+    }
+}
+
+#[async_trait]
+impl BalanceSource for CosmosClient {
+    async fn fetch_snapshot(&self, account: &TrackedAccount) -> Result<AccountSnapshot> {
+        let balances = self.get_balances(account.identifier()).await?;
+        let symbol = self.network.symbol.as_str();
+        let decimals = self.network.decimals;
+
+        let mut holdings = vec![Holding::native(symbol, balances.native_balance)];
+        holdings.extend(balances.stakes.iter().map(|stake| {
+            Holding::staked(symbol, stake.validator.clone(), TokenAmount::new(stake.total(), decimals))
+        }));
+        if !balances.pending_rewards.raw().is_zero() {
+            holdings.push(Holding::staked(symbol, "pending rewards".to_string(), balances.pending_rewards));
+        }
+        holdings.extend(balances.token_balances.into_iter().map(|token| {
+            Holding::token(token.denom, token.symbol, token.name, token.amount)
+        }));
+        holdings.extend(balances.reward_balances.into_iter().map(|token| {
+            Holding::token(token.denom, token.symbol, Some("Pending staking rewards".to_string()), token.amount)
+        }));
+
+        let mut snapshot = AccountSnapshot::new(account, holdings);
+        let unbonding: u128 = balances.stakes.iter().map(|stake| stake.unbonding).sum();
+        if unbonding > 0 {
+            snapshot = snapshot.with_detail("Unbonding", format!("{} {}", TokenAmount::new(unbonding, decimals), symbol));
+        }
+
+        Ok(snapshot.with_detail("RPC Endpoint", self.rpc.served_by()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cosmos(key: &str) -> Option<Chain> {
+        Some(Chain::Cosmos(key.to_string()))
+    }
+
+    const JUNO: &str = r#"
+        [juno]
+        name = "Juno"
+        prefix = "juno"
+        staking_denom = "ujuno"
+        symbol = "JUNO"
+        decimals = 6
+        lcd = ["https://juno-rest.publicnode.com"]
+    "#;
+
+    #[test]
+    fn test_chain_for_address() {
+        let networks = CosmosNetworks::default();
+        assert_eq!(networks.chain_for_address("cosmos15w7khykx02nh2kx94za6ad3hxgnnqx2xqh4f7c"), cosmos("cosmos"));
+        assert_eq!(networks.chain_for_address("osmo15w7khykx02nh2kx94za6ad3hxgnnqx2xgvxeg2"), cosmos("osmosis"));
+        assert_eq!(networks.chain_for_address("celestia15w7khykx02nh2kx94za6ad3hxgnnqx2x3ayey4"), cosmos("celestia"));
+        assert_eq!(networks.chain_for_address("inj15w7khykx02nh2kx94za6ad3hxgnnqx2x27zdvq"), cosmos("injective"));
+        assert_eq!(networks.chain_for_address("dydx15w7khykx02nh2kx94za6ad3hxgnnqx2xfwmd70"), cosmos("dydx"));
+
+        // Validator operators, unknown prefixes and bad checksums are not accounts
+        assert_eq!(networks.chain_for_address("cosmosvaloper15w7khykx02nh2kx94za6ad3hxgnnqx2x9rpujt"), None);
+        assert_eq!(networks.chain_for_address("juno15w7khykx02nh2kx94za6ad3hxgnnqx2xk9kjey"), None);
+        assert_eq!(networks.chain_for_address("cosmos15w7khykx02nh2kx94za6ad3hxgnnqx2xqh4f7d"), None);
+        assert_eq!(networks.chain_for_address("bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu"), None);
+
+        // Another network is only configuration
+        let networks: CosmosNetworks = toml::from_str(JUNO).unwrap();
+        networks.validate().unwrap();
+        assert_eq!(networks.chain_for_address("juno15w7khykx02nh2kx94za6ad3hxgnnqx2xk9kjey"), cosmos("juno"));
+        assert_eq!(networks.chain_for_address("cosmos15w7khykx02nh2kx94za6ad3hxgnnqx2xqh4f7c"), cosmos("cosmos"));
+        assert_eq!(networks.get("juno").unwrap().lcd, vec!["https://juno-rest.publicnode.com".to_string()]);
+    }
+
+    #[test]
+    fn test_network_names() {
+        let networks: CosmosNetworks = toml::from_str(JUNO).unwrap();
+        assert_eq!(networks.find("Juno"), cosmos("juno"));
+        assert_eq!(networks.find("cosmoshub"), cosmos("cosmos"));
+        assert_eq!(networks.find("tia"), cosmos("celestia"));
+        assert_eq!(networks.find("akash"), None);
+
+        // Names of other chains and prefixes already in use are rejected
+        let invalid = [
+            JUNO.replace("[juno]", "[solana]"),
+            JUNO.replace("prefix = \"juno\"", "prefix = \"osmo\""),
+            JUNO.replace("name = \"Juno\"", "aliases = [\"osmo\"]"),
+            JUNO.replace("https://juno-rest.publicnode.com", "not a url"),
+        ];
+        for config in invalid {
+            let networks: CosmosNetworks = toml::from_str(&config).unwrap();
+            assert!(networks.validate().is_err(), "{}", config);
+        }
+    }
+
+    #[test]
+    fn test_stored_chain_names() {
+        // Address books written when each network was its own variant still load
+        let stored: Vec<Chain> = serde_json::from_str(r#"["Cosmos", "Dydx", "Ethereum", "juno"]"#).unwrap();
+        assert_eq!(stored, vec![cosmos("cosmos").unwrap(), cosmos("dydx").unwrap(), Chain::Ethereum, cosmos("juno").unwrap()]);
+        assert_eq!(serde_json::to_string(&stored).unwrap(), r#"["cosmos","dydx","Ethereum","juno"]"#);
+    }
+
+    #[test]
+    fn test_denom_amounts_and_metadata() {
+        assert_eq!(parse_dec_amount("1234.567890000000000000").unwrap(), 1234);
+        assert_eq!(parse_dec_amount("0.5").unwrap(), 0);
+        assert!(parse_dec_amount("abc").is_err());
+
+        let networks = CosmosNetworks::default();
+        let dydx = base_denom_metadata("adydx", &networks).unwrap();
+        assert_eq!((dydx.symbol.as_deref(), dydx.decimals), (Some("DYDX"), 18));
+        let akt = base_denom_metadata("uakt", &networks).unwrap();
+        assert_eq!((akt.symbol.as_deref(), akt.decimals), (Some("AKT"), 6));
+        assert!(base_denom_metadata("factory/osmo1abc/alloyed", &networks).is_none());
+    }
+}
@@ -0,0 +1,170 @@
+use crate::config::{self, ApiKey};
+use crate::storage::Chain;
+
+use anyhow::{Context, Result};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+
+/// Etherscan V2 serves every supported chain from one endpoint, selected by
+/// chain id. `[etherscan]` in config.toml can point elsewhere.
+const ETHERSCAN_API_BASE: &str = "https://api.etherscan.io/v2/api";
+
+/// Results per request, and the most Etherscan returns for one query
+const PAGE_SIZE: usize = 1000;
+const MAX_RESULTS: usize = 10_000;
+
+#[derive(Deserialize)]
+struct EtherscanResponse {
+    status: String,
+    message: String,
+    /// A list of records, or an error message when `status` is "0"
+    result: serde_json::Value,
+}
+
+/// A transaction sent or received by the wallet (`txlist`)
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NormalTransaction {
+    pub hash: String,
+    pub block_number: String,
+    pub time_stamp: String,
+    pub from: String,
+    pub to: String,
+    pub value: String,
+    pub gas_used: String,
+    pub gas_price: String,
+    pub is_error: String,
+}
+
+/// A gas token transfer made by a contract during a transaction (`txlistinternal`)
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InternalTransaction {
+    pub hash: String,
+    pub block_number: String,
+    pub time_stamp: String,
+    pub from: String,
+    pub to: String,
+    pub value: String,
+    pub is_error: String,
+}
+
+/// An ERC-20 `Transfer` to or from the wallet (`tokentx`)
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenTransfer {
+    pub hash: String,
+    pub block_number: String,
+    pub time_stamp: String,
+    pub from: String,
+    pub to: String,
+    pub value: String,
+    pub contract_address: String,
+    pub token_name: Option<String>,
+    pub token_symbol: Option<String>,
+    pub token_decimal: String,
+}
+
+/// Client for the account endpoints of an Etherscan-compatible explorer API
+pub struct EtherscanClient {
+    base_url: String,
+    api_key: String,
+    chain_id: u64,
+    client: reqwest::Client,
+}
+
+impl EtherscanClient {
+    /// None when ETHERSCAN_API_KEY isn't configured or the chain isn't an EVM chain
+    pub fn new(chain: &Chain) -> Option<Self> {
+        let base_url = config::active().settings.etherscan.url_for(chain).unwrap_or(ETHERSCAN_API_BASE);
+        Some(Self {
+            base_url: base_url.to_string(),
+            api_key: config::api_key(ApiKey::Etherscan)?,
+            chain_id: chain.evm_chain_id()?,
+            client: reqwest::Client::new(),
+        })
+    }
+
+    /// Records of `action` for `address` between two blocks (inclusive),
+    /// newest first. Stops after `limit` records, or at the API's window of
+    /// MAX_RESULTS with a warning.
+    async fn list<T: DeserializeOwned>(
+        &self,
+        action: &str,
+        address: &str,
+        blocks: (u64, u64),
+        limit: Option<usize>,
+    ) -> Result<Vec<T>> {
+        let mut records = Vec::new();
+
+        for page in 1..=MAX_RESULTS / PAGE_SIZE {
+            let url = format!(
+                "{}?chainid={}&module=account&action={}&address={}&startblock={}&endblock={}&page={}&offset={}&sort=desc&apikey={}",
+                self.base_url, self.chain_id, action, address, blocks.0, blocks.1, page, PAGE_SIZE, self.api_key
+            );
+
+            // The API key is in the query string; keep the URL out of errors
+            let response: EtherscanResponse = self
+                .client
+                .get(&url)
+                .send()
+                .await
+                .map_err(|e| e.without_url())
+                .context("Failed to send request to Etherscan API")?
+                .json()
+                .await
+                .map_err(|e| e.without_url())
+                .context("Failed to parse Etherscan API response")?;
+
+            // An empty result is reported as status "0" too
+            if response.status != "1" {
+                if response.result.as_array().is_some_and(|result| result.is_empty()) {
+                    break;
+                }
+                anyhow::bail!(
+                    "Etherscan API error: {} ({})",
+                    response.message,
+                    response.result.as_str().unwrap_or_default()
+                );
+            }
+
+            let page_records: Vec<T> = serde_json::from_value(response.result)
+                .with_context(|| format!("Failed to parse Etherscan {} results", action))?;
+            let full_page = page_records.len() == PAGE_SIZE;
+            records.extend(page_records);
+
+            if let Some(limit) = limit {
+                if records.len() >= limit {
+                    records.truncate(limit);
+                    return Ok(records);
+                }
+            }
+            if !full_page {
+                return Ok(records);
+            }
+        }
+
+        eprintln!(
+            "Warning: Etherscan returns at most {} {} records; narrow the range with --start and --end",
+            MAX_RESULTS, action
+        );
+        Ok(records)
+    }
+
+    pub async fn transactions(&self, address: &str, blocks: (u64, u64), limit: Option<usize>) -> Result<Vec<NormalTransaction>> {
+        self.list("txlist", address, blocks, limit).await
+    }
+
+    pub async fn internal_transactions(
+        &self,
+        address: &str,
+        blocks: (u64, u64),
+        limit: Option<usize>,
+    ) -> Result<Vec<InternalTransaction>> {
+        self.list("txlistinternal", address, blocks, limit).await
+    }
+
+    pub async fn token_transfers(&self, address: &str, blocks: (u64, u64), limit: Option<usize>) -> Result<Vec<TokenTransfer>> {
+        self.list("tokentx", address, blocks, limit).await
+    }
+}
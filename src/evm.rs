@@ -1,52 +1,20 @@
 use anyhow::{Context, Result};
-use crate::storage::Chain;
+use crate::amount::{self, TokenAmount};
+use crate::multicall::{self, Call, MULTICALL3_ADDRESS};
+use crate::rpc::{self, HttpPool, RpcEndpoint};
+use crate::etherscan::EtherscanClient;
+use crate::source::{AccountSnapshot, BalanceChange, BalanceSource, Holding, TransactionRange, WalletTransaction};
+use crate::storage::{Chain, TrackedAccount};
+use async_trait::async_trait;
+use bigdecimal::num_bigint::{BigInt, BigUint};
+use bigdecimal::{ToPrimitive, Zero};
+use crate::token_cache::{self, TokenMetadata};
+use crate::token_list::{self, TokenList};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
-use std::env;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet};
 
-fn get_alchemy_rpc_url(chain: &Chain, api_key: &str) -> Option<String> {
-    match chain {
-        Chain::Ethereum => Some(format!("https://eth-mainnet.g.alchemy.com/v2/{}", api_key)),
-        Chain::Polygon => Some(format!("https://polygon-mainnet.g.alchemy.com/v2/{}", api_key)),
-        Chain::Arbitrum => Some(format!("https://arb-mainnet.g.alchemy.com/v2/{}", api_key)),
-        Chain::Optimism => Some(format!("https://opt-mainnet.g.alchemy.com/v2/{}", api_key)),
-        Chain::Base => Some(format!("https://base-mainnet.g.alchemy.com/v2/{}", api_key)),
-        _ => None, // Chain not supported by Alchemy
-    }
-}
-
-fn get_default_rpc_url(chain: &Chain) -> Result<String> {
-    // Try Alchemy first if API key is set and non-empty
-    if let Ok(api_key) = env::var("ALCHEMY_API_KEY") {
-        if !api_key.is_empty() {
-            if let Some(alchemy_url) = get_alchemy_rpc_url(chain, &api_key) {
-                return Ok(alchemy_url);
-            }
-        }
-    } else {
-        // Only show warning for commonly used chains to avoid spam
-        if matches!(chain, Chain::Base) {
-            eprintln!("Warning: ALCHEMY_API_KEY not set. Using public EVM RPCs.");
-            eprintln!("For better performance and higher limits, get an Alchemy API key at https://alchemy.com");
-        }
-    }
-
-    // Fall back to public RPCs
-    match chain {
-        Chain::Ethereum => Ok("https://eth.llamarpc.com".to_string()),
-        Chain::Polygon => Ok("https://polygon-rpc.com".to_string()),
-        Chain::BinanceSmartChain => Ok("https://bsc-dataseed.binance.org".to_string()),
-        Chain::Arbitrum => Ok("https://arb1.arbitrum.io/rpc".to_string()),
-        Chain::Optimism => Ok("https://mainnet.optimism.io".to_string()),
-        Chain::Avalanche => Ok("https://api.avax.network/ext/bc/C/rpc".to_string()),
-        Chain::Base => Ok("https://mainnet.base.org".to_string()),
-        Chain::Core => Ok("https://rpc.coredao.org".to_string()),
-        _ => anyhow::bail!("Chain {:?} is not an EVM chain", chain),
-    }
-}
-
-// Common ERC20 tokens by chain
+// Common ERC20 tokens by chain, always checked even when discovery finds nothing
 fn get_common_tokens(chain: &Chain) -> Vec<(&'static str, &'static str)> {
     match chain {
         Chain::Ethereum => vec![
@@ -91,29 +59,91 @@ fn get_common_tokens(chain: &Chain) -> Vec<(&'static str, &'static str)> {
     }
 }
 
-#[derive(Debug)]
+/// keccak256("Transfer(address,address,uint256)")
+const TRANSFER_TOPIC: &str = "0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef";
+
+/// Blocks scanned for incoming transfers the first time a wallet is queried,
+/// and for transaction history without a start date or Etherscan API key
+const LOG_SCAN_LOOKBACK: u64 = 100_000;
+const LOG_SCAN_CHUNK: u64 = 10_000;
+const LOG_SCAN_MIN_CHUNK: u64 = 500;
+
+/// Requests per JSON-RPC batch array; most providers cap batches at 50-100
+const RPC_BATCH_SIZE: usize = 50;
+/// Calls per Multicall3 aggregate3, kept well under eth_call gas limits
+const MULTICALL_CHUNK: usize = 300;
+
+/// Token list addresses are contracts, compared in lowercase
+fn normalize_contract(address: &str) -> Result<String> {
+    if !address.starts_with("0x") || address.len() != 42 {
+        anyhow::bail!("Invalid EVM contract address: {}", address);
+    }
+    Ok(address.to_lowercase())
+}
+
+/// Parse a hex quantity such as a block number or timestamp
+fn parse_hex_u64(hex: &str) -> Result<u64> {
+    u64::from_str_radix(hex.trim_start_matches("0x"), 16).with_context(|| format!("Invalid hex quantity: {}", hex))
+}
+
+/// Distinct well-formed addresses (compared case-insensitively), and for each
+/// input address the index of its entry; None for a malformed address
+fn distinct_addresses<'a>(addresses: &[&'a str]) -> (Vec<&'a str>, Vec<Option<usize>>) {
+    let mut distinct: Vec<&str> = Vec::new();
+    let slots = addresses
+        .iter()
+        .map(|address| {
+            if !address.starts_with("0x") || address.len() != 42 {
+                return None;
+            }
+            match distinct.iter().position(|seen| seen.eq_ignore_ascii_case(address)) {
+                Some(i) => Some(i),
+                None => {
+                    distinct.push(address);
+                    Some(distinct.len() - 1)
+                }
+            }
+        })
+        .collect();
+    (distinct, slots)
+}
+
+/// Results for every input address from the per-distinct-address balances
+fn fan_out(
+    slots: &[Option<usize>],
+    native: Vec<Result<TokenAmount>>,
+    token_balances: Vec<Vec<TokenBalance>>,
+) -> Vec<Result<AccountBalances>> {
+    slots
+        .iter()
+        .map(|slot| match slot.map(|i| (&native[i], &token_balances[i])) {
+            Some((Ok(native_balance), token_balances)) => Ok(AccountBalances {
+                native_balance: native_balance.clone(),
+                token_balances: token_balances.clone(),
+            }),
+            Some((Err(e), _)) => Err(anyhow::anyhow!("{:#}", e)),
+            None => Err(anyhow::anyhow!("Invalid EVM address format")),
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone)]
 pub struct TokenBalance {
     pub contract_address: String,
     pub name: Option<String>,
     pub symbol: Option<String>,
-    pub decimals: u8,
-    pub ui_amount: f64,
-    pub usd_price: Option<f64>,
-    pub usd_value: Option<f64>,
+    pub amount: TokenAmount,
 }
 
 #[derive(Debug)]
 pub struct AccountBalances {
-    pub eth_balance: f64,
-    pub eth_usd_price: Option<f64>,
-    pub eth_usd_value: Option<f64>,
+    /// Balance of the chain's gas token (ETH, POL, BNB, AVAX, CORE)
+    pub native_balance: TokenAmount,
     pub token_balances: Vec<TokenBalance>,
-    pub total_usd_value: Option<f64>,
 }
 
 pub struct EvmClient {
-    client: reqwest::Client,
-    rpc_url: String,
+    rpc: HttpPool,
     chain: Chain,
 }
 
@@ -127,6 +157,7 @@ struct JsonRpcRequest {
 
 #[derive(Deserialize)]
 struct JsonRpcResponse {
+    id: Option<u64>,
     result: Option<serde_json::Value>,
     error: Option<JsonRpcError>,
 }
@@ -136,22 +167,162 @@ struct JsonRpcError {
     message: String,
 }
 
-impl EvmClient {
-    pub fn new(rpc_url: Option<String>, chain: Chain) -> Result<Self> {
-        let url = match rpc_url {
-            Some(u) => u,
-            None => get_default_rpc_url(&chain)?,
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AlchemyTokenBalances {
+    token_balances: Vec<AlchemyTokenBalance>,
+    page_key: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AlchemyTokenBalance {
+    contract_address: String,
+    /// Hex-encoded raw balance; null when the balance could not be read
+    token_balance: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TransferLog {
+    address: String,
+    topics: Vec<String>,
+    #[serde(default)]
+    data: String,
+    block_number: Option<String>,
+    transaction_hash: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RpcTransaction {
+    from: String,
+    /// None for contract creations
+    to: Option<String>,
+    value: String,
+    gas_price: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RpcReceipt {
+    status: Option<String>,
+    gas_used: String,
+    effective_gas_price: Option<String>,
+    /// Data fee paid to L1 on OP Stack chains
+    l1_fee: Option<String>,
+}
+
+/// The address in an indexed `address` log topic
+fn topic_address(topic: &str) -> String {
+    format!("0x{}", &topic[topic.len().saturating_sub(40)..]).to_lowercase()
+}
+
+/// A wallet transaction while its transfers are collected
+#[derive(Default)]
+struct TransactionParts {
+    timestamp: Option<i64>,
+    failed: bool,
+    fee: Option<BigUint>,
+    native: BigInt,
+    tokens: BTreeMap<String, BigInt>,
+    counterparties: BTreeSet<String>,
+}
+
+/// A wallet's transactions, assembled from the transfers found in logs or
+/// explorer records and keyed by block and hash
+struct History {
+    /// Lowercase wallet address
+    wallet: String,
+    transactions: BTreeMap<(u64, String), TransactionParts>,
+}
+
+impl History {
+    fn new(wallet: &str) -> Self {
+        Self {
+            wallet: wallet.to_lowercase(),
+            transactions: BTreeMap::new(),
+        }
+    }
+
+    fn entry(&mut self, hash: &str, block: u64) -> &mut TransactionParts {
+        self.transactions.entry((block, hash.to_lowercase())).or_default()
+    }
+
+    /// Record a transfer of the gas token (`token` is None) or of an ERC-20
+    /// token. Transfers the wallet isn't part of are ignored, and transfers
+    /// to itself don't change its balance.
+    fn transfer(&mut self, hash: &str, block: u64, token: Option<&str>, from: &str, to: &str, value: BigUint) {
+        let (from, to) = (from.to_lowercase(), to.to_lowercase());
+        let (change, counterparty) = if from == to || value.is_zero() {
+            return;
+        } else if from == self.wallet {
+            (-BigInt::from(value), to)
+        } else if to == self.wallet {
+            (BigInt::from(value), from)
+        } else {
+            return;
         };
-        let client = reqwest::Client::builder()
-            .timeout(std::time::Duration::from_secs(30))
-            .build()
-            .unwrap_or_else(|_| reqwest::Client::new());
-
-        Ok(Self {
-            client,
-            rpc_url: url,
+
+        let parts = self.entry(hash, block);
+        match token {
+            Some(token) => *parts.tokens.entry(token.to_lowercase()).or_default() += change,
+            None => parts.native += change,
+        }
+        parts.counterparties.insert(counterparty);
+    }
+
+    /// The transactions newest first. Token changes without metadata are left
+    /// out with a warning, since their decimals aren't known.
+    fn finish(self, native_symbol: &str, metadata: &BTreeMap<String, TokenMetadata>) -> Vec<WalletTransaction> {
+        self.transactions
+            .into_iter()
+            .rev()
+            .map(|((block, hash), parts)| {
+                let mut changes = Vec::new();
+                if !parts.native.is_zero() {
+                    changes.push(BalanceChange {
+                        asset_id: None,
+                        symbol: Some(native_symbol.to_string()),
+                        amount: TokenAmount::new(parts.native, 18),
+                    });
+                }
+                for (token, change) in parts.tokens {
+                    if change.is_zero() {
+                        continue;
+                    }
+                    let Some(metadata) = metadata.get(&token) else {
+                        eprintln!("Warning: Unknown decimals for token {} in transaction {}; leaving it out", token, hash);
+                        continue;
+                    };
+                    changes.push(BalanceChange {
+                        symbol: metadata.symbol.clone(),
+                        amount: TokenAmount::new(change, metadata.decimals),
+                        asset_id: Some(token),
+                    });
+                }
+
+                WalletTransaction {
+                    id: hash,
+                    block,
+                    timestamp: parts.timestamp,
+                    success: !parts.failed,
+                    memo: None,
+                    fee: parts.fee.map(|fee| TokenAmount::new(fee, 18)),
+                    changes,
+                    counterparties: parts.counterparties.into_iter().collect(),
+                }
+            })
+            .collect()
+    }
+}
+
+impl EvmClient {
+    pub fn new(endpoints: Vec<RpcEndpoint>, chain: Chain) -> Self {
+        Self {
+            rpc: HttpPool::http(endpoints),
             chain,
-        })
+        }
     }
 
     async fn rpc_call(&self, method: &str, params: serde_json::Value) -> Result<serde_json::Value> {
@@ -162,258 +333,819 @@ impl EvmClient {
             id: 1,
         };
 
-        let response = self
-            .client
-            .post(&self.rpc_url)
-            .json(&request)
-            .send()
-            .await
-            .context("Failed to send RPC request")?;
+        let response = self.rpc.post("", &request).await?;
 
-        let rpc_response: JsonRpcResponse = response
-            .json()
+        let rpc_response: JsonRpcResponse = rpc::json_body(response)
             .await
             .context("Failed to parse RPC response")?;
 
-        if let Some(error) = rpc_response.error {
+        self.rpc_result(rpc_response)
+    }
+
+    fn rpc_result(&self, response: JsonRpcResponse) -> Result<serde_json::Value> {
+        if let Some(error) = response.error {
             // Check if it's a rate limit error
             if error.message.contains("rate limit") || error.message.contains("too many requests") {
-                anyhow::bail!("Rate limit exceeded. Try again in a moment or use a custom RPC URL with --rpc-url");
+                anyhow::bail!("Rate limit exceeded. Try again in a moment or configure another RPC endpoint for {} in ~/.gringotts/config.toml", self.chain.display_name());
             }
             anyhow::bail!("RPC error: {}", error.message);
         }
 
-        rpc_response
+        response
             .result
             .ok_or_else(|| anyhow::anyhow!("No result in RPC response"))
     }
 
-    pub async fn get_balances(&self, address: &str) -> Result<AccountBalances> {
-        // Validate EVM address format
-        if !address.starts_with("0x") || address.len() != 42 {
-            anyhow::bail!("Invalid EVM address format");
+    /// Send requests as JSON-RPC batch arrays, falling back to one request at
+    /// a time when the endpoint rejects batches. Each request succeeds or
+    /// fails on its own.
+    async fn rpc_batch(&self, requests: Vec<(&str, serde_json::Value)>) -> Vec<Result<serde_json::Value>> {
+        let mut results = Vec::with_capacity(requests.len());
+
+        for chunk in requests.chunks(RPC_BATCH_SIZE) {
+            match self.send_batch(chunk).await {
+                Ok(chunk_results) => results.extend(chunk_results),
+                Err(_) => {
+                    for (method, params) in chunk {
+                        results.push(self.rpc_call(method, params.clone()).await);
+                    }
+                }
+            }
+        }
+
+        results
+    }
+
+    async fn send_batch(&self, requests: &[(&str, serde_json::Value)]) -> Result<Vec<Result<serde_json::Value>>> {
+        let batch: Vec<JsonRpcRequest> = requests
+            .iter()
+            .enumerate()
+            .map(|(id, (method, params))| JsonRpcRequest {
+                jsonrpc: "2.0".to_string(),
+                method: method.to_string(),
+                params: params.clone(),
+                id: id as u64,
+            })
+            .collect();
+
+        let response = self.rpc.post("", &batch).await?;
+
+        // Endpoints without batch support answer with a single error object
+        let responses: Vec<JsonRpcResponse> = rpc::json_body(response)
+            .await
+            .context("RPC endpoint does not support batch requests")?;
+
+        // Responses may come back in any order
+        let mut by_id: BTreeMap<u64, JsonRpcResponse> = responses
+            .into_iter()
+            .filter_map(|response| response.id.map(|id| (id, response)))
+            .collect();
+
+        Ok((0..requests.len() as u64)
+            .map(|id| match by_id.remove(&id) {
+                Some(response) => self.rpc_result(response),
+                None => Err(anyhow::anyhow!("No response to batched RPC request")),
+            })
+            .collect())
+    }
+
+    /// Run read-only contract calls through Multicall3, or as a batch of
+    /// `eth_call`s on chains where it isn't available. None marks a call that
+    /// reverted or could not be made.
+    async fn call_many(&self, calls: &[Call]) -> Vec<Option<Vec<u8>>> {
+        let mut results = Vec::with_capacity(calls.len());
+        let mut use_multicall = true;
+
+        for chunk in calls.chunks(MULTICALL_CHUNK) {
+            if use_multicall {
+                match self.aggregate3(chunk).await {
+                    Ok(chunk_results) => {
+                        results.extend(chunk_results);
+                        continue;
+                    }
+                    Err(_) => use_multicall = false,
+                }
+            }
+
+            let requests = chunk
+                .iter()
+                .map(|call| ("eth_call", json!([{ "to": call.target, "data": multicall::encode_hex(&call.data) }, "latest"])))
+                .collect();
+
+            results.extend(self.rpc_batch(requests).await.into_iter().map(|result| {
+                result
+                    .ok()
+                    .and_then(|value| value.as_str().and_then(|hex| multicall::decode_hex(hex).ok()))
+            }));
         }
 
-        // Get ETH balance
-        let balance_hex = self
-            .rpc_call("eth_getBalance", json!([address, "latest"]))
+        results
+    }
+
+    async fn aggregate3(&self, calls: &[Call]) -> Result<Vec<Option<Vec<u8>>>> {
+        let data = multicall::encode_aggregate3(calls)?;
+        let result = self
+            .rpc_call("eth_call", json!([{ "to": MULTICALL3_ADDRESS, "data": multicall::encode_hex(&data) }, "latest"]))
             .await?;
 
-        let balance_str = balance_hex
+        let hex = result
             .as_str()
-            .ok_or_else(|| anyhow::anyhow!("Invalid balance format"))?;
+            .ok_or_else(|| anyhow::anyhow!("Invalid eth_call result format"))?;
 
-        // Convert hex string to u128
-        let balance_wei = u128::from_str_radix(
-            balance_str.trim_start_matches("0x"),
-            16
-        ).context("Failed to parse balance")?;
+        let results = multicall::decode_aggregate3(&multicall::decode_hex(hex)?)
+            .context("Failed to decode Multicall3 response")?;
+        if results.len() != calls.len() {
+            anyhow::bail!("Multicall3 returned {} results for {} calls", results.len(), calls.len());
+        }
+        Ok(results)
+    }
 
-        // Convert wei to ETH (1 ETH = 10^18 wei)
-        let eth_balance = balance_wei as f64 / 1_000_000_000_000_000_000.0;
+    pub async fn get_balances(&self, address: &str) -> Result<AccountBalances> {
+        self.get_balances_many(&[address])
+            .await
+            .pop()
+            .expect("one result per address")
+    }
 
-        // Query ERC20 token balances
-        let mut token_balances = Vec::new();
-        let common_tokens = get_common_tokens(&self.chain);
+    /// Balances of several wallets on this chain. Native balances, token
+    /// balances and token metadata are each read in as few requests as
+    /// possible; a failed read only affects the wallet or token it belongs to.
+    pub async fn get_balances_many(&self, addresses: &[&str]) -> Vec<Result<AccountBalances>> {
+        // The same wallet can be tracked more than once (under two companies);
+        // each distinct address is read once and the results fanned back out
+        let (valid, slots) = distinct_addresses(addresses);
 
-        for (token_address, symbol) in common_tokens {
-            match self.query_erc20_balance(address, token_address).await {
-                Ok(Some(token_balance)) => {
-                    token_balances.push(token_balance);
-                }
-                Ok(None) => {
-                    // Token balance is zero, skip
+        // Native balances; every supported chain's gas token has 18 decimals
+        let native = self
+            .rpc_batch(valid.iter().map(|address| ("eth_getBalance", json!([address, "latest"]))).collect())
+            .await;
+        let native: Vec<Result<TokenAmount>> = native
+            .into_iter()
+            .map(|result| {
+                result.and_then(|value| {
+                    let hex = value.as_str().ok_or_else(|| anyhow::anyhow!("Invalid balance format"))?;
+                    TokenAmount::from_hex(hex, 18).context("Failed to parse balance")
+                })
+            })
+            .collect();
+
+        // Token contracts to check: the built-in stablecoins, the user's token
+        // list and whatever discovery finds. Discovery through Alchemy already
+        // returns the balance; the others need a balanceOf call.
+        let token_list = token_list::load(&self.chain, normalize_contract).unwrap_or_else(|e| {
+            eprintln!("Warning: Ignoring {} token list: {:#}", self.chain.display_name(), e);
+            TokenList::default()
+        });
+
+        let mut listed: BTreeSet<String> = get_common_tokens(&self.chain)
+            .iter()
+            .map(|(token_address, _)| token_address.to_lowercase())
+            .collect();
+        listed.extend(token_list.tokens.iter().map(|token| token.address.clone()));
+
+        let wallets: Vec<usize> = (0..valid.len()).filter(|i| native[*i].is_ok()).collect();
+        let discovered = futures::future::join_all(wallets.iter().map(|i| self.discover_tokens(valid[*i]))).await;
+
+        let mut candidates: Vec<(usize, String, Option<BigUint>)> = Vec::new();
+        for (wallet, discovered) in wallets.iter().copied().zip(discovered) {
+            let mut tokens: BTreeMap<String, Option<BigUint>> = listed.iter().map(|token| (token.clone(), None)).collect();
+            match discovered {
+                Ok(discovered) => {
+                    for (token_address, balance) in discovered {
+                        if balance.is_some() || !tokens.contains_key(&token_address) {
+                            tokens.insert(token_address, balance);
+                        }
+                    }
                 }
                 Err(e) => {
-                    eprintln!("Warning: Failed to query {} balance: {}", symbol, e);
+                    eprintln!("Warning: Token discovery failed on {}: {}", self.chain.display_name(), e);
                 }
             }
+            candidates.extend(tokens.into_iter().map(|(token, balance)| (wallet, token, balance)));
         }
 
-        Ok(AccountBalances {
-            eth_balance,
-            eth_usd_price: None,
-            eth_usd_value: None,
-            token_balances,
-            total_usd_value: None,
-        })
+        // Query ERC20 token balances for every wallet at once
+        let balance_calls: Vec<(usize, Call)> = candidates
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, _, balance))| balance.is_none())
+            .filter_map(|(i, (wallet, token, _))| {
+                Call::with_address(token, multicall::BALANCE_OF, valid[*wallet]).ok().map(|call| (i, call))
+            })
+            .collect();
+        let calls: Vec<Call> = balance_calls.iter().map(|(_, call)| call.clone()).collect();
+        let mut balances: Vec<Option<BigUint>> = candidates.iter().map(|(_, _, balance)| balance.clone()).collect();
+        for ((i, _), result) in balance_calls.iter().zip(self.call_many(&calls).await) {
+            match result.as_deref().and_then(multicall::decode_uint) {
+                Some(balance) => balances[*i] = Some(balance),
+                None => eprintln!("Warning: Failed to query token {} balance", candidates[*i].1),
+            }
+        }
+
+        // Token balance is zero, skip
+        let held: Vec<(usize, &str, BigUint)> = candidates
+            .iter()
+            .zip(balances)
+            .filter_map(|((wallet, token, _), balance)| {
+                balance.filter(|b| !b.is_zero()).map(|b| (*wallet, token.as_str(), b))
+            })
+            .collect();
+
+        let metadata = self
+            .token_metadata(held.iter().map(|(_, token, _)| *token).collect(), &token_list)
+            .await;
+
+        let mut token_balances: Vec<Vec<TokenBalance>> = vec![Vec::new(); valid.len()];
+        for (wallet, token_address, balance) in held {
+            let Some(metadata) = metadata.get(token_address) else {
+                eprintln!("Warning: Failed to query token {} metadata", token_address);
+                continue;
+            };
+
+            // Airdropped lookalikes of listed tokens (a fake "USDC") would
+            // otherwise be priced as the real thing
+            if !listed.contains(token_address) && self.impersonates_listed_token(metadata, &token_list) {
+                eprintln!(
+                    "Warning: Ignoring token {} on {}: it uses the symbol of a listed token",
+                    token_address,
+                    self.chain.display_name()
+                );
+                continue;
+            }
+
+            token_balances[wallet].push(TokenBalance {
+                contract_address: token_address.to_string(),
+                name: metadata.name.clone(),
+                symbol: metadata.symbol.clone(),
+                amount: TokenAmount::new(balance, metadata.decimals),
+            });
+        }
+
+        token_cache::save();
+
+        fan_out(&slots, native, token_balances)
     }
 
-    async fn query_erc20_balance(&self, wallet_address: &str, token_address: &str) -> Result<Option<TokenBalance>> {
-        // ERC20 balanceOf(address) function signature
-        let balance_of_sig = "0x70a08231";
+    fn snapshot(&self, account: &TrackedAccount, balances: AccountBalances) -> AccountSnapshot {
+        let mut holdings = vec![Holding::native(self.chain.native_token_symbol(), balances.native_balance)];
+        holdings.extend(
+            balances
+                .token_balances
+                .into_iter()
+                .map(|token| Holding::token(token.contract_address, token.symbol, token.name, token.amount)),
+        );
 
-        // Encode the wallet address (remove 0x prefix, pad to 32 bytes)
-        let wallet_addr_clean = wallet_address.trim_start_matches("0x");
-        let padded_address = format!("{:0>64}", wallet_addr_clean);
-        let data = format!("{}{}", balance_of_sig, padded_address);
+        AccountSnapshot::new(account, holdings).with_detail("RPC Endpoint", self.rpc.served_by())
+    }
 
-        // Call eth_call
-        let result = self.rpc_call("eth_call", json!([
-            {
-                "to": token_address,
-                "data": data
-            },
-            "latest"
-        ])).await?;
+    fn impersonates_listed_token(&self, metadata: &TokenMetadata, token_list: &TokenList) -> bool {
+        let Some(symbol) = &metadata.symbol else {
+            return false;
+        };
+        get_common_tokens(&self.chain).iter().any(|(_, listed)| listed.eq_ignore_ascii_case(symbol))
+            || token_list.tokens.iter().any(|token| {
+                token.metadata.as_ref().and_then(|m| m.symbol.as_ref()).is_some_and(|s| s.eq_ignore_ascii_case(symbol))
+            })
+    }
 
-        let balance_hex = result
-            .as_str()
-            .ok_or_else(|| anyhow::anyhow!("Invalid balance format"))?;
+    /// Token contracts the wallet holds or has received: Alchemy's token API
+    /// when the endpoint supports it, otherwise a scan of Transfer logs
+    async fn discover_tokens(&self, address: &str) -> Result<Vec<(String, Option<BigUint>)>> {
+        if let Ok(balances) = self.alchemy_token_balances(address).await {
+            return Ok(balances.into_iter().map(|(token, balance)| (token, Some(balance))).collect());
+        }
+
+        let tokens = self.scan_transfer_logs(address).await?;
+        Ok(tokens.into_iter().map(|token| (token, None)).collect())
+    }
+
+    async fn alchemy_token_balances(&self, address: &str) -> Result<Vec<(String, BigUint)>> {
+        let mut balances = Vec::new();
+        let mut page_key: Option<String> = None;
+
+        loop {
+            let mut params = vec![json!(address), json!("erc20")];
+            if let Some(key) = &page_key {
+                params.push(json!({ "pageKey": key }));
+            }
+
+            let result = self.rpc_call("alchemy_getTokenBalances", json!(params)).await?;
+            let page: AlchemyTokenBalances = serde_json::from_value(result)
+                .context("Failed to parse alchemy_getTokenBalances response")?;
 
-        // Parse balance
-        let balance_u256 = u128::from_str_radix(
-            balance_hex.trim_start_matches("0x"),
-            16
-        ).unwrap_or(0);
+            for token in page.token_balances {
+                if let Some(balance) = token.token_balance {
+                    let balance = amount::parse_hex_uint(&balance)?;
+                    balances.push((token.contract_address.to_lowercase(), balance));
+                }
+            }
 
-        // If balance is zero, return None
-        if balance_u256 == 0 {
-            return Ok(None);
+            match page.page_key {
+                Some(key) => page_key = Some(key),
+                None => break,
+            }
         }
 
-        // Query token metadata
-        let decimals = self.query_erc20_decimals(token_address).await?;
-        let name = self.query_erc20_name(token_address).await.ok();
-        let symbol = self.query_erc20_symbol(token_address).await.ok();
-
-        // Calculate UI amount
-        let divisor = 10_u128.pow(decimals as u32) as f64;
-        let ui_amount = balance_u256 as f64 / divisor;
-
-        Ok(Some(TokenBalance {
-            contract_address: token_address.to_string(),
-            name,
-            symbol,
-            decimals,
-            ui_amount,
-            usd_price: None,
-            usd_value: None,
-        }))
-    }
-
-    async fn query_erc20_decimals(&self, token_address: &str) -> Result<u8> {
-        // decimals() function signature
-        let decimals_sig = "0x313ce567";
-
-        let result = self.rpc_call("eth_call", json!([
-            {
-                "to": token_address,
-                "data": decimals_sig
-            },
-            "latest"
-        ])).await?;
+        Ok(balances)
+    }
 
-        let decimals_hex = result
-            .as_str()
-            .ok_or_else(|| anyhow::anyhow!("Invalid decimals format"))?;
+    /// Find token contracts that sent the wallet a Transfer. The first scan
+    /// covers the last LOG_SCAN_LOOKBACK blocks; later runs continue from the
+    /// last scanned block, and the tokens found are kept in the token cache.
+    async fn scan_transfer_logs(&self, address: &str) -> Result<BTreeSet<String>> {
+        let wallet = address.to_lowercase();
+        let latest = self.latest_block().await?;
+
+        let mut scan = token_cache::with_cache(|cache| cache.log_scan(&self.chain, &wallet).cloned()).unwrap_or_default();
+        let oldest = latest.saturating_sub(LOG_SCAN_LOOKBACK);
+        let mut from = scan.last_block.map(|block| block + 1).unwrap_or(oldest).max(oldest);
 
-        let decimals = u8::from_str_radix(
-            decimals_hex.trim_start_matches("0x"),
-            16
-        ).unwrap_or(18);
+        let recipient_topic = format!("0x{:0>64}", wallet.trim_start_matches("0x"));
+        let mut chunk = LOG_SCAN_CHUNK;
 
-        Ok(decimals)
+        while from <= latest {
+            let to = (from + chunk - 1).min(latest);
+            let result = self
+                .rpc_call("eth_getLogs", json!([{
+                    "fromBlock": format!("0x{:x}", from),
+                    "toBlock": format!("0x{:x}", to),
+                    "topics": [TRANSFER_TOPIC, null, recipient_topic],
+                }]))
+                .await;
+
+            match result {
+                Ok(logs) => {
+                    let logs: Vec<TransferLog> = serde_json::from_value(logs)
+                        .context("Failed to parse eth_getLogs response")?;
+                    // ERC-721 transfers share the event signature but index a fourth topic
+                    scan.tokens.extend(
+                        logs.into_iter()
+                            .filter(|log| log.topics.len() == 3)
+                            .map(|log| log.address.to_lowercase()),
+                    );
+                    scan.last_block = Some(to);
+                    from = to + 1;
+                }
+                // Most RPCs cap the block range or number of results per call
+                Err(_) if chunk > LOG_SCAN_MIN_CHUNK => chunk /= 2,
+                Err(e) => {
+                    eprintln!(
+                        "Warning: Stopped scanning {} transfer logs at block {}: {}",
+                        self.chain.display_name(),
+                        from,
+                        e
+                    );
+                    break;
+                }
+            }
+        }
+
+        let tokens = scan.tokens.clone();
+        token_cache::with_cache(|cache| cache.insert_log_scan(&self.chain, &wallet, scan));
+        Ok(tokens)
     }
 
-    async fn query_erc20_name(&self, token_address: &str) -> Result<String> {
-        // name() function signature
-        let name_sig = "0x06fdde03";
+    /// Decimals, symbol and name of each token from the token list, the
+    /// cache, or the contracts themselves (then cached). Tokens whose
+    /// decimals can't be read are left out.
+    async fn token_metadata(&self, tokens: BTreeSet<&str>, token_list: &TokenList) -> BTreeMap<String, TokenMetadata> {
+        let mut metadata = BTreeMap::new();
+        let mut missing = Vec::new();
 
-        let result = self.rpc_call("eth_call", json!([
-            {
-                "to": token_address,
-                "data": name_sig
-            },
-            "latest"
-        ])).await?;
+        for token_address in tokens {
+            let known = token_list
+                .metadata(token_address)
+                .or_else(|| token_cache::with_cache(|cache| cache.metadata(&self.chain, token_address).cloned()));
 
-        let name_hex = result
-            .as_str()
-            .ok_or_else(|| anyhow::anyhow!("Invalid name format"))?;
+            match known {
+                Some(known) => {
+                    metadata.insert(token_address.to_string(), known);
+                }
+                None => missing.push(token_address),
+            }
+        }
+
+        let calls: Vec<Call> = missing
+            .iter()
+            .flat_map(|token| {
+                [multicall::DECIMALS, multicall::SYMBOL, multicall::NAME]
+                    .map(|selector| Call::new(token, selector))
+            })
+            .collect();
+        let results = self.call_many(&calls).await;
+
+        for (token_address, results) in missing.into_iter().zip(results.chunks(3)) {
+            let Some(decimals) = results[0]
+                .as_deref()
+                .and_then(multicall::decode_uint)
+                .and_then(|decimals| decimals.to_u8())
+            else {
+                continue;
+            };
 
-        // Decode string from hex (simplified - just try to decode UTF-8)
-        let name = self.decode_string_from_hex(name_hex)?;
-        Ok(name)
+            let fetched = TokenMetadata {
+                decimals,
+                symbol: results[1].as_deref().and_then(multicall::decode_string),
+                name: results[2].as_deref().and_then(multicall::decode_string),
+            };
+            token_cache::with_cache(|cache| cache.insert_metadata(&self.chain, token_address, fetched.clone()));
+            metadata.insert(token_address.to_string(), fetched);
+        }
+
+        metadata
+    }
+
+    async fn latest_block(&self) -> Result<u64> {
+        let result = self.rpc_call("eth_blockNumber", json!([])).await?;
+        let hex = result.as_str().ok_or_else(|| anyhow::anyhow!("Invalid block number format"))?;
+        parse_hex_u64(hex)
+    }
+
+    async fn block_timestamp(&self, block: u64) -> Result<i64> {
+        let result = self.rpc_call("eth_getBlockByNumber", json!([format!("0x{:x}", block), false])).await?;
+        let hex = result["timestamp"].as_str().ok_or_else(|| anyhow::anyhow!("Invalid block format"))?;
+        i64::try_from(parse_hex_u64(hex)?).context("Invalid block timestamp")
+    }
+
+    /// The first block at or after `timestamp` (`latest` when there is none yet),
+    /// by binary search over block headers
+    async fn block_at(&self, timestamp: i64, latest: u64) -> Result<u64> {
+        let (mut low, mut high) = (0, latest);
+        while low < high {
+            let mid = low + (high - low) / 2;
+            if self.block_timestamp(mid).await? < timestamp {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+        Ok(low)
     }
 
-    async fn query_erc20_symbol(&self, token_address: &str) -> Result<String> {
-        // symbol() function signature
-        let symbol_sig = "0x95d89b41";
+    /// Transaction history of `address` within `range`, newest first: gas
+    /// token transfers, ERC-20 transfers and the gas fees the wallet paid.
+    /// Uses the Etherscan API when ETHERSCAN_API_KEY is set. Otherwise it is
+    /// rebuilt from Transfer logs, which only finds transactions that moved
+    /// an ERC-20 token; without a start date the last LOG_SCAN_LOOKBACK
+    /// blocks are searched.
+    pub async fn get_transactions(&self, address: &str, range: TransactionRange) -> Result<Vec<WalletTransaction>> {
+        if !address.starts_with("0x") || address.len() != 42 {
+            anyhow::bail!("Invalid EVM address format");
+        }
+
+        let latest = self.latest_block().await.context("Failed to fetch latest block")?;
+        let end = match range.until {
+            Some(until) => self.block_at(until + 1, latest).await.context("Failed to find end block")?,
+            None => latest,
+        };
+        let start = match range.since {
+            Some(since) => Some(self.block_at(since, latest).await.context("Failed to find start block")?),
+            None => None,
+        };
+        let log_blocks = (start.unwrap_or(end.saturating_sub(LOG_SCAN_LOOKBACK)), end);
 
-        let result = self.rpc_call("eth_call", json!([
-            {
-                "to": token_address,
-                "data": symbol_sig
+        let mut history = match EtherscanClient::new(&self.chain) {
+            Some(etherscan) => match self.etherscan_history(&etherscan, address, (start.unwrap_or(0), end), range.limit).await {
+                Ok(history) => history,
+                Err(e) => {
+                    eprintln!("Warning: {:#}; reading {} transfer logs instead", e, self.chain.display_name());
+                    self.log_history(address, log_blocks, range.limit).await?
+                }
             },
-            "latest"
-        ])).await?;
+            None => self.log_history(address, log_blocks, range.limit).await?,
+        };
 
-        let symbol_hex = result
-            .as_str()
-            .ok_or_else(|| anyhow::anyhow!("Invalid symbol format"))?;
+        // Block bounds include the blocks either side; block times are exact
+        history.retain(|tx| {
+            tx.timestamp.is_none_or(|time| {
+                range.since.is_none_or(|since| time >= since) && range.until.is_none_or(|until| time <= until)
+            })
+        });
+        if let Some(limit) = range.limit {
+            history.truncate(limit);
+        }
+        Ok(history)
+    }
 
-        // Decode string from hex
-        let symbol = self.decode_string_from_hex(symbol_hex)?;
-        Ok(symbol)
+    async fn etherscan_history(
+        &self,
+        etherscan: &EtherscanClient,
+        address: &str,
+        blocks: (u64, u64),
+        limit: Option<usize>,
+    ) -> Result<Vec<WalletTransaction>> {
+        let transactions = etherscan.transactions(address, blocks, limit).await?;
+        let internal = etherscan.internal_transactions(address, blocks, limit).await?;
+        let token_transfers = etherscan.token_transfers(address, blocks, limit).await?;
+
+        let number = |value: &str| value.parse::<BigUint>().unwrap_or_default();
+        let mut history = History::new(address);
+        let mut metadata = BTreeMap::new();
+
+        for tx in &transactions {
+            let block = tx.block_number.parse().unwrap_or_default();
+            let parts = history.entry(&tx.hash, block);
+            parts.timestamp = tx.time_stamp.parse().ok();
+            parts.failed = tx.is_error == "1";
+            if tx.from.eq_ignore_ascii_case(address) {
+                parts.fee = Some(number(&tx.gas_used) * number(&tx.gas_price));
+            }
+            if tx.is_error != "1" {
+                history.transfer(&tx.hash, block, None, &tx.from, &tx.to, number(&tx.value));
+            }
+        }
+
+        for tx in internal.iter().filter(|tx| tx.is_error != "1") {
+            let block = tx.block_number.parse().unwrap_or_default();
+            history.entry(&tx.hash, block).timestamp = tx.time_stamp.parse().ok();
+            history.transfer(&tx.hash, block, None, &tx.from, &tx.to, number(&tx.value));
+        }
+
+        for transfer in &token_transfers {
+            let block = transfer.block_number.parse().unwrap_or_default();
+            history.entry(&transfer.hash, block).timestamp = transfer.time_stamp.parse().ok();
+            history.transfer(&transfer.hash, block, Some(&transfer.contract_address), &transfer.from, &transfer.to, number(&transfer.value));
+            if let Ok(decimals) = transfer.token_decimal.parse() {
+                metadata.entry(transfer.contract_address.to_lowercase()).or_insert_with(|| TokenMetadata {
+                    decimals,
+                    symbol: transfer.token_symbol.clone(),
+                    name: transfer.token_name.clone(),
+                });
+            }
+        }
+
+        Ok(history.finish(self.chain.native_token_symbol(), &metadata))
     }
 
-    fn decode_string_from_hex(&self, hex: &str) -> Result<String> {
-        let hex_clean = hex.trim_start_matches("0x");
+    /// History from the Transfer logs to and from the wallet between two
+    /// blocks, searched newest first, plus each transaction's value and fee
+    async fn log_history(&self, address: &str, blocks: (u64, u64), limit: Option<usize>) -> Result<Vec<WalletTransaction>> {
+        let wallet_topic = format!("0x{:0>64}", address.trim_start_matches("0x").to_lowercase());
+        let mut logs: Vec<TransferLog> = Vec::new();
+        let mut hashes = BTreeSet::new();
+        let mut to = blocks.1;
+        let mut chunk = LOG_SCAN_CHUNK;
+
+        loop {
+            let from = to.saturating_sub(chunk - 1).max(blocks.0);
+            let range = json!({ "fromBlock": format!("0x{:x}", from), "toBlock": format!("0x{:x}", to) });
+            let queries = [json!([TRANSFER_TOPIC, wallet_topic]), json!([TRANSFER_TOPIC, null, wallet_topic])]
+                .into_iter()
+                .map(|topics| {
+                    let mut filter = range.clone();
+                    filter["topics"] = topics;
+                    ("eth_getLogs", json!([filter]))
+                })
+                .collect();
+            let results: Result<Vec<serde_json::Value>> = self.rpc_batch(queries).await.into_iter().collect();
+
+            match results {
+                Ok(results) => {
+                    for result in results {
+                        let found: Vec<TransferLog> = serde_json::from_value(result)
+                            .context("Failed to parse eth_getLogs response")?;
+                        // ERC-721 transfers share the event signature but index a fourth topic
+                        for log in found.into_iter().filter(|log| log.topics.len() == 3) {
+                            if let Some(hash) = &log.transaction_hash {
+                                hashes.insert(hash.clone());
+                            }
+                            logs.push(log);
+                        }
+                    }
+                    if from == blocks.0 || limit.is_some_and(|limit| hashes.len() >= limit) {
+                        break;
+                    }
+                    to = from - 1;
+                }
+                // Most RPCs cap the block range or number of results per call
+                Err(_) if chunk > LOG_SCAN_MIN_CHUNK => chunk /= 2,
+                Err(e) => {
+                    eprintln!(
+                        "Warning: Stopped reading {} transfer logs at block {}: {}",
+                        self.chain.display_name(),
+                        to,
+                        e
+                    );
+                    break;
+                }
+            }
+        }
 
-        // Skip the first 64 characters (offset and length encoding)
-        if hex_clean.len() < 128 {
-            return Ok(String::new());
+        let mut history = History::new(address);
+        for log in &logs {
+            let (Some(hash), Some(block)) = (&log.transaction_hash, &log.block_number) else {
+                continue;
+            };
+            let value = amount::parse_hex_uint(&log.data).context("Invalid Transfer log value")?;
+            history.transfer(
+                hash,
+                parse_hex_u64(block)?,
+                Some(&log.address),
+                &topic_address(&log.topics[1]),
+                &topic_address(&log.topics[2]),
+                value,
+            );
         }
 
-        let data_hex = &hex_clean[128..];
+        // Gas token value, status and fee of each transaction, and block times
+        let keys: Vec<(u64, String)> = history.transactions.keys().cloned().collect();
+        let requests = keys
+            .iter()
+            .flat_map(|(_, hash)| [("eth_getTransactionByHash", json!([hash])), ("eth_getTransactionReceipt", json!([hash]))])
+            .collect();
+        let details = self.rpc_batch(requests).await;
 
-        // Convert hex to bytes
-        let bytes: Vec<u8> = (0..data_hex.len())
-            .step_by(2)
-            .filter_map(|i| u8::from_str_radix(&data_hex[i..i+2], 16).ok())
+        let block_numbers: BTreeSet<u64> = keys.iter().map(|(block, _)| *block).collect();
+        let requests = block_numbers
+            .iter()
+            .map(|block| ("eth_getBlockByNumber", json!([format!("0x{:x}", block), false])))
+            .collect();
+        let timestamps: BTreeMap<u64, i64> = block_numbers
+            .iter()
+            .zip(self.rpc_batch(requests).await)
+            .filter_map(|(block, result)| {
+                let timestamp = result.ok()?["timestamp"].as_str().and_then(|hex| parse_hex_u64(hex).ok())?;
+                let timestamp = i64::try_from(timestamp).ok()?;
+                Some((*block, timestamp))
+            })
             .collect();
 
-        // Convert to UTF-8 string, removing null bytes
-        let result = String::from_utf8_lossy(&bytes)
-            .trim_end_matches('\0')
-            .to_string();
+        for ((block, hash), details) in keys.iter().zip(details.chunks(2)) {
+            history.entry(hash, *block).timestamp = timestamps.get(block).copied();
+
+            let transaction = details[0].as_ref().ok().and_then(|value| serde_json::from_value::<RpcTransaction>(value.clone()).ok());
+            let receipt = details[1].as_ref().ok().and_then(|value| serde_json::from_value::<RpcReceipt>(value.clone()).ok());
+            let (Some(transaction), Some(receipt)) = (transaction, receipt) else {
+                eprintln!("Warning: Failed to fetch transaction {}", hash);
+                continue;
+            };
 
-        Ok(result)
+            let failed = receipt.status.as_deref() == Some("0x0");
+            let parts = history.entry(hash, *block);
+            parts.failed = failed;
+            if transaction.from.eq_ignore_ascii_case(address) {
+                let gas_price = receipt.effective_gas_price.as_ref().or(transaction.gas_price.as_ref());
+                let gas_price = gas_price.map(|price| amount::parse_hex_uint(price)).transpose()?.unwrap_or_default();
+                let l1_fee = receipt.l1_fee.map(|fee| amount::parse_hex_uint(&fee)).transpose()?.unwrap_or_default();
+                parts.fee = Some(amount::parse_hex_uint(&receipt.gas_used)? * gas_price + l1_fee);
+            }
+            if !failed {
+                let value = amount::parse_hex_uint(&transaction.value)?;
+                history.transfer(hash, *block, None, &transaction.from, transaction.to.as_deref().unwrap_or_default(), value);
+            }
+        }
+
+        let token_list = token_list::load(&self.chain, normalize_contract).unwrap_or_default();
+        let tokens: BTreeSet<String> = logs.iter().map(|log| log.address.to_lowercase()).collect();
+        let metadata = self.token_metadata(tokens.iter().map(String::as_str).collect(), &token_list).await;
+        token_cache::save();
+
+        Ok(history.finish(self.chain.native_token_symbol(), &metadata))
     }
 }
 
-// Implement PriceEnrichable trait for EVM balances
-impl crate::PriceEnrichable for AccountBalances {
-    const NATIVE_SYMBOL: &'static str = "ETH";
-
-    fn native_balance(&self) -> f64 {
-        self.eth_balance
+#[async_trait]
+impl BalanceSource for EvmClient {
+    async fn fetch_snapshot(&self, account: &TrackedAccount) -> Result<AccountSnapshot> {
+        let balances = self.get_balances(account.identifier()).await?;
+        Ok(self.snapshot(account, balances))
     }
 
-    fn set_native_usd_price(&mut self, price: f64) {
-        self.eth_usd_price = Some(price);
+    async fn fetch_snapshots(&self, accounts: &[&TrackedAccount]) -> Vec<Result<AccountSnapshot>> {
+        let addresses: Vec<&str> = accounts.iter().map(|account| account.identifier()).collect();
+        let balances = self.get_balances_many(&addresses).await;
+
+        accounts
+            .iter()
+            .zip(balances)
+            .map(|(account, balances)| Ok(self.snapshot(account, balances?)))
+            .collect()
     }
+}
 
-    fn set_native_usd_value(&mut self, value: f64) {
-        self.eth_usd_value = Some(value);
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_contract() {
+        assert_eq!(
+            normalize_contract("0x7F39C581F595B53c5cb19bD0b3f8dA6c935E2Ca0").unwrap(),
+            "0x7f39c581f595b53c5cb19bd0b3f8da6c935e2ca0"
+        );
+        assert!(normalize_contract("0x1234").is_err());
+        assert!(normalize_contract("usdc.near").is_err());
     }
 
-    fn set_total_usd_value(&mut self, value: f64) {
-        self.total_usd_value = Some(value);
+    #[test]
+    fn test_snapshot_uses_chain_native_symbol() {
+        let account = TrackedAccount::Wallet(crate::storage::WalletAddress {
+            company: "Test".to_string(),
+            name: "Treasury".to_string(),
+            address: "0x52908400098527886E0F7030069857D2E4169EE7".to_string(),
+            chain: Chain::BinanceSmartChain,
+        });
+        let client = EvmClient::new(vec![RpcEndpoint::new("http://127.0.0.1:8545")], Chain::BinanceSmartChain);
+        let balances = AccountBalances {
+            native_balance: TokenAmount::new(2_500_000_000_000_000_000u64, 18),
+            token_balances: Vec::new(),
+        };
+
+        let snapshot = client.snapshot(&account, balances);
+        assert_eq!(snapshot.holdings[0].symbol.as_deref(), Some("BNB"));
+        assert_eq!(snapshot.holdings[0].amount.to_string(), "2.5");
     }
 
-    fn enrich_token_balances(&mut self, price_cache: &HashMap<String, f64>) -> f64 {
-        let mut token_total = 0.0;
-        for token in &mut self.token_balances {
-            if let Some(symbol) = &token.symbol {
-                if let Some(&price) = price_cache.get(symbol) {
-                    token.usd_price = Some(price);
-                    token.usd_value = Some(token.ui_amount * price);
-                    token_total += token.usd_value.unwrap_or(0.0);
-                }
-            }
+    #[test]
+    fn test_duplicate_addresses_share_balances() {
+        let wallet = "0x52908400098527886E0F7030069857D2E4169EE7";
+        let other = "0xde0b295669a9fd93d5f28d9ec85e40f4cb697bae";
+        let lowercase = wallet.to_lowercase();
+        let addresses = [wallet, "0x1234", other, lowercase.as_str(), wallet];
+
+        let (distinct, slots) = distinct_addresses(&addresses);
+        assert_eq!(distinct, vec![wallet, other]);
+        assert_eq!(slots, vec![Some(0), None, Some(1), Some(0), Some(0)]);
+
+        let native = vec![Ok(TokenAmount::new(1_000_000_000_000_000_000u64, 18)), Err(anyhow::anyhow!("RPC failed"))];
+        let tokens = vec![
+            vec![TokenBalance {
+                contract_address: "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48".to_string(),
+                name: None,
+                symbol: Some("USDC".to_string()),
+                amount: TokenAmount::new(5_000_000u64, 6),
+            }],
+            Vec::new(),
+        ];
+
+        let results = fan_out(&slots, native, tokens);
+        assert_eq!(results.len(), addresses.len());
+        for i in [0, 3, 4] {
+            let balances = results[i].as_ref().unwrap();
+            assert_eq!(balances.native_balance.to_string(), "1");
+            assert_eq!(balances.token_balances[0].amount.to_string(), "5");
         }
-        token_total
+        assert_eq!(results[1].as_ref().unwrap_err().to_string(), "Invalid EVM address format");
+        assert_eq!(results[2].as_ref().unwrap_err().to_string(), "RPC failed");
+    }
+
+    #[test]
+    fn test_parse_hex_u64() {
+        assert_eq!(parse_hex_u64("0x12a05f200").unwrap(), 5_000_000_000);
+        assert!(parse_hex_u64("0xzz").is_err());
+        assert!(parse_hex_u64("0x10000000000000000").is_err());
+    }
+
+    #[test]
+    fn test_history_assembles_transactions() {
+        let wallet = "0x52908400098527886E0F7030069857D2E4169EE7";
+        let other = "0xde0b295669a9fd93d5f28d9ec85e40f4cb697bae";
+        let usdc = "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48";
+        let mut history = History::new(wallet);
+
+        // Paying 5 USDC, with the gas fee
+        history.entry("0xAA", 100).fee = Some(BigUint::from(21_000u64 * 1_000_000_000));
+        history.transfer("0xaa", 100, Some(usdc), wallet, other, BigUint::from(5_000_000u64));
+        // Receiving 0.5 ETH; a transfer to itself and one between others change nothing
+        history.transfer("0xbb", 120, None, other, wallet, BigUint::from(500_000_000_000_000_000u64));
+        history.transfer("0xbb", 120, None, wallet, wallet, BigUint::from(1u64));
+        history.transfer("0xcc", 130, Some(usdc), other, other, BigUint::from(1u64));
+
+        let metadata = BTreeMap::from([(
+            usdc.to_lowercase(),
+            TokenMetadata { decimals: 6, symbol: Some("USDC".to_string()), name: None },
+        )]);
+        let transactions = history.finish("ETH", &metadata);
+
+        assert_eq!(transactions.len(), 2);
+        assert_eq!(transactions[0].id, "0xbb");
+        assert_eq!(transactions[0].changes.len(), 1);
+        assert!(transactions[0].changes[0].asset_id.is_none());
+        assert_eq!(transactions[0].changes[0].amount.to_string(), "0.5");
+        assert!(transactions[0].fee.is_none());
+        assert_eq!(transactions[0].counterparties, vec![other.to_string()]);
+
+        assert_eq!(transactions[1].block, 100);
+        assert_eq!(transactions[1].changes[0].symbol.as_deref(), Some("USDC"));
+        assert_eq!(transactions[1].changes[0].amount.to_string(), "-5");
+        assert_eq!(transactions[1].fee.as_ref().unwrap().to_string(), "0.000021");
+    }
+
+    #[test]
+    fn test_history_leaves_out_tokens_without_metadata() {
+        let wallet = "0x52908400098527886E0F7030069857D2E4169EE7";
+        let other = "0xde0b295669a9fd93d5f28d9ec85e40f4cb697bae";
+        let unknown = "0x1111111111111111111111111111111111111111";
+        let mut history = History::new(wallet);
+
+        history.entry("0xaa", 100).fee = Some(BigUint::from(21_000u64));
+        history.transfer("0xaa", 100, Some(unknown), other, wallet, BigUint::from(10u64).pow(18));
+
+        let transactions = history.finish("ETH", &BTreeMap::new());
+
+        // The fee is still reported, but not the token change of unknown size
+        assert_eq!(transactions.len(), 1);
+        assert!(transactions[0].changes.is_empty());
+        assert!(transactions[0].fee.is_some());
     }
 }
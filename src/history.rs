@@ -0,0 +1,686 @@
+use crate::amount;
+use crate::config;
+use crate::portfolio::{add_asset_to_portfolio, asset_label, PortfolioSummary};
+use crate::source::{AccountSnapshot, HoldingKind};
+
+use anyhow::{Context, Result};
+use bigdecimal::BigDecimal;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+// Amounts and USD values are stored as exact decimal TEXT; SQLite's REAL
+// would round them to f64.
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS snapshots (
+    id              INTEGER PRIMARY KEY AUTOINCREMENT,
+    taken_at        INTEGER NOT NULL,
+    origin          TEXT NOT NULL,
+    total_usd_value TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS snapshot_holdings (
+    snapshot_id  INTEGER NOT NULL REFERENCES snapshots(id) ON DELETE CASCADE,
+    company      TEXT NOT NULL,
+    account_name TEXT NOT NULL,
+    account_id   TEXT NOT NULL,
+    source       TEXT NOT NULL,
+    kind         TEXT NOT NULL,
+    symbol       TEXT,
+    name         TEXT,
+    asset_id     TEXT,
+    decimals     INTEGER NOT NULL,
+    amount       TEXT NOT NULL,
+    usd_price    TEXT,
+    usd_value    TEXT,
+    raw_amount   TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_snapshot_holdings_snapshot ON snapshot_holdings(snapshot_id);
+";
+
+/// Bumped whenever the schema changes; stored in `PRAGMA user_version`
+const SCHEMA_VERSION: i64 = 1;
+
+/// Version 0 stored amounts and USD values as REAL. The tables are rebuilt
+/// with TEXT columns; existing values keep their (already rounded) REAL
+/// value, and `raw_amount` stays empty for them.
+const MIGRATE_V1: &str = "
+DROP INDEX IF EXISTS idx_snapshot_holdings_snapshot;
+ALTER TABLE snapshots RENAME TO snapshots_v0;
+ALTER TABLE snapshot_holdings RENAME TO snapshot_holdings_v0;
+";
+
+const MIGRATE_V1_COPY: &str = "
+INSERT INTO snapshots (id, taken_at, origin, total_usd_value)
+    SELECT id, taken_at, origin, CAST(total_usd_value AS TEXT) FROM snapshots_v0;
+INSERT INTO snapshot_holdings
+    (snapshot_id, company, account_name, account_id, source, kind,
+     symbol, name, asset_id, decimals, amount, usd_price, usd_value)
+    SELECT snapshot_id, company, account_name, account_id, source, kind,
+           symbol, name, asset_id, decimals, CAST(amount AS TEXT),
+           CAST(usd_price AS TEXT), CAST(usd_value AS TEXT)
+    FROM snapshot_holdings_v0;
+DROP TABLE snapshot_holdings_v0;
+DROP TABLE snapshots_v0;
+";
+
+/// Where a snapshot was recorded from
+#[derive(Debug, Clone, Copy)]
+pub enum SnapshotOrigin {
+    Cli,
+    Web,
+}
+
+impl SnapshotOrigin {
+    fn as_str(&self) -> &'static str {
+        match self {
+            SnapshotOrigin::Cli => "cli",
+            SnapshotOrigin::Web => "web",
+        }
+    }
+}
+
+/// Header of a saved snapshot
+#[derive(Debug, Clone)]
+pub struct SnapshotInfo {
+    pub id: i64,
+    /// Unix timestamp (seconds, UTC)
+    pub taken_at: i64,
+    pub origin: String,
+    pub account_count: usize,
+    pub total_usd_value: BigDecimal,
+}
+
+impl SnapshotInfo {
+    pub fn taken_at_display(&self) -> String {
+        chrono::DateTime::from_timestamp(self.taken_at, 0)
+            .map(|dt| dt.format("%Y-%m-%d %H:%M UTC").to_string())
+            .unwrap_or_else(|| self.taken_at.to_string())
+    }
+}
+
+/// One asset line of one account, as recorded in a snapshot
+#[derive(Debug, Clone)]
+pub struct HoldingRecord {
+    pub company: String,
+    pub account_name: String,
+    /// Chain or banking service the account belongs to
+    pub source: String,
+    pub kind: HoldingKind,
+    pub symbol: Option<String>,
+    pub amount: BigDecimal,
+    pub usd_value: Option<BigDecimal>,
+}
+
+/// A saved snapshot with all of its holdings
+#[derive(Debug, Clone)]
+pub struct StoredSnapshot {
+    pub info: SnapshotInfo,
+    pub holdings: Vec<HoldingRecord>,
+}
+
+impl StoredSnapshot {
+    /// Rebuild the per-company portfolio as it was when the snapshot was taken
+    pub fn portfolio(&self) -> PortfolioSummary {
+        let mut portfolio = PortfolioSummary::default();
+        for holding in &self.holdings {
+            if let Some(symbol) = &holding.symbol {
+                add_asset_to_portfolio(
+                    &mut portfolio,
+                    &holding.company,
+                    &asset_label(holding.kind, symbol),
+                    holding.amount.clone(),
+                    holding.usd_value.clone(),
+                );
+            }
+        }
+        portfolio
+    }
+}
+
+/// Change of one asset within a company between two snapshots
+#[derive(Debug, Clone)]
+pub struct AssetChange {
+    pub symbol: String,
+    pub amount_before: BigDecimal,
+    pub amount_after: BigDecimal,
+    pub usd_before: BigDecimal,
+    pub usd_after: BigDecimal,
+}
+
+/// Change of one company's holdings between two snapshots
+#[derive(Debug, Clone)]
+pub struct CompanyChange {
+    pub company: String,
+    pub usd_before: BigDecimal,
+    pub usd_after: BigDecimal,
+    /// Only assets whose amount or USD value changed
+    pub assets: Vec<AssetChange>,
+}
+
+/// Per-company, per-asset comparison of two snapshots
+#[derive(Debug, Clone)]
+pub struct SnapshotDiff {
+    pub from: SnapshotInfo,
+    pub to: SnapshotInfo,
+    pub companies: Vec<CompanyChange>,
+}
+
+impl SnapshotDiff {
+    pub fn between(from: &StoredSnapshot, to: &StoredSnapshot) -> Self {
+        let before = from.portfolio();
+        let after = to.portfolio();
+
+        let company_names: BTreeSet<&String> = before.companies.keys().chain(after.companies.keys()).collect();
+
+        let mut companies = Vec::new();
+        for company in company_names {
+            let old = before.companies.get(company);
+            let new = after.companies.get(company);
+
+            let symbols: BTreeSet<&String> = old
+                .into_iter()
+                .flat_map(|c| c.assets.keys())
+                .chain(new.into_iter().flat_map(|c| c.assets.keys()))
+                .collect();
+
+            let assets: Vec<AssetChange> = symbols
+                .into_iter()
+                .map(|symbol| {
+                    let old_asset = old.and_then(|c| c.assets.get(symbol));
+                    let new_asset = new.and_then(|c| c.assets.get(symbol));
+                    AssetChange {
+                        symbol: symbol.clone(),
+                        amount_before: old_asset.map(|a| a.amount.clone()).unwrap_or_default(),
+                        amount_after: new_asset.map(|a| a.amount.clone()).unwrap_or_default(),
+                        usd_before: old_asset.and_then(|a| a.usd_value.clone()).unwrap_or_default(),
+                        usd_after: new_asset.and_then(|a| a.usd_value.clone()).unwrap_or_default(),
+                    }
+                })
+                .filter(|a| a.amount_before != a.amount_after || a.usd_before != a.usd_after)
+                .collect();
+
+            if assets.is_empty() {
+                continue;
+            }
+
+            companies.push(CompanyChange {
+                company: company.clone(),
+                usd_before: old.map(|c| c.total_usd_value.clone()).unwrap_or_default(),
+                usd_after: new.map(|c| c.total_usd_value.clone()).unwrap_or_default(),
+                assets,
+            });
+        }
+
+        Self {
+            from: from.info.clone(),
+            to: to.info.clone(),
+            companies,
+        }
+    }
+
+    pub fn total_change(&self) -> BigDecimal {
+        &self.to.total_usd_value - &self.from.total_usd_value
+    }
+}
+
+/// Time window for charting history
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HistoryRange {
+    Week,
+    Month,
+    Quarter,
+    All,
+}
+
+impl HistoryRange {
+    pub fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "7d" => Ok(HistoryRange::Week),
+            "30d" => Ok(HistoryRange::Month),
+            "90d" => Ok(HistoryRange::Quarter),
+            "all" => Ok(HistoryRange::All),
+            _ => anyhow::bail!("Unknown range: {}. Use 7d, 30d, 90d or all", s),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HistoryRange::Week => "7d",
+            HistoryRange::Month => "30d",
+            HistoryRange::Quarter => "90d",
+            HistoryRange::All => "all",
+        }
+    }
+
+    /// Earliest timestamp included in the range, relative to `now`
+    pub fn since(&self, now: i64) -> Option<i64> {
+        const DAY: i64 = 24 * 60 * 60;
+        match self {
+            HistoryRange::Week => Some(now - 7 * DAY),
+            HistoryRange::Month => Some(now - 30 * DAY),
+            HistoryRange::Quarter => Some(now - 90 * DAY),
+            HistoryRange::All => None,
+        }
+    }
+}
+
+/// USD totals of one snapshot, broken down by company and by asset symbol
+#[derive(Debug, Clone)]
+pub struct HistoryPoint {
+    pub taken_at: i64,
+    pub total_usd_value: f64,
+    pub companies: BTreeMap<String, f64>,
+    pub assets: BTreeMap<String, f64>,
+}
+
+/// SQLite-backed store of balance snapshots in ~/.gringotts/history.db
+/// (or the profile's `history_db`)
+pub struct HistoryStore {
+    conn: Connection,
+}
+
+impl HistoryStore {
+    pub fn open() -> Result<Self> {
+        let path = Self::path()?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .context("Failed to create storage directory")?;
+        }
+
+        Self::open_at(&path)
+    }
+
+    pub fn path() -> Result<PathBuf> {
+        if let Some(path) = &config::active().settings.history_db {
+            return Ok(path.clone());
+        }
+
+        let home = dirs::home_dir()
+            .context("Failed to get home directory")?;
+
+        Ok(home.join(".gringotts").join("history.db"))
+    }
+
+    pub fn open_at(path: &Path) -> Result<Self> {
+        let conn = Connection::open(path)
+            .with_context(|| format!("Failed to open history database at {}", path.display()))?;
+        Self::init(conn)
+    }
+
+    #[cfg(test)]
+    pub fn open_in_memory() -> Result<Self> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(mut conn: Connection) -> Result<Self> {
+        let version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        let existing: bool = conn.query_row(
+            "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'snapshots')",
+            [],
+            |row| row.get(0),
+        )?;
+
+        if existing && version < 1 {
+            // Foreign keys are still off here, so the tables can be swapped
+            let tx = conn.transaction()?;
+            tx.execute_batch(MIGRATE_V1)?;
+            tx.execute_batch(SCHEMA)?;
+            tx.execute_batch(MIGRATE_V1_COPY)?;
+            tx.commit().context("Failed to upgrade history database")?;
+        }
+
+        conn.execute_batch(SCHEMA)
+            .context("Failed to initialize history database")?;
+        conn.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+        conn.execute_batch("PRAGMA foreign_keys = ON;")?;
+        Ok(Self { conn })
+    }
+
+    /// Record a set of (priced) account snapshots, returning the new snapshot id
+    pub fn save(&mut self, snapshots: &[AccountSnapshot], origin: SnapshotOrigin) -> Result<i64> {
+        let total_usd_value: BigDecimal = snapshots.iter().filter_map(|s| s.total_usd_value.as_ref()).sum();
+        let taken_at = chrono::Utc::now().timestamp();
+
+        let tx = self.conn.transaction()?;
+        tx.execute(
+            "INSERT INTO snapshots (taken_at, origin, total_usd_value) VALUES (?1, ?2, ?3)",
+            params![taken_at, origin.as_str(), amount::plain(&total_usd_value)],
+        )
+        .context("Failed to save snapshot")?;
+        let snapshot_id = tx.last_insert_rowid();
+
+        {
+            let mut stmt = tx.prepare(
+                "INSERT INTO snapshot_holdings
+                    (snapshot_id, company, account_name, account_id, source, kind,
+                     symbol, name, asset_id, decimals, amount, usd_price, usd_value, raw_amount)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
+            )?;
+
+            for snapshot in snapshots {
+                for holding in &snapshot.holdings {
+                    stmt.execute(params![
+                        snapshot_id,
+                        snapshot.company(),
+                        snapshot.account.name(),
+                        snapshot.account.identifier(),
+                        snapshot.account.source_name(),
+                        holding.kind.as_str(),
+                        holding.symbol,
+                        holding.name,
+                        holding.asset_id,
+                        holding.decimals(),
+                        holding.amount.to_string(),
+                        holding.usd_price.as_ref().map(amount::plain),
+                        holding.usd_value.as_ref().map(amount::plain),
+                        holding.amount.raw().to_string(),
+                    ])
+                    .context("Failed to save snapshot holding")?;
+                }
+            }
+        }
+
+        tx.commit().context("Failed to save snapshot")?;
+        Ok(snapshot_id)
+    }
+
+    /// Most recent snapshots first
+    pub fn list(&self, limit: usize) -> Result<Vec<SnapshotInfo>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT s.id, s.taken_at, s.origin, s.total_usd_value,
+                    (SELECT COUNT(DISTINCT h.account_id) FROM snapshot_holdings h WHERE h.snapshot_id = s.id)
+             FROM snapshots s
+             ORDER BY s.taken_at DESC, s.id DESC
+             LIMIT ?1",
+        )?;
+
+        let rows = stmt.query_map(params![limit as i64], Self::info_from_row)?;
+        rows.collect::<rusqlite::Result<Vec<_>>>()
+            .context("Failed to read snapshot history")
+    }
+
+    pub fn latest_id(&self) -> Result<Option<i64>> {
+        self.conn
+            .query_row("SELECT id FROM snapshots ORDER BY taken_at DESC, id DESC LIMIT 1", [], |row| row.get(0))
+            .optional()
+            .context("Failed to read snapshot history")
+    }
+
+    pub fn load(&self, id: i64) -> Result<StoredSnapshot> {
+        let info = self
+            .conn
+            .query_row(
+                "SELECT s.id, s.taken_at, s.origin, s.total_usd_value,
+                        (SELECT COUNT(DISTINCT h.account_id) FROM snapshot_holdings h WHERE h.snapshot_id = s.id)
+                 FROM snapshots s
+                 WHERE s.id = ?1",
+                params![id],
+                Self::info_from_row,
+            )
+            .optional()
+            .context("Failed to read snapshot")?
+            .with_context(|| format!("Snapshot {} not found", id))?;
+
+        let mut stmt = self.conn.prepare(
+            "SELECT company, account_name, source, kind, symbol, amount, usd_value
+             FROM snapshot_holdings
+             WHERE snapshot_id = ?1
+             ORDER BY rowid",
+        )?;
+        let holdings = stmt
+            .query_map(params![id], |row| {
+                Ok(HoldingRecord {
+                    company: row.get(0)?,
+                    account_name: row.get(1)?,
+                    source: row.get(2)?,
+                    kind: HoldingKind::from_str(&row.get::<_, String>(3)?).map_err(|e| {
+                        rusqlite::Error::FromSqlConversionFailure(3, rusqlite::types::Type::Text, e.into())
+                    })?,
+                    symbol: row.get(4)?,
+                    amount: decimal_column(row, 5)?,
+                    usd_value: row.get::<_, Option<String>>(6)?.map(|_| decimal_column(row, 6)).transpose()?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .context("Failed to read snapshot holdings")?;
+
+        Ok(StoredSnapshot { info, holdings })
+    }
+
+    /// Per-snapshot totals, oldest first, for snapshots taken at or after `since`
+    pub fn series(&self, since: Option<i64>) -> Result<Vec<HistoryPoint>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT s.id, s.taken_at, CAST(s.total_usd_value AS REAL), h.company, h.symbol,
+                    SUM(CAST(COALESCE(h.usd_value, 0) AS REAL))
+             FROM snapshots s
+             LEFT JOIN snapshot_holdings h ON h.snapshot_id = s.id
+             WHERE s.taken_at >= ?1
+             GROUP BY s.id, h.company, h.symbol
+             ORDER BY s.taken_at, s.id",
+        )?;
+
+        let rows = stmt.query_map(params![since.unwrap_or(i64::MIN)], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, f64>(2)?,
+                row.get::<_, Option<String>>(3)?,
+                row.get::<_, Option<String>>(4)?,
+                row.get::<_, f64>(5)?,
+            ))
+        })?;
+
+        let mut points: Vec<HistoryPoint> = Vec::new();
+        let mut current_id = None;
+        for row in rows {
+            let (id, taken_at, total_usd_value, company, symbol, usd_value) =
+                row.context("Failed to read snapshot history")?;
+
+            if current_id != Some(id) {
+                points.push(HistoryPoint {
+                    taken_at,
+                    total_usd_value,
+                    companies: BTreeMap::new(),
+                    assets: BTreeMap::new(),
+                });
+                current_id = Some(id);
+            }
+
+            let point = points.last_mut().expect("point was just pushed");
+            if let Some(company) = company {
+                *point.companies.entry(company).or_insert(0.0) += usd_value;
+            }
+            if let Some(symbol) = symbol {
+                *point.assets.entry(symbol).or_insert(0.0) += usd_value;
+            }
+        }
+
+        Ok(points)
+    }
+
+    fn info_from_row(row: &rusqlite::Row) -> rusqlite::Result<SnapshotInfo> {
+        Ok(SnapshotInfo {
+            id: row.get(0)?,
+            taken_at: row.get(1)?,
+            origin: row.get(2)?,
+            total_usd_value: decimal_column(row, 3)?,
+            account_count: row.get::<_, i64>(4)? as usize,
+        })
+    }
+}
+
+/// Read a TEXT column holding a decimal number
+fn decimal_column(row: &rusqlite::Row, index: usize) -> rusqlite::Result<BigDecimal> {
+    let text: String = row.get(index)?;
+    BigDecimal::from_str(&text).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(index, rusqlite::types::Type::Text, Box::new(e))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::amount::TokenAmount;
+    use crate::source::Holding;
+    use bigdecimal::Zero;
+    use crate::storage::{Chain, TrackedAccount, WalletAddress};
+
+    fn dec(value: &str) -> BigDecimal {
+        BigDecimal::from_str(value).unwrap()
+    }
+
+    fn priced_snapshot(company: &str, address: &str, symbol: &str, amount: &str, price: f64) -> AccountSnapshot {
+        let account = TrackedAccount::Wallet(WalletAddress {
+            company: company.to_string(),
+            name: format!("{} wallet", company),
+            address: address.to_string(),
+            chain: Chain::Solana,
+        });
+        let mut snapshot = AccountSnapshot::new(&account, vec![Holding::native(symbol, TokenAmount::from_decimal(&dec(amount), 9))]);
+        let prices = [(symbol.to_string(), price)].into_iter().collect();
+        snapshot.enrich_from_cache(&prices);
+        snapshot
+    }
+
+    #[test]
+    fn test_save_and_load_snapshot() {
+        let mut store = HistoryStore::open_in_memory().unwrap();
+        let snapshots = vec![
+            priced_snapshot("Acme", "addr1", "SOL", "2.0", 100.0),
+            priced_snapshot("Globex", "addr2", "SOL", "1.0", 100.0),
+        ];
+
+        let id = store.save(&snapshots, SnapshotOrigin::Cli).unwrap();
+        assert_eq!(store.latest_id().unwrap(), Some(id));
+
+        let stored = store.load(id).unwrap();
+        assert_eq!(stored.info.origin, "cli");
+        assert_eq!(stored.info.account_count, 2);
+        assert_eq!(stored.info.total_usd_value, dec("300"));
+        assert_eq!(stored.holdings.len(), 2);
+        assert_eq!(stored.holdings[0].company, "Acme");
+        assert_eq!(stored.holdings[0].usd_value, Some(dec("200")));
+
+        let listed = store.list(10).unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].id, id);
+    }
+
+    #[test]
+    fn test_load_missing_snapshot() {
+        let store = HistoryStore::open_in_memory().unwrap();
+        assert!(store.load(42).is_err());
+        assert_eq!(store.latest_id().unwrap(), None);
+    }
+
+    #[test]
+    fn test_upgrade_real_columns() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE snapshots (
+                id INTEGER PRIMARY KEY AUTOINCREMENT, taken_at INTEGER NOT NULL,
+                origin TEXT NOT NULL, total_usd_value REAL NOT NULL
+            );
+            CREATE TABLE snapshot_holdings (
+                snapshot_id INTEGER NOT NULL REFERENCES snapshots(id) ON DELETE CASCADE,
+                company TEXT NOT NULL, account_name TEXT NOT NULL, account_id TEXT NOT NULL,
+                source TEXT NOT NULL, kind TEXT NOT NULL, symbol TEXT, name TEXT, asset_id TEXT,
+                decimals INTEGER NOT NULL, amount REAL NOT NULL, usd_price REAL, usd_value REAL
+            );
+            INSERT INTO snapshots VALUES (1, 1700000000, 'manual', 300.5);
+            INSERT INTO snapshot_holdings VALUES
+                (1, 'Acme', 'Hot', 'addr1', 'Solana', 'native', 'SOL', NULL, NULL, 9, 2.5, 120.2, 300.5);",
+        )
+        .unwrap();
+
+        let store = HistoryStore::init(conn).unwrap();
+        let stored = store.load(1).unwrap();
+        assert_eq!(stored.info.total_usd_value, dec("300.5"));
+        assert_eq!(stored.holdings[0].amount, dec("2.5"));
+        assert_eq!(stored.holdings[0].usd_value, Some(dec("300.5")));
+
+        // Reopening an upgraded database leaves it alone
+        let store = HistoryStore::init(store.conn).unwrap();
+        assert_eq!(store.list(10).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_series_groups_by_company_and_asset() {
+        let mut store = HistoryStore::open_in_memory().unwrap();
+        store
+            .save(
+                &[
+                    priced_snapshot("Acme", "addr1", "SOL", "2.0", 100.0),
+                    priced_snapshot("Acme", "addr2", "ETH", "1.0", 3000.0),
+                    priced_snapshot("Globex", "addr3", "SOL", "1.0", 100.0),
+                ],
+                SnapshotOrigin::Cli,
+            )
+            .unwrap();
+        store.save(&[], SnapshotOrigin::Web).unwrap();
+
+        let points = store.series(None).unwrap();
+        assert_eq!(points.len(), 2);
+        assert_eq!(points[0].total_usd_value, 3300.0);
+        assert_eq!(points[0].companies.get("Acme"), Some(&3200.0));
+        assert_eq!(points[0].companies.get("Globex"), Some(&100.0));
+        assert_eq!(points[0].assets.get("SOL"), Some(&300.0));
+        assert_eq!(points[0].assets.get("ETH"), Some(&3000.0));
+        assert!(points[1].companies.is_empty());
+
+        let future = chrono::Utc::now().timestamp() + 60;
+        assert!(store.series(Some(future)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_history_range() {
+        assert_eq!(HistoryRange::from_str("7D").unwrap(), HistoryRange::Week);
+        assert_eq!(HistoryRange::from_str("all").unwrap().since(1_000), None);
+        assert_eq!(HistoryRange::Month.since(30 * 86_400), Some(0));
+        assert!(HistoryRange::from_str("1y").is_err());
+    }
+
+    #[test]
+    fn test_diff_per_company_and_asset() {
+        let mut store = HistoryStore::open_in_memory().unwrap();
+        let first = store
+            .save(
+                &[
+                    priced_snapshot("Acme", "addr1", "SOL", "2.0", 100.0),
+                    priced_snapshot("Globex", "addr2", "ETH", "1.0", 3000.0),
+                ],
+                SnapshotOrigin::Cli,
+            )
+            .unwrap();
+        let second = store
+            .save(
+                &[
+                    priced_snapshot("Acme", "addr1", "SOL", "3.0", 100.0),
+                    priced_snapshot("Globex", "addr2", "ETH", "1.0", 3000.0),
+                    priced_snapshot("Initech", "addr3", "NEAR", "10.0", 5.0),
+                ],
+                SnapshotOrigin::Web,
+            )
+            .unwrap();
+
+        let diff = SnapshotDiff::between(&store.load(first).unwrap(), &store.load(second).unwrap());
+
+        assert_eq!(diff.total_change(), dec("150"));
+        // Globex is unchanged and left out
+        let companies: Vec<&str> = diff.companies.iter().map(|c| c.company.as_str()).collect();
+        assert_eq!(companies, vec!["Acme", "Initech"]);
+
+        let sol = &diff.companies[0].assets[0];
+        assert_eq!(sol.symbol, "SOL");
+        assert_eq!(sol.amount_before, dec("2"));
+        assert_eq!(sol.amount_after, dec("3"));
+        assert_eq!(&sol.usd_after - &sol.usd_before, dec("100"));
+
+        let near = &diff.companies[1].assets[0];
+        assert_eq!(near.amount_before, BigDecimal::zero());
+        assert_eq!(near.usd_after, dec("50"));
+    }
+}
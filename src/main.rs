@@ -2,135 +2,49 @@ mod cli;
 mod storage;
 mod solana;
 mod evm;
+mod etherscan;
 mod price;
 mod ui;
 mod aptos;
 mod near;
 mod sui;
 mod starknet;
+mod bitcoin;
+mod cosmos;
 mod mercury;
 mod circle;
 mod web;
-
-use anyhow::Result;
+mod api;
+mod auth;
+mod source;
+mod portfolio;
+mod history;
+mod config;
+mod secrets;
+mod rpc;
+mod output;
+mod token_cache;
+mod token_list;
+mod multicall;
+mod amount;
+
+use anyhow::{Context, Result};
 use clap::Parser;
-use cli::{Cli, Commands};
-use storage::{AddressBook, BankingAccount, BankingService, Chain, WalletAddress};
-use solana::SolanaClient;
-use evm::EvmClient;
-use aptos::AptosClient;
-use near::NearClient;
-use sui::SuiClient;
-use starknet::StarknetClient;
+use cli::{Cli, Commands, ConfigCommands, HistoryCommands, SecretsCommands, UserCommands};
+use storage::{AddressBook, BankingAccount, BankingService, Chain, TrackedAccount, WalletAddress};
 use mercury::MercuryClient;
-use circle::CircleClient;
 use price::PriceService;
+use portfolio::PortfolioSummary;
+use config::{ApiKey, Config};
+use history::{HistoryStore, SnapshotDiff, SnapshotOrigin};
+use output::{AccountBalanceDto, AccountDto, OutputFormat, PortfolioDto, UtxoDto, WalletTransactionDto};
+use auth::{Role, UserStore};
+use rpc::RpcConfig;
+use source::{AccountSnapshot, FetchOptions};
 use std::collections::{HashMap, HashSet};
 use indicatif::{ProgressBar, ProgressStyle};
 use std::io::Write;
 
-// Portfolio summary structure
-struct PortfolioSummary {
-    companies: HashMap<String, CompanyAssets>,
-    total_usd_value: f64,
-}
-
-struct CompanyAssets {
-    assets: HashMap<String, AssetSummary>,
-    total_usd_value: f64,
-}
-
-struct AssetSummary {
-    symbol: String,
-    amount: f64,
-    usd_value: Option<f64>,
-}
-
-fn add_asset_to_portfolio(
-    portfolio: &mut PortfolioSummary,
-    company: &str,
-    symbol: &str,
-    amount: f64,
-    usd_value: Option<f64>,
-) {
-    if amount == 0.0 {
-        return;
-    }
-
-    let company_assets = portfolio
-        .companies
-        .entry(company.to_string())
-        .or_insert_with(|| CompanyAssets {
-            assets: HashMap::new(),
-            total_usd_value: 0.0,
-        });
-
-    let asset = company_assets
-        .assets
-        .entry(symbol.to_string())
-        .or_insert_with(|| AssetSummary {
-            symbol: symbol.to_string(),
-            amount: 0.0,
-            usd_value: Some(0.0),
-        });
-
-    asset.amount += amount;
-    if let Some(value) = usd_value {
-        if let Some(ref mut asset_value) = asset.usd_value {
-            *asset_value += value;
-        }
-        company_assets.total_usd_value += value;
-        portfolio.total_usd_value += value;
-    }
-}
-
-// Trait for price enrichment - eliminates duplicate code across chains
-trait PriceEnrichable {
-    const NATIVE_SYMBOL: &'static str;
-
-    fn native_balance(&self) -> f64;
-    fn set_native_usd_price(&mut self, price: f64);
-    fn set_native_usd_value(&mut self, value: f64);
-    fn set_total_usd_value(&mut self, value: f64);
-
-    // Default implementation returns 0.0 for chains without tokens
-    fn enrich_token_balances(&mut self, _price_cache: &HashMap<String, f64>) -> f64 {
-        0.0
-    }
-
-    // Default enrichment implementation
-    fn enrich_from_cache(&mut self, price_cache: &HashMap<String, f64>) {
-        let mut total_usd = 0.0;
-
-        // Enrich native token balance
-        if let Some(&price) = price_cache.get(Self::NATIVE_SYMBOL) {
-            self.set_native_usd_price(price);
-            let native_value = self.native_balance() * price;
-            self.set_native_usd_value(native_value);
-            total_usd += native_value;
-        }
-
-        // Enrich token balances (if any)
-        total_usd += self.enrich_token_balances(price_cache);
-
-        if total_usd > 0.0 {
-            self.set_total_usd_value(total_usd);
-        }
-    }
-}
-
-// Struct to hold wallet + balances during query phase
-enum WalletBalances {
-    Solana(WalletAddress, solana::AccountBalances),
-    Evm(WalletAddress, evm::AccountBalances),
-    Near(WalletAddress, near::AccountBalances),
-    Aptos(WalletAddress, aptos::AccountBalances),
-    Sui(WalletAddress, sui::AccountBalances),
-    Starknet(WalletAddress, starknet::AccountBalances),
-    Mercury(BankingAccount, mercury::AccountBalances),
-    Circle(BankingAccount, circle::AccountBalances),
-}
-
 #[tokio::main]
 async fn main() -> Result<()> {
     // Load environment variables from .env file if present
@@ -138,31 +52,46 @@ async fn main() -> Result<()> {
 
     let cli = Cli::parse();
 
+    // Settings for this run: config.toml with the selected profile applied
+    let config_file = Config::load()?;
+    config::activate(config_file.profile(config::selected_profile(cli.profile).as_deref())?);
+
+    // Commands that talk to APIs unlock the secrets vault up front, so a
+    // passphrase prompt never lands in the middle of progress output
+    if matches!(
+        cli.command,
+        Commands::Query { .. }
+            | Commands::QueryOne { .. }
+            | Commands::SetupMercury { .. }
+            | Commands::ExportTransactions { .. }
+            | Commands::Utxos { .. }
+            | Commands::Serve { .. }
+    ) {
+        secrets::unlock()?;
+    }
+
     match cli.command {
         Commands::Add { company, name, address, chain } => {
-            let detected_chain = if let Some(chain_str) = chain {
-                Chain::from_str(&chain_str)?
-            } else {
-                // Auto-detect chain based on address format
-                if address.len() == 42 && address.starts_with("0x") {
-                    Chain::Ethereum
-                } else {
-                    Chain::Solana
-                }
-            };
+            // Auto-detect chain based on address format unless given
+            let detected_chain = AddressBook::detect_chain(&address, chain.as_deref())?;
             add_address(company, name, address, detected_chain)?;
         }
-        Commands::List { .. } => {
-            list_addresses()?;
+        Commands::List { output, .. } => {
+            list_addresses(OutputFormat::from_str(&output)?)?;
         }
         Commands::Remove { identifier } => {
             remove_address(identifier)?;
         }
-        Commands::Query { rpc_url, no_prices } => {
-            query_all(rpc_url, no_prices).await?;
+        Commands::Query { rpc_url, no_prices, concurrency, per_endpoint, save, output } => {
+            let output = OutputFormat::from_str(&output)?;
+            let mut rpc = config::active().settings.rpc.clone();
+            for spec in &rpc_url {
+                rpc.add_override(spec, None)?;
+            }
+            query_all(&rpc, no_prices, FetchOptions { concurrency, per_endpoint }, save, output).await?;
         }
-        Commands::QueryOne { name, rpc_url, no_prices } => {
-            query_one(name, rpc_url, no_prices).await?;
+        Commands::QueryOne { name, rpc_url, no_prices, output } => {
+            query_one(name, rpc_url, no_prices, OutputFormat::from_str(&output)?).await?;
         }
         Commands::AddBank { company, name, account_id, service } => {
             let banking_service = BankingService::from_str(&service)?;
@@ -177,8 +106,31 @@ async fn main() -> Result<()> {
         Commands::ExportTransactions { name, format, start, end, output } => {
             export_transactions(name, format, start, end, output).await?;
         }
-        Commands::Serve { port } => {
-            web::start_server(port).await?;
+        Commands::Utxos { name, output } => {
+            list_utxos(name, OutputFormat::from_str(&output)?).await?;
+        }
+        Commands::Serve { port, save_snapshots, localhost, no_auth } => {
+            let settings = &config::active().settings;
+            web::start_server(web::ServeOptions {
+                port: port.or(settings.web.port).unwrap_or(3000),
+                save_snapshots: save_snapshots || settings.web.save_snapshots.unwrap_or(false),
+                localhost_only: localhost || settings.web.localhost.unwrap_or(false),
+                no_auth,
+                rpc: settings.rpc.clone(),
+            })
+            .await?;
+        }
+        Commands::User { command } => {
+            manage_users(command)?;
+        }
+        Commands::History { command } => {
+            show_history(command)?;
+        }
+        Commands::Config { command } => {
+            show_config(command, &config_file)?;
+        }
+        Commands::Secrets { command } => {
+            manage_secrets(command)?;
         }
     }
 
@@ -202,9 +154,19 @@ fn add_address(company: String, name: String, address: String, chain: Chain) ->
     Ok(())
 }
 
-fn list_addresses() -> Result<()> {
+fn list_addresses(output: OutputFormat) -> Result<()> {
     let book = AddressBook::load()?;
 
+    let accounts: Vec<AccountDto> = book.accounts().iter().map(AccountDto::from).collect();
+    match output {
+        OutputFormat::Json => return output::print_json(&accounts),
+        OutputFormat::Csv => {
+            print!("{}", output::accounts_csv(&accounts));
+            return Ok(());
+        }
+        OutputFormat::Table => {}
+    }
+
     if book.addresses.is_empty() && book.banking_accounts.is_empty() {
         println!("No addresses or accounts tracked yet.");
         println!("Use 'gringotts add' to add blockchain addresses.");
@@ -264,194 +226,53 @@ fn remove_address(identifier: String) -> Result<()> {
     Ok(())
 }
 
-// Helper function to fetch all balances from wallets and banking accounts
+// Helper function to fetch all balances from wallets and banking accounts.
+// Without a progress bar, warnings go to stderr so stdout stays machine-readable.
 async fn fetch_all_balances(
-    book: &AddressBook,
-    rpc_url: Option<String>,
-) -> Vec<WalletBalances> {
-    let total_items = book.addresses.len() + book.banking_accounts.len();
-    let pb = ProgressBar::new(total_items as u64);
-    pb.set_style(
-        ProgressStyle::default_bar()
-            .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} items ({eta})")
-            .expect("valid progress bar template")
-            .progress_chars("#>-")
-    );
-    pb.set_message("Fetching balances...");
-
-    let mut all_balances: Vec<WalletBalances> = Vec::new();
-
-    // Query blockchain wallets
-    for wallet in book.addresses.iter() {
-        match &wallet.chain {
-            Chain::Solana => {
-                let client = SolanaClient::new(rpc_url.clone());
-                match client.get_balances(&wallet.address) {
-                    Ok(balances) => {
-                        all_balances.push(WalletBalances::Solana(wallet.clone(), balances));
-                    }
-                    Err(e) => {
-                        pb.println(format!("⚠ Warning: Failed to query {} ({}): {}", wallet.name, wallet.address, e));
-                    }
-                }
-            }
-            Chain::Near => {
-                let client = NearClient::new(rpc_url.clone());
-                match client.get_balances(&wallet.address).await {
-                    Ok(balances) => {
-                        all_balances.push(WalletBalances::Near(wallet.clone(), balances));
-                    }
-                    Err(e) => {
-                        pb.println(format!("⚠ Warning: Failed to query {} ({}): {}", wallet.name, wallet.address, e));
-                    }
-                }
-            }
-            Chain::Aptos => {
-                let client = AptosClient::new(rpc_url.clone());
-                match client.get_balances(&wallet.address).await {
-                    Ok(balances) => {
-                        all_balances.push(WalletBalances::Aptos(wallet.clone(), balances));
-                    }
-                    Err(e) => {
-                        pb.println(format!("⚠ Warning: Failed to query {} ({}): {}", wallet.name, wallet.address, e));
-                    }
-                }
-            }
-            Chain::Sui => {
-                let client = SuiClient::new(rpc_url.clone());
-                match client.get_balances(&wallet.address).await {
-                    Ok(balances) => {
-                        all_balances.push(WalletBalances::Sui(wallet.clone(), balances));
-                    }
-                    Err(e) => {
-                        pb.println(format!("⚠ Warning: Failed to query {} ({}): {}", wallet.name, wallet.address, e));
-                    }
-                }
-            }
-            Chain::Starknet => {
-                let client = StarknetClient::new(rpc_url.clone());
-                match client.get_balances(&wallet.address).await {
-                    Ok(balances) => {
-                        all_balances.push(WalletBalances::Starknet(wallet.clone(), balances));
-                    }
-                    Err(e) => {
-                        pb.println(format!("⚠ Warning: Failed to query {} ({}): {}", wallet.name, wallet.address, e));
-                    }
-                }
-            }
-            // All EVM chains
-            Chain::Ethereum | Chain::Polygon | Chain::BinanceSmartChain | Chain::Arbitrum
-            | Chain::Optimism | Chain::Avalanche | Chain::Base | Chain::Core => {
-                match EvmClient::new(rpc_url.clone(), wallet.chain.clone()) {
-                    Ok(client) => match client.get_balances(&wallet.address).await {
-                        Ok(balances) => {
-                            all_balances.push(WalletBalances::Evm(wallet.clone(), balances));
-                        }
-                        Err(e) => {
-                            pb.println(format!("⚠ Warning: Failed to query {} ({}): {}", wallet.name, wallet.address, e));
-                        }
-                    },
-                    Err(e) => {
-                        pb.println(format!("⚠ Warning: Failed to create EVM client for {} ({}): {}", wallet.name, wallet.address, e));
-                    }
-                }
-            }
-        }
-        pb.inc(1);
-    }
+    accounts: &[TrackedAccount],
+    rpc: &RpcConfig,
+    options: FetchOptions,
+    show_progress: bool,
+) -> (Vec<AccountSnapshot>, Vec<(TrackedAccount, String)>) {
+    let pb = if show_progress {
+        let pb = ProgressBar::new(accounts.len() as u64);
+        pb.set_style(
+            ProgressStyle::default_bar()
+                .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} items ({eta})")
+                .expect("valid progress bar template")
+                .progress_chars("#>-")
+        );
+        pb.set_message("Fetching balances...");
+        pb
+    } else {
+        ProgressBar::hidden()
+    };
 
-    // Query banking accounts
-    for account in book.banking_accounts.iter() {
-        match &account.service {
-            BankingService::Mercury => {
-                match MercuryClient::new() {
-                    Ok(client) => {
-                        match client.get_account_balance(&account.account_id).await {
-                            Ok(balances) => {
-                                all_balances.push(WalletBalances::Mercury(account.clone(), balances));
-                            }
-                            Err(e) => {
-                                pb.println(format!("⚠ Warning: Failed to query {} ({}): {}", account.name, account.account_id, e));
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        pb.println(format!("⚠ Warning: Failed to initialize Mercury client: {}", e));
-                    }
-                }
-            }
-            BankingService::Circle => {
-                match CircleClient::new() {
-                    Ok(client) => {
-                        match client.get_balances().await {
-                            Ok(balances) => {
-                                all_balances.push(WalletBalances::Circle(account.clone(), balances));
-                            }
-                            Err(e) => {
-                                pb.println(format!("⚠ Warning: Failed to query {} Circle balances: {}", account.name, e));
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        pb.println(format!("⚠ Warning: Failed to initialize Circle client: {}", e));
-                    }
-                }
+    let mut failures = Vec::new();
+    let snapshots = source::fetch_snapshots(accounts, rpc, options, |account, error| {
+        if let Some(e) = error {
+            let warning = format!("⚠ Warning: Failed to query {} ({}): {:#}", account.name(), account.identifier(), e);
+            if show_progress {
+                pb.println(warning);
+            } else {
+                eprintln!("{}", warning);
             }
+            failures.push((account.clone(), format!("{:#}", e)));
         }
         pb.inc(1);
-    }
-
-    pb.finish_with_message(format!("✓ Successfully fetched balances from {} items", all_balances.len()));
-    println!();
-
-    all_balances
-}
+    })
+    .await;
 
-// Helper function to extract unique token symbols from all balances
-fn extract_token_symbols(all_balances: &[WalletBalances]) -> HashSet<String> {
-    let mut symbols: HashSet<String> = HashSet::new();
-
-    for wallet_balance in all_balances {
-        match wallet_balance {
-            WalletBalances::Solana(_, balances) => {
-                symbols.insert("SOL".to_string());
-                for token in &balances.token_balances {
-                    if let Some(symbol) = &token.symbol {
-                        symbols.insert(symbol.clone());
-                    }
-                }
-            }
-            WalletBalances::Evm(_, balances) => {
-                symbols.insert("ETH".to_string());
-                for token in &balances.token_balances {
-                    if let Some(symbol) = &token.symbol {
-                        symbols.insert(symbol.clone());
-                    }
-                }
-            }
-            WalletBalances::Near(_, _) => {
-                symbols.insert("NEAR".to_string());
-            }
-            WalletBalances::Aptos(_, _) => {
-                symbols.insert("APT".to_string());
-            }
-            WalletBalances::Sui(_, _) => {
-                symbols.insert("SUI".to_string());
-            }
-            WalletBalances::Starknet(_, _) => {
-                symbols.insert("ETH".to_string());
-            }
-            WalletBalances::Mercury(_, _) | WalletBalances::Circle(_, _) => {
-                // Banking balances are already in USD/EUR, no price lookup needed
-            }
-        }
+    if show_progress {
+        pb.finish_with_message(format!("✓ Successfully fetched balances from {} items", snapshots.len()));
+        println!();
     }
 
-    symbols
+    (snapshots, failures)
 }
 
 // Helper function to fetch USD prices for token symbols
-async fn fetch_prices_for_symbols(symbols: HashSet<String>) -> Result<HashMap<String, f64>> {
+async fn fetch_prices_for_symbols(symbols: HashSet<String>, show_progress: bool) -> Result<HashMap<String, f64>> {
     let mut price_cache: HashMap<String, f64> = HashMap::new();
 
     if symbols.is_empty() {
@@ -459,14 +280,19 @@ async fn fetch_prices_for_symbols(symbols: HashSet<String>) -> Result<HashMap<St
     }
 
     let price_service = PriceService::new()?;
-    let price_pb = ProgressBar::new_spinner();
-    price_pb.set_style(
-        ProgressStyle::default_spinner()
-            .template("{spinner:.green} {msg}")
-            .expect("valid spinner template")
-    );
-    price_pb.set_message(format!("Fetching USD prices for {} unique tokens...", symbols.len()));
-    price_pb.enable_steady_tick(std::time::Duration::from_millis(100));
+    let price_pb = if show_progress {
+        let price_pb = ProgressBar::new_spinner();
+        price_pb.set_style(
+            ProgressStyle::default_spinner()
+                .template("{spinner:.green} {msg}")
+                .expect("valid spinner template")
+        );
+        price_pb.set_message(format!("Fetching USD prices for {} unique tokens...", symbols.len()));
+        price_pb.enable_steady_tick(std::time::Duration::from_millis(100));
+        price_pb
+    } else {
+        ProgressBar::hidden()
+    };
 
     let symbols_vec: Vec<String> = symbols.into_iter().collect();
     match price_service.batch_fetch_prices(&symbols_vec).await {
@@ -474,482 +300,317 @@ async fn fetch_prices_for_symbols(symbols: HashSet<String>) -> Result<HashMap<St
             price_cache = prices;
             price_pb.finish_with_message(format!("✓ Successfully fetched prices for {} symbols", price_cache.len()));
         }
-        Err(e) => {
+        Err(e) if show_progress => {
             price_pb.finish_with_message(format!("⚠ Failed to fetch prices: {}", e));
             price_pb.println("Balances will be displayed without USD values.");
         }
+        Err(e) => {
+            eprintln!("⚠ Warning: Failed to fetch prices: {}", e);
+        }
+    }
+    if show_progress {
+        println!();
     }
-    println!();
 
     Ok(price_cache)
 }
 
 // Helper function to enrich balances with prices and display them
 fn enrich_and_display_balances(
-    all_balances: Vec<WalletBalances>,
+    snapshots: &mut [AccountSnapshot],
     price_cache: &HashMap<String, f64>,
 ) -> PortfolioSummary {
-    let mut portfolio = PortfolioSummary {
-        companies: HashMap::new(),
-        total_usd_value: 0.0,
-    };
-
-    for wallet_balance in all_balances {
-        match wallet_balance {
-            WalletBalances::Solana(wallet, mut balances) => {
-                balances.enrich_from_cache(price_cache);
-                ui::render_solana_balances(&wallet.company, &wallet.name, &wallet.address, &balances, &wallet.chain);
-                aggregate_solana_balances(&mut portfolio, &wallet.company, &balances);
-            }
-            WalletBalances::Evm(wallet, mut balances) => {
-                balances.enrich_from_cache(price_cache);
-                ui::render_evm_balances(&wallet.company, &wallet.name, &wallet.address, &balances, &wallet.chain);
-                aggregate_evm_balances(&mut portfolio, &wallet.company, &balances, &wallet.chain);
-            }
-            WalletBalances::Near(wallet, mut balances) => {
-                balances.enrich_from_cache(price_cache);
-                ui::render_near_balances(&wallet.company, &wallet.name, &wallet.address, &balances, &wallet.chain);
-                aggregate_near_balances(&mut portfolio, &wallet.company, &balances);
-            }
-            WalletBalances::Aptos(wallet, mut balances) => {
-                balances.enrich_from_cache(price_cache);
-                ui::render_aptos_balances(&wallet.company, &wallet.name, &wallet.address, &balances, &wallet.chain);
-                aggregate_aptos_balances(&mut portfolio, &wallet.company, &balances);
-            }
-            WalletBalances::Sui(wallet, mut balances) => {
-                balances.enrich_from_cache(price_cache);
-                ui::render_sui_balances(&wallet.company, &wallet.name, &wallet.address, &balances, &wallet.chain);
-                aggregate_sui_balances(&mut portfolio, &wallet.company, &balances);
-            }
-            WalletBalances::Starknet(wallet, mut balances) => {
-                balances.enrich_from_cache(price_cache);
-                ui::render_starknet_balances(&wallet.company, &wallet.name, &wallet.address, &balances, &wallet.chain);
-                aggregate_starknet_balances(&mut portfolio, &wallet.company, &balances);
-            }
-            WalletBalances::Mercury(account, balances) => {
-                ui::render_mercury_balances(&account.company, &account.name, &account.account_id, &balances, &account.service);
-                aggregate_mercury_balances(&mut portfolio, &account.company, &balances);
-            }
-            WalletBalances::Circle(account, balances) => {
-                ui::render_circle_balances(&account.company, &account.name, &balances, &account.service);
-                aggregate_circle_balances(&mut portfolio, &account.company, &balances);
-            }
-        }
+    for snapshot in snapshots.iter_mut() {
+        snapshot.enrich_from_cache(price_cache);
+        ui::render_account_snapshot(snapshot);
     }
 
-    portfolio
+    PortfolioSummary::from_snapshots(snapshots)
 }
 
-async fn query_all(rpc_url: Option<String>, no_prices: bool) -> Result<()> {
+async fn query_all(
+    rpc: &RpcConfig,
+    no_prices: bool,
+    options: FetchOptions,
+    save: bool,
+    output: OutputFormat,
+) -> Result<()> {
     let book = AddressBook::load()?;
 
-    if book.addresses.is_empty() && book.banking_accounts.is_empty() {
-        println!("No addresses or accounts tracked yet.");
-        println!("Use 'gringotts add' to add blockchain addresses.");
-        println!("Use 'gringotts add-bank' to add banking accounts.");
-        return Ok(());
-    }
+    if output.is_table() {
+        if book.addresses.is_empty() && book.banking_accounts.is_empty() {
+            println!("No addresses or accounts tracked yet.");
+            println!("Use 'gringotts add' to add blockchain addresses.");
+            println!("Use 'gringotts add-bank' to add banking accounts.");
+            return Ok(());
+        }
 
-    if no_prices {
-        println!("\nQuerying balances for all tracked addresses and accounts (without prices)...\n");
-    } else {
-        println!("\nQuerying balances for all tracked addresses and accounts...\n");
+        if no_prices {
+            println!("\nQuerying balances for all tracked addresses and accounts (without prices)...\n");
+        } else {
+            println!("\nQuerying balances for all tracked addresses and accounts...\n");
+        }
     }
 
     // Fetch all balances
-    let all_balances = fetch_all_balances(&book, rpc_url).await;
+    let (mut all_balances, failures) = fetch_all_balances(&book.accounts(), rpc, options, output.is_table()).await;
 
     // Extract symbols and fetch prices (skip if --no-prices)
     let price_cache = if !no_prices {
-        let symbols = extract_token_symbols(&all_balances);
-        fetch_prices_for_symbols(symbols).await?
+        let symbols = source::extract_token_symbols(&all_balances);
+        fetch_prices_for_symbols(symbols, output.is_table()).await?
     } else {
         HashMap::new()
     };
 
-    // Enrich balances with prices and display
-    let portfolio = enrich_and_display_balances(all_balances, &price_cache);
+    if output.is_table() {
+        // Enrich balances with prices and display
+        let portfolio = enrich_and_display_balances(&mut all_balances, &price_cache);
 
-    // Display portfolio summary
-    ui::render_portfolio_summary(&portfolio);
-
-    Ok(())
-}
+        // Display portfolio summary
+        ui::render_portfolio_summary(&portfolio);
+    } else {
+        for snapshot in all_balances.iter_mut() {
+            snapshot.enrich_from_cache(&price_cache);
+        }
 
-async fn query_one(identifier: String, rpc_url: Option<String>, no_prices: bool) -> Result<()> {
-    let book = AddressBook::load()?;
+        let report = PortfolioDto::new(&all_balances, &price_cache, &failures);
+        if output == OutputFormat::Json {
+            output::print_json(&report)?;
+        } else {
+            print!("{}", output::balances_csv(&report.accounts, &report.companies, Some(&report.total_usd_value)));
+        }
+    }
 
-    // Try to find wallet by name or address
-    let wallet = book.addresses.iter().find(|w| w.name == identifier || w.address == identifier);
+    if save {
+        let id = HistoryStore::open()?.save(&all_balances, SnapshotOrigin::Cli)?;
+        if output.is_table() {
+            ui::render_success(&format!("Saved snapshot #{} to history", id));
+        } else {
+            eprintln!("Saved snapshot #{} to history", id);
+        }
+    }
 
-    if let Some(wallet) = wallet {
-        println!("\nQuerying balance for '{}'...\n", wallet.name);
+    Ok(())
+}
 
-        let price_service = PriceService::new()?;
-        let mut price_cache: HashMap<String, f64> = HashMap::new();
+fn manage_users(command: UserCommands) -> Result<()> {
+    let mut users = UserStore::load()?;
 
-        // Pre-fetch prices for common symbols if not in no_prices mode
-        if !no_prices {
-            println!("Fetching cryptocurrency prices...");
-            let symbols = vec!["SOL".to_string(), "ETH".to_string(), "USDC".to_string(), "USDT".to_string()];
-            match price_service.batch_fetch_prices(&symbols).await {
-                Ok(prices) => {
-                    price_cache = prices;
-                    println!("Successfully fetched prices\n");
-                }
-                Err(e) => {
-                    eprintln!("Warning: Failed to fetch prices: {}", e);
-                    eprintln!("Will attempt to fetch prices individually as needed.\n");
-                }
+    match command {
+        UserCommands::Add { username, role } => {
+            let role = Role::from_str(&role)?;
+            let password = rpassword::prompt_password(format!("Password for {}: ", username))
+                .context("Failed to read password")?;
+            let confirm = rpassword::prompt_password("Confirm password: ")
+                .context("Failed to read password")?;
+            if password != confirm {
+                anyhow::bail!("Passwords do not match");
             }
-        }
 
-    match &wallet.chain {
-        Chain::Solana => {
-            let client = SolanaClient::new(rpc_url);
-            query_and_display_solana(&client, wallet, &price_service, &mut price_cache, no_prices).await?;
-        }
-        Chain::Near => {
-            let client = NearClient::new(rpc_url);
-            query_and_display_near(&client, &wallet.company, &wallet.name, &wallet.address, &wallet.chain, &price_service, &mut price_cache).await?;
+            users.add_user(&username, &password, role)?;
+            users.save()?;
+            ui::render_success(&format!("User '{}' added with role {}", username.trim(), role.display_name()));
         }
-        Chain::Aptos => {
-            let client = AptosClient::new(rpc_url);
-            query_and_display_aptos(&client, &wallet.company, &wallet.name, &wallet.address, &wallet.chain, &price_service, &mut price_cache).await?;
+        UserCommands::Remove { username } => {
+            users.remove_user(&username)?;
+            users.save()?;
+            ui::render_success(&format!("User '{}' removed", username));
         }
-        Chain::Sui => {
-            let client = SuiClient::new(rpc_url);
-            query_and_display_sui(&client, &wallet.company, &wallet.name, &wallet.address, &wallet.chain, &price_service, &mut price_cache).await?;
+        UserCommands::List => {
+            if users.is_empty() {
+                println!("No users configured.");
+                println!("Use 'gringotts user add <username> --role admin' to add one.");
+                return Ok(());
+            }
+            for user in &users.users {
+                println!("{:<24} {:<10} {} API token(s)", user.username, user.role.display_name(), user.token_count());
+            }
+            return Ok(());
         }
-        Chain::Starknet => {
-            let client = StarknetClient::new(rpc_url);
-            query_and_display_starknet(&client, &wallet.company, &wallet.name, &wallet.address, &wallet.chain, &price_service, &mut price_cache).await?;
+        UserCommands::Token { username } => {
+            let token = users.create_token(&username)?;
+            users.save()?;
+            println!("API token for '{}' (it will not be shown again):\n\n  {}\n", username, token);
+            println!("Use it with: curl -H \"Authorization: Bearer {}\" http://localhost:3000/api/v1/accounts", token);
         }
-        // All EVM chains
-        Chain::Ethereum | Chain::Polygon | Chain::BinanceSmartChain | Chain::Arbitrum
-        | Chain::Optimism | Chain::Avalanche | Chain::Base | Chain::Core => {
-            let client = EvmClient::new(rpc_url, wallet.chain.clone())?;
-            query_and_display_evm(&client, wallet, &price_service, &mut price_cache, no_prices).await?;
+        UserCommands::RevokeTokens { username } => {
+            let count = users.revoke_tokens(&username)?;
+            users.save()?;
+            ui::render_success(&format!("Revoked {} API token(s) for '{}'", count, username));
         }
     }
 
-        return Ok(());
-    }
-
-    // Try to find banking account
-    let account = book.banking_accounts.iter().find(|a| a.name == identifier || a.account_id == identifier);
+    println!("Restart 'gringotts serve' for user changes to take effect.");
+    Ok(())
+}
 
-    if let Some(account) = account {
-        println!("\nQuerying balance for '{}'...\n", account.name);
+fn show_history(command: HistoryCommands) -> Result<()> {
+    let store = HistoryStore::open()?;
 
-        match &account.service {
-            BankingService::Mercury => {
-                let client = MercuryClient::new()?;
-                let balances = client.get_account_balance(&account.account_id).await?;
-                ui::render_mercury_balances(&account.company, &account.name, &account.account_id, &balances, &account.service);
-            }
-            BankingService::Circle => {
-                let client = CircleClient::new()?;
-                let balances = client.get_balances().await?;
-                ui::render_circle_balances(&account.company, &account.name, &balances, &account.service);
+    match command {
+        HistoryCommands::List { limit } => {
+            let snapshots = store.list(limit)?;
+            if snapshots.is_empty() {
+                println!("No snapshots saved yet.");
+                println!("Use 'gringotts query --save' to record one.");
+                return Ok(());
             }
+            ui::render_snapshot_list(&snapshots);
+        }
+        HistoryCommands::Show { id } => {
+            let snapshot = store.load(id)?;
+            println!("\nSnapshot #{} taken {} ({})", snapshot.info.id, snapshot.info.taken_at_display(), snapshot.info.origin);
+            ui::render_snapshot_holdings(&snapshot);
+            ui::render_portfolio_summary(&snapshot.portfolio());
+        }
+        HistoryCommands::Diff { from, to } => {
+            let to = match to {
+                Some(id) => id,
+                None => store.latest_id()?.context("No snapshots saved yet")?,
+            };
+            let diff = SnapshotDiff::between(&store.load(from)?, &store.load(to)?);
+            ui::render_snapshot_diff(&diff);
         }
-
-        return Ok(());
     }
 
-    ui::render_error(&format!("No address or account found with identifier '{}'", identifier));
     Ok(())
 }
 
-async fn enrich_with_usd_prices(
-    balances: &mut solana::AccountBalances,
-    price_service: &PriceService,
-    price_cache: &mut HashMap<String, f64>,
-) -> Result<()> {
-    // Enrich SOL price
-    let sol_price = if let Some(&cached_price) = price_cache.get("SOL") {
-        cached_price
-    } else {
-        let price = price_service.get_single_price("SOL").await?;
-        price_cache.insert("SOL".to_string(), price);
-        price
-    };
-
-    balances.sol_usd_price = Some(sol_price);
-    balances.sol_usd_value = Some(balances.sol_balance * sol_price);
-
-    // Enrich token balances
-    let mut total_usd = balances.sol_usd_value.unwrap_or(0.0);
-    for token in &mut balances.token_balances {
-        if let Some(symbol) = &token.symbol {
-            let price = if let Some(&cached_price) = price_cache.get(symbol) {
-                cached_price
+fn manage_secrets(command: SecretsCommands) -> Result<()> {
+    match command {
+        SecretsCommands::Set { name, stdin } => {
+            let value = if stdin {
+                let mut value = String::new();
+                std::io::stdin().read_line(&mut value).context("Failed to read secret from stdin")?;
+                value.trim_end_matches(['\r', '\n']).to_string()
             } else {
-                match price_service.get_single_price(symbol).await {
-                    Ok(p) => {
-                        price_cache.insert(symbol.clone(), p);
-                        p
-                    }
-                    Err(e) => {
-                        eprintln!("Warning: Failed to fetch price for {} ({}): {}", symbol, token.mint, e);
-                        continue;
-                    }
-                }
+                rpassword::prompt_password(format!("Value for {}: ", name)).context("Failed to read secret")?
             };
 
-            token.usd_price = Some(price);
-            token.usd_value = Some(token.ui_amount * price);
-            total_usd += token.usd_value.unwrap_or(0.0);
-        }
-    }
+            let mut vault = secrets::Vault::open_or_create()?;
+            vault.set(&name, &value)?;
+            vault.save()?;
+            ui::render_success(&format!("Secret '{}' stored in the secrets vault", name));
 
-    if total_usd > 0.0 {
-        balances.total_usd_value = Some(total_usd);
+            let key_name = name.rsplit('/').next().unwrap_or(&name);
+            if !ApiKey::ALL.iter().any(|key| key.env_var() == key_name) {
+                let known: Vec<&str> = ApiKey::ALL.iter().map(|key| key.env_var()).collect();
+                println!("Note: gringotts only reads {}", known.join(", "));
+            }
+        }
+        SecretsCommands::Get { name } => {
+            let vault = secrets::Vault::open()?;
+            let value = vault.get(&name).with_context(|| format!("Secret '{}' not found", name))?;
+            println!("{}", value);
+        }
+        SecretsCommands::Rm { name } => {
+            let mut vault = secrets::Vault::open()?;
+            vault.remove(&name)?;
+            vault.save()?;
+            ui::render_success(&format!("Secret '{}' removed", name));
+        }
+        SecretsCommands::List => {
+            let vault = secrets::Vault::open()?;
+            let names: Vec<&String> = vault.names().collect();
+            if names.is_empty() {
+                println!("The secrets vault is empty.");
+            }
+            for name in names {
+                println!("{}", name);
+            }
+        }
     }
 
     Ok(())
 }
 
-async fn enrich_with_eth_prices(
-    balances: &mut evm::AccountBalances,
-    price_service: &PriceService,
-    price_cache: &mut HashMap<String, f64>,
-) -> Result<()> {
-    // Enrich ETH price
-    let eth_price = if let Some(&cached_price) = price_cache.get("ETH") {
-        cached_price
-    } else {
-        let price = price_service.get_single_price("ETH").await?;
-        price_cache.insert("ETH".to_string(), price);
-        price
-    };
-
-    balances.eth_usd_price = Some(eth_price);
-    balances.eth_usd_value = Some(balances.eth_balance * eth_price);
+fn show_config(command: ConfigCommands, config_file: &Config) -> Result<()> {
+    let path = Config::get_config_path()?;
 
-    // Enrich token balances
-    let mut total_usd = balances.eth_usd_value.unwrap_or(0.0);
-    for token in &mut balances.token_balances {
-        if let Some(symbol) = &token.symbol {
-            let price = if let Some(&cached_price) = price_cache.get(symbol) {
-                cached_price
-            } else {
-                match price_service.get_single_price(symbol).await {
-                    Ok(p) => {
-                        price_cache.insert(symbol.clone(), p);
-                        p
-                    }
-                    Err(e) => {
-                        eprintln!("Warning: Failed to fetch price for {}: {}", symbol, e);
-                        continue;
-                    }
-                }
-            };
+    match command {
+        ConfigCommands::Show => {
+            let profile = config::active();
 
-            token.usd_price = Some(price);
-            token.usd_value = Some(token.ui_amount * price);
-            total_usd += token.usd_value.unwrap_or(0.0);
-        }
-    }
+            println!("Config file:      {}{}", path.display(), if path.exists() { "" } else { " (not found)" });
+            println!("Profile:          {}", profile.name.as_deref().unwrap_or("(none)"));
+            println!("Address book:     {}", AddressBook::get_storage_path()?.display());
+            println!("History database: {}", HistoryStore::path()?.display());
 
-    if total_usd > 0.0 {
-        balances.total_usd_value = Some(total_usd);
-    }
-
-    Ok(())
-}
-
-async fn query_and_display_solana(
-    client: &SolanaClient,
-    wallet: &WalletAddress,
-    price_service: &PriceService,
-    price_cache: &mut HashMap<String, f64>,
-    no_prices: bool,
-) -> Result<solana::AccountBalances> {
-    match client.get_balances(&wallet.address) {
-        Ok(mut balances) => {
-            // Try to enrich with USD prices using cache (skip if --no-prices)
-            if !no_prices {
-                if let Err(e) = enrich_with_usd_prices(&mut balances, price_service, price_cache).await {
-                    eprintln!("Warning: Failed to fetch USD prices: {}", e);
-                }
+            println!("\nAPI keys:");
+            for key in ApiKey::ALL {
+                println!("  {:<18} {}", key.env_var(), config::api_key_source(key));
             }
 
-            // Use the new UI renderer
-            ui::render_solana_balances(&wallet.company, &wallet.name, &wallet.address, &balances, &wallet.chain);
-            Ok(balances)
-        }
-        Err(e) => {
-            ui::render_error(&format!("Error querying '{}' ({}): {}", wallet.name, wallet.address, e));
-            anyhow::bail!("Failed to query Solana address")
+            let settings = toml::to_string_pretty(&profile.settings.redacted())
+                .context("Failed to format settings")?;
+            if !settings.trim().is_empty() {
+                println!("\nSettings:\n\n{}", settings.trim_end());
+            }
         }
-    }
-}
-
-async fn query_and_display_evm(
-    client: &EvmClient,
-    wallet: &WalletAddress,
-    price_service: &PriceService,
-    price_cache: &mut HashMap<String, f64>,
-    no_prices: bool,
-) -> Result<evm::AccountBalances> {
-    match client.get_balances(&wallet.address).await {
-        Ok(mut balances) => {
-            // Try to enrich with USD prices using cache (skip if --no-prices)
-            if !no_prices {
-                if let Err(e) = enrich_with_eth_prices(&mut balances, price_service, price_cache).await {
-                    eprintln!("Warning: Failed to fetch USD prices: {}", e);
-                }
+        ConfigCommands::Validate => {
+            if !path.exists() {
+                println!("No config file at {}; built-in defaults are used.", path.display());
+                return Ok(());
             }
 
-            // Use the new UI renderer
-            ui::render_evm_balances(&wallet.company, &wallet.name, &wallet.address, &balances, &wallet.chain);
-            Ok(balances)
-        }
-        Err(e) => {
-            ui::render_error(&format!("Error querying '{}' ({}): {}", wallet.name, wallet.address, e));
-            anyhow::bail!("Failed to query EVM address")
+            // Loading already parsed and checked the file and every profile
+            let profiles = config_file.profile_names();
+            let summary = if profiles.is_empty() {
+                "no profiles".to_string()
+            } else {
+                format!("profiles: {}", profiles.join(", "))
+            };
+            ui::render_success(&format!("{} is valid ({})", path.display(), summary));
         }
     }
-}
-
-fn aggregate_solana_balances(portfolio: &mut PortfolioSummary, company: &str, balances: &solana::AccountBalances) {
-    add_asset_to_portfolio(portfolio, company, "SOL", balances.sol_balance, balances.sol_usd_value);
 
-    for token in &balances.token_balances {
-        if let Some(symbol) = &token.symbol {
-            add_asset_to_portfolio(portfolio, company, symbol, token.ui_amount, token.usd_value);
-        }
-    }
+    Ok(())
 }
 
-fn aggregate_evm_balances(portfolio: &mut PortfolioSummary, company: &str, balances: &evm::AccountBalances, chain: &Chain) {
-    let native_symbol = match chain {
-        Chain::BinanceSmartChain => "BNB",
-        Chain::Polygon => "MATIC",
-        Chain::Avalanche => "AVAX",
-        _ => "ETH",
-    };
-    add_asset_to_portfolio(portfolio, company, native_symbol, balances.eth_balance, balances.eth_usd_value);
+async fn query_one(identifier: String, rpc_url: Vec<String>, no_prices: bool, output: OutputFormat) -> Result<()> {
+    let book = AddressBook::load()?;
 
-    for token in &balances.token_balances {
-        if let Some(symbol) = &token.symbol {
-            add_asset_to_portfolio(portfolio, company, symbol, token.ui_amount, token.usd_value);
+    let Some(account) = book.find_account(&identifier) else {
+        if !output.is_table() {
+            anyhow::bail!("No address or account found with identifier '{}'", identifier);
         }
-    }
-}
+        ui::render_error(&format!("No address or account found with identifier '{}'", identifier));
+        return Ok(());
+    };
 
-async fn query_and_display_near(
-    client: &NearClient,
-    company: &str,
-    name: &str,
-    address: &str,
-    chain: &Chain,
-    _price_service: &PriceService,
-    _price_cache: &mut HashMap<String, f64>
-) -> Result<near::AccountBalances> {
-    match client.get_balances(address).await {
-        Ok(balances) => {
-            ui::render_near_balances(company, name, address, &balances, chain);
-            Ok(balances)
-        }
-        Err(e) => {
-            ui::render_error(&format!("Error querying '{}' ({}): {}", name, address, e));
-            anyhow::bail!("Failed to query NEAR address")
-        }
+    let mut rpc = config::active().settings.rpc.clone();
+    for spec in &rpc_url {
+        rpc.add_override(spec, account.chain())?;
     }
-}
 
-fn aggregate_near_balances(portfolio: &mut PortfolioSummary, company: &str, balances: &near::AccountBalances) {
-    add_asset_to_portfolio(portfolio, company, "NEAR", balances.near_balance, balances.near_usd_value);
-}
-
-async fn query_and_display_aptos(
-    client: &AptosClient,
-    company: &str,
-    name: &str,
-    address: &str,
-    chain: &Chain,
-    _price_service: &PriceService,
-    _price_cache: &mut HashMap<String, f64>
-) -> Result<aptos::AccountBalances> {
-    match client.get_balances(address).await {
-        Ok(balances) => {
-            ui::render_aptos_balances(company, name, address, &balances, chain);
-            Ok(balances)
-        }
-        Err(e) => {
-            ui::render_error(&format!("Error querying '{}' ({}): {}", name, address, e));
-            anyhow::bail!("Failed to query Aptos address")
-        }
+    if output.is_table() {
+        println!("\nQuerying balance for '{}'...\n", account.name());
     }
-}
 
-fn aggregate_aptos_balances(portfolio: &mut PortfolioSummary, company: &str, balances: &aptos::AccountBalances) {
-    add_asset_to_portfolio(portfolio, company, "APT", balances.apt_balance, balances.apt_usd_value);
-}
-
-async fn query_and_display_sui(
-    client: &SuiClient,
-    company: &str,
-    name: &str,
-    address: &str,
-    chain: &Chain,
-    _price_service: &PriceService,
-    _price_cache: &mut HashMap<String, f64>
-) -> Result<sui::AccountBalances> {
-    match client.get_balances(address).await {
-        Ok(balances) => {
-            ui::render_sui_balances(company, name, address, &balances, chain);
-            Ok(balances)
+    let mut snapshot = match source::fetch_snapshot(&account, &rpc).await {
+        Ok(snapshot) => snapshot,
+        Err(e) if !output.is_table() => {
+            return Err(e.context(format!("Failed to query '{}' ({})", account.name(), account.identifier())));
         }
         Err(e) => {
-            ui::render_error(&format!("Error querying '{}' ({}): {}", name, address, e));
-            anyhow::bail!("Failed to query Sui address")
+            ui::render_error(&format!("Error querying '{}' ({}): {:#}", account.name(), account.identifier(), e));
+            anyhow::bail!("Failed to query {} account", account.source_name());
         }
-    }
-}
-
-fn aggregate_sui_balances(portfolio: &mut PortfolioSummary, company: &str, balances: &sui::AccountBalances) {
-    add_asset_to_portfolio(portfolio, company, "SUI", balances.sui_balance, balances.sui_usd_value);
-}
+    };
 
-async fn query_and_display_starknet(
-    client: &StarknetClient,
-    company: &str,
-    name: &str,
-    address: &str,
-    chain: &Chain,
-    _price_service: &PriceService,
-    _price_cache: &mut HashMap<String, f64>
-) -> Result<starknet::AccountBalances> {
-    match client.get_balances(address).await {
-        Ok(balances) => {
-            ui::render_starknet_balances(company, name, address, &balances, chain);
-            Ok(balances)
-        }
-        Err(e) => {
-            ui::render_error(&format!("Error querying '{}' ({}): {}", name, address, e));
-            anyhow::bail!("Failed to query Starknet address")
-        }
+    if !no_prices {
+        let symbols: HashSet<String> = snapshot.price_symbols().map(|s| s.to_string()).collect();
+        let price_cache = fetch_prices_for_symbols(symbols, output.is_table()).await?;
+        snapshot.enrich_from_cache(&price_cache);
     }
-}
-
-fn aggregate_starknet_balances(portfolio: &mut PortfolioSummary, company: &str, balances: &starknet::AccountBalances) {
-    add_asset_to_portfolio(portfolio, company, "ETH", balances.eth_balance, balances.eth_usd_value);
-}
-
-
-fn aggregate_mercury_balances(portfolio: &mut PortfolioSummary, company: &str, balances: &mercury::AccountBalances) {
-    add_asset_to_portfolio(portfolio, company, "USD", balances.current_balance, Some(balances.current_balance));
-}
 
-fn aggregate_circle_balances(portfolio: &mut PortfolioSummary, company: &str, balances: &circle::AccountBalances) {
-    // Aggregate available balances
-    for balance in &balances.available_balances {
-        add_asset_to_portfolio(portfolio, company, &balance.currency, balance.amount, Some(balance.amount));
+    match output {
+        OutputFormat::Table => ui::render_account_snapshot(&snapshot),
+        OutputFormat::Json => output::print_json(&AccountBalanceDto::from(&snapshot))?,
+        OutputFormat::Csv => print!("{}", output::balances_csv(&[AccountBalanceDto::from(&snapshot)], &[], None)),
     }
+    Ok(())
 }
 
 fn add_banking_account(company: String, name: String, account_id: String, service: BankingService) -> Result<()> {
@@ -1018,45 +679,102 @@ async fn export_transactions(
 ) -> Result<()> {
     let book = AddressBook::load()?;
 
-    let account = book.banking_accounts.iter()
-        .find(|a| a.name == account_name || a.account_id == account_name)
+    let account = book.find_account(&account_name)
         .ok_or_else(|| anyhow::anyhow!("Account not found: {}", account_name))?;
 
-    match &account.service {
-        BankingService::Mercury => {
-            let client = MercuryClient::new()?;
-            let transactions = client.get_transactions(&account.account_id, start.as_deref(), end.as_deref()).await?;
-
-            let output_data = export_mercury_transactions(&transactions, &format)?;
+    let (output_data, count) = match &account {
+        TrackedAccount::Bank(bank) => match &bank.service {
+            BankingService::Mercury => {
+                let client = MercuryClient::new()?;
+                let transactions = client.get_transactions(&bank.account_id, start.as_deref(), end.as_deref()).await?;
 
-            match output {
-                Some(path) => {
-                    let mut file = std::fs::File::create(&path)?;
-                    file.write_all(output_data.as_bytes())?;
-                    println!("Exported {} transactions to {}", transactions.len(), path);
-                }
-                None => {
-                    println!("{}", output_data);
-                }
+                (export_mercury_transactions(&transactions, &format)?, transactions.len())
             }
+            BankingService::Circle => {
+                return Err(anyhow::anyhow!("Transaction export not yet supported for Circle accounts"));
+            }
+        },
+        TrackedAccount::Wallet(wallet) => {
+            // --end includes the whole day
+            let range = source::TransactionRange {
+                since: start.as_deref().map(day_start).transpose()?,
+                until: end.as_deref().map(|end| day_start(end).map(|start| start + 86_399)).transpose()?,
+                limit: None,
+            };
+            let endpoints = config::active().settings.rpc.endpoints_for(&wallet.chain);
+            let transactions = if wallet.chain == Chain::Solana {
+                solana::SolanaClient::new(endpoints).get_transactions(&wallet.address, range).await?
+            } else if wallet.chain.is_evm() {
+                evm::EvmClient::new(endpoints, wallet.chain.clone()).get_transactions(&wallet.address, range).await?
+            } else {
+                return Err(anyhow::anyhow!(
+                    "Transaction export not yet supported for {} wallets",
+                    wallet.chain.display_name()
+                ));
+            };
+
+            (export_wallet_transactions(&transactions, &format)?, transactions.len())
+        }
+    };
+
+    match output {
+        Some(path) => {
+            let mut file = std::fs::File::create(&path)?;
+            file.write_all(output_data.as_bytes())?;
+            println!("Exported {} transactions to {}", count, path);
         }
-        BankingService::Circle => {
-            return Err(anyhow::anyhow!("Transaction export not yet supported for Circle accounts"));
+        None => {
+            println!("{}", output_data);
         }
     }
 
     Ok(())
 }
 
-fn export_mercury_transactions(transactions: &[mercury::Transaction], format: &str) -> Result<String> {
-    fn escape_csv(s: &str) -> String {
-        if s.contains(',') || s.contains('"') || s.contains('\n') {
-            format!("\"{}\"", s.replace('"', "\"\""))
-        } else {
-            s.to_string()
-        }
+async fn list_utxos(account_name: String, output: OutputFormat) -> Result<()> {
+    let book = AddressBook::load()?;
+
+    let account = book.find_account(&account_name)
+        .ok_or_else(|| anyhow::anyhow!("Account not found: {}", account_name))?;
+    let wallet = match &account {
+        TrackedAccount::Wallet(wallet) if wallet.chain == Chain::Bitcoin => wallet,
+        _ => anyhow::bail!("'{}' is not a Bitcoin wallet", account.name()),
+    };
+
+    let client = bitcoin::BitcoinClient::new(config::active().settings.rpc.endpoints_for(&Chain::Bitcoin));
+    let balances = client.get_balances(&wallet.address).await?;
+    let utxos: Vec<UtxoDto> = balances.utxos.iter().map(UtxoDto::from).collect();
+
+    match output {
+        OutputFormat::Json => output::print_json(&utxos)?,
+        OutputFormat::Csv => print!("{}", output::utxos_csv(&utxos)),
+        OutputFormat::Table => ui::render_utxos(&wallet.name, &utxos),
     }
 
+    Ok(())
+}
+
+/// Unix time at the start of a YYYY-MM-DD day, in UTC
+fn day_start(date: &str) -> Result<i64> {
+    let day = chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .with_context(|| format!("Invalid date '{}', expected YYYY-MM-DD", date))?;
+    Ok(day.and_hms_opt(0, 0, 0).expect("midnight exists").and_utc().timestamp())
+}
+
+fn export_wallet_transactions(transactions: &[source::WalletTransaction], format: &str) -> Result<String> {
+    let transactions: Vec<WalletTransactionDto> = transactions.iter().map(WalletTransactionDto::from).collect();
+
+    let output_data = match format.to_lowercase().as_str() {
+        "json" => serde_json::to_string_pretty(&transactions)?,
+        _ => output::transactions_csv(&transactions),
+    };
+
+    Ok(output_data)
+}
+
+fn export_mercury_transactions(transactions: &[mercury::Transaction], format: &str) -> Result<String> {
+    use output::escape_csv;
+
     let output_data = match format.to_lowercase().as_str() {
         "json" => {
             serde_json::to_string_pretty(&transactions)?
@@ -1105,185 +823,6 @@ fn export_mercury_transactions(transactions: &[mercury::Transaction], format: &s
 mod tests {
     use super::*;
 
-    // Test PriceEnrichable trait default implementation
-    #[test]
-    fn test_extract_token_symbols_empty() {
-        let balances: Vec<WalletBalances> = vec![];
-        let symbols = extract_token_symbols(&balances);
-        assert!(symbols.is_empty());
-    }
-
-    #[test]
-    fn test_extract_token_symbols_solana() {
-        let wallet = WalletAddress {
-            company: "Test".to_string(),
-            name: "Test Wallet".to_string(),
-            address: "test123".to_string(),
-            chain: Chain::Solana,
-        };
-
-        let mut balances = solana::AccountBalances {
-            sol_balance: 1.0,
-            sol_usd_price: None,
-            sol_usd_value: None,
-            token_balances: vec![],
-            total_usd_value: None,
-        };
-
-        // Add a token
-        balances.token_balances.push(solana::TokenBalance {
-            mint: "test_mint".to_string(),
-            symbol: Some("USDC".to_string()),
-            name: Some("USD Coin".to_string()),
-            decimals: 6,
-            ui_amount: 100.0,
-            usd_price: None,
-            usd_value: None,
-        });
-
-        let wallet_balances = vec![WalletBalances::Solana(wallet, balances)];
-        let symbols = extract_token_symbols(&wallet_balances);
-
-        assert!(symbols.contains("SOL"));
-        assert!(symbols.contains("USDC"));
-        assert_eq!(symbols.len(), 2);
-    }
-
-    #[test]
-    fn test_extract_token_symbols_multiple_chains() {
-        let sol_wallet = WalletAddress {
-            company: "Test".to_string(),
-            name: "SOL Wallet".to_string(),
-            address: "sol123".to_string(),
-            chain: Chain::Solana,
-        };
-
-        let eth_wallet = WalletAddress {
-            company: "Test".to_string(),
-            name: "ETH Wallet".to_string(),
-            address: "0x123".to_string(),
-            chain: Chain::Ethereum,
-        };
-
-        let sol_balances = solana::AccountBalances {
-            sol_balance: 1.0,
-            sol_usd_price: None,
-            sol_usd_value: None,
-            token_balances: vec![],
-            total_usd_value: None,
-        };
-
-        let eth_balances = evm::AccountBalances {
-            eth_balance: 1.0,
-            eth_usd_price: None,
-            eth_usd_value: None,
-            token_balances: vec![],
-            total_usd_value: None,
-        };
-
-        let wallet_balances = vec![
-            WalletBalances::Solana(sol_wallet, sol_balances),
-            WalletBalances::Evm(eth_wallet, eth_balances),
-        ];
-
-        let symbols = extract_token_symbols(&wallet_balances);
-        assert!(symbols.contains("SOL"));
-        assert!(symbols.contains("ETH"));
-        assert_eq!(symbols.len(), 2);
-    }
-
-    #[test]
-    fn test_add_asset_to_portfolio() {
-        let mut portfolio = PortfolioSummary {
-            companies: HashMap::new(),
-            total_usd_value: 0.0,
-        };
-
-        add_asset_to_portfolio(&mut portfolio, "TestCo", "BTC", 1.0, Some(50000.0));
-
-        assert_eq!(portfolio.companies.len(), 1);
-        assert!(portfolio.companies.contains_key("TestCo"));
-        assert_eq!(portfolio.total_usd_value, 50000.0);
-
-        let company = portfolio.companies.get("TestCo").unwrap();
-        assert_eq!(company.total_usd_value, 50000.0);
-        assert!(company.assets.contains_key("BTC"));
-
-        let btc = company.assets.get("BTC").unwrap();
-        assert_eq!(btc.amount, 1.0);
-        assert_eq!(btc.usd_value, Some(50000.0));
-    }
-
-    #[test]
-    fn test_add_asset_to_portfolio_accumulation() {
-        let mut portfolio = PortfolioSummary {
-            companies: HashMap::new(),
-            total_usd_value: 0.0,
-        };
-
-        // Add same asset twice
-        add_asset_to_portfolio(&mut portfolio, "TestCo", "BTC", 1.0, Some(50000.0));
-        add_asset_to_portfolio(&mut portfolio, "TestCo", "BTC", 0.5, Some(25000.0));
-
-        let company = portfolio.companies.get("TestCo").unwrap();
-        let btc = company.assets.get("BTC").unwrap();
-
-        assert_eq!(btc.amount, 1.5);
-        assert_eq!(btc.usd_value, Some(75000.0));
-        assert_eq!(portfolio.total_usd_value, 75000.0);
-    }
-
-    #[test]
-    fn test_add_asset_zero_balance_ignored() {
-        let mut portfolio = PortfolioSummary {
-            companies: HashMap::new(),
-            total_usd_value: 0.0,
-        };
-
-        add_asset_to_portfolio(&mut portfolio, "TestCo", "BTC", 0.0, Some(0.0));
-
-        assert_eq!(portfolio.companies.len(), 0);
-    }
-
-    #[test]
-    fn test_price_enrichable_trait() {
-        let mut balances = near::AccountBalances {
-            near_balance: 10.0,
-            near_usd_price: None,
-            near_usd_value: None,
-            token_balances: vec![],
-            total_usd_value: None,
-        };
-
-        let mut price_cache = HashMap::new();
-        price_cache.insert("NEAR".to_string(), 5.0);
-
-        balances.enrich_from_cache(&price_cache);
-
-        assert_eq!(balances.near_usd_price, Some(5.0));
-        assert_eq!(balances.near_usd_value, Some(50.0));
-        assert_eq!(balances.total_usd_value, Some(50.0));
-    }
-
-    #[test]
-    fn test_price_enrichable_no_price_available() {
-        let mut balances = near::AccountBalances {
-            near_balance: 10.0,
-            near_usd_price: None,
-            near_usd_value: None,
-            token_balances: vec![],
-            total_usd_value: None,
-        };
-
-        let price_cache = HashMap::new(); // Empty cache
-
-        balances.enrich_from_cache(&price_cache);
-
-        assert_eq!(balances.near_usd_price, None);
-        assert_eq!(balances.near_usd_value, None);
-        assert_eq!(balances.total_usd_value, None);
-    }
-
     #[test]
     fn test_storage_round_trip() {
         use std::fs;
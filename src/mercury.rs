@@ -1,6 +1,10 @@
+use crate::amount;
+use crate::config::{self, ApiKey};
+use crate::source::{AccountSnapshot, BalanceSource, Holding};
+use crate::storage::TrackedAccount;
 use anyhow::{Context, Result};
+use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
-use std::env;
 
 const MERCURY_API_BASE: &str = "https://api.mercury.com/api/v1";
 
@@ -73,8 +77,8 @@ pub struct MercuryClient {
 
 impl MercuryClient {
     pub fn new() -> Result<Self> {
-        let api_key = env::var("MERCURY_API_KEY")
-            .context("MERCURY_API_KEY environment variable not set")?;
+        let api_key = config::api_key(ApiKey::Mercury)
+            .context("MERCURY_API_KEY not set (environment or api_keys.mercury in config.toml)")?;
 
         Ok(Self {
             api_key,
@@ -156,3 +160,19 @@ impl MercuryClient {
         Ok(all_transactions)
     }
 }
+
+#[async_trait]
+impl BalanceSource for MercuryClient {
+    async fn fetch_snapshot(&self, account: &TrackedAccount) -> Result<AccountSnapshot> {
+        let balances = self.get_account_balance(account.identifier()).await?;
+
+        let mut snapshot = AccountSnapshot::new(account, vec![Holding::fiat("USD", amount::from_f64(balances.current_balance))])
+            .with_detail("Status", balances.status)
+            .with_detail("Available Balance", format!("${:.2}", balances.available_balance));
+        if let Some(created_at) = balances.created_at {
+            snapshot = snapshot.with_detail("Created", created_at);
+        }
+
+        Ok(snapshot)
+    }
+}
@@ -0,0 +1,253 @@
+use anyhow::{Context, Result};
+use bigdecimal::num_bigint::BigUint;
+
+// Minimal ABI encoding for Multicall3 `aggregate3` and the ERC-20 reads it batches.
+// Multicall3 is deployed at the same address on every supported EVM chain.
+
+pub const MULTICALL3_ADDRESS: &str = "0xcA11bde05977b3631167028862bE2a173976CA11";
+
+/// aggregate3((address target, bool allowFailure, bytes callData)[])
+const AGGREGATE3: [u8; 4] = [0x82, 0xad, 0x56, 0xcb];
+
+pub const BALANCE_OF: [u8; 4] = [0x70, 0xa0, 0x82, 0x31];
+pub const DECIMALS: [u8; 4] = [0x31, 0x3c, 0xe5, 0x67];
+pub const SYMBOL: [u8; 4] = [0x95, 0xd8, 0x9b, 0x41];
+pub const NAME: [u8; 4] = [0x06, 0xfd, 0xde, 0x03];
+
+const WORD: usize = 32;
+
+/// A read-only contract call
+#[derive(Debug, Clone, PartialEq)]
+pub struct Call {
+    pub target: String,
+    pub data: Vec<u8>,
+}
+
+impl Call {
+    /// A call without arguments, e.g. `decimals()`
+    pub fn new(target: &str, selector: [u8; 4]) -> Self {
+        Self {
+            target: target.to_string(),
+            data: selector.to_vec(),
+        }
+    }
+
+    /// A call with a single address argument, e.g. `balanceOf(owner)`
+    pub fn with_address(target: &str, selector: [u8; 4], address: &str) -> Result<Self> {
+        let mut data = selector.to_vec();
+        data.extend(address_word(address)?);
+        Ok(Self {
+            target: target.to_string(),
+            data,
+        })
+    }
+}
+
+pub fn encode_hex(bytes: &[u8]) -> String {
+    let mut hex = String::with_capacity(2 + bytes.len() * 2);
+    hex.push_str("0x");
+    for byte in bytes {
+        hex.push_str(&format!("{:02x}", byte));
+    }
+    hex
+}
+
+pub fn decode_hex(hex: &str) -> Result<Vec<u8>> {
+    let hex = hex.trim_start_matches("0x");
+    if !hex.len().is_multiple_of(2) {
+        anyhow::bail!("Odd-length hex string");
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).context("Invalid hex string"))
+        .collect()
+}
+
+fn address_word(address: &str) -> Result<[u8; WORD]> {
+    let bytes = decode_hex(address)?;
+    if bytes.len() != 20 {
+        anyhow::bail!("Invalid EVM address: {}", address);
+    }
+    let mut word = [0u8; WORD];
+    word[12..].copy_from_slice(&bytes);
+    Ok(word)
+}
+
+fn uint_word(value: usize) -> [u8; WORD] {
+    let mut word = [0u8; WORD];
+    word[WORD - 8..].copy_from_slice(&(value as u64).to_be_bytes());
+    word
+}
+
+fn padded_len(len: usize) -> usize {
+    len.div_ceil(WORD) * WORD
+}
+
+/// Calldata for `aggregate3` with every call allowed to fail on its own
+pub fn encode_aggregate3(calls: &[Call]) -> Result<Vec<u8>> {
+    let mut data = AGGREGATE3.to_vec();
+    data.extend(uint_word(WORD)); // offset of the array argument
+    data.extend(uint_word(calls.len()));
+
+    // Each element is a dynamic tuple: its offset (relative to the start of the
+    // offsets) comes first, then the tuples themselves
+    let mut offset = calls.len() * WORD;
+    for call in calls {
+        data.extend(uint_word(offset));
+        offset += 4 * WORD + padded_len(call.data.len());
+    }
+
+    for call in calls {
+        data.extend(address_word(&call.target)?);
+        data.extend(uint_word(1)); // allowFailure
+        data.extend(uint_word(3 * WORD)); // offset of callData within the tuple
+        data.extend(uint_word(call.data.len()));
+        data.extend(&call.data);
+        data.resize(data.len() + padded_len(call.data.len()) - call.data.len(), 0);
+    }
+
+    Ok(data)
+}
+
+/// `base + offset`, where either may come from untrusted ABI data
+fn add_offset(base: usize, offset: usize) -> Result<usize> {
+    base.checked_add(offset).context("ABI offset out of range")
+}
+
+fn read_word(data: &[u8], at: usize) -> Result<&[u8]> {
+    data.get(at..add_offset(at, WORD)?).context("Truncated ABI data")
+}
+
+fn read_usize(data: &[u8], at: usize) -> Result<usize> {
+    let word = read_word(data, at)?;
+    if word[..WORD - 8].iter().any(|b| *b != 0) {
+        anyhow::bail!("ABI offset out of range");
+    }
+    usize::try_from(u64::from_be_bytes(word[WORD - 8..].try_into()?)).context("ABI offset out of range")
+}
+
+fn read_bytes(data: &[u8], at: usize) -> Result<&[u8]> {
+    let len = read_usize(data, at)?;
+    let start = add_offset(at, WORD)?;
+    data.get(start..add_offset(start, len)?).context("Truncated ABI data")
+}
+
+/// Decode the `(bool success, bytes returnData)[]` result of `aggregate3`:
+/// the return data of every call, or None for calls that reverted
+pub fn decode_aggregate3(data: &[u8]) -> Result<Vec<Option<Vec<u8>>>> {
+    let array = read_usize(data, 0)?;
+    let count = read_usize(data, array)?;
+    let base = add_offset(array, WORD)?;
+
+    (0..count)
+        .map(|i| {
+            let slot = i.checked_mul(WORD).context("ABI offset out of range")?;
+            let element = add_offset(base, read_usize(data, add_offset(base, slot)?)?)?;
+            let success = read_word(data, element)?.iter().any(|b| *b != 0);
+            let return_data = read_bytes(data, add_offset(element, read_usize(data, add_offset(element, WORD)?)?)?)?;
+            Ok(success.then(|| return_data.to_vec()))
+        })
+        .collect()
+}
+
+/// A `uint256` return value, or None when it is empty
+pub fn decode_uint(data: &[u8]) -> Option<BigUint> {
+    data.get(..WORD).map(BigUint::from_bytes_be)
+}
+
+/// A `string` return value. Some older tokens (MKR, SAI) return `bytes32`
+/// instead, which is decoded as a NUL-padded string.
+pub fn decode_string(data: &[u8]) -> Option<String> {
+    let bytes = if data.len() == WORD {
+        data
+    } else {
+        read_bytes(data, read_usize(data, 0).ok()?).ok()?
+    };
+
+    let text = String::from_utf8_lossy(bytes).trim_end_matches('\0').trim().to_string();
+    (!text.is_empty()).then_some(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOKEN: &str = "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48";
+    const WALLET: &str = "0x52908400098527886E0F7030069857D2E4169EE7";
+
+    #[test]
+    fn test_encode_aggregate3() {
+        let calls = vec![
+            Call::with_address(TOKEN, BALANCE_OF, WALLET).unwrap(),
+            Call::new(TOKEN, DECIMALS),
+        ];
+        let data = encode_aggregate3(&calls).unwrap();
+
+        assert_eq!(&data[..4], &AGGREGATE3);
+        let args = &data[4..];
+        assert_eq!(read_usize(args, 0).unwrap(), 32);
+        assert_eq!(read_usize(args, 32).unwrap(), 2);
+        // First tuple right after the two offsets; balanceOf calldata is 36
+        // bytes, padded to 64, so the second tuple starts 4 + 2 words later
+        assert_eq!(read_usize(args, 64).unwrap(), 64);
+        assert_eq!(read_usize(args, 96).unwrap(), 64 + 128 + 64);
+
+        let first = 64 + 64;
+        assert_eq!(encode_hex(&args[first + 12..first + 32]), TOKEN.to_lowercase());
+        assert_eq!(read_usize(args, first + 32).unwrap(), 1);
+        assert_eq!(read_bytes(args, first + 96).unwrap(), calls[0].data.as_slice());
+        assert_eq!(args.len() % 32, 0);
+    }
+
+    #[test]
+    fn test_decode_aggregate3() {
+        // Two results: success with a uint256, failure with empty data
+        let mut data = Vec::new();
+        data.extend(uint_word(32));
+        data.extend(uint_word(2));
+        data.extend(uint_word(64));
+        data.extend(uint_word(64 + 128));
+        data.extend(uint_word(1));
+        data.extend(uint_word(64));
+        data.extend(uint_word(32));
+        data.extend(uint_word(1_500_000));
+        data.extend(uint_word(0));
+        data.extend(uint_word(64));
+        data.extend(uint_word(0));
+
+        let results = decode_aggregate3(&data).unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(decode_uint(results[0].as_ref().unwrap()), Some(BigUint::from(1_500_000u32)));
+        assert_eq!(results[1], None);
+
+        assert!(decode_aggregate3(&data[..100]).is_err());
+
+        // Offsets that would overflow are rejected instead of wrapping
+        let mut huge = vec![0; 24];
+        huge.extend([0xff; 8]);
+        for offset in [vec![0xff; 32], huge] {
+            let mut element = data.clone();
+            element[64..96].copy_from_slice(&offset);
+            let error = decode_aggregate3(&element).unwrap_err();
+            assert_eq!(error.to_string(), "ABI offset out of range");
+            assert!(read_bytes(&offset, 0).is_err());
+        }
+        assert_eq!(decode_uint(&[0xff; 32]).map(|v| v.bits()), Some(256));
+    }
+
+    #[test]
+    fn test_decode_string() {
+        let mut abi = Vec::new();
+        abi.extend(uint_word(32));
+        abi.extend(uint_word(4));
+        abi.extend(b"USDC");
+        abi.resize(96, 0);
+        assert_eq!(decode_string(&abi).as_deref(), Some("USDC"));
+
+        let mut bytes32 = b"MKR".to_vec();
+        bytes32.resize(32, 0);
+        assert_eq!(decode_string(&bytes32).as_deref(), Some("MKR"));
+
+        assert_eq!(decode_string(&[]), None);
+    }
+}
@@ -1,31 +1,110 @@
+use crate::amount::TokenAmount;
+use crate::rpc::{self, HttpPool, RpcEndpoint};
+use crate::source::{AccountSnapshot, BalanceSource, Holding};
+use crate::storage::{Chain, TrackedAccount};
+use crate::token_cache::{self, TokenMetadata};
+use crate::token_list::{self, TokenList};
 use anyhow::{Context, Result};
+use async_trait::async_trait;
+use base64::prelude::*;
+use bigdecimal::Zero;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
+use std::collections::BTreeSet;
+
+/// yoctoNEAR an account must keep locked per byte of storage it uses
+const STORAGE_PRICE_PER_BYTE: u128 = 10_000_000_000_000_000_000;
+
+/// FastNEAR account index, used to discover the tokens and staking pools an
+/// account has interacted with
+const FASTNEAR_API_BASE: &str = "https://api.fastnear.com/v1/account";
+
+/// NEP-141 contracts checked for every account
+const COMMON_TOKENS: &[(&str, &str)] = &[
+    ("17208628f84f5d6ad33f0da3bbbeb27ffcb398eac501a31bd6ad2011e36133a1", "USDC"),
+    ("usdt.tether-token.near", "USDT"),
+    ("wrap.near", "wNEAR"),
+];
 
 #[derive(Debug)]
-#[allow(dead_code)]
 pub struct TokenBalance {
     pub contract_address: String,
+    pub name: Option<String>,
     pub symbol: Option<String>,
-    pub decimals: u8,
-    pub ui_amount: f64,
-    pub usd_price: Option<f64>,
-    pub usd_value: Option<f64>,
+    pub amount: TokenAmount,
+}
+
+/// NEAR deposited with a staking pool, in yoctoNEAR
+#[derive(Debug, Default, PartialEq)]
+pub struct StakingDeposit {
+    pub pool: String,
+    pub staked: u128,
+    /// Unstaked but still held by the pool until it can be withdrawn
+    pub unstaked: u128,
+}
+
+impl StakingDeposit {
+    pub fn total(&self) -> u128 {
+        self.staked + self.unstaked
+    }
 }
 
 #[derive(Debug)]
 pub struct AccountBalances {
-    pub near_balance: f64,
-    pub near_usd_price: Option<f64>,
-    pub near_usd_value: Option<f64>,
-    #[allow(dead_code)]
+    /// Includes the storage-locked part, which the account can't spend
+    pub near_balance: TokenAmount,
+    pub storage_locked: TokenAmount,
+    /// Locked as validator stake on top of `near_balance`
+    pub validator_locked: TokenAmount,
+    pub staking_deposits: Vec<StakingDeposit>,
     pub token_balances: Vec<TokenBalance>,
-    pub total_usd_value: Option<f64>,
+}
+
+/// Token list entries are account ids, which NEAR compares in lowercase
+fn normalize_account(account: &str) -> Result<String> {
+    if account.is_empty() {
+        anyhow::bail!("Empty account id");
+    }
+    Ok(account.to_lowercase())
+}
+
+#[derive(Deserialize)]
+struct ViewAccount {
+    amount: String,
+    locked: String,
+    storage_usage: u64,
+}
+
+#[derive(Deserialize)]
+struct FtMetadata {
+    name: Option<String>,
+    symbol: Option<String>,
+    decimals: u8,
+}
+
+/// The parts of FastNEAR's `/v1/account/<id>/full` response used for discovery
+#[derive(Deserialize)]
+struct IndexedAccount {
+    #[serde(default)]
+    tokens: Vec<IndexedToken>,
+    #[serde(default)]
+    pools: Vec<IndexedPool>,
+}
+
+#[derive(Deserialize)]
+struct IndexedToken {
+    contract_id: String,
+}
+
+#[derive(Deserialize)]
+struct IndexedPool {
+    pool_id: String,
 }
 
 pub struct NearClient {
+    rpc: HttpPool,
     client: reqwest::Client,
-    rpc_url: String,
 }
 
 #[derive(Serialize)]
@@ -48,16 +127,10 @@ struct JsonRpcError {
 }
 
 impl NearClient {
-    pub fn new(rpc_url: Option<String>) -> Self {
-        let url = rpc_url.unwrap_or_else(|| "https://rpc.mainnet.near.org".to_string());
-        let client = reqwest::Client::builder()
-            .timeout(std::time::Duration::from_secs(30))
-            .build()
-            .unwrap_or_else(|_| reqwest::Client::new());
-
+    pub fn new(endpoints: Vec<RpcEndpoint>) -> Self {
         Self {
-            client,
-            rpc_url: url,
+            rpc: HttpPool::http(endpoints),
+            client: reqwest::Client::new(),
         }
     }
 
@@ -69,16 +142,9 @@ impl NearClient {
             id: "dontcare".to_string(),
         };
 
-        let response = self
-            .client
-            .post(&self.rpc_url)
-            .json(&request)
-            .send()
-            .await
-            .context("Failed to send RPC request")?;
+        let response = self.rpc.post("", &request).await?;
 
-        let rpc_response: JsonRpcResponse = response
-            .json()
+        let rpc_response: JsonRpcResponse = rpc::json_body(response)
             .await
             .context("Failed to parse RPC response")?;
 
@@ -91,6 +157,104 @@ impl NearClient {
             .ok_or_else(|| anyhow::anyhow!("No result in RPC response"))
     }
 
+    /// Call a view method of a contract with JSON arguments and parse its JSON result
+    async fn call_function<T: DeserializeOwned>(&self, contract: &str, method: &str, args: serde_json::Value) -> Result<T> {
+        let result = self
+            .rpc_call(
+                "query",
+                json!({
+                    "request_type": "call_function",
+                    "finality": "final",
+                    "account_id": contract,
+                    "method_name": method,
+                    "args_base64": BASE64_STANDARD.encode(args.to_string())
+                }),
+            )
+            .await?;
+
+        // Contract panics come back as a successful response with an error field
+        if let Some(error) = result.get("error").and_then(|v| v.as_str()) {
+            anyhow::bail!("{}.{} failed: {}", contract, method, error);
+        }
+
+        let bytes: Vec<u8> = serde_json::from_value(result["result"].clone())
+            .with_context(|| format!("Invalid {}.{} result", contract, method))?;
+        serde_json::from_slice(&bytes).with_context(|| format!("Failed to parse {}.{} result", contract, method))
+    }
+
+    /// Token contracts and staking pools the account has interacted with
+    async fn discover(&self, address: &str) -> Result<IndexedAccount> {
+        let url = format!("{}/{}/full", FASTNEAR_API_BASE, urlencoding::encode(address));
+
+        self.client
+            .get(&url)
+            .send()
+            .await
+            .context("Failed to send request to FastNEAR API")?
+            .error_for_status()
+            .context("FastNEAR API error")?
+            .json()
+            .await
+            .context("Failed to parse FastNEAR API response")
+    }
+
+    /// A yoctoNEAR amount returned as a JSON string by a view method
+    async fn yocto_view(&self, contract: &str, method: &str, args: serde_json::Value) -> Result<u128> {
+        let amount: String = self.call_function(contract, method, args).await?;
+        amount.parse().with_context(|| format!("Invalid {}.{} amount", contract, method))
+    }
+
+    async fn staking_deposit(&self, pool: &str, address: &str) -> Result<StakingDeposit> {
+        let args = json!({ "account_id": address });
+        let (staked, unstaked) = futures::try_join!(
+            self.yocto_view(pool, "get_account_staked_balance", args.clone()),
+            self.yocto_view(pool, "get_account_unstaked_balance", args.clone()),
+        )?;
+
+        Ok(StakingDeposit {
+            pool: pool.to_string(),
+            staked,
+            unstaked,
+        })
+    }
+
+    /// Metadata for each token: from the token list, the cache, or `ft_metadata`
+    async fn token_metadata(&self, contract: &str, token_list: &TokenList) -> Result<TokenMetadata> {
+        if let Some(metadata) = token_list.metadata(contract) {
+            return Ok(metadata);
+        }
+        if let Some(metadata) = token_cache::with_cache(|cache| cache.metadata(&Chain::Near, contract).cloned()) {
+            return Ok(metadata);
+        }
+
+        let metadata: FtMetadata = self.call_function(contract, "ft_metadata", json!({})).await?;
+        let metadata = TokenMetadata {
+            decimals: metadata.decimals,
+            symbol: metadata.symbol,
+            name: metadata.name,
+        };
+        token_cache::with_cache(|cache| cache.insert_metadata(&Chain::Near, contract, metadata.clone()));
+        Ok(metadata)
+    }
+
+    async fn token_balance(&self, contract: &str, address: &str, token_list: &TokenList) -> Result<Option<TokenBalance>> {
+        let balance: String = self
+            .call_function(contract, "ft_balance_of", json!({ "account_id": address }))
+            .await?;
+        let amount: u128 = balance.parse().with_context(|| format!("Invalid {} balance", contract))?;
+        if amount == 0 {
+            return Ok(None);
+        }
+
+        let metadata = self.token_metadata(contract, token_list).await?;
+        Ok(Some(TokenBalance {
+            contract_address: contract.to_string(),
+            name: metadata.name,
+            symbol: metadata.symbol,
+            amount: TokenAmount::new(amount, metadata.decimals),
+        }))
+    }
+
     pub async fn get_balances(&self, address: &str) -> Result<AccountBalances> {
         // Query account info to get NEAR balance
         let result = self
@@ -103,53 +267,84 @@ impl NearClient {
                 }),
             )
             .await?;
+        let account: ViewAccount = serde_json::from_value(result).context("Invalid account format")?;
 
         // Parse balance from yoctoNEAR (10^24 yoctoNEAR = 1 NEAR)
-        let balance_str = result
-            .get("amount")
-            .and_then(|v| v.as_str())
-            .ok_or_else(|| anyhow::anyhow!("Invalid balance format"))?;
-
-        let balance_yocto: u128 = balance_str
-            .parse()
+        let near_balance = TokenAmount::from_raw_str(&account.amount, 24)
             .context("Failed to parse NEAR balance")?;
+        let validator_locked = TokenAmount::from_raw_str(&account.locked, 24)
+            .context("Failed to parse locked NEAR balance")?;
+        let storage_locked = TokenAmount::new(u128::from(account.storage_usage) * STORAGE_PRICE_PER_BYTE, 24);
+
+        // Tokens and pools to check: the common tokens, the user's token list
+        // and whatever the account index knows about
+        let token_list = token_list::load(&Chain::Near, normalize_account).unwrap_or_else(|e| {
+            eprintln!("Warning: Ignoring NEAR token list: {:#}", e);
+            TokenList::default()
+        });
+        let mut tokens: BTreeSet<String> = COMMON_TOKENS.iter().map(|(contract, _)| contract.to_string()).collect();
+        tokens.extend(token_list.tokens.iter().map(|token| token.address.clone()));
+        let mut pools: BTreeSet<String> = token_list.staking_pools.iter().cloned().collect();
+
+        match self.discover(address).await {
+            Ok(indexed) => {
+                tokens.extend(indexed.tokens.into_iter().map(|token| token.contract_id.to_lowercase()));
+                pools.extend(indexed.pools.into_iter().map(|pool| pool.pool_id.to_lowercase()));
+            }
+            Err(e) => eprintln!("Warning: Token discovery failed on NEAR: {:#}", e),
+        }
 
-        // Convert yoctoNEAR to NEAR (1 NEAR = 10^24 yoctoNEAR)
-        let near_balance = balance_yocto as f64 / 1_000_000_000_000_000_000_000_000.0;
+        let token_results =
+            futures::future::join_all(tokens.iter().map(|contract| self.token_balance(contract, address, &token_list))).await;
+        let mut token_balances = Vec::new();
+        for (contract, result) in tokens.iter().zip(token_results) {
+            match result {
+                Ok(Some(balance)) => token_balances.push(balance),
+                Ok(None) => {}
+                Err(e) => eprintln!("Warning: Failed to query token {} balance: {:#}", contract, e),
+            }
+        }
+        token_cache::save();
 
-        // Token balances for NEAR (NEP-141 tokens) would require additional contract calls
-        // For now, we'll just return the native NEAR balance
-        let token_balances = Vec::new();
+        let deposit_results =
+            futures::future::join_all(pools.iter().map(|pool| self.staking_deposit(pool, address))).await;
+        let mut staking_deposits = Vec::new();
+        for (pool, result) in pools.iter().zip(deposit_results) {
+            match result {
+                Ok(deposit) if deposit.total() > 0 => staking_deposits.push(deposit),
+                Ok(_) => {}
+                Err(e) => eprintln!("Warning: Failed to query staking pool {}: {:#}", pool, e),
+            }
+        }
 
         Ok(AccountBalances {
             near_balance,
-            near_usd_price: None,
-            near_usd_value: None,
+            storage_locked,
+            validator_locked,
+            staking_deposits,
             token_balances,
-            total_usd_value: None,
         })
     }
 }
 
-// Implement PriceEnrichable trait for NEAR balances
-impl crate::PriceEnrichable for AccountBalances {
-    const NATIVE_SYMBOL: &'static str = "NEAR";
-
-    fn native_balance(&self) -> f64 {
-        self.near_balance
-    }
-
-    fn set_native_usd_price(&mut self, price: f64) {
-        self.near_usd_price = Some(price);
-    }
+#[async_trait]
+impl BalanceSource for NearClient {
+    async fn fetch_snapshot(&self, account: &TrackedAccount) -> Result<AccountSnapshot> {
+        let balances = self.get_balances(account.identifier()).await?;
 
-    fn set_native_usd_value(&mut self, value: f64) {
-        self.near_usd_value = Some(value);
-    }
+        let mut holdings = vec![Holding::native("NEAR", balances.near_balance)];
+        if !balances.validator_locked.raw().is_zero() {
+            holdings.push(Holding::staked("NEAR", account.identifier().to_string(), balances.validator_locked));
+        }
+        holdings.extend(balances.staking_deposits.iter().map(|deposit| {
+            Holding::staked("NEAR", deposit.pool.clone(), TokenAmount::new(deposit.total(), 24))
+        }));
+        holdings.extend(balances.token_balances.into_iter().map(|token| {
+            Holding::token(token.contract_address, token.symbol, token.name, token.amount)
+        }));
 
-    fn set_total_usd_value(&mut self, value: f64) {
-        self.total_usd_value = Some(value);
+        Ok(AccountSnapshot::new(account, holdings)
+            .with_detail("Storage Locked", format!("{} NEAR", balances.storage_locked))
+            .with_detail("RPC Endpoint", self.rpc.served_by()))
     }
-
-    // NEAR doesn't have token balances yet, use default implementation
 }
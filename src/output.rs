@@ -0,0 +1,559 @@
+use crate::amount::{self, TokenAmount};
+use crate::bitcoin::Utxo;
+use crate::portfolio::PortfolioSummary;
+use crate::source::{AccountSnapshot, WalletTransaction};
+use crate::storage::TrackedAccount;
+
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use utoipa::ToSchema;
+
+// Machine-readable result types shared by `--output json|csv` and the JSON API.
+// Field names are part of the public schema: add fields, never rename them.
+// Amounts and USD values are exact decimal strings, never floats.
+
+/// How command results are printed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Table,
+    Json,
+    Csv,
+}
+
+impl OutputFormat {
+    pub fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "table" => Ok(OutputFormat::Table),
+            "json" => Ok(OutputFormat::Json),
+            "csv" => Ok(OutputFormat::Csv),
+            _ => anyhow::bail!("Unknown output format: {}. Use table, json or csv", s),
+        }
+    }
+
+    pub fn is_table(&self) -> bool {
+        *self == OutputFormat::Table
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum AccountKind {
+    Wallet,
+    Bank,
+}
+
+/// A tracked wallet address or banking account
+#[derive(Serialize, ToSchema)]
+pub struct AccountDto {
+    pub name: String,
+    pub company: String,
+    pub kind: AccountKind,
+    /// Wallet address or banking account id
+    pub identifier: String,
+    /// Chain or banking service
+    pub source: String,
+}
+
+impl From<&TrackedAccount> for AccountDto {
+    fn from(account: &TrackedAccount) -> Self {
+        Self {
+            name: account.name().to_string(),
+            company: account.company().to_string(),
+            kind: match account {
+                TrackedAccount::Wallet(_) => AccountKind::Wallet,
+                TrackedAccount::Bank(_) => AccountKind::Bank,
+            },
+            identifier: account.identifier().to_string(),
+            source: account.source_name().to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct HoldingDto {
+    /// native, token, staked or fiat
+    pub kind: String,
+    pub symbol: Option<String>,
+    pub name: Option<String>,
+    /// Mint, contract address or coin type (tokens only)
+    pub asset_id: Option<String>,
+    pub decimals: u8,
+    /// Balance in whole units, e.g. "1.5"
+    pub amount: String,
+    /// Balance as the integer held on-chain, in the smallest unit
+    pub raw_amount: String,
+    pub usd_price: Option<String>,
+    pub usd_value: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct AccountBalanceDto {
+    pub account: AccountDto,
+    pub holdings: Vec<HoldingDto>,
+    /// Source-specific details such as account status
+    pub details: BTreeMap<String, String>,
+    pub total_usd_value: Option<String>,
+}
+
+impl From<&AccountSnapshot> for AccountBalanceDto {
+    fn from(snapshot: &AccountSnapshot) -> Self {
+        Self {
+            account: AccountDto::from(&snapshot.account),
+            holdings: snapshot
+                .holdings
+                .iter()
+                .map(|h| HoldingDto {
+                    kind: h.kind.as_str().to_string(),
+                    symbol: h.symbol.clone(),
+                    name: h.name.clone(),
+                    asset_id: h.asset_id.clone(),
+                    decimals: h.decimals(),
+                    amount: h.amount.to_string(),
+                    raw_amount: h.amount.raw().to_string(),
+                    usd_price: h.usd_price.as_ref().map(amount::plain),
+                    usd_value: h.usd_value.as_ref().map(amount::plain),
+                })
+                .collect(),
+            details: snapshot.details.iter().cloned().collect(),
+            total_usd_value: snapshot.total_usd_value.as_ref().map(amount::plain),
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct AssetTotalDto {
+    pub symbol: String,
+    pub amount: String,
+    pub usd_value: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CompanyTotalDto {
+    pub company: String,
+    pub total_usd_value: String,
+    pub assets: Vec<AssetTotalDto>,
+}
+
+/// Per-company totals sorted by company name, assets by USD value (largest first)
+pub fn company_totals(portfolio: PortfolioSummary) -> Vec<CompanyTotalDto> {
+    let mut companies: Vec<CompanyTotalDto> = portfolio
+        .companies
+        .into_iter()
+        .map(|(company, company_assets)| {
+            let mut assets: Vec<_> = company_assets.assets.into_values().collect();
+            assets.sort_by(|a, b| b.usd_value.cmp(&a.usd_value));
+
+            CompanyTotalDto {
+                company,
+                total_usd_value: amount::plain(&company_assets.total_usd_value),
+                assets: assets
+                    .into_iter()
+                    .map(|a| AssetTotalDto {
+                        symbol: a.symbol,
+                        amount: amount::plain(&a.amount),
+                        usd_value: a.usd_value.as_ref().map(amount::plain),
+                    })
+                    .collect(),
+            }
+        })
+        .collect();
+    companies.sort_by(|a, b| a.company.cmp(&b.company));
+    companies
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct AccountErrorDto {
+    pub account: AccountDto,
+    pub error: String,
+}
+
+/// Full portfolio: per-company totals, per-account balances and the prices used
+#[derive(Serialize, ToSchema)]
+pub struct PortfolioDto {
+    pub total_usd_value: String,
+    pub companies: Vec<CompanyTotalDto>,
+    pub accounts: Vec<AccountBalanceDto>,
+    /// Symbol -> USD price used to value the holdings
+    pub prices: BTreeMap<String, f64>,
+    /// Accounts that could not be queried
+    pub errors: Vec<AccountErrorDto>,
+}
+
+impl PortfolioDto {
+    /// Build the report from priced snapshots and the accounts that failed
+    pub fn new(
+        snapshots: &[AccountSnapshot],
+        prices: &HashMap<String, f64>,
+        failures: &[(TrackedAccount, String)],
+    ) -> Self {
+        let portfolio = PortfolioSummary::from_snapshots(snapshots);
+
+        Self {
+            total_usd_value: amount::plain(&portfolio.total_usd_value),
+            companies: company_totals(portfolio),
+            accounts: snapshots.iter().map(AccountBalanceDto::from).collect(),
+            prices: prices.iter().map(|(symbol, price)| (symbol.clone(), *price)).collect(),
+            errors: failures
+                .iter()
+                .map(|(account, error)| AccountErrorDto {
+                    account: AccountDto::from(account),
+                    error: error.clone(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// How a wallet's balance of one asset changed in a transaction
+#[derive(Serialize, ToSchema)]
+pub struct BalanceChangeDto {
+    pub symbol: Option<String>,
+    /// Mint or contract address (tokens only)
+    pub asset_id: Option<String>,
+    pub decimals: u8,
+    /// Signed change in whole units, negative when sent
+    pub amount: String,
+    pub raw_amount: String,
+}
+
+/// An on-chain transaction of a tracked wallet, as exported by `export-transactions`
+#[derive(Serialize, ToSchema)]
+pub struct WalletTransactionDto {
+    /// Transaction signature or hash
+    pub id: String,
+    /// RFC 3339 block time, when known
+    pub date: Option<String>,
+    /// Slot or block number
+    pub block: u64,
+    /// completed or failed
+    pub status: String,
+    pub memo: Option<String>,
+    /// Fee paid by the wallet in the native token; changes don't include it
+    pub fee: Option<String>,
+    pub changes: Vec<BalanceChangeDto>,
+    /// Addresses whose balances moved the other way
+    pub counterparties: Vec<String>,
+}
+
+impl From<&WalletTransaction> for WalletTransactionDto {
+    fn from(tx: &WalletTransaction) -> Self {
+        Self {
+            id: tx.id.clone(),
+            date: tx
+                .timestamp
+                .and_then(|ts| chrono::DateTime::from_timestamp(ts, 0))
+                .map(|dt| dt.to_rfc3339()),
+            block: tx.block,
+            status: if tx.success { "completed" } else { "failed" }.to_string(),
+            memo: tx.memo.clone(),
+            fee: tx.fee.as_ref().map(|fee| fee.to_string()),
+            changes: tx
+                .changes
+                .iter()
+                .map(|change| BalanceChangeDto {
+                    symbol: change.symbol.clone(),
+                    asset_id: change.asset_id.clone(),
+                    decimals: change.amount.decimals(),
+                    amount: change.amount.to_string(),
+                    raw_amount: change.amount.raw().to_string(),
+                })
+                .collect(),
+            counterparties: tx.counterparties.clone(),
+        }
+    }
+}
+
+/// An unspent output of a tracked Bitcoin wallet
+#[derive(Serialize, ToSchema)]
+pub struct UtxoDto {
+    pub txid: String,
+    pub vout: u32,
+    pub address: String,
+    /// BTC
+    pub amount: String,
+    /// Satoshis
+    pub raw_amount: String,
+    /// None while unconfirmed
+    pub block_height: Option<u64>,
+}
+
+impl From<&Utxo> for UtxoDto {
+    fn from(utxo: &Utxo) -> Self {
+        let amount = TokenAmount::new(utxo.value, 8);
+        Self {
+            txid: utxo.txid.clone(),
+            vout: utxo.vout,
+            address: utxo.address.clone(),
+            amount: amount.to_string(),
+            raw_amount: amount.raw().to_string(),
+            block_height: utxo.block_height,
+        }
+    }
+}
+
+// ==================== Printing ====================
+
+pub fn print_json<T: Serialize>(value: &T) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+pub fn escape_csv(s: &str) -> String {
+    if s.contains(',') || s.contains('"') || s.contains('\n') {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+const ACCOUNTS_CSV_HEADER: &str = "name,company,kind,identifier,source";
+
+/// One row per tracked account
+pub fn accounts_csv(accounts: &[AccountDto]) -> String {
+    let mut csv = format!("{}\n", ACCOUNTS_CSV_HEADER);
+    for account in accounts {
+        let kind = match account.kind {
+            AccountKind::Wallet => "wallet",
+            AccountKind::Bank => "bank",
+        };
+        csv.push_str(&format!(
+            "{},{},{},{},{}\n",
+            escape_csv(&account.name),
+            escape_csv(&account.company),
+            kind,
+            escape_csv(&account.identifier),
+            escape_csv(&account.source),
+        ));
+    }
+    csv
+}
+
+const BALANCES_CSV_HEADER: &str =
+    "record,company,account,source,identifier,kind,symbol,name,asset_id,decimals,amount,usd_price,usd_value,raw_amount";
+
+/// A single CSV table for balances. The `record` column tells rows apart:
+/// `holding` rows carry one asset of one account, `company_asset` and
+/// `company_total` rows carry the per-company summary, and one
+/// `portfolio_total` row closes a full query.
+pub fn balances_csv(accounts: &[AccountBalanceDto], companies: &[CompanyTotalDto], total_usd_value: Option<&str>) -> String {
+    let mut csv = format!("{}\n", BALANCES_CSV_HEADER);
+
+    for balance in accounts {
+        let account = &balance.account;
+        for holding in &balance.holdings {
+            csv.push_str(&format!(
+                "holding,{},{},{},{},{},{},{},{},{},{},{},{},{}\n",
+                escape_csv(&account.company),
+                escape_csv(&account.name),
+                escape_csv(&account.source),
+                escape_csv(&account.identifier),
+                holding.kind,
+                escape_csv(holding.symbol.as_deref().unwrap_or("")),
+                escape_csv(holding.name.as_deref().unwrap_or("")),
+                escape_csv(holding.asset_id.as_deref().unwrap_or("")),
+                holding.decimals,
+                holding.amount,
+                holding.usd_price.as_deref().unwrap_or(""),
+                holding.usd_value.as_deref().unwrap_or(""),
+                holding.raw_amount,
+            ));
+        }
+    }
+
+    for company in companies {
+        for asset in &company.assets {
+            csv.push_str(&format!(
+                "company_asset,{},,,,,{},,,,{},,{},\n",
+                escape_csv(&company.company),
+                escape_csv(&asset.symbol),
+                asset.amount,
+                asset.usd_value.as_deref().unwrap_or(""),
+            ));
+        }
+        csv.push_str(&format!(
+            "company_total,{},,,,,,,,,,,{},\n",
+            escape_csv(&company.company),
+            company.total_usd_value
+        ));
+    }
+
+    if let Some(total) = total_usd_value {
+        csv.push_str(&format!("portfolio_total,,,,,,,,,,,,{},\n", total));
+    }
+
+    csv
+}
+
+const TRANSACTIONS_CSV_HEADER: &str = "date,id,block,status,symbol,asset_id,amount,raw_amount,fee,counterparties,memo";
+
+/// One row per asset whose balance changed, so amounts can be summed per
+/// asset. The fee is on the first row of its transaction only; a
+/// transaction that changed no balance gets a single row for its fee.
+/// Counterparties are separated by spaces.
+pub fn transactions_csv(transactions: &[WalletTransactionDto]) -> String {
+    let mut csv = format!("{}\n", TRANSACTIONS_CSV_HEADER);
+
+    for tx in transactions {
+        let changes: Vec<Option<&BalanceChangeDto>> = if tx.changes.is_empty() {
+            vec![None]
+        } else {
+            tx.changes.iter().map(Some).collect()
+        };
+
+        for (i, change) in changes.into_iter().enumerate() {
+            let fee = if i == 0 { tx.fee.as_deref() } else { None };
+            csv.push_str(&format!(
+                "{},{},{},{},{},{},{},{},{},{},{}\n",
+                tx.date.as_deref().unwrap_or(""),
+                tx.id,
+                tx.block,
+                tx.status,
+                escape_csv(change.and_then(|c| c.symbol.as_deref()).unwrap_or("")),
+                change.and_then(|c| c.asset_id.as_deref()).unwrap_or(""),
+                change.map(|c| c.amount.as_str()).unwrap_or(""),
+                change.map(|c| c.raw_amount.as_str()).unwrap_or(""),
+                fee.unwrap_or(""),
+                tx.counterparties.join(" "),
+                escape_csv(tx.memo.as_deref().unwrap_or("")),
+            ));
+        }
+    }
+
+    csv
+}
+
+const UTXOS_CSV_HEADER: &str = "txid,vout,address,amount,raw_amount,block_height";
+
+pub fn utxos_csv(utxos: &[UtxoDto]) -> String {
+    let mut csv = format!("{}\n", UTXOS_CSV_HEADER);
+    for utxo in utxos {
+        csv.push_str(&format!(
+            "{},{},{},{},{},{}\n",
+            utxo.txid,
+            utxo.vout,
+            utxo.address,
+            utxo.amount,
+            utxo.raw_amount,
+            utxo.block_height.map(|height| height.to_string()).unwrap_or_default(),
+        ));
+    }
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::portfolio::add_asset_to_portfolio;
+    use crate::source::Holding;
+    use bigdecimal::BigDecimal;
+    use crate::storage::{Chain, WalletAddress};
+
+    #[test]
+    fn test_company_totals_sorted() {
+        let mut portfolio = PortfolioSummary::default();
+        add_asset_to_portfolio(&mut portfolio, "Globex", "SOL", BigDecimal::from(1), Some(BigDecimal::from(100)));
+        add_asset_to_portfolio(&mut portfolio, "Acme", "USDC", BigDecimal::from(50), Some(BigDecimal::from(50)));
+        add_asset_to_portfolio(&mut portfolio, "Acme", "ETH", BigDecimal::from(1), Some(BigDecimal::from(3000)));
+
+        let companies = company_totals(portfolio);
+
+        assert_eq!(companies[0].company, "Acme");
+        assert_eq!(companies[0].total_usd_value, "3050");
+        assert_eq!(companies[0].assets[0].symbol, "ETH");
+        assert_eq!(companies[1].company, "Globex");
+    }
+
+    #[test]
+    fn test_balances_csv_rows() {
+        let account = TrackedAccount::Wallet(WalletAddress {
+            company: "Acme".to_string(),
+            name: "Hot".to_string(),
+            address: "So1ana".to_string(),
+            chain: Chain::Solana,
+        });
+        let mut snapshot = AccountSnapshot::new(
+            &account,
+            vec![
+                Holding::native("SOL", TokenAmount::new(2_000_000_000u64, 9)),
+                Holding::token("EPjFW".to_string(), Some("USDC".to_string()), None, TokenAmount::new(10_000_000u64, 6)),
+            ],
+        );
+        snapshot.enrich_from_cache(&HashMap::from([("SOL".to_string(), 150.0), ("USDC".to_string(), 1.0)]));
+
+        let report = PortfolioDto::new(&[snapshot], &HashMap::new(), &[]);
+        let csv = balances_csv(&report.accounts, &report.companies, Some(&report.total_usd_value));
+        let lines: Vec<&str> = csv.lines().collect();
+
+        assert_eq!(lines[0], BALANCES_CSV_HEADER);
+        assert_eq!(lines[1], "holding,Acme,Hot,Solana,So1ana,native,SOL,,,9,2,150,300,2000000000");
+        assert_eq!(lines[2], "holding,Acme,Hot,Solana,So1ana,token,USDC,,EPjFW,6,10,1,10,10000000");
+        assert_eq!(lines[3], "company_asset,Acme,,,,,SOL,,,,2,,300,");
+        assert_eq!(lines[5], "company_total,Acme,,,,,,,,,,,310,");
+        assert_eq!(lines[6], "portfolio_total,,,,,,,,,,,,310,");
+
+        let columns = BALANCES_CSV_HEADER.split(',').count();
+        assert!(lines.iter().all(|line| line.split(',').count() == columns));
+    }
+
+    #[test]
+    fn test_transactions_csv_rows() {
+        let change = |symbol: &str, asset_id: Option<&str>, amount: &str, raw_amount: &str| BalanceChangeDto {
+            symbol: Some(symbol.to_string()),
+            asset_id: asset_id.map(str::to_string),
+            decimals: 6,
+            amount: amount.to_string(),
+            raw_amount: raw_amount.to_string(),
+        };
+        let tx = |id: &str, changes: Vec<BalanceChangeDto>| WalletTransactionDto {
+            id: id.to_string(),
+            date: Some("2025-01-02T03:04:05+00:00".to_string()),
+            block: 42,
+            status: "completed".to_string(),
+            memo: None,
+            fee: Some("0.000005".to_string()),
+            changes,
+            counterparties: vec!["Alice".to_string(), "Bob".to_string()],
+        };
+
+        let transactions = vec![
+            tx("sig1", vec![change("SOL", None, "-1", "-1000000000"), change("USDC", Some("EPjFW"), "2.5", "2500000")]),
+            tx("sig2", vec![]),
+        ];
+        let csv = transactions_csv(&transactions);
+        let lines: Vec<&str> = csv.lines().collect();
+
+        assert_eq!(lines[0], TRANSACTIONS_CSV_HEADER);
+        assert_eq!(lines[1], "2025-01-02T03:04:05+00:00,sig1,42,completed,SOL,,-1,-1000000000,0.000005,Alice Bob,");
+        // The fee is only counted once per transaction
+        assert_eq!(lines[2], "2025-01-02T03:04:05+00:00,sig1,42,completed,USDC,EPjFW,2.5,2500000,,Alice Bob,");
+        assert_eq!(lines[3], "2025-01-02T03:04:05+00:00,sig2,42,completed,,,,,0.000005,Alice Bob,");
+        assert_eq!(lines.len(), 4);
+    }
+
+    #[test]
+    fn test_utxos_csv() {
+        let utxo = |vout: u32, value: u64, block_height: Option<u64>| Utxo {
+            txid: "f4184fc5".to_string(),
+            vout,
+            address: "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu".to_string(),
+            value,
+            block_height,
+        };
+        let utxos: Vec<UtxoDto> = [utxo(0, 150_000_000, Some(170)), utxo(1, 2_500, None)].iter().map(UtxoDto::from).collect();
+        let csv = utxos_csv(&utxos);
+        let lines: Vec<&str> = csv.lines().collect();
+
+        assert_eq!(lines[0], UTXOS_CSV_HEADER);
+        assert_eq!(lines[1], "f4184fc5,0,bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu,1.5,150000000,170");
+        // Unconfirmed outputs have no block height
+        assert_eq!(lines[2], "f4184fc5,1,bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu,0.000025,2500,");
+    }
+
+    #[test]
+    fn test_escape_csv() {
+        assert_eq!(escape_csv("plain"), "plain");
+        assert_eq!(escape_csv("Acme, Inc"), "\"Acme, Inc\"");
+        assert_eq!(escape_csv("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+}
@@ -0,0 +1,201 @@
+use crate::source::{AccountSnapshot, HoldingKind};
+use bigdecimal::{BigDecimal, Zero};
+use std::collections::HashMap;
+
+// Portfolio summary structure
+#[derive(Default)]
+pub struct PortfolioSummary {
+    pub companies: HashMap<String, CompanyAssets>,
+    pub total_usd_value: BigDecimal,
+}
+
+pub struct CompanyAssets {
+    pub assets: HashMap<String, AssetSummary>,
+    pub total_usd_value: BigDecimal,
+}
+
+pub struct AssetSummary {
+    pub symbol: String,
+    pub amount: BigDecimal,
+    pub usd_value: Option<BigDecimal>,
+}
+
+impl PortfolioSummary {
+    /// Aggregate a set of (already priced) snapshots by company and asset
+    pub fn from_snapshots(snapshots: &[AccountSnapshot]) -> Self {
+        let mut portfolio = Self::default();
+        for snapshot in snapshots {
+            aggregate_snapshot(&mut portfolio, snapshot);
+        }
+        portfolio
+    }
+}
+
+pub fn add_asset_to_portfolio(
+    portfolio: &mut PortfolioSummary,
+    company: &str,
+    symbol: &str,
+    amount: BigDecimal,
+    usd_value: Option<BigDecimal>,
+) {
+    if amount.is_zero() {
+        return;
+    }
+
+    let company_assets = portfolio
+        .companies
+        .entry(company.to_string())
+        .or_insert_with(|| CompanyAssets {
+            assets: HashMap::new(),
+            total_usd_value: BigDecimal::zero(),
+        });
+
+    let asset = company_assets
+        .assets
+        .entry(symbol.to_string())
+        .or_insert_with(|| AssetSummary {
+            symbol: symbol.to_string(),
+            amount: BigDecimal::zero(),
+            usd_value: Some(BigDecimal::zero()),
+        });
+
+    asset.amount += amount;
+    if let Some(value) = usd_value {
+        if let Some(ref mut asset_value) = asset.usd_value {
+            *asset_value += &value;
+        }
+        company_assets.total_usd_value += &value;
+        portfolio.total_usd_value += value;
+    }
+}
+
+/// Portfolio line of a holding: staked balances get a line of their own next
+/// to the liquid token, e.g. "SOL (staked)"
+pub fn asset_label(kind: HoldingKind, symbol: &str) -> String {
+    match kind {
+        HoldingKind::Staked => format!("{} (staked)", symbol),
+        _ => symbol.to_string(),
+    }
+}
+
+/// Add every holding with a known symbol to the snapshot's company
+pub fn aggregate_snapshot(portfolio: &mut PortfolioSummary, snapshot: &AccountSnapshot) {
+    for holding in &snapshot.holdings {
+        if let Some(symbol) = &holding.symbol {
+            add_asset_to_portfolio(
+                portfolio,
+                snapshot.company(),
+                &asset_label(holding.kind, symbol),
+                holding.amount.to_decimal(),
+                holding.usd_value.clone(),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::amount::TokenAmount;
+    use crate::source::Holding;
+    use crate::storage::{Chain, TrackedAccount, WalletAddress};
+    use std::str::FromStr;
+
+    fn dec(value: &str) -> BigDecimal {
+        BigDecimal::from_str(value).unwrap()
+    }
+
+    #[test]
+    fn test_add_asset_to_portfolio() {
+        let mut portfolio = PortfolioSummary {
+            companies: HashMap::new(),
+            total_usd_value: BigDecimal::zero(),
+        };
+
+        add_asset_to_portfolio(&mut portfolio, "TestCo", "BTC", dec("1.0"), Some(dec("50000")));
+
+        assert_eq!(portfolio.companies.len(), 1);
+        assert!(portfolio.companies.contains_key("TestCo"));
+        assert_eq!(portfolio.total_usd_value, dec("50000"));
+
+        let company = portfolio.companies.get("TestCo").unwrap();
+        assert_eq!(company.total_usd_value, dec("50000"));
+        assert!(company.assets.contains_key("BTC"));
+
+        let btc = company.assets.get("BTC").unwrap();
+        assert_eq!(btc.amount, dec("1"));
+        assert_eq!(btc.usd_value, Some(dec("50000")));
+    }
+
+    #[test]
+    fn test_add_asset_to_portfolio_accumulation() {
+        let mut portfolio = PortfolioSummary {
+            companies: HashMap::new(),
+            total_usd_value: BigDecimal::zero(),
+        };
+
+        // Add same asset twice
+        add_asset_to_portfolio(&mut portfolio, "TestCo", "BTC", dec("1.0"), Some(dec("50000")));
+        add_asset_to_portfolio(&mut portfolio, "TestCo", "BTC", dec("0.5"), Some(dec("25000")));
+
+        let company = portfolio.companies.get("TestCo").unwrap();
+        let btc = company.assets.get("BTC").unwrap();
+
+        assert_eq!(btc.amount, dec("1.5"));
+        assert_eq!(btc.usd_value, Some(dec("75000")));
+        assert_eq!(portfolio.total_usd_value, dec("75000"));
+    }
+
+    #[test]
+    fn test_add_asset_zero_balance_ignored() {
+        let mut portfolio = PortfolioSummary {
+            companies: HashMap::new(),
+            total_usd_value: BigDecimal::zero(),
+        };
+
+        add_asset_to_portfolio(&mut portfolio, "TestCo", "BTC", dec("0"), Some(dec("0")));
+
+        assert_eq!(portfolio.companies.len(), 0);
+    }
+
+    #[test]
+    fn test_from_snapshots_groups_uncategorized() {
+        let account = TrackedAccount::Wallet(WalletAddress {
+            company: String::new(),
+            name: "Wallet".to_string(),
+            address: "test123".to_string(),
+            chain: Chain::Solana,
+        });
+        let mut native = Holding::native("SOL", TokenAmount::new(2_000_000_000u64, 9));
+        native.usd_value = Some(dec("300"));
+        let snapshots = vec![AccountSnapshot::new(&account, vec![native])];
+
+        let portfolio = PortfolioSummary::from_snapshots(&snapshots);
+
+        assert_eq!(portfolio.total_usd_value, dec("300"));
+        let company = portfolio.companies.get("Uncategorized").unwrap();
+        assert_eq!(company.assets.get("SOL").unwrap().amount, dec("2"));
+    }
+
+    #[test]
+    fn test_staked_holdings_get_their_own_line() {
+        let account = TrackedAccount::Wallet(WalletAddress {
+            company: "Acme".to_string(),
+            name: "Validator".to_string(),
+            address: "test123".to_string(),
+            chain: Chain::Solana,
+        });
+        let mut native = Holding::native("SOL", TokenAmount::new(1_000_000_000u64, 9));
+        native.usd_value = Some(dec("150"));
+        let mut staked = Holding::staked("SOL", "stake1".to_string(), TokenAmount::new(10_000_000_000u64, 9));
+        staked.usd_value = Some(dec("1500"));
+        let snapshots = vec![AccountSnapshot::new(&account, vec![native, staked])];
+
+        let portfolio = PortfolioSummary::from_snapshots(&snapshots);
+
+        let company = portfolio.companies.get("Acme").unwrap();
+        assert_eq!(company.assets.get("SOL").unwrap().amount, dec("1"));
+        assert_eq!(company.assets.get("SOL (staked)").unwrap().amount, dec("10"));
+        assert_eq!(portfolio.total_usd_value, dec("1650"));
+    }
+}
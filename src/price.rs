@@ -1,15 +1,34 @@
+use crate::config::{self, ApiKey};
 use anyhow::{Context, Result};
 use i_am_surging::SurgeClient;
 use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::env;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-/// PriceService using Switchboard Surge for cryptocurrency prices
-/// Provides efficient price queries for 2,266+ trading pairs
+const COINGECKO_API_BASE: &str = "https://api.coingecko.com/api/v3";
+
+/// A source of USD prices, selectable in the `[prices]` section of config.toml
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PriceProvider {
+    /// Switchboard Surge (needs SURGE_API_KEY)
+    Surge,
+    /// CoinGecko simple price API (COINGECKO_API_KEY optional)
+    CoinGecko,
+}
+
+enum Provider {
+    Surge(SurgeClient),
+    CoinGecko(CoinGeckoClient),
+}
+
+/// PriceService using Switchboard Surge (and optionally CoinGecko) for cryptocurrency prices
+/// Provides efficient price queries for 2,266+ trading pairs.
+/// Providers are asked in the order configured for the active profile.
 pub struct PriceService {
-    surge_client: SurgeClient,
+    providers: Vec<Provider>,
 }
 
 // Global rate limiter shared across all PriceService instances
@@ -20,7 +39,22 @@ const MIN_REQUEST_DELAY_MS: u64 = 1000;
 
 impl PriceService {
     pub fn new() -> Result<Self> {
-        // Get the API key from environment
+        let providers = config::active()
+            .settings
+            .prices
+            .providers()
+            .into_iter()
+            .map(|provider| match provider {
+                PriceProvider::Surge => Self::surge_client().map(Provider::Surge),
+                PriceProvider::CoinGecko => Ok(Provider::CoinGecko(CoinGeckoClient::new())),
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self { providers })
+    }
+
+    fn surge_client() -> Result<SurgeClient> {
+        // Get the API key from the active profile or environment
         let api_key = Self::get_api_key().unwrap_or_else(|_| {
             eprintln!("Warning: SURGE_API_KEY not set. Price queries will fail.");
             eprintln!("Get your API key (Solana wallet address) from https://switchboard.xyz");
@@ -28,10 +62,8 @@ impl PriceService {
         });
 
         // Create the Surge client
-        let surge_client = SurgeClient::new(&api_key)
-            .context("Failed to create SurgeClient. Ensure feedIds.json is present.")?;
-
-        Ok(Self { surge_client })
+        SurgeClient::new(&api_key)
+            .context("Failed to create SurgeClient. Ensure feedIds.json is present.")
     }
 
     fn current_time_ms() -> u64 {
@@ -56,22 +88,41 @@ impl PriceService {
     }
 
     fn get_api_key() -> Result<String> {
-        env::var("SURGE_API_KEY")
-            .context("SURGE_API_KEY environment variable not set. Get your API key (Solana wallet address) from https://switchboard.xyz")
+        config::api_key(ApiKey::Surge)
+            .context("SURGE_API_KEY not set (environment or api_keys.surge in config.toml). Get your API key (Solana wallet address) from https://switchboard.xyz")
     }
 
     /// Get price for a single token symbol (e.g., "SOL", "ETH", "BTC")
-    /// Returns price in USD
+    /// Returns price in USD from the first provider that has it
     pub async fn get_single_price(&self, symbol: &str) -> Result<f64> {
+        let mut errors = Vec::new();
+
+        for provider in &self.providers {
+            let price = match provider {
+                Provider::Surge(client) => Self::surge_price(client, symbol).await,
+                Provider::CoinGecko(client) => client.get_prices(&[symbol.to_string()]).await.and_then(|prices| {
+                    prices.get(symbol).copied().context("not listed on CoinGecko")
+                }),
+            };
+            match price {
+                Ok(price) => return Ok(price),
+                Err(e) => errors.push(e.to_string()),
+            }
+        }
+
+        anyhow::bail!("Failed to get price for {}: {}", symbol, errors.join("; "))
+    }
+
+    async fn surge_price(client: &SurgeClient, symbol: &str) -> Result<f64> {
         Self::rate_limit().await;
 
         // Convert symbol to trading pair format (e.g., "SOL" -> "SOL/USD")
         let trading_pair = format!("{}/USD", symbol);
 
-        match self.surge_client.get_price(&trading_pair).await {
+        match client.get_price(&trading_pair).await {
             Ok(price_data) => Ok(price_data.value),
             Err(e) => {
-                anyhow::bail!("Failed to get price for {}: {}", symbol, e)
+                anyhow::bail!("Surge: {}", e)
             }
         }
     }
@@ -145,12 +196,33 @@ impl PriceService {
     }
 
     /// Batch fetch prices for a specific list of symbols
-    /// More efficient than individual queries
+    /// More efficient than individual queries. Each provider is only asked
+    /// for the symbols the previous ones did not price.
     pub async fn batch_fetch_prices(&self, symbols: &[String]) -> Result<HashMap<String, f64>> {
-        if symbols.is_empty() {
-            return Ok(HashMap::new());
+        let mut prices = HashMap::new();
+        let mut missing: Vec<String> = symbols.to_vec();
+
+        for provider in &self.providers {
+            if missing.is_empty() {
+                break;
+            }
+
+            let found = match provider {
+                Provider::Surge(client) => Self::surge_batch(client, &missing).await,
+                Provider::CoinGecko(client) => client.get_prices(&missing).await.unwrap_or_else(|e| {
+                    eprintln!("Warning: CoinGecko price fetch failed: {}", e);
+                    HashMap::new()
+                }),
+            };
+
+            missing.retain(|symbol| !found.contains_key(symbol));
+            prices.extend(found);
         }
 
+        Ok(prices)
+    }
+
+    async fn surge_batch(client: &SurgeClient, symbols: &[String]) -> HashMap<String, f64> {
         Self::rate_limit().await;
 
         // Convert symbols to trading pairs
@@ -161,7 +233,7 @@ impl PriceService {
 
         let trading_pair_refs: Vec<&str> = trading_pairs.iter().map(|s| s.as_str()).collect();
 
-        match self.surge_client.get_multiple_prices(&trading_pair_refs).await {
+        match client.get_multiple_prices(&trading_pair_refs).await {
             Ok(price_list) => {
                 let mut prices = HashMap::new();
 
@@ -172,14 +244,14 @@ impl PriceService {
                     }
                 }
 
-                Ok(prices)
+                prices
             }
             Err(e) => {
                 eprintln!("Warning: Batch fetch failed: {}", e);
                 // Fall back to individual queries with rate limiting
                 let mut prices = HashMap::new();
                 for symbol in symbols {
-                    match self.get_single_price(symbol).await {
+                    match Self::surge_price(client, symbol).await {
                         Ok(price) => {
                             prices.insert(symbol.clone(), price);
                         }
@@ -188,27 +260,67 @@ impl PriceService {
                         }
                     }
                 }
-                Ok(prices)
+                prices
             }
         }
     }
+}
 
-    /// Batch fetch all prices for known symbols in a single API call
-    /// This is more efficient than making separate calls for SOL, ETH, and tokens
-    pub async fn batch_fetch_all_known_prices(&self) -> Result<HashMap<String, f64>> {
-        // Only include symbols that have feeds on Switchboard
-        let known_symbols = vec![
-            "SOL", "ETH", "BTC", "USDC", "USDT",
-            "NEAR", "APT", "SUI", "AVAX", "BNB"
-        ];
+/// CoinGecko's simple price endpoint, looked up by ticker symbol
+struct CoinGeckoClient {
+    api_key: Option<String>,
+    client: reqwest::Client,
+}
 
-        self.batch_fetch_prices(&known_symbols.iter().map(|s| s.to_string()).collect::<Vec<_>>()).await
+impl CoinGeckoClient {
+    fn new() -> Self {
+        Self {
+            api_key: config::api_key(ApiKey::CoinGecko),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    async fn get_prices(&self, symbols: &[String]) -> Result<HashMap<String, f64>> {
+        let url = format!("{}/simple/price", COINGECKO_API_BASE);
+        let query: Vec<String> = symbols.iter().map(|s| s.to_lowercase()).collect();
+
+        let mut request = self
+            .client
+            .get(&url)
+            .query(&[("symbols", query.join(",")), ("vs_currencies", "usd".to_string())]);
+        if let Some(api_key) = &self.api_key {
+            request = request.header("x-cg-demo-api-key", api_key);
+        }
+
+        let response = request
+            .send()
+            .await
+            .context("Failed to send CoinGecko request")?;
+
+        if !response.status().is_success() {
+            anyhow::bail!("CoinGecko API error {}", response.status());
+        }
+
+        // {"sol": {"usd": 150.1}, ...}
+        let body: HashMap<String, HashMap<String, f64>> = response
+            .json()
+            .await
+            .context("Failed to parse CoinGecko response")?;
+
+        Ok(symbols
+            .iter()
+            .filter_map(|symbol| {
+                let price = body.get(&symbol.to_lowercase())?.get("usd")?;
+                Some((symbol.clone(), *price))
+            })
+            .collect())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::env;
 
     #[tokio::test]
     async fn test_get_sol_price() {
@@ -0,0 +1,569 @@
+use crate::config::{self, ApiKey};
+use crate::cosmos::{self, CosmosNetworks};
+use crate::storage::Chain;
+use anyhow::{Context, Result};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// An RPC endpoint from the `[rpc]` section of config.toml. Written either as
+/// a plain URL or as a table with optional auth headers and a display name.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "EndpointEntry")]
+pub struct RpcEndpoint {
+    pub url: String,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged, expecting = "expected an RPC URL or a table with url, and optionally name and headers")]
+enum EndpointEntry {
+    Url(String),
+    Full(EndpointTable),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EndpointTable {
+    url: String,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    name: Option<String>,
+}
+
+impl From<EndpointEntry> for RpcEndpoint {
+    fn from(entry: EndpointEntry) -> Self {
+        match entry {
+            EndpointEntry::Url(url) => RpcEndpoint::new(url),
+            EndpointEntry::Full(EndpointTable { url, headers, name }) => RpcEndpoint { url, headers, name },
+        }
+    }
+}
+
+impl RpcEndpoint {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            headers: BTreeMap::new(),
+            name: None,
+        }
+    }
+
+    /// Name shown in output: the configured name, otherwise just the host so
+    /// API keys embedded in the URL path or query never end up on screen
+    pub fn label(&self) -> String {
+        if let Some(name) = &self.name {
+            return name.clone();
+        }
+        reqwest::Url::parse(&self.url)
+            .ok()
+            .and_then(|url| url.host_str().map(|host| host.to_string()))
+            .unwrap_or_else(|| "custom RPC".to_string())
+    }
+
+    fn header_map(&self) -> Result<HeaderMap> {
+        let mut headers = HeaderMap::new();
+        for (name, value) in &self.headers {
+            let name = HeaderName::from_bytes(name.as_bytes())
+                .with_context(|| format!("Invalid header name '{}'", name))?;
+            let mut value = HeaderValue::from_str(value)
+                .with_context(|| format!("Invalid value for header '{}'", name))?;
+            value.set_sensitive(true);
+            headers.insert(name, value);
+        }
+        Ok(headers)
+    }
+}
+
+/// Per-chain RPC endpoints: `--rpc-url` overrides first, then the endpoints
+/// from config.toml, falling back to the built-in defaults for the chain
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct RpcConfig {
+    chains: BTreeMap<String, Vec<RpcEndpoint>>,
+    #[serde(skip)]
+    overrides: Vec<(Chain, RpcEndpoint)>,
+}
+
+impl RpcConfig {
+    /// Check chain names, URLs and headers so mistakes surface when the config is loaded
+    pub fn validate(&self, cosmos: &CosmosNetworks) -> Result<()> {
+        for (chain_name, endpoints) in &self.chains {
+            let chain = Chain::parse(chain_name, cosmos).with_context(|| format!("Invalid [rpc] entry '{}'", chain_name))?;
+            if endpoints.is_empty() {
+                anyhow::bail!("[rpc] {} lists no endpoints", chain_name);
+            }
+            for endpoint in endpoints {
+                reqwest::Url::parse(&endpoint.url)
+                    .with_context(|| format!("Invalid RPC URL for {}: {}", chain_name, endpoint.url))?;
+                endpoint.header_map().with_context(|| format!("Invalid RPC endpoint for {}", chain_name))?;
+                if chain == Chain::Solana && !endpoint.headers.is_empty() {
+                    anyhow::bail!("Headers are not supported for Solana RPC endpoints; put the API key in the URL instead");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Layer a profile's endpoints on top: a chain listed in `other` replaces
+    /// that chain's endpoints, other chains are kept
+    pub fn merge(&mut self, other: RpcConfig, cosmos: &CosmosNetworks) {
+        let replaced: Vec<Chain> = other.chains.keys().filter_map(|name| Chain::parse(name, cosmos).ok()).collect();
+        self.chains
+            .retain(|name, _| !Chain::parse(name, cosmos).map(|chain| replaced.contains(&chain)).unwrap_or(false));
+        self.chains.extend(other.chains);
+    }
+
+    /// Copy for display with header values and URL query strings hidden
+    pub fn redacted(&self) -> RpcConfig {
+        let chains = self
+            .chains
+            .iter()
+            .map(|(name, endpoints)| {
+                let endpoints = endpoints
+                    .iter()
+                    .map(|endpoint| RpcEndpoint {
+                        url: redact_url(&endpoint.url),
+                        headers: endpoint.headers.keys().map(|k| (k.clone(), REDACTED.to_string())).collect(),
+                        name: endpoint.name.clone(),
+                    })
+                    .collect();
+                (name.clone(), endpoints)
+            })
+            .collect();
+        RpcConfig { chains, overrides: Vec::new() }
+    }
+
+    /// Apply a `--rpc-url` value. `CHAIN=URL` targets one chain; a bare URL is
+    /// only accepted when the command queries a single chain (`default_chain`).
+    pub fn add_override(&mut self, spec: &str, default_chain: Option<&Chain>) -> Result<()> {
+        let (chain, url) = match spec.split_once('=') {
+            Some((chain, url)) if !chain.contains(':') && !chain.contains('/') => (Chain::from_str(chain)?, url),
+            _ => match default_chain {
+                Some(chain) => (chain.clone(), spec),
+                None => anyhow::bail!(
+                    "--rpc-url needs a chain when querying several accounts, e.g. --rpc-url solana={}",
+                    spec
+                ),
+            },
+        };
+
+        reqwest::Url::parse(url).with_context(|| format!("Invalid RPC URL: {}", url))?;
+        self.overrides.push((chain, RpcEndpoint::new(url)));
+        Ok(())
+    }
+
+    /// Endpoints to try for a chain, in order
+    pub fn endpoints_for(&self, chain: &Chain) -> Vec<RpcEndpoint> {
+        let mut endpoints: Vec<RpcEndpoint> = self
+            .overrides
+            .iter()
+            .filter(|(c, _)| c == chain)
+            .map(|(_, endpoint)| endpoint.clone())
+            .collect();
+
+        let configured: Vec<RpcEndpoint> = self
+            .chains
+            .iter()
+            .filter(|(name, _)| Chain::from_str(name).ok().as_ref() == Some(chain))
+            .flat_map(|(_, list)| list.iter().cloned())
+            .collect();
+
+        if configured.is_empty() {
+            endpoints.extend(default_endpoints(chain));
+        } else {
+            endpoints.extend(configured);
+        }
+        endpoints
+    }
+}
+
+pub const REDACTED: &str = "<redacted>";
+
+/// The URL with its path, query and credentials hidden, since providers such
+/// as Alchemy (`/v2/<key>`) and Infura (`/v3/<key>`) put the API key in the path
+pub fn redact_url(url: &str) -> String {
+    let Ok(parsed) = reqwest::Url::parse(url) else {
+        return REDACTED.to_string();
+    };
+    let Some(host) = parsed.host_str() else {
+        return REDACTED.to_string();
+    };
+
+    let mut shown = format!("{}://{}", parsed.scheme(), host);
+    if let Some(port) = parsed.port() {
+        shown.push_str(&format!(":{}", port));
+    }
+    if !matches!(parsed.path(), "" | "/") {
+        shown.push_str(&format!("/{}", REDACTED));
+    }
+    if parsed.query().is_some() {
+        shown.push_str(&format!("?{}", REDACTED));
+    }
+    shown
+}
+
+fn get_alchemy_rpc_url(chain: &Chain, api_key: &str) -> Option<String> {
+    match chain {
+        Chain::Ethereum => Some(format!("https://eth-mainnet.g.alchemy.com/v2/{}", api_key)),
+        Chain::Polygon => Some(format!("https://polygon-mainnet.g.alchemy.com/v2/{}", api_key)),
+        Chain::Arbitrum => Some(format!("https://arb-mainnet.g.alchemy.com/v2/{}", api_key)),
+        Chain::Optimism => Some(format!("https://opt-mainnet.g.alchemy.com/v2/{}", api_key)),
+        Chain::Base => Some(format!("https://base-mainnet.g.alchemy.com/v2/{}", api_key)),
+        _ => None, // Chain not supported by Alchemy
+    }
+}
+
+/// Built-in endpoints: the keyed provider (Helius, Alchemy) when its API key
+/// is set, followed by the chain's public RPC
+pub fn default_endpoints(chain: &Chain) -> Vec<RpcEndpoint> {
+    let mut endpoints = Vec::new();
+
+    match chain {
+        Chain::Solana => {
+            match config::api_key(ApiKey::Helius) {
+                Some(api_key) => {
+                    endpoints.push(RpcEndpoint::new(format!("https://mainnet.helius-rpc.com/?api-key={}", api_key)));
+                }
+                _ => {
+                    eprintln!("Warning: HELIUS_API_KEY not set. Using public Solana RPC.");
+                    eprintln!("For better performance, get a Helius API key at https://helius.dev");
+                }
+            }
+            endpoints.push(RpcEndpoint::new("https://api.mainnet-beta.solana.com"));
+        }
+        Chain::Near => endpoints.push(RpcEndpoint::new("https://rpc.mainnet.near.org")),
+        Chain::Aptos => endpoints.push(RpcEndpoint::new("https://fullnode.mainnet.aptoslabs.com/v1")),
+        Chain::Sui => endpoints.push(RpcEndpoint::new("https://fullnode.mainnet.sui.io:443")),
+        // Free public RPC from Nethermind (Blast API is no longer available)
+        Chain::Starknet => endpoints.push(RpcEndpoint::new("https://free-rpc.nethermind.io/mainnet-juno")),
+        // Esplora REST APIs
+        Chain::Bitcoin => {
+            endpoints.push(RpcEndpoint::new("https://blockstream.info/api"));
+            endpoints.push(RpcEndpoint::new("https://mempool.space/api"));
+        }
+        // Cosmos SDK LCD (REST) APIs, from the network's config
+        Chain::Cosmos(key) => {
+            if let Some(network) = cosmos::network(key) {
+                endpoints.extend(network.lcd.iter().map(|url| RpcEndpoint::new(url.as_str())));
+            }
+        }
+        Chain::Ethereum | Chain::Polygon | Chain::BinanceSmartChain | Chain::Arbitrum
+        | Chain::Optimism | Chain::Avalanche | Chain::Base | Chain::Core => {
+            match config::api_key(ApiKey::Alchemy) {
+                Some(api_key) => {
+                    if let Some(alchemy_url) = get_alchemy_rpc_url(chain, &api_key) {
+                        endpoints.push(RpcEndpoint::new(alchemy_url));
+                    }
+                }
+                None => {
+                    // Only show warning for commonly used chains to avoid spam
+                    if matches!(chain, Chain::Base) {
+                        eprintln!("Warning: ALCHEMY_API_KEY not set. Using public EVM RPCs.");
+                        eprintln!("For better performance and higher limits, get an Alchemy API key at https://alchemy.com");
+                    }
+                }
+            }
+
+            let public_url = match chain {
+                Chain::Ethereum => "https://eth.llamarpc.com",
+                Chain::Polygon => "https://polygon-rpc.com",
+                Chain::BinanceSmartChain => "https://bsc-dataseed.binance.org",
+                Chain::Arbitrum => "https://arb1.arbitrum.io/rpc",
+                Chain::Optimism => "https://mainnet.optimism.io",
+                Chain::Avalanche => "https://api.avax.network/ext/bc/C/rpc",
+                Chain::Base => "https://mainnet.base.org",
+                _ => "https://rpc.coredao.org",
+            };
+            endpoints.push(RpcEndpoint::new(public_url));
+        }
+    }
+
+    endpoints
+}
+
+// ==================== Failover ====================
+
+/// Outcome of one attempt against one endpoint
+pub enum CallError {
+    /// The endpoint is unavailable (timeout, 429, 5xx): try the next one
+    FailOver(anyhow::Error),
+    /// Any other error: another endpoint would answer the same way
+    Fatal(anyhow::Error),
+}
+
+/// A list of clients, one per endpoint, tried in order until one answers.
+/// Calls start at the endpoint that last succeeded, and every endpoint that
+/// served a request is recorded so results can say where they came from.
+pub struct Failover<C> {
+    endpoints: Vec<(String, C)>,
+    preferred: AtomicUsize,
+    served: Mutex<Vec<String>>,
+}
+
+impl<C> Failover<C> {
+    pub fn new(endpoints: Vec<(String, C)>) -> Self {
+        Self {
+            endpoints,
+            preferred: AtomicUsize::new(0),
+            served: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub async fn call<'a, T, Fut>(&'a self, call: impl Fn(&'a C) -> Fut) -> Result<T>
+    where
+        Fut: Future<Output = std::result::Result<T, CallError>>,
+    {
+        let start = self.preferred.load(Ordering::Relaxed);
+        let mut failures = Vec::new();
+
+        for offset in 0..self.endpoints.len() {
+            let index = (start + offset) % self.endpoints.len();
+            let (label, client) = &self.endpoints[index];
+
+            match call(client).await {
+                Ok(value) => {
+                    self.preferred.store(index, Ordering::Relaxed);
+                    self.record(label);
+                    return Ok(value);
+                }
+                Err(CallError::Fatal(e)) => {
+                    self.record(label);
+                    return Err(e);
+                }
+                Err(CallError::FailOver(e)) => failures.push(format!("{}: {:#}", label, e)),
+            }
+        }
+
+        if failures.is_empty() {
+            anyhow::bail!("No RPC endpoints configured");
+        }
+        anyhow::bail!("All RPC endpoints failed ({})", failures.join("; "))
+    }
+
+    fn record(&self, label: &str) {
+        let mut served = self.served.lock().unwrap_or_else(|e| e.into_inner());
+        if !served.iter().any(|l| l == label) {
+            served.push(label.to_string());
+        }
+    }
+
+    /// Endpoints that served at least one request, in first-use order
+    pub fn served_by(&self) -> String {
+        self.served.lock().unwrap_or_else(|e| e.into_inner()).join(", ")
+    }
+}
+
+/// Timeouts and connection failures move on to the next endpoint. The URL is
+/// dropped from the error since it can hold an API key (Helius, Alchemy).
+fn send_error(error: reqwest::Error) -> CallError {
+    let error = error.without_url();
+    if error.is_timeout() || error.is_connect() {
+        CallError::FailOver(error.into())
+    } else {
+        CallError::Fatal(anyhow::Error::new(error).context("Failed to send RPC request"))
+    }
+}
+
+/// Parse the JSON body of an endpoint's response, leaving the request URL
+/// out of any error for the same reason
+pub async fn json_body<T: DeserializeOwned>(response: reqwest::Response) -> Result<T> {
+    response.json().await.map_err(|e| e.without_url().into())
+}
+
+/// A reqwest client bound to one endpoint's URL and headers
+pub struct HttpEndpoint {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl HttpEndpoint {
+    async fn post<B: Serialize + ?Sized>(&self, path: &str, body: &B) -> std::result::Result<reqwest::Response, CallError> {
+        let response = self
+            .client
+            .post(format!("{}{}", self.url, path))
+            .json(body)
+            .send()
+            .await
+            .map_err(send_error)?;
+
+        Self::check_status(response)
+    }
+
+    async fn get(&self, path: &str) -> std::result::Result<reqwest::Response, CallError> {
+        let response = self
+            .client
+            .get(format!("{}{}", self.url, path))
+            .send()
+            .await
+            .map_err(send_error)?;
+
+        Self::check_status(response)
+    }
+
+    fn check_status(response: reqwest::Response) -> std::result::Result<reqwest::Response, CallError> {
+        let status = response.status();
+        if should_fail_over(status.as_u16()) {
+            return Err(CallError::FailOver(anyhow::anyhow!("HTTP {}", status)));
+        }
+        Ok(response)
+    }
+}
+
+/// Rate limits and server errors are worth retrying elsewhere
+pub fn should_fail_over(status: u16) -> bool {
+    status == 429 || (500..600).contains(&status)
+}
+
+/// JSON-over-HTTP endpoints (JSON-RPC or REST) with failover
+pub type HttpPool = Failover<HttpEndpoint>;
+
+impl Failover<HttpEndpoint> {
+    pub fn http(endpoints: Vec<RpcEndpoint>) -> Self {
+        let endpoints = endpoints
+            .into_iter()
+            .map(|endpoint| {
+                let client = reqwest::Client::builder()
+                    .timeout(REQUEST_TIMEOUT)
+                    .default_headers(endpoint.header_map().unwrap_or_default())
+                    .build()
+                    .unwrap_or_else(|_| reqwest::Client::new());
+                let url = endpoint.url.trim_end_matches('/').to_string();
+                (endpoint.label(), HttpEndpoint { client, url })
+            })
+            .collect();
+        Self::new(endpoints)
+    }
+
+    /// POST a JSON body to `path` (relative to the endpoint URL; empty for JSON-RPC)
+    pub async fn post<B: Serialize + ?Sized>(&self, path: &str, body: &B) -> Result<reqwest::Response> {
+        self.call(|endpoint| endpoint.post(path, body)).await
+    }
+
+    /// GET `path` (relative to the endpoint URL), for REST APIs
+    pub async fn get(&self, path: &str) -> Result<reqwest::Response> {
+        self.call(|endpoint| endpoint.get(path)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rpc_config() {
+        let config: RpcConfig = toml::from_str(
+            r#"
+            solana = ["https://solana.example.com/?api-key=abc"]
+
+            [[eth]]
+            url = "https://eth.example.com"
+            name = "primary"
+            headers = { Authorization = "Bearer token" }
+
+            [[eth]]
+            url = "https://backup.example.com"
+            "#,
+        )
+        .unwrap();
+        config.validate(&CosmosNetworks::default()).unwrap();
+
+        let eth = config.endpoints_for(&Chain::Ethereum);
+        assert_eq!(eth.len(), 2);
+        assert_eq!(eth[0].label(), "primary");
+        assert_eq!(eth[0].headers["Authorization"], "Bearer token");
+        assert_eq!(eth[1].label(), "backup.example.com");
+
+        let solana = config.endpoints_for(&Chain::Solana);
+        assert_eq!(solana, vec![RpcEndpoint::new("https://solana.example.com/?api-key=abc")]);
+        assert_eq!(solana[0].label(), "solana.example.com");
+
+        // Unconfigured chains keep the built-in endpoints
+        assert_eq!(config.endpoints_for(&Chain::Sui), default_endpoints(&Chain::Sui));
+    }
+
+    #[test]
+    fn test_validate_rejects_bad_entries() {
+        let unknown: RpcConfig = toml::from_str(r#"dogecoin = ["https://doge.example.com"]"#).unwrap();
+        assert!(unknown.validate(&CosmosNetworks::default()).is_err());
+
+        let bad_url: RpcConfig = toml::from_str(r#"near = ["not a url"]"#).unwrap();
+        assert!(bad_url.validate(&CosmosNetworks::default()).is_err());
+
+        let solana_headers: RpcConfig =
+            toml::from_str(r#"solana = [{ url = "https://s.example.com", headers = { "x-api-key" = "k" } }]"#).unwrap();
+        assert!(solana_headers.validate(&CosmosNetworks::default()).is_err());
+    }
+
+    #[test]
+    fn test_rpc_url_overrides() {
+        let mut config = RpcConfig::default();
+        config.add_override("near=https://near.example.com", None).unwrap();
+        config.add_override("https://sol.example.com/?api-key=a=b", Some(&Chain::Solana)).unwrap();
+        assert!(config.add_override("https://sol.example.com", None).is_err());
+        assert!(config.add_override("dogecoin=https://doge.example.com", None).is_err());
+
+        assert_eq!(config.endpoints_for(&Chain::Near)[0].url, "https://near.example.com");
+        // Overrides come before the defaults, which stay as fallback
+        assert_eq!(config.endpoints_for(&Chain::Near).len(), 2);
+        assert_eq!(config.endpoints_for(&Chain::Solana)[0].url, "https://sol.example.com/?api-key=a=b");
+    }
+
+    #[tokio::test]
+    async fn test_errors_hide_endpoint_urls() {
+        // Nothing listens on port 1, so the request fails to connect
+        let pool = HttpPool::http(vec![RpcEndpoint::new("http://127.0.0.1:1/v2/path-key?api-key=query-key")]);
+
+        let error = pool.post("", &serde_json::json!({})).await.unwrap_err();
+        let message = format!("{:#}", error);
+        assert!(message.contains("127.0.0.1"), "{}", message);
+        assert!(!message.contains("path-key") && !message.contains("query-key"), "{}", message);
+    }
+
+    #[tokio::test]
+    async fn test_failover_order_and_record() {
+        let pool = Failover::new(vec![
+            ("down".to_string(), 503),
+            ("limited".to_string(), 429),
+            ("up".to_string(), 200),
+        ]);
+
+        let result = pool
+            .call(|status: &u16| async move {
+                if should_fail_over(*status) {
+                    Err(CallError::FailOver(anyhow::anyhow!("HTTP {}", status)))
+                } else {
+                    Ok(*status)
+                }
+            })
+            .await
+            .unwrap();
+        assert_eq!(result, 200);
+        assert_eq!(pool.served_by(), "up");
+
+        // A fatal error stops immediately, starting from the endpoint that last worked
+        let error = pool
+            .call(|_: &u16| async { Err::<(), _>(CallError::Fatal(anyhow::anyhow!("invalid address"))) })
+            .await
+            .unwrap_err();
+        assert_eq!(error.to_string(), "invalid address");
+
+        let all_down = Failover::new(vec![("a".to_string(), 500), ("b".to_string(), 429)]);
+        let error = all_down
+            .call(|status: &u16| async move { Err::<(), _>(CallError::FailOver(anyhow::anyhow!("HTTP {}", status))) })
+            .await
+            .unwrap_err();
+        assert_eq!(error.to_string(), "All RPC endpoints failed (a: HTTP 500; b: HTTP 429)");
+        assert_eq!(all_down.served_by(), "");
+    }
+}
@@ -0,0 +1,378 @@
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use anyhow::{Context, Result};
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Read instead of prompting, for servers and CI. Keep it out of `.env`.
+pub const PASSPHRASE_ENV: &str = "GRINGOTTS_VAULT_PASSPHRASE";
+
+const VAULT_VERSION: u32 = 1;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+/// Bound into every ciphertext so a vault can't be swapped for another format
+const ASSOCIATED_DATA: &[u8] = b"gringotts-vault-v1";
+
+/// Secrets of the vault for this run, unlocked on first use
+static UNLOCKED: OnceLock<Option<BTreeMap<String, String>>> = OnceLock::new();
+
+/// Argon2id cost parameters, stored with the vault so they can be raised later
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+struct KdfParams {
+    memory_kib: u32,
+    iterations: u32,
+    parallelism: u32,
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        Self {
+            memory_kib: 19 * 1024,
+            iterations: 2,
+            parallelism: 1,
+        }
+    }
+}
+
+/// On-disk format of ~/.gringotts/secrets.vault. Names and values are both
+/// inside the ciphertext; only the KDF salt and parameters are in the clear.
+#[derive(Serialize, Deserialize)]
+struct VaultFile {
+    version: u32,
+    kdf: KdfParams,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+/// Encrypted store for API keys: AES-256-GCM with a key derived from a
+/// passphrase by Argon2id
+pub struct Vault {
+    kdf: KdfParams,
+    salt: [u8; SALT_LEN],
+    key: [u8; 32],
+    secrets: BTreeMap<String, String>,
+}
+
+impl Vault {
+    fn new(passphrase: &str, kdf: KdfParams) -> Result<Self> {
+        let mut salt = [0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+
+        Ok(Self {
+            kdf,
+            salt,
+            key: derive_key(passphrase, &salt, kdf)?,
+            secrets: BTreeMap::new(),
+        })
+    }
+
+    /// Open the vault, prompting for the passphrase (or reading it from
+    /// GRINGOTTS_VAULT_PASSPHRASE). Errors when there is no vault yet.
+    pub fn open() -> Result<Self> {
+        let path = Self::get_storage_path()?;
+        if !path.exists() {
+            anyhow::bail!("No secrets vault yet. Add a secret with 'gringotts secrets set <NAME>'");
+        }
+
+        let content = fs::read_to_string(&path)
+            .context("Failed to read secrets vault")?;
+        let file: VaultFile = serde_json::from_str(&content)
+            .context("Failed to parse secrets vault")?;
+
+        Self::decrypt(&file, &read_passphrase("Vault passphrase: ")?)
+    }
+
+    /// Open the vault, or create it with a new passphrase when there is none
+    pub fn open_or_create() -> Result<Self> {
+        if Self::get_storage_path()?.exists() {
+            return Self::open();
+        }
+
+        println!("Creating a new secrets vault at {}", Self::get_storage_path()?.display());
+        Self::new(&new_passphrase()?, KdfParams::default())
+    }
+
+    fn decrypt(file: &VaultFile, passphrase: &str) -> Result<Self> {
+        if file.version != VAULT_VERSION {
+            anyhow::bail!("Unsupported secrets vault version {}", file.version);
+        }
+
+        let salt: [u8; SALT_LEN] = decode_fixed(&file.salt).context("Invalid vault salt")?;
+        let nonce: [u8; NONCE_LEN] = decode_fixed(&file.nonce).context("Invalid vault nonce")?;
+        let ciphertext = BASE64.decode(&file.ciphertext).context("Invalid vault ciphertext")?;
+
+        let key = derive_key(passphrase, &salt, file.kdf)?;
+        let plaintext = Aes256Gcm::new(&key.into())
+            .decrypt(Nonce::from_slice(&nonce), Payload { msg: &ciphertext, aad: ASSOCIATED_DATA })
+            .map_err(|_| anyhow::anyhow!("Wrong passphrase, or the secrets vault is corrupted"))?;
+
+        Ok(Self {
+            kdf: file.kdf,
+            salt,
+            key,
+            secrets: serde_json::from_slice(&plaintext).context("Failed to parse decrypted secrets")?,
+        })
+    }
+
+    /// Encrypt with a fresh nonce; the salt and key stay the same
+    fn encrypt(&self) -> Result<VaultFile> {
+        let mut nonce = [0u8; NONCE_LEN];
+        OsRng.fill_bytes(&mut nonce);
+
+        let plaintext = serde_json::to_vec(&self.secrets)?;
+        let ciphertext = Aes256Gcm::new(&self.key.into())
+            .encrypt(Nonce::from_slice(&nonce), Payload { msg: &plaintext, aad: ASSOCIATED_DATA })
+            .map_err(|_| anyhow::anyhow!("Failed to encrypt secrets"))?;
+
+        Ok(VaultFile {
+            version: VAULT_VERSION,
+            kdf: self.kdf,
+            salt: BASE64.encode(self.salt),
+            nonce: BASE64.encode(nonce),
+            ciphertext: BASE64.encode(ciphertext),
+        })
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = Self::get_storage_path()?;
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .context("Failed to create storage directory")?;
+        }
+
+        let content = serde_json::to_string_pretty(&self.encrypt()?)
+            .context("Failed to serialize secrets vault")?;
+
+        write_private_file(&path, &content)
+            .context("Failed to write secrets vault")?;
+
+        Ok(())
+    }
+
+    pub fn get_storage_path() -> Result<PathBuf> {
+        let home = dirs::home_dir()
+            .context("Failed to get home directory")?;
+
+        Ok(home.join(".gringotts").join("secrets.vault"))
+    }
+
+    pub fn get(&self, name: &str) -> Option<&String> {
+        self.secrets.get(name)
+    }
+
+    pub fn set(&mut self, name: &str, value: &str) -> Result<()> {
+        validate_name(name)?;
+        if value.is_empty() {
+            anyhow::bail!("Secret value cannot be empty");
+        }
+        self.secrets.insert(name.to_string(), value.to_string());
+        Ok(())
+    }
+
+    pub fn remove(&mut self, name: &str) -> Result<()> {
+        self.secrets
+            .remove(name)
+            .map(|_| ())
+            .with_context(|| format!("Secret '{}' not found", name))
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &String> {
+        self.secrets.keys()
+    }
+}
+
+/// Secret names are env var style (`MERCURY_API_KEY`), optionally scoped to a
+/// profile (`sandbox/MERCURY_API_KEY`)
+fn validate_name(name: &str) -> Result<()> {
+    let valid_part = |part: &str| {
+        !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+    };
+    let valid = match name.split_once('/') {
+        Some((profile, key)) => valid_part(profile) && valid_part(key),
+        None => valid_part(name),
+    };
+    if !valid {
+        anyhow::bail!("Invalid secret name '{}'. Use a name like MERCURY_API_KEY or sandbox/MERCURY_API_KEY", name);
+    }
+    Ok(())
+}
+
+fn derive_key(passphrase: &str, salt: &[u8], kdf: KdfParams) -> Result<[u8; 32]> {
+    let params = Params::new(kdf.memory_kib, kdf.iterations, kdf.parallelism, Some(32))
+        .map_err(|e| anyhow::anyhow!("Invalid vault KDF parameters: {}", e))?;
+
+    let mut key = [0u8; 32];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| anyhow::anyhow!("Failed to derive vault key: {}", e))?;
+    Ok(key)
+}
+
+fn decode_fixed<const N: usize>(encoded: &str) -> Result<[u8; N]> {
+    let bytes = BASE64.decode(encoded)?;
+    bytes
+        .try_into()
+        .map_err(|bytes: Vec<u8>| anyhow::anyhow!("expected {} bytes, got {}", N, bytes.len()))
+}
+
+fn read_passphrase(prompt: &str) -> Result<String> {
+    if let Ok(passphrase) = env::var(PASSPHRASE_ENV) {
+        return Ok(passphrase);
+    }
+    rpassword::prompt_password(prompt).context("Failed to read vault passphrase")
+}
+
+fn new_passphrase() -> Result<String> {
+    let passphrase = match env::var(PASSPHRASE_ENV) {
+        Ok(passphrase) => passphrase,
+        Err(_) => {
+            let passphrase = read_passphrase("New vault passphrase: ")?;
+            if passphrase != read_passphrase("Confirm passphrase: ")? {
+                anyhow::bail!("Passphrases do not match");
+            }
+            passphrase
+        }
+    };
+
+    if passphrase.len() < 8 {
+        anyhow::bail!("Vault passphrase must be at least 8 characters");
+    }
+    Ok(passphrase)
+}
+
+fn open_for_run() -> Result<Option<BTreeMap<String, String>>> {
+    if !Vault::get_storage_path()?.exists() {
+        return Ok(None);
+    }
+    Ok(Some(Vault::open()?.secrets))
+}
+
+/// Unlock the vault now rather than on the first key lookup, so a wrong
+/// passphrase stops the command and the prompt doesn't interrupt its output
+pub fn unlock() -> Result<()> {
+    if UNLOCKED.get().is_none() {
+        let secrets = open_for_run().context("Failed to unlock secrets vault")?;
+        let _ = UNLOCKED.set(secrets);
+    }
+    Ok(())
+}
+
+/// Look up a secret for the active profile: `<profile>/<name>` first, then `<name>`
+pub fn lookup(profile: Option<&str>, name: &str) -> Option<String> {
+    let secrets = UNLOCKED.get_or_init(|| {
+        open_for_run().unwrap_or_else(|e| {
+            eprintln!("Warning: Failed to unlock secrets vault: {:#}. Using environment variables.", e);
+            None
+        })
+    });
+    let secrets = secrets.as_ref()?;
+
+    profile
+        .and_then(|profile| secrets.get(&format!("{}/{}", profile, name)))
+        .or_else(|| secrets.get(name))
+        .cloned()
+}
+
+/// Write a file only its owner can read or write. New files are created with
+/// mode 0600, so the content is never readable by others, even briefly.
+pub fn write_private_file(path: &Path, content: &str) -> std::io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    let mut file = options.open(path)?;
+    // The mode only applies to new files; tighten ones written by older versions
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    }
+    file.write_all(content.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Cheap parameters so the tests stay fast
+    const TEST_KDF: KdfParams = KdfParams {
+        memory_kib: 64,
+        iterations: 1,
+        parallelism: 1,
+    };
+
+    #[test]
+    fn test_vault_round_trip() {
+        let mut vault = Vault::new("correct horse", TEST_KDF).unwrap();
+        vault.set("MERCURY_API_KEY", "secret-token:abc123").unwrap();
+        vault.set("sandbox/MERCURY_API_KEY", "secret-token:sandbox").unwrap();
+
+        let file = vault.encrypt().unwrap();
+        let serialized = serde_json::to_string(&file).unwrap();
+        assert!(!serialized.contains("abc123"));
+        assert!(!serialized.contains("MERCURY"));
+
+        let opened = Vault::decrypt(&file, "correct horse").unwrap();
+        assert_eq!(opened.get("MERCURY_API_KEY").unwrap(), "secret-token:abc123");
+        assert_eq!(opened.names().count(), 2);
+
+        // Every save uses a new nonce
+        assert_ne!(vault.encrypt().unwrap().nonce, file.nonce);
+    }
+
+    #[test]
+    fn test_wrong_passphrase_or_tampering() {
+        let mut vault = Vault::new("correct horse", TEST_KDF).unwrap();
+        vault.set("CIRCLE_API_KEY", "key").unwrap();
+        let mut file = vault.encrypt().unwrap();
+
+        assert!(Vault::decrypt(&file, "wrong horse").is_err());
+
+        let mut ciphertext = BASE64.decode(&file.ciphertext).unwrap();
+        ciphertext[0] ^= 1;
+        file.ciphertext = BASE64.encode(ciphertext);
+        assert!(Vault::decrypt(&file, "correct horse").is_err());
+    }
+
+    #[test]
+    fn test_secret_names() {
+        let mut vault = Vault::new("correct horse", TEST_KDF).unwrap();
+        assert!(vault.set("HELIUS_API_KEY", "k").is_ok());
+        assert!(vault.set("prod/HELIUS_API_KEY", "k").is_ok());
+        assert!(vault.set("has space", "k").is_err());
+        assert!(vault.set("a/b/c", "k").is_err());
+        assert!(vault.set("EMPTY", "").is_err());
+        assert!(vault.remove("MISSING").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_private_file() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = std::env::temp_dir().join(format!("gringotts-private-{}", std::process::id()));
+        fs::write(&path, "old").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+
+        write_private_file(&path, "secret").unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "secret");
+        fs::remove_file(&path).unwrap();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+}
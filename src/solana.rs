@@ -1,34 +1,55 @@
+use crate::amount::TokenAmount;
+use crate::rpc::{should_fail_over, CallError, Failover, RpcEndpoint};
+use crate::source::{AccountSnapshot, BalanceChange, BalanceSource, Holding, TransactionRange, WalletTransaction};
+use crate::storage::{Chain, TrackedAccount};
+use crate::token_cache::{self, TokenMetadata as CachedMetadata};
 use anyhow::{Context, Result};
+use async_trait::async_trait;
 use base64::prelude::*;
+use bigdecimal::num_bigint::{BigInt, Sign};
+use bigdecimal::{BigDecimal, Zero};
+use futures::stream::StreamExt;
 use mpl_token_metadata::accounts::Metadata;
-use solana_account_decoder_client_types::UiAccountData;
-use solana_client::rpc_client::{RpcClient, GetConfirmedSignaturesForAddress2Config};
-use solana_client::rpc_config::RpcTransactionConfig;
+use solana_account_decoder_client_types::{UiAccountData, UiAccountEncoding};
+use solana_client::client_error::{ClientError, ClientErrorKind};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_client::GetConfirmedSignaturesForAddress2Config;
+use solana_client::rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig, RpcTransactionConfig};
+use solana_client::rpc_filter::{Memcmp, RpcFilterType};
+use solana_sdk::account::Account;
 use solana_sdk::commitment_config::CommitmentConfig;
 use solana_sdk::pubkey::Pubkey;
-use solana_sdk::program_pack::Pack;
 use solana_sdk::signature::Signature;
-use solana_transaction_status_client_types::UiTransactionEncoding;
-use std::env;
+use solana_sdk::stake::state::StakeStateV2;
+use solana_transaction_status_client_types::{UiTransactionEncoding, UiTransactionStatusMeta, UiTransactionTokenBalance};
+use spl_token_2022::extension::interest_bearing_mint::InterestBearingConfig;
+use spl_token_2022::extension::transfer_fee::TransferFeeConfig;
+use spl_token_2022::extension::{BaseStateWithExtensions, StateWithExtensions};
+use spl_token_metadata_interface::state::TokenMetadata;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::future::Future;
 use std::str::FromStr;
-use std::collections::HashMap;
 
-const HELIUS_RPC_TEMPLATE: &str = "https://mainnet.helius-rpc.com/?api-key={}";
-const SOLANA_PUBLIC_RPC: &str = "https://api.mainnet-beta.solana.com";
-
-fn get_default_rpc_url() -> Option<String> {
-    match env::var("HELIUS_API_KEY") {
-        Ok(api_key) => Some(HELIUS_RPC_TEMPLATE.replace("{}", &api_key).to_string()),
-        Err(_) => {
-            eprintln!("Warning: HELIUS_API_KEY not set. Using public Solana RPC.");
-            eprintln!("For better performance, get a Helius API key at https://helius.dev");
-            None
-        }
-    }
+pub struct SolanaClient {
+    rpc: Failover<RpcClient>,
 }
 
-pub struct SolanaClient {
-    client: RpcClient,
+/// Timeouts, connection failures, rate limits and server errors move on to
+/// the next endpoint. HTTP errors lose their URL, which can hold an API key.
+fn rpc_error(error: ClientError) -> CallError {
+    let (fail_over, error) = match error.kind {
+        ClientErrorKind::Reqwest(e) => (
+            e.is_timeout() || e.is_connect() || e.status().is_some_and(|status| should_fail_over(status.as_u16())),
+            anyhow::Error::new(e.without_url()).context("Failed to send RPC request"),
+        ),
+        kind => (false, anyhow::Error::new(ClientError { request: error.request, kind })),
+    };
+
+    if fail_over {
+        CallError::FailOver(error)
+    } else {
+        CallError::Fatal(error)
+    }
 }
 
 #[derive(Debug)]
@@ -36,264 +57,714 @@ pub struct TokenBalance {
     pub mint: String,
     pub name: Option<String>,
     pub symbol: Option<String>,
-    pub decimals: u8,
-    pub ui_amount: f64,
-    pub usd_price: Option<f64>,
-    pub usd_value: Option<f64>,
+    pub amount: TokenAmount,
 }
 
-#[derive(Debug)]
-pub struct AccountBalances {
-    pub sol_balance: f64,
-    pub sol_usd_price: Option<f64>,
-    pub sol_usd_value: Option<f64>,
-    pub token_balances: Vec<TokenBalance>,
-    pub total_usd_value: Option<f64>,
-}
+/// getMultipleAccounts accepts at most 100 accounts per request
+const MULTIPLE_ACCOUNTS_CHUNK: usize = 100;
 
-#[derive(Debug)]
-pub struct SolanaTransaction {
-    pub signature: String,
-    pub timestamp: Option<i64>,
-    pub slot: u64,
-    pub success: bool,
-    pub memo: Option<String>,
-    pub sol_change: f64, // SOL change (positive = received, negative = sent)
+/// getSignaturesForAddress returns at most 1000 signatures per request
+const SIGNATURES_PAGE: usize = 1000;
+
+/// Transactions fetched at the same time when reading history
+const TRANSACTION_CONCURRENCY: usize = 4;
+
+/// What a token account's balance means, read from its mint. Both token
+/// programs share the mint layout; only Token-2022 mints carry extensions.
+#[derive(Debug, Default)]
+struct MintInfo {
+    decimals: u8,
+    name: Option<String>,
+    symbol: Option<String>,
+    interest: Option<InterestBearingConfig>,
+    transfer_fee: Option<TransferFeeConfig>,
 }
 
-impl SolanaClient {
-    pub fn new(rpc_url: Option<String>) -> Self {
-        let url = rpc_url
-            .or_else(get_default_rpc_url)
-            .unwrap_or_else(|| SOLANA_PUBLIC_RPC.to_string());
+impl MintInfo {
+    /// Decimals and extensions of a mint account; name and symbol come from
+    /// the token-metadata extension, if the mint has one
+    fn unpack(data: &[u8]) -> Option<Self> {
+        let mint = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(data).ok()?;
+        let metadata = mint.get_variable_len_extension::<TokenMetadata>().ok();
+        Some(Self {
+            decimals: mint.base.decimals,
+            name: metadata.as_ref().map(|metadata| metadata.name.clone()),
+            symbol: metadata.map(|metadata| metadata.symbol),
+            interest: mint.get_extension::<InterestBearingConfig>().ok().copied(),
+            transfer_fee: mint.get_extension::<TransferFeeConfig>().ok().copied(),
+        })
+    }
 
+    fn cached(metadata: CachedMetadata) -> Self {
         Self {
-            client: RpcClient::new(url),
+            decimals: metadata.decimals,
+            name: metadata.name,
+            symbol: metadata.symbol,
+            ..Default::default()
         }
     }
 
-    fn get_token_metadata(&self, mint: &Pubkey) -> Option<(String, String)> {
-        // Derive the metadata PDA for this mint
-        let metadata_seeds = &[
-            b"metadata",
-            mpl_token_metadata::ID.as_ref(),
-            mint.as_ref(),
-        ];
-
-        let (metadata_pda, _) = Pubkey::find_program_address(
-            metadata_seeds,
-            &mpl_token_metadata::ID,
-        );
+    /// The balance as shown to users: net of the fee a transfer of the whole
+    /// balance would be charged this epoch, plus accrued interest
+    fn amount(&self, raw: u64, epoch: Option<u64>) -> TokenAmount {
+        let net = match (&self.transfer_fee, epoch) {
+            (Some(fee), Some(epoch)) => raw.saturating_sub(fee.calculate_epoch_fee(epoch, raw).unwrap_or(0)),
+            _ => raw,
+        };
 
-        // Try to fetch the metadata account
-        if let Ok(account_data) = self.client.get_account_data(&metadata_pda) {
-            if let Ok(metadata) = Metadata::from_bytes(&account_data) {
-                return Some((metadata.name.trim_matches('\0').to_string(), metadata.symbol.trim_matches('\0').to_string()));
-            }
+        let amount = TokenAmount::new(raw, self.decimals);
+        let interest = self.interest.as_ref().and_then(|interest| {
+            interest.amount_to_ui_amount(net, self.decimals, chrono::Utc::now().timestamp())
+        });
+        match interest.and_then(|ui| BigDecimal::from_str(&ui).ok()) {
+            Some(ui_amount) => amount.with_ui_amount(ui_amount),
+            None if net != raw => amount.with_ui_amount(TokenAmount::new(net, self.decimals).to_decimal()),
+            None => amount,
         }
+    }
+}
 
-        None
+/// `fetch` run on chunks of at most MULTIPLE_ACCOUNTS_CHUNK keys at the same
+/// time, with the results joined back in the order of `pubkeys`
+async fn fetch_chunked<'a, T, F, Fut>(pubkeys: &'a [Pubkey], fetch: F) -> Result<Vec<T>>
+where
+    F: Fn(&'a [Pubkey]) -> Fut,
+    Fut: Future<Output = Result<Vec<T>>>,
+{
+    let fetched = futures::future::join_all(pubkeys.chunks(MULTIPLE_ACCOUNTS_CHUNK).map(&fetch)).await;
+
+    let mut results = Vec::with_capacity(pubkeys.len());
+    for (chunk, fetched) in pubkeys.chunks(MULTIPLE_ACCOUNTS_CHUNK).zip(fetched) {
+        let fetched = fetched?;
+        if fetched.len() != chunk.len() {
+            anyhow::bail!("getMultipleAccounts returned {} accounts for {} keys", fetched.len(), chunk.len());
+        }
+        results.extend(fetched);
     }
+    Ok(results)
+}
 
-    fn get_mint_decimals(&self, mint: &Pubkey) -> Option<u8> {
-        if let Ok(account_data) = self.client.get_account_data(mint) {
-            if let Ok(mint_account) = spl_token::state::Mint::unpack(&account_data) {
-                return Some(mint_account.decimals);
+/// Mint accounts and Metaplex metadata accounts to read. Mints in the token
+/// cache need neither, except Token-2022 mints: their interest rate and
+/// transfer fee can change, so the mint itself is re-read.
+fn accounts_to_read(mints: &BTreeMap<Pubkey, Pubkey>, cached: &HashMap<Pubkey, CachedMetadata>) -> (Vec<Pubkey>, Vec<Pubkey>) {
+    let mint_reads = mints
+        .iter()
+        .filter(|(mint, program)| !cached.contains_key(mint) || **program == spl_token_2022::id())
+        .map(|(mint, _)| *mint)
+        .collect();
+    let metadata_reads = mints.keys().filter(|mint| !cached.contains_key(mint)).copied().collect();
+    (mint_reads, metadata_reads)
+}
+
+/// Cached names and symbols override those of the mints that were read;
+/// mints that weren't read are taken from the cache as they are
+fn merge_cached(infos: &mut HashMap<Pubkey, MintInfo>, cached: HashMap<Pubkey, CachedMetadata>) {
+    for (mint, metadata) in cached {
+        match infos.get_mut(&mint) {
+            Some(info) => {
+                info.name = metadata.name;
+                info.symbol = metadata.symbol;
+            }
+            None => {
+                infos.insert(mint, MintInfo::cached(metadata));
             }
         }
-        None
     }
+}
 
-    pub fn get_balances(&self, address: &str) -> Result<AccountBalances> {
-        let pubkey = Pubkey::from_str(address)
-            .context("Invalid Solana address")?;
+/// Address of a mint's Metaplex metadata account
+fn metadata_pda(mint: &Pubkey) -> Pubkey {
+    let seeds = &[b"metadata", mpl_token_metadata::ID.as_ref(), mint.as_ref()];
+    Pubkey::find_program_address(seeds, &mpl_token_metadata::ID).0
+}
 
-        // Get SOL balance
-        let lamports = self.client
-            .get_balance(&pubkey)
-            .context("Failed to fetch SOL balance")?;
-        let sol_balance = lamports as f64 / 1_000_000_000.0;
-
-        // Get token accounts
-        let token_accounts = self.client
-            .get_token_accounts_by_owner(&pubkey, solana_client::rpc_request::TokenAccountsFilter::ProgramId(spl_token::id()))
-            .context("Failed to fetch token accounts")?;
-
-        let mut token_balances = Vec::new();
-
-        for account in token_accounts {
-            // Handle different UiAccountData formats
-            match &account.account.data {
-                UiAccountData::Binary(data, _encoding) => {
-                    // Decode base64 data
-                    if let Ok(decoded) = BASE64_STANDARD.decode(data) {
-                        // Parse token account data
-                        if let Ok(token_account) = spl_token::state::Account::unpack(&decoded) {
-                            let mint_pubkey = token_account.mint;
-
-                            // Fetch decimals from mint
-                            let decimals = self.get_mint_decimals(&mint_pubkey).unwrap_or(0);
-
-                            // Calculate UI amount
-                            let ui_amount = if decimals > 0 {
-                                token_account.amount as f64 / 10_f64.powi(decimals as i32)
-                            } else {
-                                token_account.amount as f64
-                            };
-
-                            // Try to fetch metadata
-                            let (name, symbol) = self.get_token_metadata(&mint_pubkey)
-                                .map(|(n, s)| (Some(n), Some(s)))
-                                .unwrap_or((None, None));
-
-                            token_balances.push(TokenBalance {
-                                mint: mint_pubkey.to_string(),
-                                name,
-                                symbol,
-                                decimals,
-                                ui_amount,
-                                usd_price: None,
-                                usd_value: None,
-                            });
-                        }
-                    }
+/// Name and symbol from a Metaplex metadata account
+fn parse_metaplex_metadata(data: &[u8]) -> Option<(String, String)> {
+    let metadata = Metadata::from_bytes(data).ok()?;
+    Some((metadata.name.trim_matches('\0').to_string(), metadata.symbol.trim_matches('\0').to_string()))
+}
+
+/// A wallet's token account, of either token program
+#[derive(Debug)]
+struct TokenAccount {
+    program: Pubkey,
+    mint: Pubkey,
+    amount: u64,
+    /// Decimals, when the RPC returned the account parsed
+    decimals: Option<u8>,
+}
+
+/// Mint, raw amount and (when the RPC parsed it) decimals of a token account
+/// of either token program, in either of the encodings the RPC may return
+fn parse_token_account(data: &UiAccountData) -> Option<(Pubkey, u64, Option<u8>)> {
+    match data {
+        UiAccountData::Binary(data, _encoding) => {
+            let decoded = BASE64_STANDARD.decode(data).ok()?;
+            let account = StateWithExtensions::<spl_token_2022::state::Account>::unpack(&decoded).ok()?;
+            Some((account.base.mint, account.base.amount, None))
+        }
+        UiAccountData::Json(parsed) => {
+            if parsed.parsed.get("type").and_then(|v| v.as_str()) != Some("account") {
+                return None;
+            }
+            let info = parsed.parsed.get("info")?;
+            let mint = Pubkey::from_str(info.get("mint")?.as_str()?).ok()?;
+            let token_amount = info.get("tokenAmount")?;
+            // The raw integer amount; uiAmount is a lossy float
+            let amount = token_amount.get("amount")?.as_str()?.parse().ok()?;
+            let decimals = token_amount.get("decimals").and_then(|d| d.as_u64()).and_then(|d| u8::try_from(d).ok());
+            Some((mint, amount, decimals))
+        }
+        _ => None,
+    }
+}
+
+/// A stake account the tracked address can stake or withdraw from, split by
+/// the state of its delegation in the current epoch (all in lamports)
+#[derive(Debug, Default, PartialEq)]
+pub struct StakeAccount {
+    pub address: String,
+    /// Vote account of the validator the stake is delegated to
+    pub validator: Option<String>,
+    pub delegated: u64,
+    pub activating: u64,
+    pub deactivating: u64,
+    /// Undelegated or fully deactivated lamports, including the rent reserve
+    pub inactive: u64,
+}
+
+impl StakeAccount {
+    /// Classify a stake account's lamports. Stake delegated in an epoch is
+    /// effective from the next one, and deactivated stake is withdrawable from
+    /// the epoch after deactivation; the network's warmup and cooldown rate
+    /// limits are not modelled.
+    fn new(address: String, lamports: u64, state: &StakeStateV2, epoch: u64) -> Self {
+        let mut stake = Self {
+            address,
+            inactive: lamports,
+            ..Default::default()
+        };
+
+        if let StakeStateV2::Stake(_, delegated, _) = state {
+            let delegation = &delegated.delegation;
+            stake.validator = Some(delegation.voter_pubkey.to_string());
+
+            let amount = delegation.stake.min(lamports);
+            let deactivated = delegation.deactivation_epoch != u64::MAX;
+            if deactivated && delegation.deactivation_epoch == delegation.activation_epoch {
+                // Deactivated before it ever became effective
+            } else if deactivated {
+                if epoch <= delegation.deactivation_epoch {
+                    stake.deactivating = amount;
                 }
-                UiAccountData::Json(parsed) => {
-                    // Handle JSON parsed account data
-                    if let Some(info) = parsed.parsed.as_object() {
-                        if let (Some(info_obj), Some(type_str)) =
-                            (info.get("info").and_then(|v| v.as_object()), info.get("type").and_then(|v| v.as_str())) {
-                            if type_str == "account" {
-                                let mint = info_obj.get("mint")
-                                    .and_then(|v| v.as_str())
-                                    .unwrap_or("unknown")
-                                    .to_string();
-                                let decimals = info_obj.get("tokenAmount")
-                                    .and_then(|v| v.as_object())
-                                    .and_then(|ta| ta.get("decimals"))
-                                    .and_then(|d| d.as_u64())
-                                    .unwrap_or(0) as u8;
-                                let ui_amount = info_obj.get("tokenAmount")
-                                    .and_then(|v| v.as_object())
-                                    .and_then(|ta| ta.get("uiAmount"))
-                                    .and_then(|u| u.as_f64())
-                                    .unwrap_or(0.0);
-
-                                // Try to fetch metadata
-                                let (name, symbol) = if let Ok(mint_pubkey) = Pubkey::from_str(&mint) {
-                                    self.get_token_metadata(&mint_pubkey)
-                                        .map(|(n, s)| (Some(n), Some(s)))
-                                        .unwrap_or((None, None))
-                                } else {
-                                    (None, None)
-                                };
-
-                                token_balances.push(TokenBalance {
-                                    mint,
-                                    name,
-                                    symbol,
-                                    decimals,
-                                    ui_amount,
-                                    usd_price: None,
-                                    usd_value: None,
-                                });
-                            }
-                        }
-                    }
+            } else if epoch > delegation.activation_epoch {
+                stake.delegated = amount;
+            } else {
+                stake.activating = amount;
+            }
+            stake.inactive = lamports - stake.delegated - stake.activating - stake.deactivating;
+        }
+
+        stake
+    }
+
+    pub fn lamports(&self) -> u64 {
+        self.delegated + self.activating + self.deactivating + self.inactive
+    }
+
+    fn summary(&self) -> String {
+        let sol = |lamports: u64| TokenAmount::new(lamports, 9);
+        format!(
+            "{} SOL delegated, {} activating, {} deactivating, {} inactive (validator {})",
+            sol(self.delegated),
+            sol(self.activating),
+            sol(self.deactivating),
+            sol(self.inactive),
+            self.validator.as_deref().unwrap_or("none"),
+        )
+    }
+}
+
+#[derive(Debug)]
+pub struct AccountBalances {
+    pub sol_balance: TokenAmount,
+    pub token_balances: Vec<TokenBalance>,
+    pub stake_accounts: Vec<StakeAccount>,
+}
+
+/// The tracked wallet's side of a transaction
+#[derive(Debug, Default)]
+struct TransactionEffects {
+    fee: Option<TokenAmount>,
+    changes: Vec<BalanceChange>,
+    counterparties: BTreeSet<String>,
+}
+
+/// Work out the fee `wallet` paid and how its SOL and token balances changed
+/// from a transaction's pre and post balances. Counterparties are the other
+/// accounts (for tokens, their owners) whose balance of the same asset moved
+/// the opposite way.
+fn transaction_effects(wallet: &str, account_keys: &[String], meta: &UiTransactionStatusMeta) -> TransactionEffects {
+    let mut effects = TransactionEffects::default();
+
+    let lamport_change = |index: usize| {
+        let pre = meta.pre_balances.get(index).copied().unwrap_or(0);
+        let post = meta.post_balances.get(index).copied().unwrap_or(0);
+        BigInt::from(post) - BigInt::from(pre)
+    };
+
+    let pre_tokens: Vec<UiTransactionTokenBalance> = Option::from(meta.pre_token_balances.clone()).unwrap_or_default();
+    let post_tokens: Vec<UiTransactionTokenBalance> = Option::from(meta.post_token_balances.clone()).unwrap_or_default();
+
+    // Token balance changes per owner and mint. Old transactions don't
+    // record owners; the token account stands in for its owner there.
+    let mut token_changes: BTreeMap<(String, String), (BigInt, u8)> = BTreeMap::new();
+    for (balances, sign) in [(&pre_tokens, -1), (&post_tokens, 1)] {
+        for balance in balances {
+            let Ok(amount) = BigInt::from_str(&balance.ui_token_amount.amount) else {
+                continue;
+            };
+            let owner = Option::<String>::from(balance.owner.clone())
+                .or_else(|| account_keys.get(balance.account_index as usize).cloned())
+                .unwrap_or_default();
+            let entry = token_changes
+                .entry((owner, balance.mint.clone()))
+                .or_insert_with(|| (BigInt::zero(), balance.ui_token_amount.decimals));
+            entry.0 += amount * sign;
+        }
+    }
+    let token_accounts: HashSet<usize> =
+        pre_tokens.iter().chain(&post_tokens).map(|balance| balance.account_index as usize).collect();
+
+    if let Some(index) = account_keys.iter().position(|key| key == wallet) {
+        let mut change = lamport_change(index);
+
+        // The first account pays the fee
+        if index == 0 {
+            effects.fee = Some(TokenAmount::new(meta.fee, 9));
+            change += meta.fee;
+        }
+
+        if !change.is_zero() {
+            for (other, key) in account_keys.iter().enumerate() {
+                if other != index && !token_accounts.contains(&other) && opposite(&change, &lamport_change(other)) {
+                    effects.counterparties.insert(key.clone());
                 }
-                _ => {}
+            }
+            effects.changes.push(BalanceChange {
+                asset_id: None,
+                symbol: Some("SOL".to_string()),
+                amount: TokenAmount::new(change, 9),
+            });
+        }
+    }
+
+    for ((owner, mint), (change, decimals)) in &token_changes {
+        if owner != wallet || change.is_zero() {
+            continue;
+        }
+        for ((other, other_mint), (other_change, _)) in &token_changes {
+            if other_mint == mint && other != wallet && opposite(change, other_change) {
+                effects.counterparties.insert(other.clone());
             }
         }
+        effects.changes.push(BalanceChange {
+            asset_id: Some(mint.clone()),
+            symbol: None,
+            amount: TokenAmount::new(change.clone(), *decimals),
+        });
+    }
 
-        Ok(AccountBalances {
-            sol_balance,
-            sol_usd_price: None,
-            sol_usd_value: None,
-            token_balances,
-            total_usd_value: None,
+    effects
+}
+
+/// Whether two balance changes go in opposite directions
+fn opposite(a: &BigInt, b: &BigInt) -> bool {
+    a.sign() != Sign::NoSign && a.sign() == -b.sign()
+}
+
+impl SolanaClient {
+    pub fn new(endpoints: Vec<RpcEndpoint>) -> Self {
+        let clients = endpoints
+            .into_iter()
+            .map(|endpoint| (endpoint.label(), RpcClient::new(endpoint.url)))
+            .collect();
+
+        Self {
+            rpc: Failover::new(clients),
+        }
+    }
+
+    /// Accounts at `pubkeys`, in order, with `None` where no account exists.
+    /// Long lists are split into chunks that are requested concurrently.
+    async fn get_multiple_accounts(&self, pubkeys: &[Pubkey]) -> Result<Vec<Option<Account>>> {
+        fetch_chunked(pubkeys, |chunk| {
+            self.rpc
+                .call(move |client| async move { client.get_multiple_accounts(chunk).await.map_err(rpc_error) })
         })
+        .await
     }
 
-    pub fn get_transactions(&self, address: &str, limit: usize) -> Result<Vec<SolanaTransaction>> {
-        let pubkey = Pubkey::from_str(address)
-            .context("Invalid Solana address")?;
-        let address_str = address.to_string();
+    pub async fn get_balances(&self, address: &str) -> Result<AccountBalances> {
+        self.get_balances_many(&[address])
+            .await
+            .pop()
+            .expect("one result per address")
+    }
 
-        // Get recent transaction signatures
-        let config = GetConfirmedSignaturesForAddress2Config {
-            limit: Some(limit),
-            ..Default::default()
+    /// Balances of several wallets. SOL balances, mints and token metadata
+    /// are read with getMultipleAccounts for all wallets at once; token and
+    /// stake accounts are still listed per wallet. A failed read only
+    /// affects the wallet or token it belongs to.
+    pub async fn get_balances_many(&self, addresses: &[&str]) -> Vec<Result<AccountBalances>> {
+        let pubkeys: Vec<Option<Pubkey>> = addresses.iter().map(|address| Pubkey::from_str(address).ok()).collect();
+        let wallets: Vec<Pubkey> = pubkeys.iter().flatten().copied().collect();
+
+        // SOL balances; a wallet that never received anything has no account
+        let sol = match self.get_multiple_accounts(&wallets).await {
+            Ok(accounts) => accounts,
+            Err(e) => {
+                return pubkeys
+                    .iter()
+                    .map(|pubkey| match pubkey {
+                        Some(_) => Err(anyhow::anyhow!("Failed to fetch SOL balance: {:#}", e)),
+                        None => Err(anyhow::anyhow!("Invalid Solana address")),
+                    })
+                    .collect();
+            }
+        };
+
+        let (token_accounts, stake_accounts) = futures::future::join(
+            futures::future::join_all(wallets.iter().map(|wallet| self.get_token_accounts(wallet))),
+            futures::future::join_all(wallets.iter().map(|wallet| self.find_stake_accounts(wallet))),
+        )
+        .await;
+
+        // Every mint held by any of the wallets, with the program that owns it
+        let mut held_mints = BTreeMap::new();
+        for account in token_accounts.iter().flatten().flatten() {
+            held_mints.insert(account.mint, account.program);
+        }
+        let mints = self.resolve_mints(&held_mints).await;
+
+        // Stake activation and transfer fees depend on the epoch; only ask
+        // for it when needed
+        let stake_found = stake_accounts.iter().any(|found| found.as_ref().is_ok_and(|found| !found.is_empty()));
+        let epoch = if stake_found || mints.values().any(|mint| mint.transfer_fee.is_some()) {
+            match self.rpc.call(|client| async move { client.get_epoch_info().await.map_err(rpc_error) }).await {
+                Ok(info) => Some(info.epoch),
+                Err(e) => {
+                    eprintln!("Warning: Failed to fetch epoch: {:#}", e);
+                    None
+                }
+            }
+        } else {
+            None
         };
 
-        let signatures = self.client
-            .get_signatures_for_address_with_config(&pubkey, config)
-            .context("Failed to fetch transaction signatures")?;
+        let mut results = wallets.iter().zip(sol).zip(token_accounts).zip(stake_accounts).map(
+            |(((wallet, sol), token_accounts), stake_accounts)| -> Result<AccountBalances> {
+                let token_accounts = token_accounts.context("Failed to fetch token accounts")?;
+
+                let token_balances = token_accounts
+                    .into_iter()
+                    .map(|account| match mints.get(&account.mint) {
+                        Some(mint_info) => TokenBalance {
+                            mint: account.mint.to_string(),
+                            name: mint_info.name.clone(),
+                            symbol: mint_info.symbol.clone(),
+                            amount: mint_info.amount(account.amount, epoch),
+                        },
+                        // The mint couldn't be read; fall back to the decimals
+                        // the RPC parsed from the token account
+                        None => TokenBalance {
+                            mint: account.mint.to_string(),
+                            name: None,
+                            symbol: None,
+                            amount: TokenAmount::new(account.amount, account.decimals.unwrap_or(0)),
+                        },
+                    })
+                    .collect();
+
+                // Stake accounts are optional extras; an RPC that refuses
+                // getProgramAccounts shouldn't hide the rest of the wallet
+                let stake_accounts = match (stake_accounts, epoch) {
+                    (Ok(found), Some(epoch)) => found
+                        .into_iter()
+                        .filter_map(|(address, account)| {
+                            let state: StakeStateV2 = bincode::deserialize(&account.data).ok()?;
+                            Some(StakeAccount::new(address.to_string(), account.lamports, &state, epoch))
+                        })
+                        .collect(),
+                    (Ok(found), None) if found.is_empty() => Vec::new(),
+                    (Ok(_), None) => {
+                        eprintln!("Warning: Skipping stake accounts for {}: epoch unknown", wallet);
+                        Vec::new()
+                    }
+                    (Err(e), _) => {
+                        eprintln!("Warning: Failed to fetch stake accounts for {}: {:#}", wallet, e);
+                        Vec::new()
+                    }
+                };
+
+                Ok(AccountBalances {
+                    sol_balance: TokenAmount::new(sol.map_or(0, |account| account.lamports), 9),
+                    token_balances,
+                    stake_accounts,
+                })
+            },
+        );
+
+        pubkeys
+            .iter()
+            .map(|pubkey| match pubkey {
+                Some(_) => results.next().expect("one result per wallet"),
+                None => Err(anyhow::anyhow!("Invalid Solana address")),
+            })
+            .collect()
+    }
+
+    fn snapshot(&self, account: &TrackedAccount, balances: AccountBalances) -> AccountSnapshot {
+        let mut holdings = vec![Holding::native("SOL", balances.sol_balance)];
+        holdings.extend(balances.stake_accounts.iter().map(|stake| {
+            Holding::staked("SOL", stake.address.clone(), TokenAmount::new(stake.lamports(), 9))
+        }));
+        holdings.extend(balances.token_balances.into_iter().map(|token| {
+            Holding::token(token.mint, token.symbol, token.name, token.amount)
+        }));
+
+        let mut snapshot = AccountSnapshot::new(account, holdings).with_detail("RPC Endpoint", self.rpc.served_by());
+        for stake in &balances.stake_accounts {
+            snapshot = snapshot.with_detail(&format!("Stake {}", stake.address), stake.summary());
+        }
+        snapshot
+    }
+
+    /// Token accounts of the original token program and of Token-2022
+    async fn get_token_accounts(&self, owner: &Pubkey) -> Result<Vec<TokenAccount>> {
+        let programs = [spl_token::id(), spl_token_2022::id()];
+        let results = futures::future::join_all(programs.iter().map(|program| {
+            self.rpc.call(move |client| async move {
+                client
+                    .get_token_accounts_by_owner(owner, solana_client::rpc_request::TokenAccountsFilter::ProgramId(*program))
+                    .await
+                    .map_err(rpc_error)
+            })
+        }))
+        .await;
+
+        let mut token_accounts = Vec::new();
+        for (program, result) in programs.iter().zip(results) {
+            match result {
+                Ok(accounts) => {
+                    token_accounts.extend(accounts.iter().filter_map(|account| {
+                        let (mint, amount, decimals) = parse_token_account(&account.account.data)?;
+                        Some(TokenAccount {
+                            program: *program,
+                            mint,
+                            amount,
+                            decimals,
+                        })
+                    }));
+                }
+                Err(e) if *program == spl_token::id() => return Err(e),
+                Err(e) => eprintln!("Warning: Failed to fetch Token-2022 accounts: {:#}", e),
+            }
+        }
 
-        let mut transactions = Vec::new();
+        Ok(token_accounts)
+    }
 
-        // Only fetch detailed info for first 20 transactions to avoid rate limits
-        let detail_limit = 20.min(signatures.len());
+    /// Decimals, name, symbol and extensions of each mint, keyed by mint.
+    /// Decimals, names and symbols come from the token cache when known (see
+    /// `accounts_to_read`). Everything else is fetched in one batch of
+    /// getMultipleAccounts calls and cached. Mints that can't be read are
+    /// left out.
+    async fn resolve_mints(&self, mints: &BTreeMap<Pubkey, Pubkey>) -> HashMap<Pubkey, MintInfo> {
+        let cached: HashMap<Pubkey, CachedMetadata> = token_cache::with_cache(|cache| {
+            mints
+                .keys()
+                .filter_map(|mint| Some((*mint, cache.metadata(&Chain::Solana, &mint.to_string())?.clone())))
+                .collect()
+        });
+
+        let (mint_reads, metadata_reads) = accounts_to_read(mints, &cached);
+
+        let addresses: Vec<Pubkey> = mint_reads.iter().copied().chain(metadata_reads.iter().map(metadata_pda)).collect();
+        let accounts = self.get_multiple_accounts(&addresses).await.unwrap_or_else(|e| {
+            eprintln!("Warning: Failed to fetch token mints: {:#}", e);
+            Vec::new()
+        });
+
+        let mut infos = HashMap::new();
+        if accounts.len() == addresses.len() {
+            let (mint_accounts, metadata_accounts) = accounts.split_at(mint_reads.len());
+
+            for (mint, account) in mint_reads.iter().zip(mint_accounts) {
+                if let Some(info) = account.as_ref().and_then(|account| MintInfo::unpack(&account.data)) {
+                    infos.insert(*mint, info);
+                }
+            }
 
-        for (idx, sig_info) in signatures.iter().enumerate() {
-            let signature_str = sig_info.signature.clone();
-            let timestamp = sig_info.block_time;
-            let slot = sig_info.slot;
-            let success = sig_info.err.is_none();
-            let memo = sig_info.memo.clone();
+            for (mint, account) in metadata_reads.iter().zip(metadata_accounts) {
+                let Some(info) = infos.get_mut(mint) else {
+                    continue;
+                };
 
-            // Try to get transaction details to calculate SOL change (only for first few)
-            let mut sol_change: f64 = 0.0;
+                // Metaplex metadata first, then the Token-2022 metadata extension
+                if let Some((name, symbol)) = account.as_ref().and_then(|account| parse_metaplex_metadata(&account.data)) {
+                    info.name = Some(name);
+                    info.symbol = Some(symbol);
+                }
 
-            if idx < detail_limit {
-                if let Ok(sig) = Signature::from_str(&signature_str) {
-                    let tx_config = RpcTransactionConfig {
-                        encoding: Some(UiTransactionEncoding::JsonParsed),
-                        commitment: Some(CommitmentConfig::confirmed()),
-                        max_supported_transaction_version: Some(0),
+                let metadata = CachedMetadata {
+                    decimals: info.decimals,
+                    symbol: info.symbol.clone(),
+                    name: info.name.clone(),
+                };
+                token_cache::with_cache(|cache| cache.insert_metadata(&Chain::Solana, &mint.to_string(), metadata));
+            }
+        }
+
+        merge_cached(&mut infos, cached);
+
+        token_cache::save();
+        infos
+    }
+
+    /// Stake accounts whose stake or withdraw authority is `owner`
+    async fn find_stake_accounts(&self, owner: &Pubkey) -> Result<BTreeMap<Pubkey, Account>> {
+        // Meta starts after the 4-byte enum tag and the 8-byte rent reserve:
+        // the staker authority is at offset 12, the withdrawer at 44
+        let mut accounts = BTreeMap::new();
+        for offset in [12, 44] {
+            let found = self
+                .rpc
+                .call(|client| async move {
+                    let config = RpcProgramAccountsConfig {
+                        filters: Some(vec![RpcFilterType::Memcmp(Memcmp::new_base58_encoded(offset, owner.as_ref()))]),
+                        account_config: RpcAccountInfoConfig {
+                            encoding: Some(UiAccountEncoding::Base64),
+                            ..Default::default()
+                        },
+                        ..Default::default()
                     };
+                    client
+                        .get_program_accounts_with_config(&solana_sdk::stake::program::id(), config)
+                        .await
+                        .map_err(rpc_error)
+                })
+                .await?;
+            accounts.extend(found);
+        }
 
-                    // Small delay between RPC calls to avoid rate limiting
-                    if idx > 0 {
-                        std::thread::sleep(std::time::Duration::from_millis(100));
-                    }
+        Ok(accounts)
+    }
 
-                    match self.client.get_transaction_with_config(&sig, tx_config) {
-                        Ok(tx) => {
-                            // Calculate balance change from pre/post balances
-                            if let Some(meta) = &tx.transaction.meta {
-                                // Get account keys from the transaction JSON
-                                let account_keys = Self::extract_account_keys(&tx.transaction.transaction)
-                                    .unwrap_or_default();
-
-                                for (i, key) in account_keys.iter().enumerate() {
-                                    if key == &address_str {
-                                        if i < meta.pre_balances.len() && i < meta.post_balances.len() {
-                                            let lamport_change = meta.post_balances[i] as i64 - meta.pre_balances[i] as i64;
-                                            sol_change = lamport_change as f64 / 1_000_000_000.0;
-                                        }
-                                        break;
-                                    }
-                                }
-                            }
-                        }
-                        Err(_) => {
-                            // Transaction fetch failed - likely rate limited or old transaction
-                        }
-                    }
+    /// The wallet's transactions within `range`, newest first. Signatures are
+    /// paged with a `before` cursor until the start of the range, then every
+    /// transaction is fetched for its fee and balance changes. A transaction
+    /// that can't be fetched is kept without them, with a warning.
+    pub async fn get_transactions(&self, address: &str, range: TransactionRange) -> Result<Vec<WalletTransaction>> {
+        let pubkey = Pubkey::from_str(address)
+            .context("Invalid Solana address")?;
+
+        let mut signatures = Vec::new();
+        let mut before: Option<Signature> = None;
+        'pages: loop {
+            let page = self.rpc
+                .call(|client| async move {
+                    let config = GetConfirmedSignaturesForAddress2Config {
+                        before,
+                        limit: Some(SIGNATURES_PAGE),
+                        ..Default::default()
+                    };
+                    client
+                        .get_signatures_for_address_with_config(&pubkey, config)
+                        .await
+                        .map_err(rpc_error)
+                })
+                .await
+                .context("Failed to fetch transaction signatures")?;
+            let full_page = page.len() == SIGNATURES_PAGE;
+
+            for info in page {
+                let block_time = info.block_time;
+                if range.since.is_some_and(|since| block_time.is_some_and(|time| time < since)) {
+                    break 'pages;
+                }
+                before = Some(Signature::from_str(&info.signature).context("Invalid transaction signature")?);
+                if range.until.is_some_and(|until| block_time.is_some_and(|time| time > until)) {
+                    continue;
+                }
+
+                signatures.push(info);
+                if range.limit.is_some_and(|limit| signatures.len() >= limit) {
+                    break 'pages;
                 }
             }
 
-            transactions.push(SolanaTransaction {
-                signature: signature_str,
-                timestamp,
-                slot,
-                success,
-                memo,
-                sol_change,
-            });
+            if !full_page {
+                break;
+            }
         }
 
-        Ok(transactions)
+        let effects: Vec<Result<TransactionEffects>> = futures::stream::iter(
+            signatures.iter().map(|info| self.get_transaction_effects(&info.signature, address)),
+        )
+        .buffered(TRANSACTION_CONCURRENCY)
+        .collect()
+        .await;
+
+        Ok(signatures
+            .into_iter()
+            .zip(effects)
+            .map(|(info, effects)| {
+                let mut effects = effects.unwrap_or_else(|e| {
+                    eprintln!("Warning: Failed to fetch transaction {}: {:#}", info.signature, e);
+                    TransactionEffects::default()
+                });
+
+                for change in &mut effects.changes {
+                    if let Some(mint) = &change.asset_id {
+                        change.symbol = token_cache::with_cache(|cache| {
+                            cache.metadata(&Chain::Solana, mint).and_then(|metadata| metadata.symbol.clone())
+                        });
+                    }
+                }
+
+                WalletTransaction {
+                    id: info.signature,
+                    block: info.slot,
+                    timestamp: info.block_time,
+                    success: info.err.is_none(),
+                    memo: info.memo,
+                    fee: effects.fee,
+                    changes: effects.changes,
+                    counterparties: effects.counterparties.into_iter().collect(),
+                }
+            })
+            .collect())
+    }
+
+    async fn get_transaction_effects(&self, signature: &str, wallet: &str) -> Result<TransactionEffects> {
+        let signature = &Signature::from_str(signature).context("Invalid transaction signature")?;
+        let transaction = self.rpc
+            .call(|client| async move {
+                let config = RpcTransactionConfig {
+                    encoding: Some(UiTransactionEncoding::JsonParsed),
+                    commitment: Some(CommitmentConfig::confirmed()),
+                    max_supported_transaction_version: Some(0),
+                };
+                client.get_transaction_with_config(signature, config).await.map_err(rpc_error)
+            })
+            .await?;
+
+        let meta = transaction.transaction.meta.as_ref().context("Transaction has no status metadata")?;
+        let account_keys = Self::extract_account_keys(&transaction.transaction.transaction)
+            .context("Transaction has no readable account keys")?;
+        Ok(transaction_effects(wallet, &account_keys, meta))
     }
 
     fn extract_account_keys(tx: &solana_transaction_status_client_types::EncodedTransaction) -> Option<Vec<String>> {
@@ -322,37 +793,103 @@ impl SolanaClient {
     }
 }
 
-// Implement PriceEnrichable trait for Solana balances
-impl crate::PriceEnrichable for AccountBalances {
-    const NATIVE_SYMBOL: &'static str = "SOL";
-
-    fn native_balance(&self) -> f64 {
-        self.sol_balance
+#[async_trait]
+impl BalanceSource for SolanaClient {
+    async fn fetch_snapshot(&self, account: &TrackedAccount) -> Result<AccountSnapshot> {
+        let balances = self.get_balances(account.identifier()).await?;
+        Ok(self.snapshot(account, balances))
     }
 
-    fn set_native_usd_price(&mut self, price: f64) {
-        self.sol_usd_price = Some(price);
-    }
+    async fn fetch_snapshots(&self, accounts: &[&TrackedAccount]) -> Vec<Result<AccountSnapshot>> {
+        let addresses: Vec<&str> = accounts.iter().map(|account| account.identifier()).collect();
+        let balances = self.get_balances_many(&addresses).await;
 
-    fn set_native_usd_value(&mut self, value: f64) {
-        self.sol_usd_value = Some(value);
+        accounts
+            .iter()
+            .zip(balances)
+            .map(|(account, balances)| Ok(self.snapshot(account, balances?)))
+            .collect()
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[tokio::test]
+    async fn test_fetch_chunked_reassembles_in_order() {
+        let pubkeys: Vec<Pubkey> = (0..250).map(|_| Pubkey::new_unique()).collect();
+        let sizes = Mutex::new(Vec::new());
+
+        let fetched = fetch_chunked(&pubkeys, |chunk| {
+            sizes.lock().unwrap().push(chunk.len());
+            async move { Ok(chunk.to_vec()) }
+        })
+        .await
+        .unwrap();
 
-    fn set_total_usd_value(&mut self, value: f64) {
-        self.total_usd_value = Some(value);
+        assert_eq!(*sizes.lock().unwrap(), vec![100, 100, 50]);
+        assert_eq!(fetched, pubkeys);
+
+        // Nothing to fetch, no request
+        let none: Vec<Pubkey> = fetch_chunked(&[], |_| async { anyhow::bail!("unexpected request") }).await.unwrap();
+        assert!(none.is_empty());
     }
 
-    fn enrich_token_balances(&mut self, price_cache: &HashMap<String, f64>) -> f64 {
-        let mut token_total = 0.0;
-        for token in &mut self.token_balances {
-            if let Some(symbol) = &token.symbol {
-                if let Some(&price) = price_cache.get(symbol) {
-                    token.usd_price = Some(price);
-                    token.usd_value = Some(token.ui_amount * price);
-                    token_total += token.usd_value.unwrap_or(0.0);
-                }
+    #[tokio::test]
+    async fn test_fetch_chunked_fails_on_a_bad_chunk() {
+        let pubkeys: Vec<Pubkey> = (0..150).map(|_| Pubkey::new_unique()).collect();
+        let second = pubkeys[100];
+
+        let failed = fetch_chunked(&pubkeys, |chunk| async move {
+            if chunk[0] == second {
+                anyhow::bail!("rate limited");
             }
-        }
-        token_total
+            Ok(chunk.to_vec())
+        })
+        .await;
+        assert_eq!(failed.unwrap_err().to_string(), "rate limited");
+
+        // A short response would shift every later account onto the wrong key
+        let short = fetch_chunked(&pubkeys, |chunk| async move { Ok(chunk[1..].to_vec()) }).await;
+        assert!(short.is_err());
+    }
+
+    #[test]
+    fn test_cached_mints_skip_reads() {
+        let cached_spl = Pubkey::new_unique();
+        let cached_token_2022 = Pubkey::new_unique();
+        let unknown = Pubkey::new_unique();
+        let mints = BTreeMap::from([
+            (cached_spl, spl_token::id()),
+            (cached_token_2022, spl_token_2022::id()),
+            (unknown, spl_token::id()),
+        ]);
+        let metadata = |symbol: &str| CachedMetadata {
+            decimals: 6,
+            symbol: Some(symbol.to_string()),
+            name: None,
+        };
+        let cached = HashMap::from([(cached_spl, metadata("USDC")), (cached_token_2022, metadata("PYUSD"))]);
+
+        let (mints_to_read, metadata_to_read) = accounts_to_read(&mints, &cached);
+        assert_eq!(mints_to_read, vec![cached_token_2022, unknown]);
+        assert_eq!(metadata_to_read, vec![unknown]);
+
+        // The re-read Token-2022 mint keeps its decimals and takes the cached symbol
+        let mut infos = HashMap::from([(
+            cached_token_2022,
+            MintInfo {
+                decimals: 9,
+                ..Default::default()
+            },
+        )]);
+        merge_cached(&mut infos, cached);
+        assert_eq!(infos[&cached_token_2022].decimals, 9);
+        assert_eq!(infos[&cached_token_2022].symbol.as_deref(), Some("PYUSD"));
+        assert_eq!(infos[&cached_spl].decimals, 6);
+        assert_eq!(infos[&cached_spl].symbol.as_deref(), Some("USDC"));
+        assert!(!infos.contains_key(&unknown));
     }
 }
@@ -0,0 +1,622 @@
+use crate::amount::{self, TokenAmount};
+use crate::aptos::AptosClient;
+use crate::bitcoin::BitcoinClient;
+use crate::cosmos::CosmosClient;
+use crate::circle::CircleClient;
+use crate::evm::EvmClient;
+use crate::mercury::MercuryClient;
+use crate::near::NearClient;
+use crate::rpc::RpcConfig;
+use crate::solana::SolanaClient;
+use crate::starknet::StarknetClient;
+use crate::storage::{BankingService, Chain, TrackedAccount};
+use crate::sui::SuiClient;
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use bigdecimal::{BigDecimal, Zero};
+use futures::stream::{FuturesUnordered, StreamExt};
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::hash::Hash;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+
+/// What kind of asset a holding line represents
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HoldingKind {
+    /// The chain's gas token (SOL, ETH, NEAR, ...)
+    Native,
+    /// A token identified by a mint, contract address or coin type
+    Token,
+    /// The gas token locked in staking, e.g. a Solana stake account
+    Staked,
+    /// A currency balance held with a banking service
+    Fiat,
+}
+
+impl HoldingKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HoldingKind::Native => "native",
+            HoldingKind::Token => "token",
+            HoldingKind::Staked => "staked",
+            HoldingKind::Fiat => "fiat",
+        }
+    }
+
+    pub fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "native" => Ok(HoldingKind::Native),
+            "token" => Ok(HoldingKind::Token),
+            "staked" => Ok(HoldingKind::Staked),
+            "fiat" => Ok(HoldingKind::Fiat),
+            _ => anyhow::bail!("Unknown holding kind: {}", s),
+        }
+    }
+}
+
+/// A single asset line within an account
+#[derive(Debug, Clone)]
+pub struct Holding {
+    pub kind: HoldingKind,
+    pub symbol: Option<String>,
+    pub name: Option<String>,
+    /// Mint, contract address or coin type for tokens; stake account for staked holdings
+    pub asset_id: Option<String>,
+    /// Exact balance in the smallest unit, with the asset's decimals
+    pub amount: TokenAmount,
+    pub usd_price: Option<BigDecimal>,
+    pub usd_value: Option<BigDecimal>,
+}
+
+impl Holding {
+    pub fn native(symbol: &str, amount: TokenAmount) -> Self {
+        Self {
+            kind: HoldingKind::Native,
+            symbol: Some(symbol.to_string()),
+            name: None,
+            asset_id: None,
+            amount,
+            usd_price: None,
+            usd_value: None,
+        }
+    }
+
+    pub fn token(
+        asset_id: String,
+        symbol: Option<String>,
+        name: Option<String>,
+        amount: TokenAmount,
+    ) -> Self {
+        Self {
+            kind: HoldingKind::Token,
+            symbol,
+            name,
+            asset_id: Some(asset_id),
+            amount,
+            usd_price: None,
+            usd_value: None,
+        }
+    }
+
+    /// Gas token held in a stake account; priced like the native token
+    pub fn staked(symbol: &str, stake_account: String, amount: TokenAmount) -> Self {
+        Self {
+            kind: HoldingKind::Staked,
+            symbol: Some(symbol.to_string()),
+            name: None,
+            asset_id: Some(stake_account),
+            amount,
+            usd_price: None,
+            usd_value: None,
+        }
+    }
+
+    /// A banking balance. USD balances are priced at face value; other
+    /// currencies are priced through the price cache like any other symbol.
+    pub fn fiat(symbol: &str, amount: BigDecimal) -> Self {
+        let usd_price = if symbol == "USD" || symbol == "USDC" { Some(BigDecimal::from(1)) } else { None };
+
+        Self {
+            kind: HoldingKind::Fiat,
+            symbol: Some(symbol.to_string()),
+            name: None,
+            asset_id: None,
+            usd_value: usd_price.as_ref().map(|_| amount.clone()),
+            amount: TokenAmount::from_decimal(&amount, 2),
+            usd_price,
+        }
+    }
+
+    pub fn decimals(&self) -> u8 {
+        self.amount.decimals()
+    }
+
+    /// Human readable label, e.g. "USD Coin (USDC)"
+    pub fn display_name(&self) -> String {
+        match (&self.name, &self.symbol) {
+            (Some(name), Some(symbol)) => format!("{} ({})", name, symbol),
+            (Some(name), None) => name.clone(),
+            (None, Some(symbol)) => symbol.clone(),
+            (None, None) => "Unknown Token".to_string(),
+        }
+    }
+}
+
+/// Balances of one tracked account at a point in time, in a chain-agnostic shape
+#[derive(Debug, Clone)]
+pub struct AccountSnapshot {
+    pub account: TrackedAccount,
+    pub holdings: Vec<Holding>,
+    /// Extra source-specific information shown alongside the account (e.g. status)
+    pub details: Vec<(String, String)>,
+    pub total_usd_value: Option<BigDecimal>,
+}
+
+impl AccountSnapshot {
+    pub fn new(account: &TrackedAccount, holdings: Vec<Holding>) -> Self {
+        let mut snapshot = Self {
+            account: account.clone(),
+            holdings,
+            details: Vec::new(),
+            total_usd_value: None,
+        };
+        snapshot.update_total();
+        snapshot
+    }
+
+    pub fn with_detail(mut self, label: &str, value: impl Into<String>) -> Self {
+        self.details.push((label.to_string(), value.into()));
+        self
+    }
+
+    /// Company used for portfolio grouping
+    pub fn company(&self) -> &str {
+        if self.account.company().is_empty() {
+            "Uncategorized"
+        } else {
+            self.account.company()
+        }
+    }
+
+    /// Symbols that still need a USD price
+    pub fn price_symbols(&self) -> impl Iterator<Item = &str> {
+        self.holdings
+            .iter()
+            .filter(|h| h.usd_price.is_none())
+            .filter_map(|h| h.symbol.as_deref())
+    }
+
+    /// Fill in USD prices and values from a symbol -> price cache
+    pub fn enrich_from_cache(&mut self, price_cache: &HashMap<String, f64>) {
+        for holding in &mut self.holdings {
+            if holding.usd_price.is_some() {
+                continue;
+            }
+            if let Some(price) = holding.symbol.as_ref().and_then(|s| price_cache.get(s)) {
+                let price = amount::from_f64(*price);
+                holding.usd_value = Some(holding.amount.to_decimal() * &price);
+                holding.usd_price = Some(price);
+            }
+        }
+        self.update_total();
+    }
+
+    fn update_total(&mut self) {
+        let total: BigDecimal = self.holdings.iter().filter_map(|h| h.usd_value.as_ref()).sum();
+        self.total_usd_value = if total > BigDecimal::zero() { Some(total) } else { None };
+    }
+}
+
+/// Which part of a wallet's transaction history to fetch, by block time (Unix seconds)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TransactionRange {
+    pub since: Option<i64>,
+    pub until: Option<i64>,
+    /// At most this many transactions, newest first
+    pub limit: Option<usize>,
+}
+
+/// How one asset's balance of a tracked wallet changed in a transaction
+#[derive(Debug, Clone)]
+pub struct BalanceChange {
+    /// Mint, contract address or coin type; `None` for the chain's gas token
+    pub asset_id: Option<String>,
+    pub symbol: Option<String>,
+    /// Negative when the wallet sent
+    pub amount: TokenAmount,
+}
+
+/// An on-chain transaction as seen by one tracked wallet
+#[derive(Debug)]
+pub struct WalletTransaction {
+    /// Signature or transaction hash
+    pub id: String,
+    /// Slot or block number
+    pub block: u64,
+    pub timestamp: Option<i64>,
+    pub success: bool,
+    pub memo: Option<String>,
+    /// Fee in the gas token, when the wallet paid it
+    pub fee: Option<TokenAmount>,
+    /// Gas token and token balance changes, not counting the fee
+    pub changes: Vec<BalanceChange>,
+    /// Addresses whose balances moved the other way
+    pub counterparties: Vec<String>,
+}
+
+/// A chain or banking integration that can report balances for a tracked account
+#[async_trait]
+pub trait BalanceSource: Send + Sync {
+    async fn fetch_snapshot(&self, account: &TrackedAccount) -> Result<AccountSnapshot>;
+
+    /// Fetch several accounts served by this client. Sources that can batch
+    /// their reads override this; by default accounts are fetched one by one.
+    async fn fetch_snapshots(&self, accounts: &[&TrackedAccount]) -> Vec<Result<AccountSnapshot>> {
+        let mut snapshots = Vec::with_capacity(accounts.len());
+        for account in accounts {
+            snapshots.push(self.fetch_snapshot(account).await);
+        }
+        snapshots
+    }
+}
+
+/// Build the client responsible for an account's chain or banking service
+pub fn client_for(account: &TrackedAccount, rpc: &RpcConfig) -> Result<Box<dyn BalanceSource>> {
+    let client: Box<dyn BalanceSource> = match account {
+        TrackedAccount::Wallet(wallet) => {
+            let endpoints = rpc.endpoints_for(&wallet.chain);
+            match &wallet.chain {
+                Chain::Solana => Box::new(SolanaClient::new(endpoints)),
+                Chain::Near => Box::new(NearClient::new(endpoints)),
+                Chain::Aptos => Box::new(AptosClient::new(endpoints)),
+                Chain::Sui => Box::new(SuiClient::new(endpoints)),
+                Chain::Starknet => Box::new(StarknetClient::new(endpoints)),
+                Chain::Bitcoin => Box::new(BitcoinClient::new(endpoints)),
+                Chain::Cosmos(_) => Box::new(CosmosClient::new(endpoints, &wallet.chain)?),
+                Chain::Ethereum | Chain::Polygon | Chain::BinanceSmartChain | Chain::Arbitrum
+                | Chain::Optimism | Chain::Avalanche | Chain::Base | Chain::Core => {
+                    Box::new(EvmClient::new(endpoints, wallet.chain.clone()))
+                }
+            }
+        }
+        TrackedAccount::Bank(bank) => match &bank.service {
+            BankingService::Mercury => {
+                Box::new(MercuryClient::new().context("Failed to initialize Mercury client")?)
+            }
+            BankingService::Circle => {
+                Box::new(CircleClient::new().context("Failed to initialize Circle client")?)
+            }
+        },
+    };
+
+    Ok(client)
+}
+
+/// Fetch the balances of a single account
+pub async fn fetch_snapshot(account: &TrackedAccount, rpc: &RpcConfig) -> Result<AccountSnapshot> {
+    client_for(account, rpc)?.fetch_snapshot(account).await
+}
+
+/// Limits applied when fetching many accounts at once
+#[derive(Debug, Clone, Copy)]
+pub struct FetchOptions {
+    /// Maximum number of accounts fetched at the same time
+    pub concurrency: usize,
+    /// Maximum number of accounts fetched at the same time from one chain or banking service
+    pub per_endpoint: usize,
+}
+
+impl Default for FetchOptions {
+    fn default() -> Self {
+        Self {
+            concurrency: 8,
+            per_endpoint: 2,
+        }
+    }
+}
+
+/// Fetch balances for every account concurrently, within the limits in `options`.
+/// `on_done` is called as each account finishes; the result keeps the order of
+/// `accounts`, with failed accounts left out.
+///
+/// Wallets on the same EVM chain, and all Solana wallets, are fetched together
+/// as one batch so their reads can share Multicall3, JSON-RPC batch and
+/// getMultipleAccounts requests.
+pub async fn fetch_snapshots<F>(
+    accounts: &[TrackedAccount],
+    rpc: &RpcConfig,
+    options: FetchOptions,
+    mut on_done: F,
+) -> Vec<AccountSnapshot>
+where
+    F: FnMut(&TrackedAccount, Option<&anyhow::Error>),
+{
+    let batches = batch_accounts(accounts);
+
+    let results = run_bounded(
+        &batches,
+        options,
+        |batch| accounts[batch[0]].source_name().to_string(),
+        |batch| fetch_batch(accounts, batch, rpc),
+        |batch, results| {
+            for (index, result) in batch.iter().zip(results) {
+                on_done(&accounts[*index], result.as_ref().err());
+            }
+        },
+    )
+    .await;
+
+    let mut snapshots: Vec<(usize, AccountSnapshot)> = batches
+        .iter()
+        .zip(results)
+        .flat_map(|(batch, results)| batch.iter().copied().zip(results))
+        .filter_map(|(index, result)| result.ok().map(|snapshot| (index, snapshot)))
+        .collect();
+
+    snapshots.sort_by_key(|(index, _)| *index);
+    snapshots.into_iter().map(|(_, snapshot)| snapshot).collect()
+}
+
+/// Group account indices into fetch batches: one per EVM chain, one for
+/// Solana, and one per account for everything else
+fn batch_accounts(accounts: &[TrackedAccount]) -> Vec<Vec<usize>> {
+    let mut batches: Vec<Vec<usize>> = Vec::new();
+    let mut chain_batches: HashMap<&str, usize> = HashMap::new();
+
+    for (index, account) in accounts.iter().enumerate() {
+        match account.chain().filter(|chain| chain.is_evm() || **chain == Chain::Solana) {
+            Some(chain) => match chain_batches.get(chain.key()) {
+                Some(batch) => batches[*batch].push(index),
+                None => {
+                    chain_batches.insert(chain.key(), batches.len());
+                    batches.push(vec![index]);
+                }
+            },
+            None => batches.push(vec![index]),
+        }
+    }
+
+    batches
+}
+
+async fn fetch_batch(accounts: &[TrackedAccount], batch: &[usize], rpc: &RpcConfig) -> Vec<Result<AccountSnapshot>> {
+    let batch: Vec<&TrackedAccount> = batch.iter().map(|index| &accounts[*index]).collect();
+
+    match client_for(batch[0], rpc) {
+        Ok(client) => client.fetch_snapshots(&batch).await,
+        Err(e) => batch.iter().map(|_| Err(anyhow::anyhow!("{:#}", e))).collect(),
+    }
+}
+
+/// Run `task` for every item with at most `options.concurrency` tasks in flight
+/// overall and at most `options.per_endpoint` in flight per `endpoint` key.
+/// Results are returned in input order.
+async fn run_bounded<'a, T, K, R, Fut>(
+    items: &'a [T],
+    options: FetchOptions,
+    endpoint: impl Fn(&T) -> K,
+    task: impl Fn(&'a T) -> Fut,
+    mut on_done: impl FnMut(&T, &R),
+) -> Vec<R>
+where
+    K: Eq + Hash,
+    Fut: Future<Output = R>,
+{
+    let global = Arc::new(Semaphore::new(options.concurrency.max(1)));
+    let mut endpoint_limits: HashMap<K, Arc<Semaphore>> = HashMap::new();
+
+    let mut pending = FuturesUnordered::new();
+    for (index, item) in items.iter().enumerate() {
+        let endpoint_limit = endpoint_limits
+            .entry(endpoint(item))
+            .or_insert_with(|| Arc::new(Semaphore::new(options.per_endpoint.max(1))))
+            .clone();
+        let global = global.clone();
+        let fut = task(item);
+
+        pending.push(async move {
+            // Take the endpoint permit first so a task waiting on a busy endpoint
+            // doesn't hold a global slot that another endpoint could use
+            let _endpoint_permit = endpoint_limit.acquire_owned().await.expect("semaphore is never closed");
+            let _global_permit = global.acquire_owned().await.expect("semaphore is never closed");
+            (index, fut.await)
+        });
+    }
+
+    let mut results = Vec::with_capacity(items.len());
+    while let Some((index, result)) = pending.next().await {
+        on_done(&items[index], &result);
+        results.push((index, result));
+    }
+
+    results.sort_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+/// Unique symbols across all snapshots that need a price lookup
+pub fn extract_token_symbols(snapshots: &[AccountSnapshot]) -> HashSet<String> {
+    snapshots
+        .iter()
+        .flat_map(|s| s.price_symbols())
+        .map(|s| s.to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{BankingAccount, WalletAddress};
+
+    fn wallet(name: &str, chain: Chain) -> TrackedAccount {
+        TrackedAccount::Wallet(WalletAddress {
+            company: "Test".to_string(),
+            name: name.to_string(),
+            address: "test123".to_string(),
+            chain,
+        })
+    }
+
+    #[test]
+    fn test_extract_token_symbols_empty() {
+        let snapshots: Vec<AccountSnapshot> = vec![];
+        let symbols = extract_token_symbols(&snapshots);
+        assert!(symbols.is_empty());
+    }
+
+    #[test]
+    fn test_extract_token_symbols_solana() {
+        let holdings = vec![
+            Holding::native("SOL", TokenAmount::new(1_000_000_000u64, 9)),
+            Holding::token(
+                "test_mint".to_string(),
+                Some("USDC".to_string()),
+                Some("USD Coin".to_string()),
+                TokenAmount::new(100_000_000u64, 6),
+            ),
+        ];
+
+        let snapshots = vec![AccountSnapshot::new(&wallet("Test Wallet", Chain::Solana), holdings)];
+        let symbols = extract_token_symbols(&snapshots);
+
+        assert!(symbols.contains("SOL"));
+        assert!(symbols.contains("USDC"));
+        assert_eq!(symbols.len(), 2);
+    }
+
+    #[test]
+    fn test_extract_token_symbols_multiple_chains() {
+        let snapshots = vec![
+            AccountSnapshot::new(&wallet("SOL Wallet", Chain::Solana), vec![Holding::native("SOL", TokenAmount::new(1_000_000_000u64, 9))]),
+            AccountSnapshot::new(&wallet("ETH Wallet", Chain::Ethereum), vec![Holding::native("ETH", TokenAmount::new(1_000_000_000_000_000_000u64, 18))]),
+        ];
+
+        let symbols = extract_token_symbols(&snapshots);
+        assert!(symbols.contains("SOL"));
+        assert!(symbols.contains("ETH"));
+        assert_eq!(symbols.len(), 2);
+    }
+
+    #[test]
+    fn test_extract_token_symbols_skips_usd_banking() {
+        let account = TrackedAccount::Bank(BankingAccount {
+            company: "Test".to_string(),
+            name: "Checking".to_string(),
+            account_id: "acct".to_string(),
+            service: BankingService::Mercury,
+        });
+        let snapshots = vec![AccountSnapshot::new(&account, vec![Holding::fiat("USD", BigDecimal::from(1000))])];
+
+        assert!(extract_token_symbols(&snapshots).is_empty());
+        assert_eq!(snapshots[0].total_usd_value, Some(BigDecimal::from(1000)));
+    }
+
+    #[test]
+    fn test_enrich_from_cache() {
+        let mut snapshot = AccountSnapshot::new(&wallet("NEAR Wallet", Chain::Near), vec![Holding::native("NEAR", TokenAmount::from_raw_str("10000000000000000000000000", 24).unwrap())]);
+
+        let mut price_cache = HashMap::new();
+        price_cache.insert("NEAR".to_string(), 5.0);
+
+        snapshot.enrich_from_cache(&price_cache);
+
+        assert_eq!(snapshot.holdings[0].usd_price, Some(BigDecimal::from(5)));
+        assert_eq!(snapshot.holdings[0].usd_value, Some(BigDecimal::from(50)));
+        assert_eq!(snapshot.total_usd_value, Some(BigDecimal::from(50)));
+    }
+
+    #[test]
+    fn test_enrich_from_cache_no_price_available() {
+        let mut snapshot = AccountSnapshot::new(&wallet("NEAR Wallet", Chain::Near), vec![Holding::native("NEAR", TokenAmount::from_raw_str("10000000000000000000000000", 24).unwrap())]);
+
+        let price_cache = HashMap::new(); // Empty cache
+
+        snapshot.enrich_from_cache(&price_cache);
+
+        assert_eq!(snapshot.holdings[0].usd_price, None);
+        assert_eq!(snapshot.holdings[0].usd_value, None);
+        assert_eq!(snapshot.total_usd_value, None);
+    }
+
+    #[test]
+    fn test_batch_accounts_groups_by_chain() {
+        let accounts = vec![
+            wallet("A", Chain::Ethereum),
+            wallet("B", Chain::Solana),
+            wallet("C", Chain::Base),
+            wallet("D", Chain::Ethereum),
+            wallet("E", Chain::Solana),
+        ];
+
+        assert_eq!(batch_accounts(&accounts), vec![vec![0, 3], vec![1, 4], vec![2]]);
+    }
+
+    #[tokio::test]
+    async fn test_run_bounded_keeps_input_order() {
+        let items: Vec<u64> = vec![30, 10, 20, 0];
+        let options = FetchOptions { concurrency: 4, per_endpoint: 4 };
+
+        let mut completed = Vec::new();
+        let results = run_bounded(
+            &items,
+            options,
+            |_| "endpoint",
+            |delay| async move {
+                tokio::time::sleep(std::time::Duration::from_millis(*delay)).await;
+                *delay
+            },
+            |item, _| completed.push(*item),
+        )
+        .await;
+
+        assert_eq!(results, vec![30, 10, 20, 0]);
+        assert_eq!(completed.len(), 4);
+        assert_ne!(completed, items);
+    }
+
+    #[tokio::test]
+    async fn test_run_bounded_respects_limits() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let items: Vec<(&str, usize)> = (0..12).map(|i| (if i % 2 == 0 { "a" } else { "b" }, i)).collect();
+        let options = FetchOptions { concurrency: 3, per_endpoint: 2 };
+
+        let in_flight = AtomicUsize::new(0);
+        let max_in_flight = AtomicUsize::new(0);
+        let in_flight_a = AtomicUsize::new(0);
+        let max_in_flight_a = AtomicUsize::new(0);
+
+        let results = run_bounded(
+            &items,
+            options,
+            |(endpoint, _)| *endpoint,
+            |(endpoint, i)| {
+                let (in_flight, max_in_flight) = (&in_flight, &max_in_flight);
+                let (in_flight_a, max_in_flight_a) = (&in_flight_a, &max_in_flight_a);
+                async move {
+                    let now = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_in_flight.fetch_max(now, Ordering::SeqCst);
+                    if *endpoint == "a" {
+                        let now_a = in_flight_a.fetch_add(1, Ordering::SeqCst) + 1;
+                        max_in_flight_a.fetch_max(now_a, Ordering::SeqCst);
+                    }
+
+                    tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+
+                    if *endpoint == "a" {
+                        in_flight_a.fetch_sub(1, Ordering::SeqCst);
+                    }
+                    in_flight.fetch_sub(1, Ordering::SeqCst);
+                    *i
+                }
+            },
+            |_, _| {},
+        )
+        .await;
+
+        assert_eq!(results, (0..12).collect::<Vec<_>>());
+        assert_eq!(max_in_flight.load(Ordering::SeqCst), 3);
+        assert!(max_in_flight_a.load(Ordering::SeqCst) <= 2);
+    }
+}
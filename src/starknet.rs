@@ -1,31 +1,108 @@
+use crate::amount::{self, TokenAmount};
+use crate::rpc::{self, HttpPool, RpcEndpoint};
+use crate::source::{AccountSnapshot, BalanceSource, Holding};
+use crate::storage::{Chain, TrackedAccount};
+use crate::token_cache::{self, TokenMetadata};
+use crate::token_list::{self, TokenList};
 use anyhow::{Context, Result};
+use async_trait::async_trait;
+use bigdecimal::num_bigint::BigUint;
+use bigdecimal::Zero;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
+use std::collections::BTreeSet;
 
 #[derive(Debug)]
-#[allow(dead_code)]
 pub struct TokenBalance {
     pub contract_address: String,
+    pub name: Option<String>,
     pub symbol: Option<String>,
-    pub decimals: u8,
-    pub ui_amount: f64,
-    pub usd_price: Option<f64>,
-    pub usd_value: Option<f64>,
+    pub amount: TokenAmount,
 }
 
 #[derive(Debug)]
 pub struct AccountBalances {
-    pub eth_balance: f64,
-    pub eth_usd_price: Option<f64>,
-    pub eth_usd_value: Option<f64>,
-    #[allow(dead_code)]
+    /// STRK, the gas token
+    pub strk_balance: TokenAmount,
     pub token_balances: Vec<TokenBalance>,
-    pub total_usd_value: Option<f64>,
+}
+
+const STRK_CONTRACT: &str = "0x04718f5a0fc34cc1af16a1cdee98ffb20c31f5cd61d6ab07201858f4287c938d";
+
+/// ERC-20 contracts checked for every account
+const COMMON_TOKENS: &[(&str, &str)] = &[
+    ("0x049d36570d4e46f48e99674bd3fcc84644ddd6b96f7c741b1562b82f9e004dc7", "ETH"),
+    ("0x033068f6539f8e6e6b131e6b2b814e6c34a5224bc66947c47dab9dfee93b35fb", "USDC"),
+    ("0x053c91253bc9682c04929ca02ed00b3e423f6710d2ee7e0d5ebb06f3ecf368a8", "USDC.e"),
+    ("0x068f5c6a61780768455de69077e07e89787839bf8166decfbf92b645209c0fb8", "USDT"),
+];
+
+// starknet_keccak of the entry point names
+const BALANCE_OF_SELECTOR: &str = "0x2e4263afad30923c891518314c3c95dbe830a16874e8abc5777a9a20b54c76e";
+const DECIMALS_SELECTOR: &str = "0x4c4fb1ab068f6039d5780c68dd0fa2f8742cceb3426d19667778ca7f3518a9";
+const SYMBOL_SELECTOR: &str = "0x216b05c387bab9ac31918a3e61672f4618601f3c598a2f3f2710f37053e1ea4";
+const NAME_SELECTOR: &str = "0x361458367e696363fbcc70777d07ebbd2394e89fd0adcaf147faccd1d294d60";
+
+/// Full-length lowercase form of a contract or account address
+fn normalize_address(address: &str) -> Result<String> {
+    let hex = address
+        .strip_prefix("0x")
+        .ok_or_else(|| anyhow::anyhow!("Invalid Starknet address format: must start with 0x"))?;
+    if hex.is_empty() || hex.len() > 64 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        anyhow::bail!("Invalid Starknet address format: must be hexadecimal");
+    }
+    Ok(format!("0x{:0>64}", hex.to_lowercase()))
+}
+
+/// A u256 returned as two felts, low 128 bits first
+fn decode_u256(felts: &[String]) -> Result<BigUint> {
+    let [low, high] = felts else {
+        anyhow::bail!("Expected a u256 (two felts), got {} felts", felts.len());
+    };
+    Ok(amount::parse_hex_uint(low)? + (amount::parse_hex_uint(high)? << 128))
+}
+
+/// The bytes of a felt holding a short string, without leading zeros
+fn felt_bytes(felt: &str, len: Option<usize>) -> Result<Vec<u8>> {
+    let bytes = amount::parse_hex_uint(felt)?.to_bytes_be();
+    let bytes = if bytes == [0] { Vec::new() } else { bytes };
+    match len {
+        // Words of a ByteArray keep their leading zero bytes
+        Some(len) if bytes.len() <= len => Ok([vec![0; len - bytes.len()], bytes].concat()),
+        Some(_) => anyhow::bail!("String word longer than its length"),
+        None => Ok(bytes),
+    }
+}
+
+/// A string returned as a Cairo 0 short string (one felt), or as a Cairo 1
+/// ByteArray: the number of full 31-byte words, the words, then the pending
+/// word and its length
+fn decode_string(felts: &[String]) -> Result<String> {
+    let bytes = match felts {
+        [short] => felt_bytes(short, None)?,
+        [count, rest @ ..] => {
+            let count = usize::try_from(amount::parse_hex_uint(count)?).context("Invalid string length")?;
+            if rest.len().checked_sub(2) != Some(count) {
+                anyhow::bail!("Invalid ByteArray encoding");
+            }
+            let pending_len = usize::try_from(amount::parse_hex_uint(&rest[count + 1])?).context("Invalid string length")?;
+            if pending_len >= 31 {
+                anyhow::bail!("Invalid ByteArray encoding");
+            }
+            let mut bytes = Vec::new();
+            for word in &rest[..count] {
+                bytes.extend(felt_bytes(word, Some(31))?);
+            }
+            bytes.extend(felt_bytes(&rest[count], Some(pending_len))?);
+            bytes
+        }
+        [] => anyhow::bail!("Empty string result"),
+    };
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
 }
 
 pub struct StarknetClient {
-    client: reqwest::Client,
-    rpc_url: String,
+    rpc: HttpPool,
 }
 
 #[derive(Serialize)]
@@ -48,17 +125,9 @@ struct JsonRpcError {
 }
 
 impl StarknetClient {
-    pub fn new(rpc_url: Option<String>) -> Self {
-        // Use free public RPC from Nethermind (Blast API is no longer available)
-        let url = rpc_url.unwrap_or_else(|| "https://free-rpc.nethermind.io/mainnet-juno".to_string());
-        let client = reqwest::Client::builder()
-            .timeout(std::time::Duration::from_secs(30))
-            .build()
-            .unwrap_or_else(|_| reqwest::Client::new());
-
+    pub fn new(endpoints: Vec<RpcEndpoint>) -> Self {
         Self {
-            client,
-            rpc_url: url,
+            rpc: HttpPool::http(endpoints),
         }
     }
 
@@ -70,16 +139,9 @@ impl StarknetClient {
             id: 1,
         };
 
-        let response = self
-            .client
-            .post(&self.rpc_url)
-            .json(&request)
-            .send()
-            .await
-            .context("Failed to send RPC request")?;
+        let response = self.rpc.post("", &request).await?;
 
-        let rpc_response: JsonRpcResponse = response
-            .json()
+        let rpc_response: JsonRpcResponse = rpc::json_body(response)
             .await
             .context("Failed to parse RPC response")?;
 
@@ -92,80 +154,149 @@ impl StarknetClient {
             .ok_or_else(|| anyhow::anyhow!("No result in RPC response"))
     }
 
-    pub async fn get_balances(&self, address: &str) -> Result<AccountBalances> {
-        // Validate address format (Starknet addresses are 0x-prefixed hex)
-        if !address.starts_with("0x") {
-            anyhow::bail!("Invalid Starknet address format: must start with 0x");
-        }
-
-        // ETH contract address on Starknet
-        let eth_contract = "0x049d36570d4e46f48e99674bd3fcc84644ddd6b96f7c741b1562b82f9e004dc7";
-
-        // Query ETH balance using starknet_call
-        // Call balanceOf(address) function
+    /// Call a view entry point of a contract and return the felts it returned
+    async fn call(&self, contract: &str, selector: &str, calldata: &[&str]) -> Result<Vec<String>> {
         let result = self
             .rpc_call(
                 "starknet_call",
                 json!({
                     "request": {
-                        "contract_address": eth_contract,
-                        "entry_point_selector": "0x2e4263afad30923c891518314c3c95dbe830a16874e8abc5777a9a20b54c76e", // balanceOf selector
-                        "calldata": [address]
+                        "contract_address": contract,
+                        "entry_point_selector": selector,
+                        "calldata": calldata
                     },
                     "block_id": "latest"
                 }),
             )
             .await?;
 
-        // Parse balance result
-        let balance_hex = result
-            .as_array()
-            .and_then(|arr| arr.first())
-            .and_then(|v| v.as_str())
-            .ok_or_else(|| anyhow::anyhow!("Invalid balance format"))?;
+        serde_json::from_value(result).context("Invalid starknet_call result")
+    }
+
+    async fn balance_of(&self, contract: &str, address: &str) -> Result<BigUint> {
+        decode_u256(&self.call(contract, BALANCE_OF_SELECTOR, &[address]).await?)
+    }
+
+    /// Decimals, symbol and name of a token, via the token list or cache
+    async fn token_metadata(&self, contract: &str, token_list: &TokenList) -> Result<TokenMetadata> {
+        if let Some(metadata) = token_list.metadata(contract) {
+            return Ok(metadata);
+        }
+        if let Some(metadata) = token_cache::with_cache(|cache| cache.metadata(&Chain::Starknet, contract).cloned()) {
+            return Ok(metadata);
+        }
+
+        let (decimals, symbol, name) = futures::try_join!(
+            self.call(contract, DECIMALS_SELECTOR, &[]),
+            self.call(contract, SYMBOL_SELECTOR, &[]),
+            self.call(contract, NAME_SELECTOR, &[]),
+        )?;
+        let decimals = decimals
+            .first()
+            .and_then(|felt| amount::parse_hex_uint(felt).ok())
+            .and_then(|decimals| u8::try_from(decimals).ok())
+            .ok_or_else(|| anyhow::anyhow!("Invalid decimals"))?;
+
+        let metadata = TokenMetadata {
+            decimals,
+            symbol: decode_string(&symbol).ok(),
+            name: decode_string(&name).ok(),
+        };
+        token_cache::with_cache(|cache| cache.insert_metadata(&Chain::Starknet, contract, metadata.clone()));
+        Ok(metadata)
+    }
 
-        // Parse hex string to u128
-        let balance_wei = u128::from_str_radix(
-            balance_hex.trim_start_matches("0x"),
-            16
-        ).unwrap_or(0);
+    pub async fn get_balances(&self, address: &str) -> Result<AccountBalances> {
+        let address = normalize_address(address)?;
+
+        // 1 STRK = 10^18 fri
+        let strk_balance = TokenAmount::new(
+            self.balance_of(STRK_CONTRACT, &address).await.context("Failed to fetch STRK balance")?,
+            18,
+        );
 
-        // Convert wei to ETH (1 ETH = 10^18 wei)
-        let eth_balance = balance_wei as f64 / 1_000_000_000_000_000_000.0;
+        // Tokens to check: the common tokens and the user's token list
+        let token_list = token_list::load(&Chain::Starknet, normalize_address).unwrap_or_else(|e| {
+            eprintln!("Warning: Ignoring Starknet token list: {:#}", e);
+            TokenList::default()
+        });
+        let mut tokens: BTreeSet<String> = COMMON_TOKENS.iter().map(|(contract, _)| contract.to_string()).collect();
+        tokens.extend(token_list.tokens.iter().map(|token| token.address.clone()));
+        tokens.remove(STRK_CONTRACT);
 
-        // Token balances would require additional contract calls
-        // For now, we'll just return the native ETH balance
-        let token_balances = Vec::new();
+        let balances = futures::future::join_all(tokens.iter().map(|contract| self.balance_of(contract, &address))).await;
+        let mut held = Vec::new();
+        for (contract, balance) in tokens.iter().zip(balances) {
+            match balance {
+                Ok(balance) if balance.is_zero() => {}
+                Ok(balance) => held.push((contract, balance)),
+                Err(e) => eprintln!("Warning: Failed to query token {} balance: {:#}", contract, e),
+            }
+        }
+
+        let metadata = futures::future::join_all(held.iter().map(|(contract, _)| self.token_metadata(contract, &token_list))).await;
+        let mut token_balances = Vec::new();
+        for ((contract, balance), metadata) in held.into_iter().zip(metadata) {
+            match metadata {
+                Ok(metadata) => token_balances.push(TokenBalance {
+                    contract_address: contract.clone(),
+                    name: metadata.name,
+                    symbol: metadata.symbol,
+                    amount: TokenAmount::new(balance, metadata.decimals),
+                }),
+                Err(e) => eprintln!("Warning: Failed to query token {} metadata: {:#}", contract, e),
+            }
+        }
+        token_cache::save();
 
         Ok(AccountBalances {
-            eth_balance,
-            eth_usd_price: None,
-            eth_usd_value: None,
+            strk_balance,
             token_balances,
-            total_usd_value: None,
         })
     }
 }
 
-// Implement PriceEnrichable trait for Starknet balances
-impl crate::PriceEnrichable for AccountBalances {
-    const NATIVE_SYMBOL: &'static str = "ETH";
+#[async_trait]
+impl BalanceSource for StarknetClient {
+    async fn fetch_snapshot(&self, account: &TrackedAccount) -> Result<AccountSnapshot> {
+        let balances = self.get_balances(account.identifier()).await?;
 
-    fn native_balance(&self) -> f64 {
-        self.eth_balance
-    }
+        let mut holdings = vec![Holding::native("STRK", balances.strk_balance)];
+        holdings.extend(balances.token_balances.into_iter().map(|token| {
+            Holding::token(token.contract_address, token.symbol, token.name, token.amount)
+        }));
 
-    fn set_native_usd_price(&mut self, price: f64) {
-        self.eth_usd_price = Some(price);
+        Ok(AccountSnapshot::new(account, holdings).with_detail("RPC Endpoint", self.rpc.served_by()))
     }
+}
 
-    fn set_native_usd_value(&mut self, value: f64) {
-        self.eth_usd_value = Some(value);
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn felts(values: &[&str]) -> Vec<String> {
+        values.iter().map(|v| v.to_string()).collect()
     }
 
-    fn set_total_usd_value(&mut self, value: f64) {
-        self.total_usd_value = Some(value);
+    #[test]
+    fn test_decode_u256_uses_both_felts() {
+        assert_eq!(decode_u256(&felts(&["0x2a", "0x0"])).unwrap(), BigUint::from(42u8));
+        assert_eq!(decode_u256(&felts(&["0x1", "0x1"])).unwrap(), (BigUint::from(1u8) << 128) + 1u8);
+        assert!(decode_u256(&felts(&["0x1"])).is_err());
     }
 
-    // Starknet doesn't have token balances yet, use default implementation
+    #[test]
+    fn test_decode_string() {
+        // Cairo 0 short string "ETH"
+        assert_eq!(decode_string(&felts(&["0x455448"])).unwrap(), "ETH");
+        // ByteArray "STRK": no full words, pending word of 4 bytes
+        assert_eq!(decode_string(&felts(&["0x0", "0x5354524b", "0x4"])).unwrap(), "STRK");
+        // ByteArray spanning a full 31-byte word
+        let long = "Starknet Token with a long name";
+        let word = format!("0x{}", long.bytes().map(|b| format!("{:02x}", b)).collect::<String>());
+        assert_eq!(decode_string(&felts(&["0x1", &word, "0x21", "0x1"])).unwrap(), format!("{}!", long));
+        assert!(decode_string(&felts(&["0x2", "0x0", "0x0"])).is_err());
+        assert!(decode_string(&felts(&["0xffffffffffffffff", "0x0", "0x0"])).is_err());
+        assert!(decode_string(&felts(&["0x0", "0x0", "0xffffffffffffffff"])).is_err());
+    }
 }
@@ -1,9 +1,12 @@
+use crate::config;
+use crate::cosmos::{self, CosmosNetworks};
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::PathBuf;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(from = "String", into = "String")]
 #[allow(clippy::enum_variant_names)]
 pub enum Chain {
     Solana,
@@ -19,6 +22,44 @@ pub enum Chain {
     Aptos,
     Sui,
     Starknet,
+    Bitcoin,
+    /// A Cosmos SDK network, by its key in the config (`cosmos`, `osmosis`, ...)
+    Cosmos(String),
+}
+
+/// Chains are stored by variant name, and Cosmos SDK networks by key. An
+/// unknown name is kept as a Cosmos network: taking a network out of the
+/// config makes its accounts fail to load balances, not the address book.
+impl From<String> for Chain {
+    fn from(name: String) -> Self {
+        match name.as_str() {
+            "Solana" => Chain::Solana,
+            "Ethereum" => Chain::Ethereum,
+            "Polygon" => Chain::Polygon,
+            "BinanceSmartChain" => Chain::BinanceSmartChain,
+            "Arbitrum" => Chain::Arbitrum,
+            "Optimism" => Chain::Optimism,
+            "Avalanche" => Chain::Avalanche,
+            "Base" => Chain::Base,
+            "Core" => Chain::Core,
+            "Near" => Chain::Near,
+            "Aptos" => Chain::Aptos,
+            "Sui" => Chain::Sui,
+            "Starknet" => Chain::Starknet,
+            "Bitcoin" => Chain::Bitcoin,
+            // Also reads the Cosmos, Osmosis, Celestia, Injective and Dydx variants
+            _ => Chain::Cosmos(name.to_lowercase()),
+        }
+    }
+}
+
+impl From<Chain> for String {
+    fn from(chain: Chain) -> Self {
+        match chain {
+            Chain::Cosmos(key) => key,
+            other => format!("{:?}", other),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -45,25 +86,41 @@ impl BankingService {
 }
 
 impl Chain {
+    /// A chain by name or alias, including the Cosmos SDK networks of the active config
     pub fn from_str(s: &str) -> Result<Self> {
-        match s.to_lowercase().as_str() {
-            "solana" | "sol" => Ok(Chain::Solana),
-            "ethereum" | "eth" => Ok(Chain::Ethereum),
-            "polygon" | "matic" => Ok(Chain::Polygon),
-            "bsc" | "binance" | "bnb" => Ok(Chain::BinanceSmartChain),
-            "arbitrum" | "arb" => Ok(Chain::Arbitrum),
-            "optimism" | "op" => Ok(Chain::Optimism),
-            "avalanche" | "avax" => Ok(Chain::Avalanche),
-            "base" => Ok(Chain::Base),
-            "core" => Ok(Chain::Core),
-            "near" => Ok(Chain::Near),
-            "aptos" | "apt" => Ok(Chain::Aptos),
-            "sui" => Ok(Chain::Sui),
-            "starknet" | "stark" => Ok(Chain::Starknet),
-            _ => anyhow::bail!("Unknown chain: {}", s),
+        Self::parse(s, &config::active().settings.cosmos)
+    }
+
+    /// A chain by name or alias, with Cosmos SDK networks from `cosmos`
+    pub fn parse(s: &str, cosmos: &CosmosNetworks) -> Result<Self> {
+        match Self::builtin(s) {
+            Some(chain) => Ok(chain),
+            None => cosmos.find(s).ok_or_else(|| anyhow::anyhow!("Unknown chain: {}", s)),
         }
     }
 
+    /// A chain other than a Cosmos SDK network, by name or alias
+    pub fn builtin(s: &str) -> Option<Self> {
+        let chain = match s.to_lowercase().as_str() {
+            "solana" | "sol" => Chain::Solana,
+            "ethereum" | "eth" => Chain::Ethereum,
+            "polygon" | "matic" | "pol" => Chain::Polygon,
+            "bsc" | "binance" | "bnb" => Chain::BinanceSmartChain,
+            "arbitrum" | "arb" => Chain::Arbitrum,
+            "optimism" | "op" => Chain::Optimism,
+            "avalanche" | "avax" => Chain::Avalanche,
+            "base" => Chain::Base,
+            "core" => Chain::Core,
+            "near" => Chain::Near,
+            "aptos" | "apt" => Chain::Aptos,
+            "sui" => Chain::Sui,
+            "starknet" | "stark" => Chain::Starknet,
+            "bitcoin" | "btc" => Chain::Bitcoin,
+            _ => return None,
+        };
+        Some(chain)
+    }
+
     pub fn display_name(&self) -> &str {
         match self {
             Chain::Solana => "Solana",
@@ -79,6 +136,44 @@ impl Chain {
             Chain::Aptos => "Aptos",
             Chain::Sui => "Sui",
             Chain::Starknet => "Starknet",
+            Chain::Bitcoin => "Bitcoin",
+            Chain::Cosmos(key) => cosmos::network(key).and_then(|network| network.name.as_deref()).unwrap_or(key),
+        }
+    }
+
+    /// Short lowercase name used in file names and cache keys
+    pub fn key(&self) -> &str {
+        match self {
+            Chain::Solana => "solana",
+            Chain::Ethereum => "ethereum",
+            Chain::Polygon => "polygon",
+            Chain::BinanceSmartChain => "bsc",
+            Chain::Arbitrum => "arbitrum",
+            Chain::Optimism => "optimism",
+            Chain::Avalanche => "avalanche",
+            Chain::Base => "base",
+            Chain::Core => "core",
+            Chain::Near => "near",
+            Chain::Aptos => "aptos",
+            Chain::Sui => "sui",
+            Chain::Starknet => "starknet",
+            Chain::Bitcoin => "bitcoin",
+            Chain::Cosmos(key) => key,
+        }
+    }
+
+    /// EIP-155 chain id of EVM chains
+    pub fn evm_chain_id(&self) -> Option<u64> {
+        match self {
+            Chain::Ethereum => Some(1),
+            Chain::Optimism => Some(10),
+            Chain::BinanceSmartChain => Some(56),
+            Chain::Polygon => Some(137),
+            Chain::Core => Some(1116),
+            Chain::Base => Some(8453),
+            Chain::Arbitrum => Some(42161),
+            Chain::Avalanche => Some(43114),
+            _ => None,
         }
     }
 
@@ -102,7 +197,7 @@ impl Chain {
         match self {
             Chain::Solana => "SOL",
             Chain::Ethereum => "ETH",
-            Chain::Polygon => "MATIC",
+            Chain::Polygon => "POL",
             Chain::BinanceSmartChain => "BNB",
             Chain::Arbitrum => "ETH",
             Chain::Optimism => "ETH",
@@ -113,6 +208,8 @@ impl Chain {
             Chain::Aptos => "APT",
             Chain::Sui => "SUI",
             Chain::Starknet => "STRK",
+            Chain::Bitcoin => "BTC",
+            Chain::Cosmos(key) => cosmos::network(key).map_or("", |network| network.symbol.as_str()),
         }
     }
 }
@@ -140,6 +237,52 @@ pub struct BankingAccount {
     pub service: BankingService,
 }
 
+/// A tracked blockchain address or banking account
+#[derive(Debug, Clone)]
+pub enum TrackedAccount {
+    Wallet(WalletAddress),
+    Bank(BankingAccount),
+}
+
+impl TrackedAccount {
+    pub fn company(&self) -> &str {
+        match self {
+            TrackedAccount::Wallet(w) => &w.company,
+            TrackedAccount::Bank(a) => &a.company,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            TrackedAccount::Wallet(w) => &w.name,
+            TrackedAccount::Bank(a) => &a.name,
+        }
+    }
+
+    /// The blockchain address or banking account ID
+    pub fn identifier(&self) -> &str {
+        match self {
+            TrackedAccount::Wallet(w) => &w.address,
+            TrackedAccount::Bank(a) => &a.account_id,
+        }
+    }
+
+    /// Display name of the chain or banking service
+    pub fn source_name(&self) -> &str {
+        match self {
+            TrackedAccount::Wallet(w) => w.chain.display_name(),
+            TrackedAccount::Bank(a) => a.service.display_name(),
+        }
+    }
+
+    pub fn chain(&self) -> Option<&Chain> {
+        match self {
+            TrackedAccount::Wallet(w) => Some(&w.chain),
+            TrackedAccount::Bank(_) => None,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AddressBook {
     pub addresses: Vec<WalletAddress>,
@@ -155,7 +298,25 @@ impl AddressBook {
         }
     }
 
-    fn detect_chain(address: &str, specified_chain: Option<&str>) -> Result<Chain> {
+    /// All tracked accounts: blockchain addresses first, then banking accounts,
+    /// each in address book order
+    pub fn accounts(&self) -> Vec<TrackedAccount> {
+        self.addresses
+            .iter()
+            .cloned()
+            .map(TrackedAccount::Wallet)
+            .chain(self.banking_accounts.iter().cloned().map(TrackedAccount::Bank))
+            .collect()
+    }
+
+    /// Find a tracked account by name, address or banking account ID
+    pub fn find_account(&self, identifier: &str) -> Option<TrackedAccount> {
+        self.accounts()
+            .into_iter()
+            .find(|a| a.name() == identifier || a.identifier() == identifier)
+    }
+
+    pub fn detect_chain(address: &str, specified_chain: Option<&str>) -> Result<Chain> {
         // If chain is specified, use it
         if let Some(chain_str) = specified_chain {
             return Chain::from_str(chain_str);
@@ -168,6 +329,16 @@ impl AddressBook {
                 return Ok(Chain::Ethereum);
             }
 
+        // Bitcoin addresses, extended public keys and output descriptors
+        if crate::bitcoin::is_bitcoin_account(address) {
+            return Ok(Chain::Bitcoin);
+        }
+
+        // Cosmos SDK addresses, by bech32 prefix (cosmos1..., osmo1..., ...)
+        if let Some(chain) = cosmos::chain_for_address(address) {
+            return Ok(chain);
+        }
+
         // Default to Solana for base58-encoded addresses
         Ok(Chain::Solana)
     }
@@ -249,7 +420,11 @@ impl AddressBook {
         Ok(())
     }
 
-    fn get_storage_path() -> Result<PathBuf> {
+    pub fn get_storage_path() -> Result<PathBuf> {
+        if let Some(path) = &config::active().settings.address_book {
+            return Ok(path.clone());
+        }
+
         let home = dirs::home_dir()
             .context("Failed to get home directory")?;
 
@@ -1,31 +1,102 @@
+use crate::amount::TokenAmount;
+use crate::rpc::{self, HttpPool, RpcEndpoint};
+use crate::source::{AccountSnapshot, BalanceSource, Holding};
+use crate::storage::{Chain, TrackedAccount};
+use crate::token_cache::{self, TokenMetadata};
 use anyhow::{Context, Result};
+use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 
+const SUI_COIN_TYPE: &str = "0x2::sui::SUI";
+
 #[derive(Debug)]
-#[allow(dead_code)]
 pub struct TokenBalance {
     pub coin_type: String,
+    pub name: Option<String>,
     pub symbol: Option<String>,
-    pub decimals: u8,
-    pub ui_amount: f64,
-    pub usd_price: Option<f64>,
-    pub usd_value: Option<f64>,
+    pub amount: TokenAmount,
+}
+
+/// A StakedSui object, in MIST
+#[derive(Debug, Default, PartialEq)]
+pub struct StakedSui {
+    pub id: String,
+    pub validator: String,
+    /// `Active`, or `Pending` until the epoch after it was staked
+    pub status: String,
+    pub principal: u64,
+    pub estimated_reward: u64,
+}
+
+impl StakedSui {
+    pub fn mist(&self) -> u64 {
+        self.principal + self.estimated_reward
+    }
 }
 
 #[derive(Debug)]
 pub struct AccountBalances {
-    pub sui_balance: f64,
-    pub sui_usd_price: Option<f64>,
-    pub sui_usd_value: Option<f64>,
-    #[allow(dead_code)]
+    pub sui_balance: TokenAmount,
+    pub staked: Vec<StakedSui>,
     pub token_balances: Vec<TokenBalance>,
-    pub total_usd_value: Option<f64>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Balance {
+    coin_type: String,
+    total_balance: String,
+}
+
+#[derive(Deserialize)]
+struct CoinMetadata {
+    decimals: u8,
+    name: String,
+    symbol: String,
+}
+
+/// Stakes with one validator, as returned by `suix_getStakes`
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DelegatedStake {
+    validator_address: String,
+    stakes: Vec<Stake>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Stake {
+    staked_sui_id: String,
+    status: String,
+    principal: String,
+    /// Only present for active stakes
+    estimated_reward: Option<String>,
+}
+
+fn staked_sui(delegations: Vec<DelegatedStake>) -> Result<Vec<StakedSui>> {
+    let mut staked = Vec::new();
+    for delegation in delegations {
+        for stake in delegation.stakes {
+            staked.push(StakedSui {
+                principal: stake.principal.parse().context("Invalid stake principal")?,
+                estimated_reward: stake
+                    .estimated_reward
+                    .map(|reward| reward.parse())
+                    .transpose()
+                    .context("Invalid stake reward")?
+                    .unwrap_or(0),
+                id: stake.staked_sui_id,
+                validator: delegation.validator_address.clone(),
+                status: stake.status,
+            });
+        }
+    }
+    Ok(staked)
 }
 
 pub struct SuiClient {
-    client: reqwest::Client,
-    rpc_url: String,
+    rpc: HttpPool,
 }
 
 #[derive(Serialize)]
@@ -48,16 +119,9 @@ struct JsonRpcError {
 }
 
 impl SuiClient {
-    pub fn new(rpc_url: Option<String>) -> Self {
-        let url = rpc_url.unwrap_or_else(|| "https://fullnode.mainnet.sui.io:443".to_string());
-        let client = reqwest::Client::builder()
-            .timeout(std::time::Duration::from_secs(30))
-            .build()
-            .unwrap_or_else(|_| reqwest::Client::new());
-
+    pub fn new(endpoints: Vec<RpcEndpoint>) -> Self {
         Self {
-            client,
-            rpc_url: url,
+            rpc: HttpPool::http(endpoints),
         }
     }
 
@@ -69,16 +133,9 @@ impl SuiClient {
             id: 1,
         };
 
-        let response = self
-            .client
-            .post(&self.rpc_url)
-            .json(&request)
-            .send()
-            .await
-            .context("Failed to send RPC request")?;
+        let response = self.rpc.post("", &request).await?;
 
-        let rpc_response: JsonRpcResponse = response
-            .json()
+        let rpc_response: JsonRpcResponse = rpc::json_body(response)
             .await
             .context("Failed to parse RPC response")?;
 
@@ -91,66 +148,119 @@ impl SuiClient {
             .ok_or_else(|| anyhow::anyhow!("No result in RPC response"))
     }
 
+    /// Decimals, name and symbol of a coin type, via the cache
+    async fn coin_metadata(&self, coin_type: &str) -> Result<TokenMetadata> {
+        if let Some(metadata) = token_cache::with_cache(|cache| cache.metadata(&Chain::Sui, coin_type).cloned()) {
+            return Ok(metadata);
+        }
+
+        let result = self.rpc_call("suix_getCoinMetadata", json!([coin_type])).await?;
+        let metadata: Option<CoinMetadata> = serde_json::from_value(result).context("Invalid coin metadata")?;
+        let metadata = metadata.ok_or_else(|| anyhow::anyhow!("No coin metadata"))?;
+
+        let metadata = TokenMetadata {
+            decimals: metadata.decimals,
+            symbol: Some(metadata.symbol),
+            name: Some(metadata.name),
+        };
+        token_cache::with_cache(|cache| cache.insert_metadata(&Chain::Sui, coin_type, metadata.clone()));
+        Ok(metadata)
+    }
+
     pub async fn get_balances(&self, address: &str) -> Result<AccountBalances> {
         // Validate address format (Sui addresses are 0x-prefixed hex, 64 chars after 0x)
         if !address.starts_with("0x") {
             anyhow::bail!("Invalid Sui address format: must start with 0x");
         }
 
-        // Query SUI balance using suix_getBalance
-        let result = self
-            .rpc_call(
-                "suix_getBalance",
-                json!([address, "0x2::sui::SUI"]),
-            )
-            .await?;
+        // Every coin type the address holds, summed over its coin objects
+        let result = self.rpc_call("suix_getAllBalances", json!([address])).await?;
+        let balances: Vec<Balance> = serde_json::from_value(result).context("Invalid balance format")?;
 
         // Parse balance from MIST (10^9 MIST = 1 SUI)
-        let balance_str = result
-            .get("totalBalance")
-            .and_then(|v| v.as_str())
-            .ok_or_else(|| anyhow::anyhow!("Invalid balance format"))?;
-
-        let balance_mist: u64 = balance_str
-            .parse()
-            .context("Failed to parse SUI balance")?;
+        let mut sui_balance = TokenAmount::new(0u64, 9);
+        let mut held = Vec::new();
+        for balance in balances {
+            if balance.coin_type == SUI_COIN_TYPE {
+                sui_balance = TokenAmount::from_raw_str(&balance.total_balance, 9)
+                    .context("Failed to parse SUI balance")?;
+            } else if balance.total_balance != "0" {
+                held.push(balance);
+            }
+        }
 
-        // Convert MIST to SUI (1 SUI = 10^9 MIST)
-        let sui_balance = balance_mist as f64 / 1_000_000_000.0;
+        let metadata = futures::future::join_all(held.iter().map(|balance| self.coin_metadata(&balance.coin_type))).await;
+        let mut token_balances = Vec::new();
+        for (balance, metadata) in held.into_iter().zip(metadata) {
+            let metadata = match metadata {
+                Ok(metadata) => metadata,
+                Err(e) => {
+                    eprintln!("Warning: Failed to query coin {} metadata: {:#}", balance.coin_type, e);
+                    continue;
+                }
+            };
+            token_balances.push(TokenBalance {
+                amount: TokenAmount::from_raw_str(&balance.total_balance, metadata.decimals)
+                    .with_context(|| format!("Failed to parse {} balance", balance.coin_type))?,
+                coin_type: balance.coin_type,
+                name: metadata.name,
+                symbol: metadata.symbol,
+            });
+        }
+        token_cache::save();
 
-        // Token balances would require additional queries
-        // For now, we'll just return the native SUI balance
-        let token_balances = Vec::new();
+        let result = self.rpc_call("suix_getStakes", json!([address])).await?;
+        let delegations: Vec<DelegatedStake> = serde_json::from_value(result).context("Invalid stakes format")?;
+        let staked = staked_sui(delegations)?;
 
         Ok(AccountBalances {
             sui_balance,
-            sui_usd_price: None,
-            sui_usd_value: None,
+            staked,
             token_balances,
-            total_usd_value: None,
         })
     }
 }
 
-// Implement PriceEnrichable trait for Sui balances
-impl crate::PriceEnrichable for AccountBalances {
-    const NATIVE_SYMBOL: &'static str = "SUI";
+#[async_trait]
+impl BalanceSource for SuiClient {
+    async fn fetch_snapshot(&self, account: &TrackedAccount) -> Result<AccountSnapshot> {
+        let balances = self.get_balances(account.identifier()).await?;
 
-    fn native_balance(&self) -> f64 {
-        self.sui_balance
-    }
+        let mut holdings = vec![Holding::native("SUI", balances.sui_balance)];
+        holdings.extend(balances.staked.iter().map(|stake| {
+            Holding::staked("SUI", stake.id.clone(), TokenAmount::new(stake.mist(), 9))
+        }));
+        holdings.extend(balances.token_balances.into_iter().map(|token| {
+            Holding::token(token.coin_type, token.symbol, token.name, token.amount)
+        }));
 
-    fn set_native_usd_price(&mut self, price: f64) {
-        self.sui_usd_price = Some(price);
+        Ok(AccountSnapshot::new(account, holdings).with_detail("RPC Endpoint", self.rpc.served_by()))
     }
+}
 
-    fn set_native_usd_value(&mut self, value: f64) {
-        self.sui_usd_value = Some(value);
-    }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    fn set_total_usd_value(&mut self, value: f64) {
-        self.total_usd_value = Some(value);
-    }
+    #[test]
+    fn test_staked_sui_includes_rewards() {
+        let delegations: Vec<DelegatedStake> = serde_json::from_str(
+            r#"[{
+                "validatorAddress": "0x1a",
+                "stakingPool": "0x2b",
+                "stakes": [
+                    { "stakedSuiId": "0x3c", "stakeRequestEpoch": "400", "stakeActiveEpoch": "401", "principal": "1000000000000", "status": "Active", "estimatedReward": "25000000000" },
+                    { "stakedSuiId": "0x4d", "stakeRequestEpoch": "500", "stakeActiveEpoch": "501", "principal": "2000000000", "status": "Pending" }
+                ]
+            }]"#,
+        )
+        .unwrap();
 
-    // Sui doesn't have token balances yet, use default implementation
+        let staked = staked_sui(delegations).unwrap();
+        assert_eq!(staked.len(), 2);
+        assert_eq!(staked[0].validator, "0x1a");
+        assert_eq!(staked[0].mist(), 1_025_000_000_000);
+        assert_eq!(staked[1].status, "Pending");
+        assert_eq!(staked[1].estimated_reward, 0);
+    }
 }
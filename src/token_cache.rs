@@ -0,0 +1,165 @@
+use crate::storage::Chain;
+
+use anyhow::{Context, Result};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// Shared by every client in the process; loaded on first use
+static CACHE: Lazy<Mutex<TokenCache>> = Lazy::new(|| {
+    Mutex::new(TokenCache::load().unwrap_or_else(|e| {
+        eprintln!("Warning: Ignoring token cache: {:#}", e);
+        TokenCache::default()
+    }))
+});
+
+/// Token metadata that never changes once a token exists
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TokenMetadata {
+    pub decimals: u8,
+    pub symbol: Option<String>,
+    pub name: Option<String>,
+}
+
+/// How far an EVM wallet's incoming `Transfer` logs have been scanned, and
+/// the token contracts found so far
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LogScan {
+    pub last_block: Option<u64>,
+    pub tokens: BTreeSet<String>,
+}
+
+/// Token metadata and discovery state in ~/.gringotts/token_cache.json, so
+/// they are not re-queried on every run. Entries are keyed by chain plus
+/// token (or wallet) address.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct TokenCache {
+    #[serde(default)]
+    metadata: BTreeMap<String, TokenMetadata>,
+    #[serde(default)]
+    log_scans: BTreeMap<String, LogScan>,
+    #[serde(skip)]
+    dirty: bool,
+}
+
+fn cache_key(chain: &Chain, id: &str) -> String {
+    format!("{}:{}", chain.key(), id)
+}
+
+impl TokenCache {
+    fn load() -> Result<Self> {
+        let path = Self::get_storage_path()?;
+
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = fs::read_to_string(&path)
+            .context("Failed to read token cache")?;
+
+        serde_json::from_str(&content)
+            .context("Failed to parse token cache")
+    }
+
+    fn save(&mut self) -> Result<()> {
+        if !self.dirty {
+            return Ok(());
+        }
+
+        let path = Self::get_storage_path()?;
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .context("Failed to create storage directory")?;
+        }
+
+        let content = serde_json::to_string_pretty(self)
+            .context("Failed to serialize token cache")?;
+
+        // Write then rename so a concurrent reader never sees half a file
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, content)
+            .context("Failed to write token cache")?;
+        fs::rename(&tmp, &path)
+            .context("Failed to write token cache")?;
+
+        self.dirty = false;
+        Ok(())
+    }
+
+    fn get_storage_path() -> Result<PathBuf> {
+        let home = dirs::home_dir()
+            .context("Failed to get home directory")?;
+
+        Ok(home.join(".gringotts").join("token_cache.json"))
+    }
+
+    pub fn metadata(&self, chain: &Chain, token: &str) -> Option<&TokenMetadata> {
+        self.metadata.get(&cache_key(chain, token))
+    }
+
+    pub fn insert_metadata(&mut self, chain: &Chain, token: &str, metadata: TokenMetadata) {
+        self.metadata.insert(cache_key(chain, token), metadata);
+        self.dirty = true;
+    }
+
+    pub fn log_scan(&self, chain: &Chain, wallet: &str) -> Option<&LogScan> {
+        self.log_scans.get(&cache_key(chain, wallet))
+    }
+
+    pub fn insert_log_scan(&mut self, chain: &Chain, wallet: &str, scan: LogScan) {
+        self.log_scans.insert(cache_key(chain, wallet), scan);
+        self.dirty = true;
+    }
+}
+
+/// Run `f` against the process-wide cache
+pub fn with_cache<T>(f: impl FnOnce(&mut TokenCache) -> T) -> T {
+    let mut cache = CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    f(&mut cache)
+}
+
+/// Write pending cache changes to disk. Failing to save only costs a
+/// re-query next time, so callers report it as a warning.
+pub fn save() {
+    if let Err(e) = with_cache(|cache| cache.save()) {
+        eprintln!("Warning: {:#}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_round_trip() {
+        let mut cache = TokenCache::default();
+        let usdc = TokenMetadata {
+            decimals: 6,
+            symbol: Some("USDC".to_string()),
+            name: Some("USD Coin".to_string()),
+        };
+        cache.insert_metadata(&Chain::Ethereum, "0xa0b8", usdc.clone());
+        cache.insert_log_scan(
+            &Chain::Base,
+            "0xwallet",
+            LogScan {
+                last_block: Some(100),
+                tokens: BTreeSet::from(["0xtoken".to_string()]),
+            },
+        );
+        assert!(cache.dirty);
+
+        let json = serde_json::to_string(&cache).unwrap();
+        let loaded: TokenCache = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(loaded.metadata(&Chain::Ethereum, "0xa0b8"), Some(&usdc));
+        // Keys are per chain
+        assert_eq!(loaded.metadata(&Chain::Polygon, "0xa0b8"), None);
+        assert_eq!(loaded.log_scan(&Chain::Base, "0xwallet").unwrap().last_block, Some(100));
+        assert!(!loaded.dirty);
+    }
+}
@@ -0,0 +1,180 @@
+use crate::storage::Chain;
+use crate::token_cache::TokenMetadata;
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::fs;
+use std::path::PathBuf;
+
+// Token lists in ~/.gringotts/tokens/<chain>.json. Each chain's client says
+// what a token address looks like there: an EVM contract, a NEAR account, an
+// Aptos type name or object address, a Starknet contract.
+
+/// A token from the user's token list, with metadata when the list has it
+pub struct ListedToken {
+    /// Normalized token address (contract, account, metadata object, ...)
+    pub address: String,
+    pub metadata: Option<TokenMetadata>,
+}
+
+/// Tokens and staking pools listed in ~/.gringotts/tokens/<chain>.json
+#[derive(Default)]
+pub struct TokenList {
+    pub tokens: Vec<ListedToken>,
+    pub staking_pools: Vec<String>,
+}
+
+impl TokenList {
+    /// Metadata the list gives for `address`, which takes precedence over the chain's
+    pub fn metadata(&self, address: &str) -> Option<TokenMetadata> {
+        self.tokens
+            .iter()
+            .find(|token| token.address == address)
+            .and_then(|token| token.metadata.clone())
+    }
+}
+
+/// Entry in a token list: a token address, or an object with `address`
+/// (or `contract`), `symbol`, `name` and `decimals`. Standard EVM token lists
+/// also give each token's `chainId`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TokenListEntry {
+    Address(String),
+    Token {
+        #[serde(alias = "contract")]
+        address: String,
+        #[serde(rename = "chainId")]
+        chain_id: Option<u64>,
+        symbol: Option<String>,
+        name: Option<String>,
+        decimals: Option<u8>,
+    },
+}
+
+/// A plain array of token entries, or `{"tokens": [...], "staking_pools": [...]}`
+/// (which also reads standard token lists)
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TokenListFile {
+    Entries(Vec<TokenListEntry>),
+    TokenList {
+        #[serde(default)]
+        tokens: Vec<TokenListEntry>,
+        #[serde(default)]
+        staking_pools: Vec<String>,
+    },
+}
+
+fn token_list_path(chain: &Chain) -> Result<PathBuf> {
+    let home = dirs::home_dir()
+        .context("Failed to get home directory")?;
+
+    Ok(home.join(".gringotts").join("tokens").join(format!("{}.json", chain.key())))
+}
+
+/// The user's token list for a chain. `normalize` puts addresses in the
+/// form the chain's client compares them in.
+pub fn load(chain: &Chain, normalize: impl Fn(&str) -> Result<String>) -> Result<TokenList> {
+    let path = token_list_path(chain)?;
+
+    if !path.exists() {
+        return Ok(TokenList::default());
+    }
+
+    let content = fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let file: TokenListFile = serde_json::from_str(&content)
+        .with_context(|| format!("Failed to parse {}", path.display()))?;
+
+    parse_token_list(file, chain, normalize)
+}
+
+/// Entries for another EVM chain id are skipped, as standard token lists
+/// cover several chains
+fn parse_token_list(file: TokenListFile, chain: &Chain, normalize: impl Fn(&str) -> Result<String>) -> Result<TokenList> {
+    let (entries, staking_pools) = match file {
+        TokenListFile::Entries(entries) => (entries, Vec::new()),
+        TokenListFile::TokenList { tokens, staking_pools } => (tokens, staking_pools),
+    };
+
+    let mut tokens = Vec::new();
+    for entry in entries {
+        let (address, metadata) = match entry {
+            TokenListEntry::Address(address) => (address, None),
+            TokenListEntry::Token { chain_id: Some(chain_id), .. }
+                if chain.evm_chain_id().is_some_and(|id| id != chain_id) =>
+            {
+                continue
+            }
+            TokenListEntry::Token { address, symbol, name, decimals, .. } => {
+                (address, decimals.map(|decimals| TokenMetadata { decimals, symbol, name }))
+            }
+        };
+        tokens.push(ListedToken {
+            address: normalize(&address).with_context(|| format!("Invalid token address in token list: {}", address))?,
+            metadata,
+        });
+    }
+
+    let staking_pools = staking_pools
+        .iter()
+        .map(|pool| normalize(pool).with_context(|| format!("Invalid staking pool in token list: {}", pool)))
+        .collect::<Result<_>>()?;
+
+    Ok(TokenList { tokens, staking_pools })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lowercase(address: &str) -> Result<String> {
+        if address.is_empty() {
+            anyhow::bail!("empty address");
+        }
+        Ok(address.to_lowercase())
+    }
+
+    #[test]
+    fn test_parse_token_list() {
+        let plain: TokenListFile = serde_json::from_str(
+            r#"[
+                "Token.Sweat",
+                { "address": "aurora", "symbol": "ETH", "decimals": 18 }
+            ]"#,
+        )
+        .unwrap();
+        let list = parse_token_list(plain, &Chain::Near, lowercase).unwrap();
+        assert_eq!(list.tokens[0].address, "token.sweat");
+        assert!(list.tokens[0].metadata.is_none());
+        assert_eq!(list.metadata("aurora").unwrap().decimals, 18);
+        assert!(list.staking_pools.is_empty());
+
+        let with_pools: TokenListFile = serde_json::from_str(
+            r#"{ "tokens": [{ "contract": "meta-pool.near" }], "staking_pools": ["Astro-Stakers.poolv1.near"] }"#,
+        )
+        .unwrap();
+        let list = parse_token_list(with_pools, &Chain::Near, lowercase).unwrap();
+        assert_eq!(list.tokens[0].address, "meta-pool.near");
+        assert_eq!(list.staking_pools, vec!["astro-stakers.poolv1.near".to_string()]);
+
+        let bad: TokenListFile = serde_json::from_str(r#"[""]"#).unwrap();
+        assert!(parse_token_list(bad, &Chain::Near, lowercase).is_err());
+    }
+
+    #[test]
+    fn test_parse_standard_token_list() {
+        // Standard token lists cover several chains; only this chain's entries are used
+        let list: TokenListFile = serde_json::from_str(
+            r#"{ "name": "Treasury", "tokens": [
+                { "chainId": 1, "address": "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2", "symbol": "WETH", "name": "Wrapped Ether", "decimals": 18 },
+                { "chainId": 42161, "address": "0x912CE59144191C1204E64559FE8253a0e49E6548", "symbol": "ARB", "name": "Arbitrum", "decimals": 18 }
+            ]}"#,
+        )
+        .unwrap();
+        let list = parse_token_list(list, &Chain::Arbitrum, lowercase).unwrap();
+        assert_eq!(list.tokens.len(), 1);
+        assert_eq!(list.metadata("0x912ce59144191c1204e64559fe8253a0e49e6548").unwrap().symbol.as_deref(), Some("ARB"));
+    }
+}
@@ -1,30 +1,11 @@
-use crate::aptos;
-use crate::circle;
-use crate::evm;
-use crate::mercury;
-use crate::near;
-use crate::solana;
-use crate::starknet;
-use crate::storage::{BankingAccount, BankingService, Chain, WalletAddress};
-use crate::sui;
-
-fn format_usd(value: f64) -> String {
-    let formatted = format!("{:.2}", value);
-    let parts: Vec<&str> = formatted.split('.').collect();
-    let integer_part = parts[0];
-    let decimal_part = parts.get(1).unwrap_or(&"00");
-
-    // Add commas to integer part
-    let mut result = String::new();
-    for (i, ch) in integer_part.chars().rev().enumerate() {
-        if i > 0 && i % 3 == 0 {
-            result.push(',');
-        }
-        result.push(ch);
-    }
+use crate::amount::{format_fixed, format_usd};
+use crate::history::{SnapshotDiff, SnapshotInfo, StoredSnapshot};
+use crate::output::UtxoDto;
+use crate::portfolio::PortfolioSummary;
+use crate::source::{AccountSnapshot, Holding, HoldingKind};
+use crate::storage::{BankingAccount, Chain, TrackedAccount, WalletAddress};
 
-    format!("{}.{}", result.chars().rev().collect::<String>(), decimal_part)
-}
+use bigdecimal::{BigDecimal, Zero};
 
 fn truncate_string(s: &str, max_len: usize) -> String {
     if s.len() <= max_len {
@@ -166,235 +147,181 @@ pub fn render_addresses(addresses: &[WalletAddress], banking_accounts: &[Banking
     println!("╰{}╯\n", "─".repeat(table_width - 2));
 }
 
-pub fn render_solana_balances(company: &str, name: &str, address: &str, balances: &solana::AccountBalances, chain: &Chain) {
-    const MIN_WIDTH: usize = 79;
-
-    // Collect all content lines to calculate max width
-    let mut lines = Vec::new();
-
-    // Header lines
-    let display_company = if company.is_empty() { "-" } else { company };
-    lines.push(format!("Company: {}", display_company));
-    lines.push(format!("Wallet: {}", name));
-    lines.push(format!("Address: {}", &address[..42.min(address.len())]));
-    lines.push(format!("Chain: {}", chain.display_name()));
-
-    // SOL Balance line
-    let sol_line = if let Some(usd_value) = balances.sol_usd_value {
-        if let Some(price) = balances.sol_usd_price {
-            format!("SOL Balance: {:.9} SOL (${} @ ${})", balances.sol_balance, format_usd(usd_value), format_usd(price))
-        } else {
-            format!("SOL Balance: {:.9} SOL (${})", balances.sol_balance, format_usd(usd_value))
-        }
-    } else {
-        format!("SOL Balance: {:.9} SOL", balances.sol_balance)
-    };
-    lines.push(sol_line);
-
-    // Token balance lines
-    if !balances.token_balances.is_empty() {
-        lines.push("TOKEN BALANCES".to_string());
-        for token in &balances.token_balances {
-            let token_display = match (&token.name, &token.symbol) {
-                (Some(name), Some(symbol)) => format!("{} ({})", name, symbol),
-                (Some(name), None) => name.clone(),
-                (None, Some(symbol)) => symbol.clone(),
-                (None, None) => "Unknown Token".to_string(),
-            };
-            lines.push(token_display);
-
-            let mint_display = if token.mint.len() > 44 {
-                format!("    Mint: {}...{}", &token.mint[..20], &token.mint[token.mint.len()-20..])
-            } else {
-                format!("    Mint: {}", token.mint)
-            };
-            lines.push(mint_display);
-
-            let balance_str = if let Some(usd_value) = token.usd_value {
-                if let Some(price) = token.usd_price {
-                    format!("    Balance: {:.6} (${} @ ${:.6})", token.ui_amount, format_usd(usd_value), price)
-                } else {
-                    format!("    Balance: {:.6} (${})", token.ui_amount, format_usd(usd_value))
-                }
-            } else {
-                format!("    Balance: {:.6}", token.ui_amount)
-            };
-            lines.push(balance_str);
-            lines.push(format!("    Decimals: {}", token.decimals));
-        }
-    }
-
-    // Total USD Value line
-    if let Some(total) = balances.total_usd_value {
-        lines.push(format!("TOTAL USD VALUE: ${}", format_usd(total)));
+/// Label for the identifier of a token on a given chain
+fn asset_id_label(chain: Option<&Chain>) -> &'static str {
+    match chain {
+        Some(Chain::Solana) => "Mint",
+        Some(Chain::Aptos) | Some(Chain::Sui) => "Coin Type",
+        _ => "Contract",
     }
+}
 
-    // Calculate max width needed
-    let max_content_width = lines.iter().map(|l| l.len()).max().unwrap_or(MIN_WIDTH);
-    let box_width = max_content_width.max(MIN_WIDTH);
+fn format_balance_line(holding: &Holding) -> String {
+    let symbol = holding.symbol.as_deref().unwrap_or("?");
 
-    // Top border
-    println!("\n╔{}╗", "═".repeat(box_width + 2));
+    let amount = holding.amount.to_decimal();
 
-    // Header section
-    println!("║  {:<width$} ║", lines[0], width = box_width);
-    println!("║  {:<width$} ║", lines[1], width = box_width);
-    println!("║  {:<width$} ║", lines[2], width = box_width);
-    println!("║  {:<width$} ║", lines[3], width = box_width);
-    println!("╠{}╣", "═".repeat(box_width + 2));
+    if holding.kind == HoldingKind::Fiat {
+        return format!("{} Balance: ${}", symbol, format_usd(&amount));
+    }
 
-    // SOL Balance
-    println!("║  {:<width$} ║", lines[4], width = box_width);
-    println!("╠{}╣", "═".repeat(box_width + 2));
+    let label = match (holding.kind, &holding.asset_id) {
+        (HoldingKind::Staked, Some(stake_account)) => format!("{} Staked ({})", symbol, truncate_string(stake_account, 13)),
+        _ => format!("{} Balance", symbol),
+    };
 
-    // Token Balances
-    if balances.token_balances.is_empty() {
-        println!("║  {:<width$} ║", "Token Balances: None", width = box_width);
-    } else {
-        println!("║  {:<width$} ║", lines[5], width = box_width);
-        println!("╟{}╢", "─".repeat(box_width + 2));
-
-        let mut line_idx = 6;
-        for _ in &balances.token_balances {
-            println!("║  {:<width$} ║", lines[line_idx], width = box_width);     // Token name
-            println!("║  {:<width$} ║", lines[line_idx + 1], width = box_width); // Mint
-            println!("║  {:<width$} ║", lines[line_idx + 2], width = box_width); // Balance
-            println!("║  {:<width$} ║", lines[line_idx + 3], width = box_width); // Decimals
-            println!("╟{}╢", "─".repeat(box_width + 2));
-            line_idx += 4;
+    let amount_str = format_fixed(&amount, 9);
+    if let Some(usd_value) = &holding.usd_value {
+        if let Some(price) = &holding.usd_price {
+            format!("{}: {} {} (${} @ ${})", label, amount_str, symbol, format_usd(usd_value), format_usd(price))
+        } else {
+            format!("{}: {} {} (${})", label, amount_str, symbol, format_usd(usd_value))
         }
+    } else {
+        format!("{}: {} {}", label, amount_str, symbol)
     }
-
-    // Total USD Value
-    if balances.total_usd_value.is_some() {
-        let total_line_idx = lines.len() - 1;
-        println!("╠{}╣", "═".repeat(box_width + 2));
-        println!("║  {:<width$} ║", lines[total_line_idx], width = box_width);
-    }
-
-    // Bottom border
-    println!("╚{}╝\n", "═".repeat(box_width + 2));
 }
 
-pub fn render_error(error: &str) {
-    println!("\n╭─────────────────────────────────────────────────────────────────────────────────╮");
-    println!("│ ERROR                                                                            │");
-    println!("├─────────────────────────────────────────────────────────────────────────────────┤");
-    println!("│ {}                                                                              │", error);
-    println!("╰─────────────────────────────────────────────────────────────────────────────────╯\n");
-}
+fn format_token_lines(holding: &Holding, id_label: &str) -> Vec<String> {
+    let mut lines = vec![holding.display_name()];
 
-pub fn render_success(message: &str) {
-    println!("\n{}\n", message);
+    if let Some(asset_id) = &holding.asset_id {
+        let id_display = if asset_id.len() > 44 {
+            format!("    {}: {}...{}", id_label, &asset_id[..20], &asset_id[asset_id.len()-20..])
+        } else {
+            format!("    {}: {}", id_label, asset_id)
+        };
+        lines.push(id_display);
+    }
+
+    let amount_str = format_fixed(&holding.amount.to_decimal(), 6);
+    let balance_str = if let Some(usd_value) = &holding.usd_value {
+        if let Some(price) = &holding.usd_price {
+            format!("    Balance: {} (${} @ ${})", amount_str, format_usd(usd_value), format_fixed(price, 6))
+        } else {
+            format!("    Balance: {} (${})", amount_str, format_usd(usd_value))
+        }
+    } else {
+        format!("    Balance: {}", amount_str)
+    };
+    lines.push(balance_str);
+    lines.push(format!("    Decimals: {}", holding.decimals()));
+    lines
 }
 
-pub fn render_evm_balances(company: &str, name: &str, address: &str, balances: &evm::AccountBalances, chain: &Chain) {
+/// Render the balances of a single wallet or banking account
+pub fn render_account_snapshot(snapshot: &AccountSnapshot) {
     const MIN_WIDTH: usize = 79;
 
-    // Collect all content lines to calculate max width
-    let mut lines = Vec::new();
+    let account = &snapshot.account;
 
     // Header lines
-    let display_company = if company.is_empty() { "-" } else { company };
-    lines.push(format!("Company: {}", display_company));
-    lines.push(format!("Wallet: {}", name));
-    lines.push(format!("Address: {}", address));
-    lines.push(format!("Chain: {}", chain.display_name()));
-
-    // Native token balance line (ETH, CORE, MATIC, BNB, AVAX, etc.)
-    let native_symbol = chain.native_token_symbol();
-    let native_line = if let Some(usd_value) = balances.eth_usd_value {
-        if let Some(price) = balances.eth_usd_price {
-            format!("{} Balance: {:.9} {} (${} @ ${})", native_symbol, balances.eth_balance, native_symbol, format_usd(usd_value), format_usd(price))
-        } else {
-            format!("{} Balance: {:.9} {} (${})", native_symbol, balances.eth_balance, native_symbol, format_usd(usd_value))
+    let mut header = Vec::new();
+    let display_company = if account.company().is_empty() { "-" } else { account.company() };
+    header.push(format!("Company: {}", display_company));
+    match account {
+        TrackedAccount::Wallet(_) => {
+            header.push(format!("Wallet: {}", account.name()));
+            header.push(format!("Address: {}", account.identifier()));
+            header.push(format!("Chain: {}", account.source_name()));
         }
-    } else {
-        format!("{} Balance: {:.9} {}", native_symbol, balances.eth_balance, native_symbol)
-    };
-    lines.push(native_line);
-
-    // Token balance lines
-    if !balances.token_balances.is_empty() {
-        lines.push("ERC20 TOKEN BALANCES".to_string());
-        for token in &balances.token_balances {
-            let token_display = match (&token.name, &token.symbol) {
-                (Some(name), Some(symbol)) => format!("{} ({})", name, symbol),
-                (Some(name), None) => name.clone(),
-                (None, Some(symbol)) => symbol.clone(),
-                (None, None) => "Unknown Token".to_string(),
-            };
-            lines.push(token_display);
-
-            lines.push(format!("    Contract: {}", token.contract_address));
-
-            let balance_str = if let Some(usd_value) = token.usd_value {
-                if let Some(price) = token.usd_price {
-                    format!("    Balance: {:.6} (${} @ ${:.6})", token.ui_amount, format_usd(usd_value), price)
-                } else {
-                    format!("    Balance: {:.6} (${})", token.ui_amount, format_usd(usd_value))
-                }
-            } else {
-                format!("    Balance: {:.6}", token.ui_amount)
-            };
-            lines.push(balance_str);
-            lines.push(format!("    Decimals: {}", token.decimals));
+        TrackedAccount::Bank(_) => {
+            header.push(format!("Account: {}", account.name()));
+            header.push(format!("Account ID: {}", account.identifier()));
+            header.push(format!("Service: {}", account.source_name()));
         }
     }
-
-    // Total USD Value line
-    if let Some(total) = balances.total_usd_value {
-        lines.push(format!("TOTAL USD VALUE: ${}", format_usd(total)));
+    for (label, value) in &snapshot.details {
+        header.push(format!("{}: {}", label, value));
     }
 
+    // Native and fiat balance lines
+    let balance_lines: Vec<String> = snapshot
+        .holdings
+        .iter()
+        .filter(|h| h.kind != HoldingKind::Token)
+        .map(format_balance_line)
+        .collect();
+
+    // Token lines, one group per token
+    let id_label = asset_id_label(account.chain());
+    let token_groups: Vec<Vec<String>> = snapshot
+        .holdings
+        .iter()
+        .filter(|h| h.kind == HoldingKind::Token)
+        .map(|h| format_token_lines(h, id_label))
+        .collect();
+
+    let total_line = snapshot
+        .total_usd_value
+        .as_ref()
+        .map(|total| format!("TOTAL USD VALUE: ${}", format_usd(total)));
+
     // Calculate max width needed
-    let max_content_width = lines.iter().map(|l| l.len()).max().unwrap_or(MIN_WIDTH);
+    let max_content_width = header
+        .iter()
+        .chain(balance_lines.iter())
+        .chain(token_groups.iter().flatten())
+        .chain(total_line.iter())
+        .map(|l| l.len())
+        .max()
+        .unwrap_or(MIN_WIDTH);
     let box_width = max_content_width.max(MIN_WIDTH);
 
-    // Top border
+    // Top border and header section
     println!("\n╔{}╗", "═".repeat(box_width + 2));
+    for line in &header {
+        println!("║  {:<width$} ║", line, width = box_width);
+    }
 
-    // Header section
-    println!("║  {:<width$} ║", lines[0], width = box_width);
-    println!("║  {:<width$} ║", lines[1], width = box_width);
-    println!("║  {:<width$} ║", lines[2], width = box_width);
-    println!("║  {:<width$} ║", lines[3], width = box_width);
-    println!("╠{}╣", "═".repeat(box_width + 2));
-
-    // ETH Balance
-    println!("║  {:<width$} ║", lines[4], width = box_width);
-    println!("╠{}╣", "═".repeat(box_width + 2));
+    // Native / fiat balances
+    if !balance_lines.is_empty() {
+        println!("╠{}╣", "═".repeat(box_width + 2));
+        for line in &balance_lines {
+            println!("║  {:<width$} ║", line, width = box_width);
+        }
+    }
 
     // Token Balances
-    if balances.token_balances.is_empty() {
-        println!("║  {:<width$} ║", "Token Balances: None", width = box_width);
-    } else {
-        println!("║  {:<width$} ║", lines[5], width = box_width);
-        println!("╟{}╢", "─".repeat(box_width + 2));
-
-        let mut line_idx = 6;
-        for _ in &balances.token_balances {
-            println!("║  {:<width$} ║", lines[line_idx], width = box_width);     // Token name
-            println!("║  {:<width$} ║", lines[line_idx + 1], width = box_width); // Contract
-            println!("║  {:<width$} ║", lines[line_idx + 2], width = box_width); // Balance
-            println!("║  {:<width$} ║", lines[line_idx + 3], width = box_width); // Decimals
+    if let TrackedAccount::Wallet(_) = account {
+        println!("╠{}╣", "═".repeat(box_width + 2));
+        if token_groups.is_empty() {
+            println!("║  {:<width$} ║", "Token Balances: None", width = box_width);
+        } else {
+            println!("║  {:<width$} ║", "TOKEN BALANCES", width = box_width);
             println!("╟{}╢", "─".repeat(box_width + 2));
-            line_idx += 4;
+            for group in &token_groups {
+                for line in group {
+                    println!("║  {:<width$} ║", line, width = box_width);
+                }
+                println!("╟{}╢", "─".repeat(box_width + 2));
+            }
         }
     }
 
     // Total USD Value
-    if balances.total_usd_value.is_some() {
-        let total_line_idx = lines.len() - 1;
+    if let Some(total_line) = total_line {
         println!("╠{}╣", "═".repeat(box_width + 2));
-        println!("║  {:<width$} ║", lines[total_line_idx], width = box_width);
+        println!("║  {:<width$} ║", total_line, width = box_width);
     }
 
     // Bottom border
     println!("╚{}╝\n", "═".repeat(box_width + 2));
 }
 
-pub fn render_portfolio_summary(portfolio: &crate::PortfolioSummary) {
+pub fn render_error(error: &str) {
+    println!("\n╭─────────────────────────────────────────────────────────────────────────────────╮");
+    println!("│ ERROR                                                                            │");
+    println!("├─────────────────────────────────────────────────────────────────────────────────┤");
+    println!("│ {}                                                                              │", error);
+    println!("╰─────────────────────────────────────────────────────────────────────────────────╯\n");
+}
+
+pub fn render_success(message: &str) {
+    println!("\n{}\n", message);
+}
+
+pub fn render_portfolio_summary(portfolio: &PortfolioSummary) {
     const BOX_WIDTH: usize = 81;
 
     println!("\n╔═════════════════════════════════════════════════════════════════════════════════╗");
@@ -402,7 +329,7 @@ pub fn render_portfolio_summary(portfolio: &crate::PortfolioSummary) {
     println!("╠═════════════════════════════════════════════════════════════════════════════════╣");
 
     // Total Portfolio Value with proper padding
-    let total_value_str = format!("Total Portfolio Value: ${}", format_usd(portfolio.total_usd_value));
+    let total_value_str = format!("Total Portfolio Value: ${}", format_usd(&portfolio.total_usd_value));
     let total_value_len = total_value_str.len();
     let total_padding = (BOX_WIDTH - 2).saturating_sub(total_value_len);
     println!("║  {}{:width$} ║", total_value_str, "", width = total_padding);
@@ -416,7 +343,7 @@ pub fn render_portfolio_summary(portfolio: &crate::PortfolioSummary) {
 
     // Sort companies by USD value (descending)
     let mut sorted_companies: Vec<_> = portfolio.companies.iter().collect();
-    sorted_companies.sort_by(|a, b| b.1.total_usd_value.total_cmp(&a.1.total_usd_value));
+    sorted_companies.sort_by(|a, b| b.1.total_usd_value.cmp(&a.1.total_usd_value));
 
     for (company_name, company) in sorted_companies {
         println!("╠═════════════════════════════════════════════════════════════════════════════════╣");
@@ -428,7 +355,7 @@ pub fn render_portfolio_summary(portfolio: &crate::PortfolioSummary) {
         println!("║  {}{:width$} ║", company_header, "", width = company_padding);
 
         // Company total value
-        let company_value_str = format!("Total Value: ${}", format_usd(company.total_usd_value));
+        let company_value_str = format!("Total Value: ${}", format_usd(&company.total_usd_value));
         let company_value_len = company_value_str.len();
         let company_value_padding = if company_value_len + 2 < BOX_WIDTH - 2 { BOX_WIDTH - 2 - company_value_len - 2 } else { 0 };
         println!("║    {}{:width$} ║", company_value_str, "", width = company_value_padding);
@@ -441,9 +368,10 @@ pub fn render_portfolio_summary(portfolio: &crate::PortfolioSummary) {
             // Sort assets by USD value (descending)
             let mut sorted_assets: Vec<_> = company.assets.iter().collect();
             sorted_assets.sort_by(|a, b| {
-                let a_val = a.1.usd_value.unwrap_or(0.0);
-                let b_val = b.1.usd_value.unwrap_or(0.0);
-                b_val.total_cmp(&a_val)
+                let zero = BigDecimal::zero();
+                let a_val = a.1.usd_value.as_ref().unwrap_or(&zero);
+                let b_val = b.1.usd_value.as_ref().unwrap_or(&zero);
+                b_val.cmp(a_val)
             });
 
             for (_, asset) in sorted_assets {
@@ -454,14 +382,13 @@ pub fn render_portfolio_summary(portfolio: &crate::PortfolioSummary) {
                 println!("║      {}{:width$} ║", symbol_str, "", width = symbol_padding);
 
                 // Amount and USD Value on same line if USD value exists
-                let usd_value = asset.usd_value.unwrap_or(0.0);
-                if usd_value > 0.0 {
-                    let detail_str = format!("{:.6} (${:})", asset.amount, format_usd(usd_value));
+                let amount_str = format_fixed(&asset.amount, 6);
+                if let Some(usd_value) = asset.usd_value.as_ref().filter(|v| *v > &BigDecimal::zero()) {
+                    let detail_str = format!("{} (${:})", amount_str, format_usd(usd_value));
                     let detail_len = detail_str.len();
                     let detail_padding = if detail_len + 8 < BOX_WIDTH - 2 { BOX_WIDTH - 2 - detail_len - 8 } else { 0 };
                     println!("║          {}{:width$} ║", detail_str, "", width = detail_padding);
                 } else {
-                    let amount_str = format!("{:.6}", asset.amount);
                     let amount_len = amount_str.len();
                     let amount_padding = if amount_len + 8 < BOX_WIDTH - 2 { BOX_WIDTH - 2 - amount_len - 8 } else { 0 };
                     println!("║          {}{:width$} ║", amount_str, "", width = amount_padding);
@@ -473,188 +400,173 @@ pub fn render_portfolio_summary(portfolio: &crate::PortfolioSummary) {
     println!("╚═════════════════════════════════════════════════════════════════════════════════╝\n");
 }
 
-#[allow(clippy::too_many_arguments)]
-fn render_simple_balance(
-    company: &str,
-    name: &str,
-    address: &str,
-    chain: &Chain,
-    symbol: &str,
-    balance: f64,
-    usd_price: Option<f64>,
-    usd_value: Option<f64>,
-    total_usd_value: Option<f64>,
-) {
-    const MIN_WIDTH: usize = 79;
-    let mut lines = Vec::new();
-
-    let display_company = if company.is_empty() { "-" } else { company };
-    lines.push(format!("Company: {}", display_company));
-    lines.push(format!("Wallet: {}", name));
-    lines.push(format!("Address: {}", address));
-    lines.push(format!("Chain: {}", chain.display_name()));
-
-    let balance_line = if let Some(usd_val) = usd_value {
-        if let Some(price) = usd_price {
-            format!("{} Balance: {:.9} {} (${} @ ${})", symbol, balance, symbol, format_usd(usd_val), format_usd(price))
-        } else {
-            format!("{} Balance: {:.9} {} (${})", symbol, balance, symbol, format_usd(usd_val))
-        }
+fn format_signed_usd(value: &BigDecimal) -> String {
+    if value < &BigDecimal::zero() {
+        format!("-${}", format_usd(&-value))
     } else {
-        format!("{} Balance: {:.9} {}", symbol, balance, symbol)
-    };
-    lines.push(balance_line);
-
-    if let Some(total) = total_usd_value {
-        lines.push(format!("TOTAL USD VALUE: ${}", format_usd(total)));
+        format!("+${}", format_usd(value))
     }
+}
 
-    let max_content_width = lines.iter().map(|l| l.len()).max().unwrap_or(MIN_WIDTH);
-    let box_width = max_content_width.max(MIN_WIDTH);
-
-    println!("\n╔{}╗", "═".repeat(box_width + 2));
-    println!("║  {:<width$} ║", lines[0], width = box_width);
-    println!("║  {:<width$} ║", lines[1], width = box_width);
-    println!("║  {:<width$} ║", lines[2], width = box_width);
-    println!("║  {:<width$} ║", lines[3], width = box_width);
-    println!("╠{}╣", "═".repeat(box_width + 2));
-    println!("║  {:<width$} ║", lines[4], width = box_width);
-
-    if total_usd_value.is_some() {
-        println!("╠{}╣", "═".repeat(box_width + 2));
-        println!("║  {:<width$} ║", lines[5], width = box_width);
+fn format_signed_amount(value: &BigDecimal) -> String {
+    if value < &BigDecimal::zero() {
+        format_fixed(value, 6)
+    } else {
+        format!("+{}", format_fixed(value, 6))
     }
-
-    println!("╚{}╝\n", "═".repeat(box_width + 2));
 }
 
-pub fn render_near_balances(company: &str, name: &str, address: &str, balances: &near::AccountBalances, chain: &Chain) {
-    render_simple_balance(
-        company, name, address, chain, "NEAR",
-        balances.near_balance, balances.near_usd_price, balances.near_usd_value, balances.total_usd_value,
-    );
-}
+pub fn render_snapshot_list(snapshots: &[SnapshotInfo]) {
+    const BOX_WIDTH: usize = 81;
 
-pub fn render_aptos_balances(company: &str, name: &str, address: &str, balances: &aptos::AccountBalances, chain: &Chain) {
-    render_simple_balance(
-        company, name, address, chain, "APT",
-        balances.apt_balance, balances.apt_usd_price, balances.apt_usd_value, balances.total_usd_value,
-    );
-}
+    println!("\n╔═════════════════════════════════════════════════════════════════════════════════╗");
+    println!("║                                SAVED SNAPSHOTS                                  ║");
+    println!("╠═════════════════════════════════════════════════════════════════════════════════╣");
 
-pub fn render_sui_balances(company: &str, name: &str, address: &str, balances: &sui::AccountBalances, chain: &Chain) {
-    render_simple_balance(
-        company, name, address, chain, "SUI",
-        balances.sui_balance, balances.sui_usd_price, balances.sui_usd_value, balances.total_usd_value,
-    );
-}
+    let header = format!("{:>6}  {:<22} {:<6} {:>8}  {:>20}", "ID", "Taken", "From", "Accounts", "Total Value");
+    println!("║  {:<width$} ║", header, width = BOX_WIDTH - 2);
+    println!("╟─────────────────────────────────────────────────────────────────────────────────╢");
+
+    for snapshot in snapshots {
+        let line = format!(
+            "{:>6}  {:<22} {:<6} {:>8}  {:>20}",
+            format!("#{}", snapshot.id),
+            snapshot.taken_at_display(),
+            snapshot.origin,
+            snapshot.account_count,
+            format!("${}", format_usd(&snapshot.total_usd_value)),
+        );
+        println!("║  {:<width$} ║", line, width = BOX_WIDTH - 2);
+    }
 
-pub fn render_starknet_balances(company: &str, name: &str, address: &str, balances: &starknet::AccountBalances, chain: &Chain) {
-    render_simple_balance(
-        company, name, address, chain, "ETH",
-        balances.eth_balance, balances.eth_usd_price, balances.eth_usd_value, balances.total_usd_value,
-    );
+    println!("╚═════════════════════════════════════════════════════════════════════════════════╝\n");
 }
 
-pub fn render_mercury_balances(company: &str, name: &str, account_id: &str, balances: &mercury::AccountBalances, service: &BankingService) {
-    const MIN_WIDTH: usize = 79;
-    let mut lines = Vec::new();
-
-    let display_company = if company.is_empty() { "-" } else { company };
-    lines.push(format!("Company: {}", display_company));
-    lines.push(format!("Account: {}", name));
-    lines.push(format!("Account ID: {}", account_id));
-    lines.push(format!("Service: {}", service.display_name()));
-    lines.push(format!("Status: {}", balances.status));
+pub fn render_utxos(name: &str, utxos: &[UtxoDto]) {
+    const BOX_WIDTH: usize = 81;
 
-    lines.push(format!("Available Balance: ${}", format_usd(balances.available_balance)));
-    lines.push(format!("Current Balance: ${}", format_usd(balances.current_balance)));
+    println!("\n╔═════════════════════════════════════════════════════════════════════════════════╗");
+    let title = format!("UTXOS - {}", name);
+    println!("║  {:<width$} ║", title, width = BOX_WIDTH - 2);
+    println!("╠═════════════════════════════════════════════════════════════════════════════════╣");
 
-    if let Some(created_at) = &balances.created_at {
-        lines.push(format!("Created: {}", created_at));
+    if utxos.is_empty() {
+        println!("║  {:<width$} ║", "No unspent outputs", width = BOX_WIDTH - 2);
+        println!("╚═════════════════════════════════════════════════════════════════════════════════╝\n");
+        return;
     }
 
-    let max_content_width = lines.iter().map(|l| l.len()).max().unwrap_or(MIN_WIDTH);
-    let box_width = max_content_width.max(MIN_WIDTH);
+    let header = format!("{:<20} {:<42} {:>14}", "Outpoint", "Address", "BTC");
+    println!("║  {:<width$} ║", header, width = BOX_WIDTH - 2);
+    println!("╟─────────────────────────────────────────────────────────────────────────────────╢");
 
-    println!("\n╔{}╗", "═".repeat(box_width + 2));
-    println!("║  {:<width$} ║", lines[0], width = box_width);
-    println!("║  {:<width$} ║", lines[1], width = box_width);
-    println!("║  {:<width$} ║", lines[2], width = box_width);
-    println!("║  {:<width$} ║", lines[3], width = box_width);
-    println!("║  {:<width$} ║", lines[4], width = box_width);
-    println!("╠{}╣", "═".repeat(box_width + 2));
-    println!("║  {:<width$} ║", lines[5], width = box_width);
-    println!("║  {:<width$} ║", lines[6], width = box_width);
-
-    if balances.created_at.is_some() {
-        println!("╠{}╣", "═".repeat(box_width + 2));
-        println!("║  {:<width$} ║", lines[7], width = box_width);
+    for utxo in utxos {
+        // Shortened txid; the full outpoint is in the json and csv output
+        let outpoint = format!("{}…:{}", &utxo.txid[..utxo.txid.len().min(12)], utxo.vout);
+        let address = if utxo.address.chars().count() > 42 {
+            format!("{}…", utxo.address.chars().take(41).collect::<String>())
+        } else {
+            utxo.address.clone()
+        };
+        let amount = match utxo.block_height {
+            Some(_) => utxo.amount.clone(),
+            None => format!("{}*", utxo.amount),
+        };
+        let line = format!("{:<20} {:<42} {:>14}", outpoint, address, amount);
+        println!("║  {:<width$} ║", line, width = BOX_WIDTH - 2);
     }
 
-    println!("╚{}╝\n", "═".repeat(box_width + 2));
+    if utxos.iter().any(|utxo| utxo.block_height.is_none()) {
+        println!("╟─────────────────────────────────────────────────────────────────────────────────╢");
+        println!("║  {:<width$} ║", "* unconfirmed", width = BOX_WIDTH - 2);
+    }
+
+    println!("╚═════════════════════════════════════════════════════════════════════════════════╝\n");
 }
 
-pub fn render_circle_balances(company: &str, name: &str, balances: &circle::AccountBalances, service: &BankingService) {
-    const MIN_WIDTH: usize = 79;
-    let mut lines = Vec::new();
-
-    let display_company = if company.is_empty() { "-" } else { company };
-    lines.push(format!("Company: {}", display_company));
-    lines.push(format!("Account: {}", name));
-    lines.push(format!("Service: {}", service.display_name()));
-
-    // Available balances section
-    if !balances.available_balances.is_empty() {
-        lines.push("AVAILABLE BALANCES".to_string());
-        for balance in &balances.available_balances {
-            let currency_display = if balance.currency == "USD" {
-                "USDC"
-            } else if balance.currency == "EUR" {
-                "EURC"
-            } else {
-                &balance.currency
-            };
-            lines.push(format!("  {}: ${}", currency_display, format_usd(balance.amount)));
-        }
-    }
+pub fn render_snapshot_holdings(snapshot: &StoredSnapshot) {
+    const BOX_WIDTH: usize = 81;
 
-    // Unsettled balances section
-    if !balances.unsettled_balances.is_empty() {
-        let has_nonzero_unsettled = balances.unsettled_balances.iter().any(|b| b.amount > 0.0);
-        if has_nonzero_unsettled {
-            lines.push("UNSETTLED BALANCES (pending)".to_string());
-            for balance in &balances.unsettled_balances {
-                if balance.amount > 0.0 {
-                    let currency_display = if balance.currency == "USD" {
-                        "USDC"
-                    } else if balance.currency == "EUR" {
-                        "EURC"
-                    } else {
-                        &balance.currency
-                    };
-                    lines.push(format!("  {}: ${}", currency_display, format_usd(balance.amount)));
-                }
-            }
+    println!("\n╔═════════════════════════════════════════════════════════════════════════════════╗");
+    println!("║                                    ACCOUNTS                                     ║");
+
+    let mut current_account: Option<(&str, &str)> = None;
+    for holding in snapshot.holdings.iter().filter(|h| !h.amount.is_zero()) {
+        let account = (holding.account_name.as_str(), holding.source.as_str());
+        if current_account != Some(account) {
+            println!("╠═════════════════════════════════════════════════════════════════════════════════╣");
+            let header = format!("{} ({}) - {}", holding.account_name, holding.source, holding.company);
+            println!("║  {:<width$} ║", header, width = BOX_WIDTH - 2);
+            current_account = Some(account);
         }
+
+        let symbol = holding.symbol.as_deref().unwrap_or("Unknown Token");
+        let amount_str = format_fixed(&holding.amount, 6);
+        let line = match &holding.usd_value {
+            Some(value) if value > &BigDecimal::zero() => format!("{}: {} (${})", symbol, amount_str, format_usd(value)),
+            _ => format!("{}: {}", symbol, amount_str),
+        };
+        println!("║      {:<width$} ║", line, width = BOX_WIDTH - 6);
     }
 
-    let max_content_width = lines.iter().map(|l| l.len()).max().unwrap_or(MIN_WIDTH);
-    let box_width = max_content_width.max(MIN_WIDTH);
+    println!("╚═════════════════════════════════════════════════════════════════════════════════╝");
+}
 
-    println!("\n╔{}╗", "═".repeat(box_width + 2));
+pub fn render_snapshot_diff(diff: &SnapshotDiff) {
+    const BOX_WIDTH: usize = 81;
 
-    // Print header lines
-    for line in lines.iter().take(3) {
-        println!("║  {:<width$} ║", line, width = box_width);
+    println!("\n╔═════════════════════════════════════════════════════════════════════════════════╗");
+    println!("║                                SNAPSHOT DIFF                                    ║");
+    println!("╠═════════════════════════════════════════════════════════════════════════════════╣");
+
+    let from_line = format!("From: #{} ({})  ${}", diff.from.id, diff.from.taken_at_display(), format_usd(&diff.from.total_usd_value));
+    let to_line = format!("To:   #{} ({})  ${}", diff.to.id, diff.to.taken_at_display(), format_usd(&diff.to.total_usd_value));
+    let change_line = format!("Change: {}", format_signed_usd(&diff.total_change()));
+    println!("║  {:<width$} ║", from_line, width = BOX_WIDTH - 2);
+    println!("║  {:<width$} ║", to_line, width = BOX_WIDTH - 2);
+    println!("║  {:<width$} ║", change_line, width = BOX_WIDTH - 2);
+
+    if diff.companies.is_empty() {
+        println!("╠═════════════════════════════════════════════════════════════════════════════════╣");
+        println!("║  No changes                                                                     ║");
+        println!("╚═════════════════════════════════════════════════════════════════════════════════╝\n");
+        return;
     }
 
-    println!("╠{}╣", "═".repeat(box_width + 2));
+    for company in &diff.companies {
+        println!("╠═════════════════════════════════════════════════════════════════════════════════╣");
 
-    // Print remaining lines
-    for line in lines.iter().skip(3) {
-        println!("║  {:<width$} ║", line, width = box_width);
+        let company_header = format!(
+            "COMPANY: {}  ${} → ${} ({})",
+            company.company,
+            format_usd(&company.usd_before),
+            format_usd(&company.usd_after),
+            format_signed_usd(&(&company.usd_after - &company.usd_before)),
+        );
+        println!("║  {:<width$} ║", company_header, width = BOX_WIDTH - 2);
+        println!("╟─────────────────────────────────────────────────────────────────────────────────╢");
+
+        for asset in &company.assets {
+            let symbol_str = format!("{}:", asset.symbol);
+            println!("║      {:<width$} ║", symbol_str, width = BOX_WIDTH - 6);
+
+            let amount_str = format!(
+                "{} → {} ({})",
+                format_fixed(&asset.amount_before, 6),
+                format_fixed(&asset.amount_after, 6),
+                format_signed_amount(&(&asset.amount_after - &asset.amount_before)),
+            );
+            println!("║          {:<width$} ║", amount_str, width = BOX_WIDTH - 10);
+
+            let value_str = format!(
+                "${} → ${} ({})",
+                format_usd(&asset.usd_before),
+                format_usd(&asset.usd_after),
+                format_signed_usd(&(&asset.usd_after - &asset.usd_before)),
+            );
+            println!("║          {:<width$} ║", value_str, width = BOX_WIDTH - 10);
+        }
     }
 
-    println!("╚{}╝\n", "═".repeat(box_width + 2));
+    println!("╚═════════════════════════════════════════════════════════════════════════════════╝\n");
 }
@@ -1,68 +1,59 @@
-use crate::aptos::AptosClient;
-use crate::circle::CircleClient;
+use crate::amount;
+use crate::api;
+use crate::auth::{self, Auth, Principal, UserStore};
+use crate::config;
 use crate::evm::EvmClient;
+use crate::history::{HistoryPoint, HistoryRange, HistoryStore, SnapshotOrigin};
 use crate::mercury::MercuryClient;
-use crate::near::NearClient;
+use crate::portfolio::{self, PortfolioSummary};
 use crate::price::PriceService;
+use crate::rpc::RpcConfig;
 use crate::solana::SolanaClient;
-use crate::starknet::StarknetClient;
-use crate::storage::{AddressBook, BankingService, Chain};
-use crate::sui::SuiClient;
+use crate::source::{self, AccountSnapshot, FetchOptions, TransactionRange};
+use crate::storage::{AddressBook, BankingService, Chain, TrackedAccount};
 
 use askama::Template;
+use bigdecimal::{BigDecimal, Zero};
 use axum::{
-    extract::Path,
-    http::StatusCode,
-    response::{Html, IntoResponse},
+    extract::{ConnectInfo, Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware,
+    response::{AppendHeaders, Html, IntoResponse, Redirect, Response},
     routing::{delete, get, post},
-    Form, Router,
+    Extension, Form, Router,
 };
-use serde::Deserialize;
-use std::collections::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
 use std::net::SocketAddr;
+use std::sync::Arc;
 
-// Custom filter for formatting USD values
+// Custom filters for formatting USD values and amounts. They accept exact
+// decimals as well as the f64 values used by charts.
 mod filters {
-    pub fn format_usd(value: &f64) -> askama::Result<String> {
-        let formatted = format!("{:.2}", value);
-        Ok(add_commas(&formatted))
-    }
+    use crate::amount;
+    use bigdecimal::{BigDecimal, Zero};
+    use std::fmt::Display;
+    use std::str::FromStr;
 
-    pub fn format_amount(value: &f64) -> askama::Result<String> {
-        if *value < 0.0001 && *value > 0.0 {
-            Ok(format!("{:.8}", value))
-        } else if *value < 1.0 {
-            Ok(format!("{:.6}", value))
-        } else {
-            let formatted = format!("{:.4}", value);
-            Ok(add_commas(&formatted))
-        }
+    fn to_decimal<T: Display>(value: &T) -> BigDecimal {
+        BigDecimal::from_str(&value.to_string()).unwrap_or_default()
     }
 
-    fn add_commas(s: &str) -> String {
-        let parts: Vec<&str> = s.split('.').collect();
-        let integer_part = parts[0];
-        let decimal_part = parts.get(1).unwrap_or(&"");
-
-        let with_commas: String = integer_part
-            .chars()
-            .rev()
-            .enumerate()
-            .fold(String::new(), |mut acc, (i, c)| {
-                if i > 0 && i % 3 == 0 {
-                    acc.push(',');
-                }
-                acc.push(c);
-                acc
-            })
-            .chars()
-            .rev()
-            .collect();
+    pub fn format_usd<T: Display>(value: &T) -> askama::Result<String> {
+        Ok(amount::format_usd(&to_decimal(value)))
+    }
 
-        if decimal_part.is_empty() {
-            with_commas
+    pub fn format_amount<T: Display>(value: &T) -> askama::Result<String> {
+        let value = to_decimal(value);
+        let magnitude = value.abs();
+        let dust = BigDecimal::new(1.into(), 4);
+        let one = BigDecimal::from(1);
+        if magnitude < dust && !magnitude.is_zero() {
+            Ok(amount::format_fixed(&value, 8))
+        } else if magnitude < one {
+            Ok(amount::format_fixed(&value, 6))
         } else {
-            format!("{}.{}", with_commas, decimal_part)
+            Ok(amount::group_thousands(&amount::format_fixed(&value, 4)))
         }
     }
 
@@ -77,6 +68,26 @@ struct IndexTemplate {
     companies: Vec<CompanyGroup>,
     wallet_count: usize,
     bank_count: usize,
+    /// Signed-in user, empty when authentication is disabled
+    username: String,
+    can_edit: bool,
+    /// Key and name of each Cosmos SDK network, for the chain picker
+    cosmos_networks: Vec<(String, String)>,
+}
+
+#[derive(Template)]
+#[template(path = "login.html")]
+struct LoginTemplate {
+    error: String,
+    csrf_token: String,
+}
+
+#[derive(Deserialize)]
+struct LoginForm {
+    username: String,
+    password: String,
+    #[serde(default)]
+    csrf_token: String,
 }
 
 struct CompanyGroup {
@@ -88,7 +99,7 @@ struct CompanyGroup {
 #[derive(Template)]
 #[template(path = "balances.html")]
 struct BalancesTemplate {
-    total_usd: f64,
+    total_usd: BigDecimal,
     companies: Vec<(String, Vec<AssetView>)>,
     error: String,
 }
@@ -108,18 +119,16 @@ struct SingleBalanceTemplate {
     name: String,
     address: String,
     chain: String,
-    native_symbol: String,
-    native_balance: f64,
-    native_usd: f64,
-    tokens: Vec<TokenView>,
-    total_usd: f64,
+    holdings: Vec<TokenView>,
+    total_usd: BigDecimal,
     error: String,
 }
 
 struct TokenView {
     symbol: String,
-    balance: f64,
-    usd_value: f64,
+    balance: BigDecimal,
+    /// None when unpriced or worth nothing
+    usd_value: Option<BigDecimal>,
 }
 
 #[derive(Template)]
@@ -134,7 +143,7 @@ struct TransactionsTemplate {
 struct TransactionView {
     date: String,
     description: String,
-    amount: f64,
+    amount: BigDecimal,
     #[allow(dead_code)]
     currency: String,
     tx_type: String,
@@ -160,8 +169,46 @@ struct BankingView {
 
 struct AssetView {
     symbol: String,
-    amount: f64,
-    usd_value: f64,
+    amount: BigDecimal,
+    /// None when unpriced or worth nothing
+    usd_value: Option<BigDecimal>,
+}
+
+#[derive(Template)]
+#[template(path = "history.html")]
+struct HistoryTemplate {}
+
+#[derive(Template)]
+#[template(path = "history_chart.html")]
+struct HistoryChartTemplate {
+    /// Range selector buttons as (range, is_selected)
+    ranges: Vec<(&'static str, bool)>,
+    range: String,
+    snapshot_count: usize,
+    latest_total: f64,
+    change: f64,
+    chart_json: String,
+    error: String,
+}
+
+/// Chart.js-ready series for the history page
+#[derive(Serialize)]
+struct ChartData {
+    labels: Vec<String>,
+    total: Vec<f64>,
+    companies: Vec<ChartSeries>,
+    assets: Vec<ChartSeries>,
+}
+
+#[derive(Serialize)]
+struct ChartSeries {
+    label: String,
+    values: Vec<f64>,
+}
+
+#[derive(Deserialize)]
+struct HistoryQuery {
+    range: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -172,36 +219,166 @@ struct AddAccountForm {
     chain: String,
 }
 
-pub async fn start_server(port: u16) -> anyhow::Result<()> {
-    let app = Router::new()
+#[derive(Clone)]
+pub(crate) struct AppState {
+    pub save_snapshots: bool,
+    /// None when authentication is disabled
+    pub auth: Option<Arc<Auth>>,
+    pub rpc: Arc<RpcConfig>,
+}
+
+pub struct ServeOptions {
+    pub port: u16,
+    pub save_snapshots: bool,
+    /// Bind to 127.0.0.1 instead of all interfaces
+    pub localhost_only: bool,
+    pub no_auth: bool,
+    pub rpc: RpcConfig,
+}
+
+pub async fn start_server(options: ServeOptions) -> anyhow::Result<()> {
+    let port = options.port;
+    let users = UserStore::load()?;
+
+    let auth = if options.no_auth {
+        None
+    } else if users.is_empty() {
+        if !options.localhost_only {
+            anyhow::bail!(
+                "No users configured. Add one with 'gringotts user add <username> --role admin', \
+                 or pass --localhost to serve without authentication on this machine only"
+            );
+        }
+        None
+    } else {
+        Some(Arc::new(Auth::new(users)))
+    };
+
+    let state = AppState {
+        save_snapshots: options.save_snapshots,
+        auth,
+        rpc: Arc::new(options.rpc),
+    };
+
+    let protected = Router::new()
         .route("/", get(index))
         .route("/accounts", post(add_account))
         .route("/accounts/:name", delete(remove_account))
         .route("/balances", get(query_balances))
         .route("/balances/:name", get(query_single_balance))
-        .route("/transactions/:name", get(get_transactions));
+        .route("/transactions/:name", get(get_transactions))
+        .route("/history", get(history_page))
+        .route("/history/chart", get(history_chart))
+        .route("/logout", post(logout))
+        .nest("/api/v1", api::routes())
+        .route_layer(middleware::from_fn_with_state(state.clone(), auth::require_auth));
 
-    // Bind to 0.0.0.0 to accept connections from local network
-    let addr = SocketAddr::from(([0, 0, 0, 0], port));
+    let app = Router::new()
+        .route("/login", get(login_page).post(login))
+        .merge(protected)
+        .with_state(state.clone());
+
+    // Bind to 0.0.0.0 to accept connections from local network, unless restricted
+    let addr = if options.localhost_only {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    } else {
+        SocketAddr::from(([0, 0, 0, 0], port))
+    };
+    let auth_status = if state.auth.is_some() { "enabled" } else { "disabled" };
 
     println!("\n╔═══════════════════════════════════════════════════════════════╗");
     println!("║          Gringotts Web Server Started                        ║");
     println!("╠═══════════════════════════════════════════════════════════════╣");
     println!("║  Local:          http://localhost:{}                       ║", port);
-    println!("║  Network:        http://<your-ip>:{}                      ║", port);
-    println!("╠═══════════════════════════════════════════════════════════════╣");
-    println!("║  To find your IP address:                                    ║");
-    println!("║    macOS/Linux:  ifconfig | grep 'inet '                    ║");
-    println!("║    Windows:      ipconfig                                    ║");
+    if !options.localhost_only {
+        println!("║  Network:        http://<your-ip>:{}                      ║", port);
+    }
+    println!("║  Authentication: {:<45}║", auth_status);
+    if !options.localhost_only {
+        println!("╠═══════════════════════════════════════════════════════════════╣");
+        println!("║  To find your IP address:                                    ║");
+        println!("║    macOS/Linux:  ifconfig | grep 'inet '                    ║");
+        println!("║    Windows:      ipconfig                                    ║");
+    }
     println!("╚═══════════════════════════════════════════════════════════════╝\n");
 
+    if state.auth.is_none() && !options.localhost_only {
+        eprintln!("Warning: authentication is disabled and the server is reachable from the network.");
+    }
+
     let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
+    // Client addresses are needed to throttle failed logins
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await?;
 
     Ok(())
 }
 
-async fn index() -> impl IntoResponse {
+/// The login page with a fresh form token
+fn login_form(status: StatusCode, error: &str) -> Response {
+    let (csrf_token, cookie) = auth::login_form_token();
+    let page = LoginTemplate {
+        error: error.to_string(),
+        csrf_token,
+    };
+    (status, AppendHeaders([(header::SET_COOKIE, cookie)]), Html(page.render().unwrap_or_default())).into_response()
+}
+
+async fn login_page(State(state): State<AppState>) -> Response {
+    if state.auth.is_none() {
+        return Redirect::to("/").into_response();
+    }
+    login_form(StatusCode::OK, "")
+}
+
+async fn login(
+    State(state): State<AppState>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Form(form): Form<LoginForm>,
+) -> Response {
+    let Some(auth) = state.auth.as_ref() else {
+        return Redirect::to("/").into_response();
+    };
+
+    if !auth::verify_login_form(&headers, &form.csrf_token) {
+        return login_form(StatusCode::FORBIDDEN, "The sign-in form expired. Please try again.");
+    }
+
+    match auth.login(client.ip(), form.username.trim(), &form.password) {
+        Ok((session_id, csrf_token)) => {
+            let [session_cookie, csrf_cookie] = auth::login_cookies(&session_id, &csrf_token);
+            (
+                AppendHeaders([(header::SET_COOKIE, session_cookie), (header::SET_COOKIE, csrf_cookie)]),
+                Redirect::to("/"),
+            )
+                .into_response()
+        }
+        Err(auth::LoginError::InvalidCredentials) => login_form(StatusCode::UNAUTHORIZED, "Invalid username or password"),
+        Err(auth::LoginError::Throttled(wait)) => login_form(
+            StatusCode::TOO_MANY_REQUESTS,
+            &format!("Too many failed sign-in attempts. Try again in {} seconds.", wait.as_secs().max(1)),
+        ),
+    }
+}
+
+async fn logout(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    if let (Some(auth), Some(session_id)) = (state.auth.as_ref(), auth::cookie_value(&headers, auth::SESSION_COOKIE)) {
+        auth.logout(session_id);
+    }
+
+    let [session_cookie, csrf_cookie] = auth::logout_cookies();
+    (
+        StatusCode::OK,
+        AppendHeaders([
+            (header::SET_COOKIE, session_cookie),
+            (header::SET_COOKIE, csrf_cookie),
+            (header::HeaderName::from_static("hx-redirect"), "/login".to_string()),
+        ]),
+    )
+        .into_response()
+}
+
+async fn index(State(state): State<AppState>, Extension(principal): Extension<Principal>) -> impl IntoResponse {
     let book = match AddressBook::load() {
         Ok(b) => b,
         Err(_) => AddressBook::new(),
@@ -267,6 +444,15 @@ async fn index() -> impl IntoResponse {
         companies,
         wallet_count,
         bank_count,
+        username: if state.auth.is_some() { principal.username.clone() } else { String::new() },
+        can_edit: principal.is_admin(),
+        cosmos_networks: config::active()
+            .settings
+            .cosmos
+            .all()
+            .into_iter()
+            .map(|(key, network)| (key.to_string(), network.name.clone().unwrap_or_else(|| key.to_string())))
+            .collect(),
     };
 
     Html(template.render().unwrap_or_else(|e| format!("Template error: {}", e)))
@@ -284,19 +470,6 @@ async fn add_account(Form(form): Form<AddAccountForm>) -> impl IntoResponse {
         Some(form.chain.clone())
     };
 
-    let chain_display = if let Some(ref c) = chain_opt {
-        Chain::from_str(c)
-            .map(|ch| ch.display_name().to_string())
-            .unwrap_or_else(|_| "Unknown".to_string())
-    } else {
-        // Auto-detect
-        if form.address.starts_with("0x") && form.address.len() == 42 {
-            "Ethereum".to_string()
-        } else {
-            "Solana".to_string()
-        }
-    };
-
     if let Err(e) = book.add_address(
         form.company.clone(),
         form.name.clone(),
@@ -306,6 +479,12 @@ async fn add_account(Form(form): Form<AddAccountForm>) -> impl IntoResponse {
         return (StatusCode::BAD_REQUEST, Html(format!("Error: {}", e)));
     }
 
+    let chain_display = book
+        .addresses
+        .last()
+        .map(|wallet| wallet.chain.display_name().to_string())
+        .unwrap_or_default();
+
     if let Err(e) = book.save() {
         return (StatusCode::INTERNAL_SERVER_ERROR, Html(format!("Error: {}", e)));
     }
@@ -348,13 +527,61 @@ async fn remove_account(Path(name): Path<String>) -> impl IntoResponse {
     (StatusCode::OK, Html(String::new()))
 }
 
-async fn query_balances() -> impl IntoResponse {
+/// Fetch USD prices for every symbol the snapshots need
+pub(crate) async fn fetch_prices(snapshots: &[AccountSnapshot]) -> HashMap<String, f64> {
+    let symbols: Vec<String> = source::extract_token_symbols(snapshots).into_iter().collect();
+    if symbols.is_empty() {
+        return HashMap::new();
+    }
+
+    match PriceService::new() {
+        Ok(price_service) => price_service.batch_fetch_prices(&symbols).await.unwrap_or_default(),
+        Err(_) => HashMap::new(),
+    }
+}
+
+/// Priced balances of a set of accounts, plus the accounts that failed
+pub(crate) struct FetchedBalances {
+    pub snapshots: Vec<AccountSnapshot>,
+    pub prices: HashMap<String, f64>,
+    pub failures: Vec<(TrackedAccount, String)>,
+}
+
+/// Fetch and price every account, recording a history snapshot when enabled
+pub(crate) async fn fetch_priced_snapshots(accounts: &[TrackedAccount], state: &AppState) -> FetchedBalances {
+    let mut failures = Vec::new();
+    let mut snapshots = source::fetch_snapshots(accounts, &state.rpc, FetchOptions::default(), |account, error| {
+        if let Some(e) = error {
+            failures.push((account.clone(), format!("{:#}", e)));
+        }
+    })
+    .await;
+
+    let prices = fetch_prices(&snapshots).await;
+    for snapshot in &mut snapshots {
+        snapshot.enrich_from_cache(&prices);
+    }
+
+    if state.save_snapshots {
+        if let Err(e) = HistoryStore::open().and_then(|mut store| store.save(&snapshots, SnapshotOrigin::Web)) {
+            eprintln!("Warning: Failed to save snapshot: {:#}", e);
+        }
+    }
+
+    FetchedBalances {
+        snapshots,
+        prices,
+        failures,
+    }
+}
+
+async fn query_balances(State(state): State<AppState>) -> impl IntoResponse {
     let book = match AddressBook::load() {
         Ok(b) => b,
         Err(e) => {
             return Html(
                 BalancesTemplate {
-                    total_usd: 0.0,
+                    total_usd: BigDecimal::zero(),
                     companies: vec![],
                     error: format!("Failed to load accounts: {}", e),
                 }
@@ -367,7 +594,7 @@ async fn query_balances() -> impl IntoResponse {
     if book.addresses.is_empty() && book.banking_accounts.is_empty() {
         return Html(
             BalancesTemplate {
-                total_usd: 0.0,
+                total_usd: BigDecimal::zero(),
                 companies: vec![],
                 error: String::new(),
             }
@@ -376,218 +603,37 @@ async fn query_balances() -> impl IntoResponse {
         );
     }
 
-    // Query all balances and aggregate
-    let mut portfolio: HashMap<String, HashMap<String, (f64, f64)>> = HashMap::new();
-    let mut all_symbols: HashSet<String> = HashSet::new();
-
-    // Query crypto wallets
-    for wallet in &book.addresses {
-        match &wallet.chain {
-            Chain::Solana => {
-                let client = SolanaClient::new(None);
-                if let Ok(balances) = client.get_balances(&wallet.address) {
-                    all_symbols.insert("SOL".to_string());
-                    let company = if wallet.company.is_empty() {
-                        "Uncategorized"
-                    } else {
-                        &wallet.company
-                    };
-                    let entry = portfolio.entry(company.to_string()).or_default();
-                    let sol_entry = entry.entry("SOL".to_string()).or_insert((0.0, 0.0));
-                    sol_entry.0 += balances.sol_balance;
-
-                    for token in &balances.token_balances {
-                        if let Some(symbol) = &token.symbol {
-                            all_symbols.insert(symbol.clone());
-                            let token_entry = entry.entry(symbol.clone()).or_insert((0.0, 0.0));
-                            token_entry.0 += token.ui_amount;
-                        }
-                    }
-                }
-            }
-            Chain::Near => {
-                let client = NearClient::new(None);
-                if let Ok(balances) = client.get_balances(&wallet.address).await {
-                    all_symbols.insert("NEAR".to_string());
-                    let company = if wallet.company.is_empty() {
-                        "Uncategorized"
-                    } else {
-                        &wallet.company
-                    };
-                    let entry = portfolio.entry(company.to_string()).or_default();
-                    let near_entry = entry.entry("NEAR".to_string()).or_insert((0.0, 0.0));
-                    near_entry.0 += balances.near_balance;
-                }
-            }
-            Chain::Aptos => {
-                let client = AptosClient::new(None);
-                if let Ok(balances) = client.get_balances(&wallet.address).await {
-                    all_symbols.insert("APT".to_string());
-                    let company = if wallet.company.is_empty() {
-                        "Uncategorized"
-                    } else {
-                        &wallet.company
-                    };
-                    let entry = portfolio.entry(company.to_string()).or_default();
-                    let apt_entry = entry.entry("APT".to_string()).or_insert((0.0, 0.0));
-                    apt_entry.0 += balances.apt_balance;
-                }
-            }
-            Chain::Sui => {
-                let client = SuiClient::new(None);
-                if let Ok(balances) = client.get_balances(&wallet.address).await {
-                    all_symbols.insert("SUI".to_string());
-                    let company = if wallet.company.is_empty() {
-                        "Uncategorized"
-                    } else {
-                        &wallet.company
-                    };
-                    let entry = portfolio.entry(company.to_string()).or_default();
-                    let sui_entry = entry.entry("SUI".to_string()).or_insert((0.0, 0.0));
-                    sui_entry.0 += balances.sui_balance;
-                }
-            }
-            Chain::Starknet => {
-                let client = StarknetClient::new(None);
-                if let Ok(balances) = client.get_balances(&wallet.address).await {
-                    all_symbols.insert("ETH".to_string());
-                    let company = if wallet.company.is_empty() {
-                        "Uncategorized"
-                    } else {
-                        &wallet.company
-                    };
-                    let entry = portfolio.entry(company.to_string()).or_default();
-                    let eth_entry = entry.entry("ETH".to_string()).or_insert((0.0, 0.0));
-                    eth_entry.0 += balances.eth_balance;
-                }
-            }
-            // EVM chains
-            Chain::Ethereum
-            | Chain::Polygon
-            | Chain::BinanceSmartChain
-            | Chain::Arbitrum
-            | Chain::Optimism
-            | Chain::Avalanche
-            | Chain::Base
-            | Chain::Core => {
-                if let Ok(client) = EvmClient::new(None, wallet.chain.clone()) {
-                    if let Ok(balances) = client.get_balances(&wallet.address).await {
-                        let native_symbol = wallet.chain.native_token_symbol();
-                        all_symbols.insert(native_symbol.to_string());
-                        let company = if wallet.company.is_empty() {
-                            "Uncategorized"
-                        } else {
-                            &wallet.company
-                        };
-                        let entry = portfolio.entry(company.to_string()).or_default();
-                        let native_entry =
-                            entry.entry(native_symbol.to_string()).or_insert((0.0, 0.0));
-                        native_entry.0 += balances.eth_balance;
-
-                        for token in &balances.token_balances {
-                            if let Some(symbol) = &token.symbol {
-                                all_symbols.insert(symbol.clone());
-                                let token_entry = entry.entry(symbol.clone()).or_insert((0.0, 0.0));
-                                token_entry.0 += token.ui_amount;
-                            }
-                        }
-                    }
-                }
-            }
-        }
-    }
-
-    // Query banking accounts
-    for account in &book.banking_accounts {
-        match &account.service {
-            BankingService::Mercury => {
-                if let Ok(client) = MercuryClient::new() {
-                    if let Ok(balances) = client.get_account_balance(&account.account_id).await {
-                        let company = if account.company.is_empty() {
-                            "Uncategorized"
-                        } else {
-                            &account.company
-                        };
-                        let entry = portfolio.entry(company.to_string()).or_default();
-                        let usd_entry = entry.entry("USD".to_string()).or_insert((0.0, 0.0));
-                        usd_entry.0 += balances.current_balance;
-                        usd_entry.1 += balances.current_balance; // USD is already in USD
-                    }
-                }
-            }
-            BankingService::Circle => {
-                if let Ok(client) = CircleClient::new() {
-                    if let Ok(balances) = client.get_balances().await {
-                        let company = if account.company.is_empty() {
-                            "Uncategorized"
-                        } else {
-                            &account.company
-                        };
-                        let entry = portfolio.entry(company.to_string()).or_default();
-                        for balance in &balances.available_balances {
-                            let symbol = match balance.currency.as_str() {
-                                "USD" => "USDC",
-                                "EUR" => "EURC",
-                                _ => &balance.currency,
-                            };
-                            let currency_entry = entry.entry(symbol.to_string()).or_insert((0.0, 0.0));
-                            currency_entry.0 += balance.amount;
-                            if balance.currency == "USD" {
-                                currency_entry.1 += balance.amount;
-                            }
-                        }
-                    }
-                }
-            }
-        }
-    }
-
-    // Fetch prices for crypto assets
-    if let Ok(price_service) = PriceService::new() {
-        let symbols: Vec<String> = all_symbols.into_iter().collect();
-        if let Ok(prices) = price_service.batch_fetch_prices(&symbols).await {
-            // Apply prices to portfolio
-            for assets in portfolio.values_mut() {
-                for (symbol, (amount, usd_value)) in assets.iter_mut() {
-                    if *usd_value == 0.0 {
-                        if let Some(&price) = prices.get(symbol) {
-                            *usd_value = *amount * price;
-                        }
-                    }
-                }
-            }
-        }
-    }
-
-    // Calculate totals and format for template
-    let mut total_usd = 0.0;
-    let mut companies: Vec<(String, Vec<AssetView>)> = Vec::new();
+    // Query all balances, price them and aggregate
+    let fetched = fetch_priced_snapshots(&book.accounts(), &state).await;
+    let portfolio = PortfolioSummary::from_snapshots(&fetched.snapshots);
 
-    let mut sorted_companies: Vec<_> = portfolio.into_iter().collect();
+    // Format for template
+    let mut sorted_companies: Vec<_> = portfolio.companies.into_iter().collect();
     sorted_companies.sort_by(|a, b| a.0.cmp(&b.0));
 
-    for (company, assets) in sorted_companies {
-        let mut asset_views: Vec<AssetView> = assets
-            .into_iter()
-            .map(|(symbol, (amount, usd_value))| {
-                total_usd += usd_value;
-                AssetView {
-                    symbol,
-                    amount,
-                    usd_value,
-                }
-            })
-            .collect();
-
-        // Sort by USD value descending
-        asset_views.sort_by(|a, b| b.usd_value.partial_cmp(&a.usd_value).unwrap());
-
-        companies.push((company, asset_views));
-    }
+    let companies: Vec<(String, Vec<AssetView>)> = sorted_companies
+        .into_iter()
+        .map(|(company, assets)| {
+            let mut asset_views: Vec<AssetView> = assets
+                .assets
+                .into_values()
+                .map(|asset| AssetView {
+                    symbol: asset.symbol,
+                    amount: asset.amount,
+                    usd_value: asset.usd_value.filter(|v| !v.is_zero()),
+                })
+                .collect();
+
+            // Sort by USD value descending
+            asset_views.sort_by(|a, b| b.usd_value.cmp(&a.usd_value));
+
+            (company, asset_views)
+        })
+        .collect();
 
     Html(
         BalancesTemplate {
-            total_usd,
+            total_usd: portfolio.total_usd_value,
             companies,
             error: String::new(),
         }
@@ -596,343 +642,84 @@ async fn query_balances() -> impl IntoResponse {
     )
 }
 
-async fn query_single_balance(Path(name): Path<String>) -> impl IntoResponse {
-    let book = match AddressBook::load() {
-        Ok(b) => b,
-        Err(e) => {
-            return Html(
-                SingleBalanceTemplate {
-                    name: name.clone(),
-                    address: String::new(),
-                    chain: String::new(),
-                    native_symbol: String::new(),
-                    native_balance: 0.0,
-                    native_usd: 0.0,
-                    tokens: vec![],
-                    total_usd: 0.0,
-                    error: format!("Failed to load accounts: {}", e),
-                }
-                .render()
-                .unwrap_or_default(),
-            );
-        }
-    };
-
-    // Try to find in crypto addresses first
-    if let Some(wallet) = book.addresses.iter().find(|a| a.name == name) {
-        return query_wallet_balance(wallet).await;
-    }
-
-    // Try to find in banking accounts
-    if let Some(account) = book.banking_accounts.iter().find(|a| a.name == name) {
-        return query_bank_balance(account).await;
-    }
-
+fn single_balance_error(name: &str, address: &str, chain: &str, error: String) -> Html<String> {
     Html(
         SingleBalanceTemplate {
-            name: name.clone(),
-            address: String::new(),
-            chain: String::new(),
-            native_symbol: String::new(),
-            native_balance: 0.0,
-            native_usd: 0.0,
-            tokens: vec![],
-            total_usd: 0.0,
-            error: format!("Account '{}' not found", name),
+            name: name.to_string(),
+            address: address.to_string(),
+            chain: chain.to_string(),
+            holdings: vec![],
+            total_usd: BigDecimal::zero(),
+            error,
         }
         .render()
         .unwrap_or_default(),
     )
 }
 
-async fn query_wallet_balance(wallet: &crate::storage::WalletAddress) -> Html<String> {
-    let chain_name = wallet.chain.display_name().to_string();
-    let native_symbol = wallet.chain.native_token_symbol().to_string();
-
-    let mut native_balance = 0.0;
-    let mut native_usd = 0.0;
-    let mut tokens: Vec<TokenView> = vec![];
-    let mut total_usd = 0.0;
-    let mut error = String::new();
-
-    // Fetch prices
-    let price_cache: HashMap<String, f64> = if let Ok(price_service) = PriceService::new() {
-        price_service
-            .batch_fetch_all_known_prices()
-            .await
-            .unwrap_or_default()
-    } else {
-        HashMap::new()
+async fn query_single_balance(State(state): State<AppState>, Path(name): Path<String>) -> impl IntoResponse {
+    let book = match AddressBook::load() {
+        Ok(b) => b,
+        Err(e) => return single_balance_error(&name, "", "", format!("Failed to load accounts: {}", e)),
     };
 
-    match &wallet.chain {
-        Chain::Solana => {
-            let client = SolanaClient::new(None);
-            match client.get_balances(&wallet.address) {
-                Ok(balances) => {
-                    native_balance = balances.sol_balance;
-                    if let Some(&price) = price_cache.get("SOL") {
-                        native_usd = native_balance * price;
-                        total_usd += native_usd;
-                    }
-                    for token in &balances.token_balances {
-                        if let Some(symbol) = &token.symbol {
-                            let usd = price_cache
-                                .get(symbol)
-                                .map(|p| token.ui_amount * p)
-                                .unwrap_or(0.0);
-                            total_usd += usd;
-                            tokens.push(TokenView {
-                                symbol: symbol.clone(),
-                                balance: token.ui_amount,
-                                usd_value: usd,
-                            });
-                        }
-                    }
-                }
-                Err(e) => error = format!("Failed to query: {}", e),
-            }
-        }
-        Chain::Near => {
-            let client = NearClient::new(None);
-            match client.get_balances(&wallet.address).await {
-                Ok(balances) => {
-                    native_balance = balances.near_balance;
-                    if let Some(&price) = price_cache.get("NEAR") {
-                        native_usd = native_balance * price;
-                        total_usd = native_usd;
-                    }
-                }
-                Err(e) => error = format!("Failed to query: {}", e),
-            }
-        }
-        Chain::Aptos => {
-            let client = AptosClient::new(None);
-            match client.get_balances(&wallet.address).await {
-                Ok(balances) => {
-                    native_balance = balances.apt_balance;
-                    if let Some(&price) = price_cache.get("APT") {
-                        native_usd = native_balance * price;
-                        total_usd = native_usd;
-                    }
-                }
-                Err(e) => error = format!("Failed to query: {}", e),
-            }
-        }
-        Chain::Sui => {
-            let client = SuiClient::new(None);
-            match client.get_balances(&wallet.address).await {
-                Ok(balances) => {
-                    native_balance = balances.sui_balance;
-                    if let Some(&price) = price_cache.get("SUI") {
-                        native_usd = native_balance * price;
-                        total_usd = native_usd;
-                    }
-                }
-                Err(e) => error = format!("Failed to query: {}", e),
-            }
-        }
-        Chain::Starknet => {
-            let client = StarknetClient::new(None);
-            match client.get_balances(&wallet.address).await {
-                Ok(balances) => {
-                    native_balance = balances.eth_balance;
-                    if let Some(&price) = price_cache.get("ETH") {
-                        native_usd = native_balance * price;
-                        total_usd = native_usd;
-                    }
-                }
-                Err(e) => error = format!("Failed to query: {}", e),
-            }
-        }
-        Chain::Ethereum
-        | Chain::Polygon
-        | Chain::BinanceSmartChain
-        | Chain::Arbitrum
-        | Chain::Optimism
-        | Chain::Avalanche
-        | Chain::Base
-        | Chain::Core => {
-            if let Ok(client) = EvmClient::new(None, wallet.chain.clone()) {
-                match client.get_balances(&wallet.address).await {
-                    Ok(balances) => {
-                        native_balance = balances.eth_balance;
-                        if let Some(&price) = price_cache.get("ETH") {
-                            native_usd = native_balance * price;
-                            total_usd += native_usd;
-                        }
-                        for token in &balances.token_balances {
-                            if let Some(symbol) = &token.symbol {
-                                let usd = price_cache
-                                    .get(symbol)
-                                    .map(|p| token.ui_amount * p)
-                                    .unwrap_or(0.0);
-                                total_usd += usd;
-                                tokens.push(TokenView {
-                                    symbol: symbol.clone(),
-                                    balance: token.ui_amount,
-                                    usd_value: usd,
-                                });
-                            }
-                        }
-                    }
-                    Err(e) => error = format!("Failed to query: {}", e),
-                }
-            } else {
-                error = "Failed to create EVM client".to_string();
-            }
-        }
+    match book.find_account(&name) {
+        Some(account) => query_account_balance(&account, &state.rpc).await,
+        None => single_balance_error(&name, "", "", format!("Account '{}' not found", name)),
     }
+}
 
-    // Sort tokens by USD value
-    tokens.sort_by(|a, b| b.usd_value.partial_cmp(&a.usd_value).unwrap_or(std::cmp::Ordering::Equal));
+async fn query_account_balance(account: &TrackedAccount, rpc: &RpcConfig) -> Html<String> {
+    let mut snapshot = match source::fetch_snapshot(account, rpc).await {
+        Ok(snapshot) => snapshot,
+        Err(e) => {
+            return single_balance_error(
+                account.name(),
+                account.identifier(),
+                account.source_name(),
+                format!("Failed to query: {:#}", e),
+            );
+        }
+    };
+
+    let prices = fetch_prices(std::slice::from_ref(&snapshot)).await;
+    snapshot.enrich_from_cache(&prices);
+
+    let mut holdings: Vec<TokenView> = snapshot
+        .holdings
+        .iter()
+        .filter_map(|h| {
+            h.symbol.as_ref().map(|symbol| TokenView {
+                symbol: portfolio::asset_label(h.kind, symbol),
+                balance: h.amount.to_decimal(),
+                usd_value: h.usd_value.clone().filter(|v| !v.is_zero()),
+            })
+        })
+        .collect();
+
+    // Keep the native/fiat line first, then tokens by USD value
+    let native_count = snapshot
+        .holdings
+        .iter()
+        .take_while(|h| h.kind != source::HoldingKind::Token)
+        .count();
+    holdings[native_count..].sort_by(|a, b| b.usd_value.cmp(&a.usd_value));
 
     Html(
         SingleBalanceTemplate {
-            name: wallet.name.clone(),
-            address: wallet.address.clone(),
-            chain: chain_name,
-            native_symbol,
-            native_balance,
-            native_usd,
-            tokens,
-            total_usd,
-            error,
+            name: account.name().to_string(),
+            address: account.identifier().to_string(),
+            chain: account.source_name().to_string(),
+            holdings,
+            total_usd: snapshot.total_usd_value.unwrap_or_default(),
+            error: String::new(),
         }
         .render()
         .unwrap_or_default(),
     )
 }
 
-async fn query_bank_balance(account: &crate::storage::BankingAccount) -> Html<String> {
-    let service_name = account.service.display_name().to_string();
-
-    match &account.service {
-        BankingService::Mercury => {
-            match MercuryClient::new() {
-                Ok(client) => {
-                    match client.get_account_balance(&account.account_id).await {
-                        Ok(balances) => {
-                            Html(
-                                SingleBalanceTemplate {
-                                    name: account.name.clone(),
-                                    address: account.account_id.clone(),
-                                    chain: service_name,
-                                    native_symbol: "USD".to_string(),
-                                    native_balance: balances.current_balance,
-                                    native_usd: balances.current_balance,
-                                    tokens: vec![],
-                                    total_usd: balances.current_balance,
-                                    error: String::new(),
-                                }
-                                .render()
-                                .unwrap_or_default(),
-                            )
-                        }
-                        Err(e) => Html(
-                            SingleBalanceTemplate {
-                                name: account.name.clone(),
-                                address: account.account_id.clone(),
-                                chain: service_name,
-                                native_symbol: String::new(),
-                                native_balance: 0.0,
-                                native_usd: 0.0,
-                                tokens: vec![],
-                                total_usd: 0.0,
-                                error: format!("Failed to query: {}", e),
-                            }
-                            .render()
-                            .unwrap_or_default(),
-                        ),
-                    }
-                }
-                Err(e) => Html(
-                    SingleBalanceTemplate {
-                        name: account.name.clone(),
-                        address: account.account_id.clone(),
-                        chain: service_name,
-                        native_symbol: String::new(),
-                        native_balance: 0.0,
-                        native_usd: 0.0,
-                        tokens: vec![],
-                        total_usd: 0.0,
-                        error: format!("Failed to initialize client: {}", e),
-                    }
-                    .render()
-                    .unwrap_or_default(),
-                ),
-            }
-        }
-        BankingService::Circle => {
-            match CircleClient::new() {
-                Ok(client) => {
-                    match client.get_balances().await {
-                        Ok(balances) => {
-                            let mut tokens: Vec<TokenView> = vec![];
-                            let mut total = 0.0;
-                            for bal in &balances.available_balances {
-                                let usd = if bal.currency == "USD" { bal.amount } else { 0.0 };
-                                total += usd;
-                                tokens.push(TokenView {
-                                    symbol: bal.currency.clone(),
-                                    balance: bal.amount,
-                                    usd_value: usd,
-                                });
-                            }
-                            Html(
-                                SingleBalanceTemplate {
-                                    name: account.name.clone(),
-                                    address: account.account_id.clone(),
-                                    chain: service_name,
-                                    native_symbol: "USD".to_string(),
-                                    native_balance: total,
-                                    native_usd: total,
-                                    tokens,
-                                    total_usd: total,
-                                    error: String::new(),
-                                }
-                                .render()
-                                .unwrap_or_default(),
-                            )
-                        }
-                        Err(e) => Html(
-                            SingleBalanceTemplate {
-                                name: account.name.clone(),
-                                address: account.account_id.clone(),
-                                chain: service_name,
-                                native_symbol: String::new(),
-                                native_balance: 0.0,
-                                native_usd: 0.0,
-                                tokens: vec![],
-                                total_usd: 0.0,
-                                error: format!("Failed to query: {}", e),
-                            }
-                            .render()
-                            .unwrap_or_default(),
-                        ),
-                    }
-                }
-                Err(e) => Html(
-                    SingleBalanceTemplate {
-                        name: account.name.clone(),
-                        address: account.account_id.clone(),
-                        chain: service_name,
-                        native_symbol: String::new(),
-                        native_balance: 0.0,
-                        native_usd: 0.0,
-                        tokens: vec![],
-                        total_usd: 0.0,
-                        error: format!("Failed to initialize client: {}", e),
-                    }
-                    .render()
-                    .unwrap_or_default(),
-                ),
-            }
-        }
-    }
-}
-
-async fn get_transactions(Path(name): Path<String>) -> impl IntoResponse {
+async fn get_transactions(State(state): State<AppState>, Path(name): Path<String>) -> impl IntoResponse {
     let book = match AddressBook::load() {
         Ok(b) => b,
         Err(e) => {
@@ -956,7 +743,7 @@ async fn get_transactions(Path(name): Path<String>) -> impl IntoResponse {
 
     // Check if it's a crypto wallet
     if let Some(wallet) = book.addresses.iter().find(|a| a.name == name) {
-        return get_wallet_transactions(wallet).await;
+        return get_wallet_transactions(wallet, &state.rpc).await;
     }
 
     Html(
@@ -989,7 +776,8 @@ async fn get_bank_transactions(account: &crate::storage::BankingAccount) -> Html
                                         date.clone()
                                     };
 
-                                    let tx_type = if tx.amount >= 0.0 {
+                                    let amount = amount::from_f64(tx.amount);
+                                    let tx_type = if amount >= BigDecimal::zero() {
                                         "deposit".to_string()
                                     } else {
                                         "withdrawal".to_string()
@@ -1004,7 +792,7 @@ async fn get_bank_transactions(account: &crate::storage::BankingAccount) -> Html
                                     TransactionView {
                                         date: date_formatted,
                                         description,
-                                        amount: tx.amount,
+                                        amount,
                                         currency: "USD".to_string(),
                                         tx_type,
                                         status: tx.status.clone(),
@@ -1063,7 +851,7 @@ async fn get_bank_transactions(account: &crate::storage::BankingAccount) -> Html
     }
 }
 
-async fn get_wallet_transactions(wallet: &crate::storage::WalletAddress) -> Html<String> {
+async fn get_wallet_transactions(wallet: &crate::storage::WalletAddress, rpc: &RpcConfig) -> Html<String> {
     let chain_name = wallet.chain.display_name();
     let explorer_url = match &wallet.chain {
         Chain::Solana => format!("https://solscan.io/account/{}", wallet.address),
@@ -1079,16 +867,32 @@ async fn get_wallet_transactions(wallet: &crate::storage::WalletAddress) -> Html
         Chain::Aptos => format!("https://explorer.aptoslabs.com/account/{}", wallet.address),
         Chain::Sui => format!("https://suiscan.xyz/account/{}", wallet.address),
         Chain::Starknet => format!("https://starkscan.co/contract/{}", wallet.address),
+        Chain::Bitcoin => format!("https://mempool.space/address/{}", wallet.address),
+        Chain::Cosmos(key) => format!("https://www.mintscan.io/{}/address/{}", key, wallet.address),
     };
 
-    // For Solana, fetch actual transactions
-    if let Chain::Solana = &wallet.chain {
-        let client = SolanaClient::new(None);
-        match client.get_transactions(&wallet.address, 50) {
+    // For Solana and EVM chains, fetch actual transactions
+    let range = TransactionRange {
+        limit: Some(50),
+        ..Default::default()
+    };
+    let result = if wallet.chain == Chain::Solana {
+        Some(SolanaClient::new(rpc.endpoints_for(&Chain::Solana)).get_transactions(&wallet.address, range).await)
+    } else if wallet.chain.is_evm() {
+        let client = EvmClient::new(rpc.endpoints_for(&wallet.chain), wallet.chain.clone());
+        Some(client.get_transactions(&wallet.address, range).await)
+    } else {
+        None
+    };
+
+    if let Some(result) = result {
+        match result {
             Ok(txs) => {
+                // One row per asset whose balance changed; a transaction that
+                // only cost a fee gets a row for the fee
                 let transactions: Vec<TransactionView> = txs
                     .iter()
-                    .map(|tx| {
+                    .flat_map(|tx| {
                         let date = tx.timestamp
                             .map(|ts| {
                                 chrono::DateTime::from_timestamp(ts, 0)
@@ -1099,31 +903,53 @@ async fn get_wallet_transactions(wallet: &crate::storage::WalletAddress) -> Html
 
                         let status = if tx.success { "Completed" } else { "Failed" };
                         let description = tx.memo.clone().unwrap_or_else(|| {
-                            format!("Slot {}", tx.slot)
+                            if tx.id.len() > 16 {
+                                format!("{}...", &tx.id[..16])
+                            } else {
+                                tx.id.clone()
+                            }
                         });
-
-                        // Link to explorer for signature
-                        let sig_short = if tx.signature.len() > 16 {
-                            format!("{}...", &tx.signature[..16])
-                        } else {
-                            tx.signature.clone()
+                        let counterparty = match tx.counterparties.as_slice() {
+                            [] => String::new(),
+                            [one] => one.clone(),
+                            [first, rest @ ..] => format!("{} +{} more", first, rest.len()),
                         };
 
-                        let tx_type = if tx.sol_change >= 0.0 {
-                            "deposit".to_string()
-                        } else {
-                            "withdrawal".to_string()
-                        };
-
-                        TransactionView {
-                            date,
-                            description,
-                            amount: tx.sol_change,
-                            currency: "SOL".to_string(),
-                            tx_type,
-                            status: status.to_string(),
-                            counterparty: sig_short,
+                        let mut rows: Vec<(BigDecimal, String)> = tx
+                            .changes
+                            .iter()
+                            .map(|change| {
+                                let currency = match (&change.symbol, &change.asset_id) {
+                                    (Some(symbol), _) => symbol.clone(),
+                                    (None, Some(asset_id)) if asset_id.len() > 8 => format!("{}...", &asset_id[..8]),
+                                    (None, asset_id) => asset_id.clone().unwrap_or_default(),
+                                };
+                                (change.amount.to_decimal(), currency)
+                            })
+                            .collect();
+                        if rows.is_empty() {
+                            if let Some(fee) = &tx.fee {
+                                rows.push((-fee.to_decimal(), wallet.chain.native_token_symbol().to_string()));
+                            }
                         }
+
+                        rows.into_iter().map(move |(amount, currency)| {
+                            let tx_type = if amount >= BigDecimal::zero() {
+                                "deposit".to_string()
+                            } else {
+                                "withdrawal".to_string()
+                            };
+
+                            TransactionView {
+                                date: date.clone(),
+                                description: description.clone(),
+                                amount,
+                                currency,
+                                tx_type,
+                                status: status.to_string(),
+                                counterparty: counterparty.clone(),
+                            }
+                        })
                     })
                     .collect();
 
@@ -1132,7 +958,7 @@ async fn get_wallet_transactions(wallet: &crate::storage::WalletAddress) -> Html
                         name: wallet.name.clone(),
                         account_type: format!("{} Wallet", chain_name),
                         transactions,
-                        error: format!("Note: For detailed transaction info, visit <a href=\"{}\" target=\"_blank\">Solscan</a>", explorer_url),
+                        error: format!("Note: For detailed transaction info, visit <a href=\"{}\" target=\"_blank\">the block explorer</a>", explorer_url),
                     }
                     .render()
                     .unwrap_or_default(),
@@ -1144,7 +970,7 @@ async fn get_wallet_transactions(wallet: &crate::storage::WalletAddress) -> Html
                         name: wallet.name.clone(),
                         account_type: format!("{} Wallet", chain_name),
                         transactions: vec![],
-                        error: format!("Failed to fetch transactions: {}. <a href=\"{}\" target=\"_blank\">View on Solscan</a>", e, explorer_url),
+                        error: format!("Failed to fetch transactions: {}. <a href=\"{}\" target=\"_blank\">View on the block explorer</a>", e, explorer_url),
                     }
                     .render()
                     .unwrap_or_default(),
@@ -1165,3 +991,180 @@ async fn get_wallet_transactions(wallet: &crate::storage::WalletAddress) -> Html
         .unwrap_or_default(),
     )
 }
+
+// Maximum number of asset symbols drawn individually in the stacked chart
+const MAX_CHART_ASSETS: usize = 8;
+
+async fn history_page() -> impl IntoResponse {
+    Html(HistoryTemplate {}.render().unwrap_or_default())
+}
+
+async fn history_chart(Query(params): Query<HistoryQuery>) -> impl IntoResponse {
+    let range = match HistoryRange::from_str(params.range.as_deref().unwrap_or("30d")) {
+        Ok(range) => range,
+        Err(e) => {
+            return Html(
+                HistoryChartTemplate {
+                    ranges: history_ranges(None),
+                    range: String::new(),
+                    snapshot_count: 0,
+                    latest_total: 0.0,
+                    change: 0.0,
+                    chart_json: String::new(),
+                    error: e.to_string(),
+                }
+                .render()
+                .unwrap_or_default(),
+            );
+        }
+    };
+
+    let ranges = history_ranges(Some(range));
+    let since = range.since(chrono::Utc::now().timestamp());
+    let points = match HistoryStore::open().and_then(|store| store.series(since)) {
+        Ok(points) => points,
+        Err(e) => {
+            return Html(
+                HistoryChartTemplate {
+                    ranges,
+                    range: range.as_str().to_string(),
+                    snapshot_count: 0,
+                    latest_total: 0.0,
+                    change: 0.0,
+                    chart_json: String::new(),
+                    error: format!("Failed to load history: {:#}", e),
+                }
+                .render()
+                .unwrap_or_default(),
+            );
+        }
+    };
+
+    let latest_total = points.last().map(|p| p.total_usd_value).unwrap_or(0.0);
+    let change = latest_total - points.first().map(|p| p.total_usd_value).unwrap_or(0.0);
+
+    // Escape "</" so company or token names can't close the surrounding <script> tag
+    let chart_json = serde_json::to_string(&build_chart_data(&points))
+        .unwrap_or_default()
+        .replace("</", "<\\/");
+
+    Html(
+        HistoryChartTemplate {
+            ranges,
+            range: range.as_str().to_string(),
+            snapshot_count: points.len(),
+            latest_total,
+            change,
+            chart_json,
+            error: String::new(),
+        }
+        .render()
+        .unwrap_or_default(),
+    )
+}
+
+fn history_ranges(selected: Option<HistoryRange>) -> Vec<(&'static str, bool)> {
+    [HistoryRange::Week, HistoryRange::Month, HistoryRange::Quarter, HistoryRange::All]
+        .into_iter()
+        .map(|r| (r.as_str(), Some(r) == selected))
+        .collect()
+}
+
+fn build_chart_data(points: &[HistoryPoint]) -> ChartData {
+    let labels = points
+        .iter()
+        .map(|p| {
+            chrono::DateTime::from_timestamp(p.taken_at, 0)
+                .map(|dt| dt.format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or_default()
+        })
+        .collect();
+    let total = points.iter().map(|p| p.total_usd_value).collect();
+
+    let series_for = |names: Vec<String>, values: &dyn Fn(&HistoryPoint, &str) -> f64| -> Vec<ChartSeries> {
+        names
+            .into_iter()
+            .map(|name| ChartSeries {
+                values: points.iter().map(|p| values(p, &name)).collect(),
+                label: name,
+            })
+            .collect()
+    };
+
+    let company_names: Vec<String> = points
+        .iter()
+        .flat_map(|p| p.companies.keys().cloned())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+    let companies = series_for(company_names, &|p, name| p.companies.get(name).copied().unwrap_or(0.0));
+
+    // Keep the largest assets in the newest snapshot and fold the rest into
+    // "Other"; assets no longer held there count as 0
+    let latest = points.last();
+    let mut ranked: Vec<(String, f64)> = points
+        .iter()
+        .flat_map(|p| p.assets.keys().cloned())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .map(|symbol| {
+            let value = latest.and_then(|p| p.assets.get(&symbol)).copied().unwrap_or(0.0);
+            (symbol, value)
+        })
+        .collect();
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+    let top_assets: Vec<String> = ranked.iter().take(MAX_CHART_ASSETS).map(|(s, _)| s.clone()).collect();
+
+    let mut assets = series_for(top_assets.clone(), &|p, name| p.assets.get(name).copied().unwrap_or(0.0));
+    if ranked.len() > MAX_CHART_ASSETS {
+        assets.push(ChartSeries {
+            label: "Other".to_string(),
+            values: points
+                .iter()
+                .map(|p| {
+                    p.assets
+                        .iter()
+                        .filter(|(symbol, _)| !top_assets.contains(symbol))
+                        .map(|(_, value)| value)
+                        .sum()
+                })
+                .collect(),
+        });
+    }
+
+    ChartData {
+        labels,
+        total,
+        companies,
+        assets,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_chart_ranks_assets_by_newest_snapshot() {
+        let point = |taken_at: i64, assets: &[(&str, f64)]| HistoryPoint {
+            taken_at,
+            total_usd_value: assets.iter().map(|(_, value)| value).sum(),
+            companies: BTreeMap::new(),
+            assets: assets.iter().map(|(symbol, value)| (symbol.to_string(), *value)).collect(),
+        };
+        // SOLD was large but is gone from the newest snapshot
+        let held: Vec<(String, f64)> = (0..MAX_CHART_ASSETS).map(|i| (format!("HELD{}", i), 10.0 + i as f64)).collect();
+        let held: Vec<(&str, f64)> = held.iter().map(|(symbol, value)| (symbol.as_str(), *value)).collect();
+        let mut earlier = held.clone();
+        earlier.push(("SOLD", 1_000_000.0));
+        let points = vec![point(1, &earlier), point(2, &held)];
+
+        let chart = build_chart_data(&points);
+        let labels: Vec<&str> = chart.assets.iter().map(|series| series.label.as_str()).collect();
+        assert_eq!(labels.len(), MAX_CHART_ASSETS + 1);
+        assert!(!labels.contains(&"SOLD"));
+        assert_eq!(labels.last(), Some(&"Other"));
+        assert_eq!(chart.assets.last().unwrap().values, vec![1_000_000.0, 0.0]);
+    }
+}